@@ -0,0 +1,238 @@
+//! Output for screen readers (`--accessible`)
+//!
+//! No color or symbols to read out: every result is a labeled sentence, and totals are summarized at the end
+
+// Standard library
+use std::io::{self, Write}; // For writing results
+use std::ops::Range; // For match spans
+
+// My stuff
+use crate::error::Error;
+use crate::i18n::{fill, Catalog};
+use crate::printer::{hashed_line, truncate_matches, Printer};
+use crate::search::Match;
+use crate::{Argument, PathDisplay};
+
+/// Prints each result as `File X, line N: ...`, followed by a summary
+#[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)] // Flags are naturally bools
+pub struct AccessiblePrinter<W> {
+    writer: W,
+    catalog: &'static Catalog,
+    count: bool,
+    count_matches: bool,
+    merge_adjacent: bool,
+    matching_lines: usize,
+    files_with_matches: usize,
+    hash_salt: Option<String>,
+    full_matches: bool,
+    paths: PathDisplay,
+}
+
+impl<W: Write> AccessiblePrinter<W> {
+    /// Printer for the output options in `config`, writing to `writer`
+    pub fn new(config: &Argument, writer: W) -> Self {
+        AccessiblePrinter {
+            writer,
+            catalog: config.lang().catalog(),
+            count: config.count,
+            count_matches: config.count_matches,
+            merge_adjacent: config.merge_adjacent,
+            matching_lines: 0,
+            files_with_matches: 0,
+            hash_salt: config.hash_salt(),
+            full_matches: config.full_matches,
+            paths: config.path_display(),
+        }
+    }
+
+    /// Give back the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_file(&mut self, path: &str, matches: &[Match]) -> io::Result<()> {
+        self.matching_lines += matches.len();
+        self.files_with_matches += usize::from(!matches.is_empty());
+
+        if self.count || self.count_matches {
+            let (template, count) = if self.count {
+                (self.catalog.accessible_count, matches.len())
+            } else {
+                (self.catalog.accessible_count_matches, matches.iter().map(|found| found.spans().len()).sum())
+            };
+
+            return writeln!(self.writer, "{}", fill(template, &[("file", path), ("count", &count.to_string())]));
+        }
+
+        if self.merge_adjacent {
+            for block in matches.chunk_by(|previous, next| next.line_number() == previous.line_number() + 1) {
+                if let [first, .., last] = block {
+                    let start = first.line_number().to_string();
+                    let end = last.line_number().to_string();
+
+                    writeln!(
+                        self.writer,
+                        "{}",
+                        fill(self.catalog.accessible_block, &[("file", path), ("start", &start), ("end", &end)]),
+                    )?;
+
+                    for found in block {
+                        writeln!(self.writer, "{}", self.text(found))?;
+                    }
+                } else {
+                    self.write_line(path, &block[0])?; // A single line reads better as a normal result
+                }
+            }
+
+            return Ok(());
+        }
+
+        for found in matches {
+            self.write_line(path, found)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_line(&mut self, path: &str, found: &Match) -> io::Result<()> {
+        let line = found.line_number().to_string();
+
+        writeln!(
+            self.writer,
+            "{}",
+            fill(self.catalog.accessible_line, &[("file", path), ("line", &line), ("text", &self.text(found))]),
+        )
+    }
+
+    /// The line, or its hash with `--hash-lines`
+    fn text(&self, found: &Match) -> String {
+        match &self.hash_salt {
+            Some(salt) => hashed_line(found, salt),
+            None if self.full_matches => found.line().to_string(),
+            None => truncate_matches(found.line(), found.spans(), self.catalog.truncated_match).0,
+        }
+    }
+
+    fn write_summary(&mut self) -> io::Result<()> {
+        if self.matching_lines == 0 {
+            return writeln!(self.writer, "{}", self.catalog.accessible_no_matches);
+        }
+
+        let count = self.matching_lines.to_string();
+        let files = self.files_with_matches.to_string();
+
+        writeln!(self.writer, "{}", fill(self.catalog.accessible_summary, &[("count", &count), ("files", &files)]))
+    }
+}
+
+impl<W: Write> Printer for AccessiblePrinter<W> {
+    fn file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        self.write_file(&self.paths.display(path), matches).map_err(Error::Output)
+    }
+
+    fn binary_file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        if self.count || self.count_matches || matches.is_empty() {
+            return self.file(path, matches);
+        }
+
+        self.matching_lines += matches.len();
+        self.files_with_matches += 1;
+
+        let path = self.paths.display(path);
+
+        writeln!(self.writer, "{}", fill(self.catalog.accessible_binary, &[("file", &path)])).map_err(Error::Output)
+    }
+
+    fn binary_matches(&mut self, path: &str, bytes: &[u8], spans: &[Range<usize>]) -> Result<(), Error> {
+        self.matching_lines += spans.len();
+        self.files_with_matches += usize::from(!spans.is_empty());
+
+        let path = self.paths.display(path);
+
+        for span in spans {
+            let offset = span.start.to_string();
+            let text = String::from_utf8_lossy(&bytes[span.clone()]);
+
+            writeln!(
+                self.writer,
+                "{}",
+                fill(self.catalog.accessible_binary_match, &[("file", &path), ("offset", &offset), ("text", &text)]),
+            )
+            .map_err(Error::Output)?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.write_summary().and_then(|()| self.writer.flush()).map_err(Error::Output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lang, Searcher};
+
+    /// Everything `config` prints for each `(path, contents)`, summary included
+    fn print(config: Argument, files: &[(&str, &str)]) -> String {
+        let mut printer = AccessiblePrinter::new(&config, Vec::new());
+        let searcher = Searcher::new(config).unwrap();
+
+        for (path, contents) in files {
+            printer.file(path, &searcher.search_str(contents)).unwrap();
+        }
+
+        printer.finish().unwrap();
+
+        String::from_utf8(printer.into_inner()).unwrap()
+    }
+
+    fn config(pattern: &str) -> Argument {
+        Argument { pattern: Some(String::from(pattern)), lang: Some(Lang::En), ..Default::default() }
+    }
+
+    #[test]
+    fn test_accessible_lines_and_summary() {
+        let result = print(config("o"), &[("a.txt", "one\ntwo\nsix\n"), ("b.txt", "six\n")]);
+
+        assert_eq!(
+            result,
+            "File a.txt, line 1: one\nFile a.txt, line 2: two\nMatching lines: 2. Files with matches: 1.\n",
+        );
+    }
+
+    #[test]
+    fn test_accessible_no_matches() {
+        assert_eq!(print(config("z"), &[("a.txt", "one\n")]), "No matches found.\n");
+    }
+
+    #[test]
+    fn test_accessible_counts() {
+        let count = Argument { count: true, ..config("o") };
+        let count_matches = Argument { count_matches: true, ..config("o") };
+
+        assert!(print(count, &[("a.txt", "oo\nxo\n")]).starts_with("File a.txt, matching lines: 2.\n"));
+        assert!(print(count_matches, &[("a.txt", "oo\nxo\n")]).starts_with("File a.txt, matches: 3.\n"));
+    }
+
+    #[test]
+    fn test_accessible_merge_adjacent() {
+        let config = Argument { merge_adjacent: true, ..config("x") };
+
+        let result = print(config, &[("a.txt", "x1\nx2\n\nx4\n")]);
+
+        assert!(result.starts_with("File a.txt, lines 1 to 2:\nx1\nx2\nFile a.txt, line 4: x4\n"));
+    }
+
+    #[test]
+    fn test_accessible_spanish() {
+        let config = Argument { lang: Some(Lang::Es), ..config("o") };
+
+        assert_eq!(
+            print(config, &[("a.txt", "uno\n")]),
+            "Archivo a.txt, línea 1: uno\nLíneas coincidentes: 1. Archivos con coincidencias: 1.\n",
+        );
+    }
+}
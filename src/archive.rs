@@ -0,0 +1,364 @@
+//! The files inside zip and tar archives, for searching them like other files (`--search-archives`)
+//!
+//! Archives are read in memory, so what's taken out of each one (archives inside it included) is
+//! capped by `--archive-max-size`, and archives inside archives are only opened down to
+//! `--archive-depth`, so a zip bomb can't take the search down.
+
+// Standard library
+use std::fs::File; // For reading archives
+use std::io::{self, Read}; // For reading tar members
+use std::path::Path; // For file extensions
+
+// My stuff
+use crate::decompress;
+
+/// What archives are, going by their names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+/// The kind of archive named `name`, if it is one
+fn kind(name: &str) -> Option<Kind> {
+    let path = Path::new(name);
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+    match extension.as_str() {
+        "zip" => Some(Kind::Zip),
+        "tar" => Some(Kind::Tar),
+        "tgz" => Some(Kind::TarGz),
+        "gz" if Path::new(path.file_stem()?).extension().is_some_and(|inner| inner.eq_ignore_ascii_case("tar")) => Some(Kind::TarGz),
+        _ => None,
+    }
+}
+
+/// Whether the file named `name` is an archive to search inside
+pub(crate) fn is_archive(name: &str) -> bool {
+    kind(name).is_some()
+}
+
+/// A file in an archive: its path inside it (with `!` between nested archives, like
+/// `logs.tar!2024.zip!app.log`) and its contents
+#[derive(Debug)]
+pub(crate) struct Member {
+    pub(crate) name: String,
+    pub(crate) contents: io::Result<Vec<u8>>,
+}
+
+/// How much more can be taken out of an archive
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Limits {
+    /// Levels of archives still to open, counting the one being read
+    pub(crate) depth: usize,
+    /// Bytes that can still be taken out
+    pub(crate) size: u64,
+}
+
+/// The contents of the archive at `path`, if it isn't bigger than `limit`, so a huge one isn't
+/// read into memory whole
+///
+/// # Errors
+///
+/// Will error if the file can't be read, or is too big
+pub(crate) fn read(path: &str, limit: u64) -> io::Result<Vec<u8>> {
+    decompress::read_at_most(File::open(path)?, limit).map_err(too_big)
+}
+
+/// Files in the archive named `name` with contents `bytes`, in the order they're stored
+///
+/// Directories and links are left out. A member that can't be taken out (like an encrypted one)
+/// has the error as its contents, and when `limits` run out the last member says so.
+///
+/// # Errors
+///
+/// Will error if `bytes` aren't an archive of the kind its name says (or one that isn't supported,
+/// like a zip64 file)
+pub(crate) fn members(name: &str, bytes: &[u8], limits: &mut Limits) -> io::Result<Vec<Member>> {
+    let mut members = Vec::new();
+
+    match kind(name) {
+        Some(Kind::Zip) => read_zip(bytes, limits, &mut members)?,
+        Some(Kind::Tar) => read_tar(bytes, limits, &mut members)?,
+        Some(Kind::TarGz) => {
+            let tar = decompress::gunzip(bytes, limits.size).map_err(too_big)?;
+            limits.size -= tar.len() as u64;
+            read_tar(&tar, limits, &mut members)?;
+        }
+        None => return Err(io::Error::other("not an archive")),
+    }
+
+    // Open the archives inside, while there are levels left
+    if limits.depth <= 1 {
+        return Ok(members);
+    }
+
+    let mut inner = Limits { depth: limits.depth - 1, size: limits.size };
+    let mut opened = Vec::with_capacity(members.len());
+
+    for member in members {
+        match &member.contents {
+            Ok(bytes) if is_archive(&member.name) && inner.size > 0 => match self::members(&member.name, bytes, &mut inner) {
+                Ok(nested) => opened.extend(nested.into_iter().map(|nested| Member { name: format!("{}!{}", member.name, nested.name), ..nested })),
+                Err(e) => opened.push(Member { contents: Err(e), ..member }),
+            },
+            _ => opened.push(member),
+        }
+    }
+
+    limits.size = inner.size;
+
+    Ok(opened)
+}
+
+/// The error for going over `--archive-max-size`, if `e` says decompressing went over the limit
+fn too_big(e: io::Error) -> io::Error {
+    if e.kind() == io::ErrorKind::FileTooLarge {
+        io::Error::other("not searched, over the --archive-max-size limit")
+    } else {
+        e
+    }
+}
+
+/// Take a member of `size` bytes out of `limits`, or say that it doesn't fit
+fn take(limits: &mut Limits, name: &str, size: u64, members: &mut Vec<Member>) -> bool {
+    if size > limits.size {
+        let message = String::from("this and later archive members not searched, over the --archive-max-size limit");
+        members.push(Member { name: name.to_string(), contents: Err(io::Error::other(message)) });
+        limits.size = 0;
+
+        return false;
+    }
+
+    limits.size -= size;
+    true
+}
+
+/// Add the regular files in the tar archive `bytes` to `members`
+fn read_tar(bytes: &[u8], limits: &mut Limits, members: &mut Vec<Member>) -> io::Result<()> {
+    let mut archive = tar::Archive::new(bytes);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = entry.path()?.to_string_lossy().into_owned();
+
+        if !take(limits, &name, entry.size(), members) {
+            break;
+        }
+
+        let mut contents = Vec::new();
+        let contents = entry.read_to_end(&mut contents).map(|_| contents);
+
+        members.push(Member { name, contents });
+    }
+
+    Ok(())
+}
+
+/// Signature of the end of central directory record, at the end of a zip file
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+/// Signature of each central directory entry
+const CENTRAL_DIRECTORY_ENTRY: u32 = 0x0201_4b50;
+/// Signature of each member's local header
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+
+/// Zip members stored as they are
+const STORED: u16 = 0;
+/// Zip members compressed with deflate
+const DEFLATED: u16 = 8;
+
+/// The error for a zip file that isn't one, or a damaged one
+fn corrupt_zip() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "not a zip file, or a damaged one")
+}
+
+/// The `N` bytes at `at` in `bytes`
+fn field<const N: usize>(bytes: &[u8], at: usize) -> io::Result<[u8; N]> {
+    at.checked_add(N).and_then(|end| bytes.get(at..end)).and_then(|field| field.try_into().ok()).ok_or_else(corrupt_zip)
+}
+
+/// Little-endian 16-bit number at `at` in `bytes`
+fn u16_at(bytes: &[u8], at: usize) -> io::Result<u16> {
+    field(bytes, at).map(u16::from_le_bytes)
+}
+
+/// Little-endian 32-bit number at `at` in `bytes`
+fn u32_at(bytes: &[u8], at: usize) -> io::Result<u32> {
+    field(bytes, at).map(u32::from_le_bytes)
+}
+
+/// 32-bit offset or length at `at` in `bytes`
+fn usize_at(bytes: &[u8], at: usize) -> io::Result<usize> {
+    usize::try_from(u32_at(bytes, at)?).map_err(|_| corrupt_zip())
+}
+
+/// Add the files in the zip archive `bytes` to `members`, going by its central directory
+fn read_zip(bytes: &[u8], limits: &mut Limits, members: &mut Vec<Member>) -> io::Result<()> {
+    // The end record is last, followed by a comment of up to 64K
+    let end = (0..=bytes.len().saturating_sub(22))
+        .rev()
+        .take(22 + usize::from(u16::MAX))
+        .find(|&at| u32_at(bytes, at).is_ok_and(|signature| signature == END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(corrupt_zip)?;
+
+    let entries = u16_at(bytes, end + 10)?;
+
+    if entries == u16::MAX || u32_at(bytes, end + 16)? == u32::MAX {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "zip64 files aren't supported"));
+    }
+
+    let mut at = usize_at(bytes, end + 16)?;
+
+    for _ in 0..entries {
+        if u32_at(bytes, at)? != CENTRAL_DIRECTORY_ENTRY {
+            return Err(corrupt_zip());
+        }
+
+        let flags = u16_at(bytes, at + 8)?;
+        let method = u16_at(bytes, at + 10)?;
+        let crc = u32_at(bytes, at + 16)?;
+        let compressed_size = usize_at(bytes, at + 20)?;
+        let size = u32_at(bytes, at + 24)?;
+        let name_len = usize::from(u16_at(bytes, at + 28)?);
+        let extra_len = usize::from(u16_at(bytes, at + 30)?);
+        let comment_len = usize::from(u16_at(bytes, at + 32)?);
+        let local_header = usize_at(bytes, at + 42)?;
+
+        let name = bytes.get(at + 46..at + 46 + name_len).ok_or_else(corrupt_zip)?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        at += 46 + name_len + extra_len + comment_len;
+
+        if name.ends_with('/') {
+            continue; // A directory
+        }
+
+        if !take(limits, &name, u64::from(size), members) {
+            break;
+        }
+
+        let data = zip_data(bytes, local_header, compressed_size);
+
+        let contents = match method {
+            _ if flags & 1 == 1 => Err(io::Error::new(io::ErrorKind::Unsupported, "encrypted, not searched")),
+            STORED => data.map(<[u8]>::to_vec),
+            DEFLATED => data.and_then(|data| decompress::inflate(data, crc, size, u64::from(size))),
+            _ => Err(io::Error::new(io::ErrorKind::Unsupported, format!("compression method {method} isn't supported"))),
+        };
+
+        members.push(Member { name, contents });
+    }
+
+    Ok(())
+}
+
+/// The compressed data of the member whose local header is at `local_header`
+fn zip_data(bytes: &[u8], local_header: usize, compressed_size: usize) -> io::Result<&[u8]> {
+    if u32_at(bytes, local_header)? != LOCAL_HEADER {
+        return Err(corrupt_zip());
+    }
+
+    // The lengths here can differ from the central directory's
+    let name_len = usize::from(u16_at(bytes, local_header + 26)?);
+    let extra_len = usize::from(u16_at(bytes, local_header + 28)?);
+    let start = local_header + 30 + name_len + extra_len;
+
+    bytes.get(start..start + compressed_size).ok_or_else(corrupt_zip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A zip file with `a.txt` stored, a `dir/` directory and `dir/b.txt` deflated
+    const ZIP: &[u8] = b"\x50\x4b\x03\x04\x14\x00\x00\x00\x00\x00\x00\x00\x21\x00\x4c\x47\x95\x4f\x0b\x00\x00\x00\x0b\x00\
+        \x00\x00\x05\x00\x00\x00\x61\x2e\x74\x78\x74\x73\x74\x6f\x72\x65\x64\x20\x66\x6f\x78\x0a\x50\x4b\
+        \x03\x04\x14\x00\x00\x00\x00\x00\x00\x00\x21\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x04\x00\x00\x00\x64\x69\x72\x2f\x50\x4b\x03\x04\x14\x00\x00\x00\x08\x00\x00\x00\x21\x00\x66\xf5\
+        \xa7\xd5\x12\x00\x00\x00\x04\x01\x00\x00\x09\x00\x00\x00\x64\x69\x72\x2f\x62\x2e\x74\x78\x74\x4b\
+        \x49\x4d\xcb\x49\x2c\x49\x4d\x51\x48\xcb\xaf\xe0\x4a\x19\x99\x1c\x00\x50\x4b\x01\x02\x14\x03\x14\
+        \x00\x00\x00\x00\x00\x00\x00\x21\x00\x4c\x47\x95\x4f\x0b\x00\x00\x00\x0b\x00\x00\x00\x05\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x01\x00\x00\x00\x00\x61\x2e\x74\x78\x74\x50\x4b\x01\x02\
+        \x14\x03\x14\x00\x00\x00\x00\x00\x00\x00\x21\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x01\x2e\x00\x00\x00\x64\x69\x72\x2f\x50\x4b\
+        \x01\x02\x14\x03\x14\x00\x00\x00\x08\x00\x00\x00\x21\x00\x66\xf5\xa7\xd5\x12\x00\x00\x00\x04\x01\
+        \x00\x00\x09\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x01\x50\x00\x00\x00\x64\x69\x72\x2f\
+        \x62\x2e\x74\x78\x74\x50\x4b\x05\x06\x00\x00\x00\x00\x03\x00\x03\x00\x9c\x00\x00\x00\x89\x00\x00\
+        \x00\x00\x00";
+
+    fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *contents).unwrap();
+        }
+
+        builder.into_inner().unwrap()
+    }
+
+    fn names(members: &[Member]) -> Vec<&str> {
+        members.iter().map(|member| member.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(kind("logs.ZIP"), Some(Kind::Zip));
+        assert_eq!(kind("backup.tar"), Some(Kind::Tar));
+        assert_eq!(kind("backup.tar.gz"), Some(Kind::TarGz));
+        assert_eq!(kind("backup.tgz"), Some(Kind::TarGz));
+        assert_eq!(kind("app.log.gz"), None);
+    }
+
+    #[test]
+    fn test_zip_members() {
+        let mut limits = Limits { depth: 1, size: 1 << 20 };
+        let members = members("sample.zip", ZIP, &mut limits).unwrap();
+
+        assert_eq!(names(&members), vec!["a.txt", "dir/b.txt"]);
+        assert_eq!(members[0].contents.as_ref().unwrap(), b"stored fox\n");
+        assert_eq!(members[1].contents.as_ref().unwrap(), "deflated fox\n".repeat(20).as_bytes());
+    }
+
+    #[test]
+    fn test_nested_archives() {
+        let inner = tar(&[("inner.txt", b"fox\n")]);
+        let outer = tar(&[("a.txt", b"dog\n"), ("inner.tar", &inner)]);
+
+        let members_at = |depth| members("outer.tar", &outer, &mut Limits { depth, size: 1 << 20 }).unwrap();
+
+        assert_eq!(names(&members_at(2)), vec!["a.txt", "inner.tar!inner.txt"]);
+        assert_eq!(names(&members_at(1)), vec!["a.txt", "inner.tar"]); // Not opened
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let archive = tar(&[("a.txt", b"0123456789"), ("b.txt", b"0123456789"), ("c.txt", b"0123456789")]);
+
+        let members = members("a.tar", &archive, &mut Limits { depth: 1, size: 15 }).unwrap();
+
+        assert_eq!(names(&members), vec!["a.txt", "b.txt"]);
+        assert!(members[0].contents.is_ok());
+        assert!(members[1].contents.as_ref().unwrap_err().to_string().contains("--archive-max-size"));
+
+        // Too big to read at all
+        let temp = crate::temp::TempDir::new("archive");
+        let path = temp.write("a.tar", &archive);
+
+        assert_eq!(read(&path, archive.len() as u64).unwrap(), archive);
+        assert!(read(&path, 15).unwrap_err().to_string().contains("--archive-max-size"));
+    }
+
+    #[test]
+    fn test_not_an_archive() {
+        assert!(members("a.zip", b"plain text", &mut Limits { depth: 1, size: 100 }).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+//! Splitting a command line in one string into arguments, for [`Argument::from_args_str`](crate::Argument::from_args_str)
+//!
+//! Quoting works like in a POSIX shell, but nothing is expanded (no variables, globs or `~`), so
+//! a stored string always means the same arguments.
+
+/// The arguments in `line`, split at whitespace outside quotes
+///
+/// `'...'` keeps everything inside as it is, `"..."` too except that `\` escapes `"`, `\`, `$`
+/// and `` ` ``, and outside quotes `\` escapes any character.
+///
+/// # Errors
+///
+/// Errors with a message if a quote isn't closed or the line ends with a lone `\`
+pub(crate) fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None; // Only an argument once something (even '') is in it
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                args.extend(current.take());
+            }
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(format!("unclosed ' in `{line}`")),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(format!("unclosed \" in `{line}`")),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(format!("unclosed \" in `{line}`")),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => current.get_or_insert_with(String::new).push(c),
+                None => return Err(format!("`{line}` ends with a lone \\")),
+            },
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    args.extend(current);
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(split_args("  -i -w\t--type rs \n").unwrap(), vec!["-i", "-w", "--type", "rs"]);
+        assert_eq!(split_args("-e 'fox jumps' a\\ b.txt").unwrap(), vec!["-e", "fox jumps", "a b.txt"]);
+        assert_eq!(split_args(r#""say \"hi\" \d" '' x'y'"z""#).unwrap(), vec![r#"say "hi" \d"#, "", "xyz"]);
+        assert_eq!(split_args("'$HOME' *.rs").unwrap(), vec!["$HOME", "*.rs"]); // Nothing expanded
+    }
+
+    #[test]
+    fn test_split_args_errors() {
+        assert_eq!(split_args("-e 'fox"), Err(String::from("unclosed ' in `-e 'fox`")));
+        assert!(split_args(r#"-e "fox\"#).is_err());
+        assert!(split_args("fox \\").is_err());
+    }
+}
@@ -0,0 +1,138 @@
+//! Least recently used cache, for remembering the matches in repeated lines (`--line-cache`)
+
+// Standard library
+use std::collections::HashMap; // For finding entries
+
+/// No entry, for the ends of the recency list
+const NONE: usize = usize::MAX;
+
+#[derive(Debug)]
+struct Entry<V> {
+    key: String,
+    value: V,
+    newer: usize,
+    older: usize,
+}
+
+/// Cache holding at most `capacity` values, dropping the least recently used one when it's full
+///
+/// Entries live in a `Vec` and are linked from most to least recently used by index, so every
+/// operation is constant time
+#[derive(Debug)]
+pub(crate) struct LruCache<V> {
+    capacity: usize,
+    indexes: HashMap<String, usize>,
+    entries: Vec<Entry<V>>,
+    newest: usize,
+    oldest: usize,
+}
+
+impl<V> LruCache<V> {
+    /// Empty cache for at most `capacity` values (at least 1)
+    pub(crate) fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+
+        LruCache {
+            capacity,
+            indexes: HashMap::with_capacity(capacity),
+            entries: Vec::with_capacity(capacity),
+            newest: NONE,
+            oldest: NONE,
+        }
+    }
+
+    /// The value for `key`, which becomes the most recently used
+    pub(crate) fn get(&mut self, key: &str) -> Option<&V> {
+        let index = *self.indexes.get(key)?;
+
+        self.unlink(index);
+        self.link_newest(index);
+
+        Some(&self.entries[index].value)
+    }
+
+    /// Add (or replace) the value for `key`, dropping the least recently used value if full
+    pub(crate) fn insert(&mut self, key: &str, value: V) {
+        if let Some(&index) = self.indexes.get(key) {
+            self.entries[index].value = value;
+            self.unlink(index);
+            self.link_newest(index);
+            return;
+        }
+
+        let entry = Entry { key: key.to_string(), value, newer: NONE, older: NONE };
+
+        let index = if self.entries.len() < self.capacity {
+            self.entries.push(entry);
+            self.entries.len() - 1
+        } else {
+            // Reuse the oldest entry's slot
+            let index = self.oldest;
+            self.unlink(index);
+            self.indexes.remove(&self.entries[index].key);
+            self.entries[index] = entry;
+            index
+        };
+
+        self.indexes.insert(key.to_string(), index);
+        self.link_newest(index);
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let Entry { newer, older, .. } = self.entries[index];
+
+        match newer {
+            NONE => self.newest = older,
+            newer => self.entries[newer].older = older,
+        }
+
+        match older {
+            NONE => self.oldest = newer,
+            older => self.entries[older].newer = newer,
+        }
+    }
+
+    fn link_newest(&mut self, index: usize) {
+        self.entries[index].newer = NONE;
+        self.entries[index].older = self.newest;
+
+        match self.newest {
+            NONE => self.oldest = index,
+            newest => self.entries[newest].newer = index,
+        }
+
+        self.newest = index;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_cache_drops_least_recently_used() {
+        let mut cache = LruCache::new(2);
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        assert_eq!(cache.get("a"), Some(&1)); // Now b is the oldest
+        cache.insert("c", 3);
+
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(&1));
+        assert_eq!(cache.get("c"), Some(&3));
+    }
+
+    #[test]
+    fn test_lru_cache_replace_and_capacity_one() {
+        let mut cache = LruCache::new(0); // Treated as 1
+
+        cache.insert("a", 1);
+        cache.insert("a", 2);
+        assert_eq!(cache.get("a"), Some(&2));
+
+        cache.insert("b", 3);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("b"), Some(&3));
+    }
+}
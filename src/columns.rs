@@ -0,0 +1,98 @@
+//! Column ranges for restricting matches to part of each line (`--columns`)
+
+/// 1-based, inclusive range of characters in a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnRange {
+    start: usize,
+    end: Option<usize>, // None means to the end of the line
+}
+
+impl ColumnRange {
+    /// Byte offsets of the range in `line`, clamped to the line
+    pub(crate) fn byte_range(self, line: &str) -> (usize, usize) {
+        // Byte offset of a 1-based column (or the line length if past the end)
+        let offset = |column: usize| {
+            line.char_indices()
+                .nth(column - 1)
+                .map_or(line.len(), |(index, _)| index)
+        };
+
+        let start = offset(self.start);
+        let end = self.end.map_or(line.len(), |end| offset(end + 1));
+
+        (start, end)
+    }
+
+    /// The part of `line` inside the range
+    pub(crate) fn slice(self, line: &str) -> &str {
+        let (start, end) = self.byte_range(line);
+
+        &line[start..end]
+    }
+}
+
+/// Parse `START-END`, `START-`, `-END` or a single `COLUMN`
+///
+/// # Errors
+///
+/// Will error if a column isn't a positive number or the range is backwards
+pub fn parse_column_range(range: &str) -> Result<ColumnRange, String> {
+    let column = |number: &str| match number.trim().parse::<usize>() {
+        Ok(0) | Err(_) => Err(format!("invalid column `{number}` (columns start at 1)")),
+        Ok(column) => Ok(column),
+    };
+
+    let (start, end) = if let Some((start, end)) = range.split_once('-') {
+        (
+            if start.is_empty() { 1 } else { column(start)? },
+            if end.is_empty() { None } else { Some(column(end)?) },
+        )
+    } else {
+        let column = column(range)?;
+        (column, Some(column))
+    };
+
+    if end.is_some_and(|end| end < start) {
+        return Err(format!("column range `{range}` ends before it starts"));
+    }
+
+    Ok(ColumnRange { start, end })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_column_range_forms() {
+        assert_eq!(parse_column_range("1-80"), Ok(ColumnRange { start: 1, end: Some(80) }));
+        assert_eq!(parse_column_range("10-"), Ok(ColumnRange { start: 10, end: None }));
+        assert_eq!(parse_column_range("-5"), Ok(ColumnRange { start: 1, end: Some(5) }));
+        assert_eq!(parse_column_range("3"), Ok(ColumnRange { start: 3, end: Some(3) }));
+    }
+
+    #[test]
+    fn test_parse_column_range_invalid() {
+        assert!(parse_column_range("0-5").is_err());
+        assert!(parse_column_range("a-b").is_err());
+        assert!(parse_column_range("9-2").is_err());
+        assert!(parse_column_range("").is_err());
+    }
+
+    #[test]
+    fn test_slice() {
+        let range = parse_column_range("3-5").unwrap();
+
+        assert_eq!(range.slice("abcdefg"), "cde");
+        assert_eq!(range.slice("abcd"), "cd");
+        assert_eq!(range.slice("a"), "");
+    }
+
+    #[test]
+    fn test_slice_counts_characters_not_bytes() {
+        let range = parse_column_range("2-3").unwrap();
+
+        assert_eq!(range.slice("héllo"), "él");
+        assert_eq!(range.byte_range("héllo"), (1, 4));
+    }
+}
@@ -0,0 +1,130 @@
+//! Comparing the matches in two trees (`--compare OLD NEW`)
+//!
+//! Files are aligned by their path relative to each root, and matching lines by their text, so
+//! lines that only moved within a file aren't reported.
+
+// Standard library
+use std::collections::{BTreeMap, HashMap}; // For grouping by relative path
+use std::error::Error; // For error handling
+use std::io::{self, Write}; // For writing the report
+use std::path::Path; // For joining relative paths
+
+// My stuff
+use crate::search::Match;
+use crate::{files_to_search, strip_root, warn_skipped, Argument, Searcher};
+
+/// Matches under a root, by path relative to it
+type RootMatches = BTreeMap<String, Vec<Match>>;
+
+/// Matches of the search under `root`, searched recursively
+fn root_matches(arg: &Argument, root: &str) -> Result<RootMatches, Box<dyn Error>> {
+    let searcher = Searcher::new(Argument {
+        files: vec![root.to_string()],
+        recursive: true,
+        compare: Vec::new(),
+        ..arg.clone()
+    })?;
+
+    let mut matches = RootMatches::new();
+
+    for file in files_to_search(searcher.config()) {
+        let found = match searcher.search_path(&file) {
+            Ok(found) => found,
+            Err(e) => {
+                warn_skipped(arg, &e); // Keep going, like recursive search does
+                continue;
+            }
+        };
+
+        if !found.is_empty() {
+            let relative = strip_root(&file, root).map_or(file.clone(), |rest| rest.to_string_lossy().into_owned());
+            matches.insert(relative, found);
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Matches in `these` whose line isn't in the same file in `others` (as many times), with the file's relative path
+fn only_in<'a>(these: &'a RootMatches, others: &RootMatches) -> Vec<(&'a str, &'a Match)> {
+    let mut only = Vec::new();
+
+    for (relative, matches) in these {
+        // How many times each line is left to be paired up in the other tree
+        let mut unpaired: HashMap<&str, usize> = HashMap::new();
+
+        for found in others.get(relative).into_iter().flatten() {
+            *unpaired.entry(found.line()).or_default() += 1;
+        }
+
+        for found in matches {
+            match unpaired.get_mut(found.line()) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => only.push((relative.as_str(), found)),
+            }
+        }
+    }
+
+    only
+}
+
+/// Print the matches only under OLD (with `-`), then the ones only under NEW (with `+`)
+///
+/// # Errors
+///
+/// Will error if a pattern is invalid
+pub fn print_comparison(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    let [old, new] = arg.compare.as_slice() else {
+        return Ok(()); // clap makes sure there are two
+    };
+
+    let old_matches = root_matches(arg, old)?;
+    let new_matches = root_matches(arg, new)?;
+    let mut out = io::stdout().lock();
+
+    for (sign, root, only) in [
+        ('-', old, only_in(&old_matches, &new_matches)),
+        ('+', new, only_in(&new_matches, &old_matches)),
+    ] {
+        for (relative, found) in only {
+            let path = Path::new(root).join(relative).to_string_lossy().into_owned();
+
+            writeln!(out, "{sign} {}:{}: {}", arg.display_path(&path), found.line_number(), found.line())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_only_in_pairs_lines_by_file() {
+        let dir = TempDir::new("compare");
+
+        for (file, contents) in [
+            ("old/a.txt", "use x\nuse x\nkeep x\n"),
+            ("old/b.txt", "gone x\n"),
+            ("new/a.txt", "keep x\n\nuse x\n"),
+            ("new/c.txt", "added x\n"),
+        ] {
+            dir.write(file, contents);
+        }
+
+        let arg = Argument { pattern: Some(String::from("x")), ..Default::default() };
+        let old = root_matches(&arg, &dir.join("old").to_string_lossy());
+        let new = root_matches(&arg, &dir.join("new").to_string_lossy());
+        let (old, new) = (old.unwrap(), new.unwrap());
+
+        let summary = |only: Vec<(&str, &Match)>| -> Vec<(String, usize)> {
+            only.into_iter().map(|(relative, found)| (relative.to_string(), found.line_number())).collect()
+        };
+
+        // One `use x` is left over, and moved lines aren't reported
+        assert_eq!(summary(only_in(&old, &new)), vec![(String::from("a.txt"), 2), (String::from("b.txt"), 1)]);
+        assert_eq!(summary(only_in(&new, &old)), vec![(String::from("c.txt"), 1)]);
+    }
+}
@@ -0,0 +1,249 @@
+//! Searching compressed files through the program that decompresses them (`--search-zip`) or a
+//! `--pre` command, and decompressing archive members in memory (`--search-archives`)
+
+// Standard library
+use std::fs::File; // For feeding files to --pre commands
+use std::io::{self, Read}; // For reading what's decompressed
+use std::path::Path; // For file extensions
+use std::process::{Child, ChildStdout, Command, Stdio}; // For running the decompressor
+use std::thread; // For reading stderr alongside stdout
+
+// External crates
+use flate2::read::{DeflateDecoder, GzDecoder}; // For archives
+use flate2::Crc; // For checking zip members
+
+/// Command that writes a file with each extension to stdout, decompressed
+const DECOMPRESSORS: [(&str, &[&str]); 4] = [
+    ("gz", &["gzip", "-d", "-c"]),
+    ("bz2", &["bzip2", "-d", "-c"]),
+    ("xz", &["xz", "-d", "-c"]),
+    ("zst", &["zstd", "-q", "-d", "-c"]),
+];
+
+/// The command that decompresses the file at `path`, if it's compressed (going by its extension)
+fn decompressor(path: &str) -> Option<&'static [&'static str]> {
+    let extension = Path::new(path).extension()?.to_str()?;
+
+    DECOMPRESSORS.iter().find(|(known, _)| known.eq_ignore_ascii_case(extension)).map(|(_, command)| *command)
+}
+
+/// A program that was started, with its stderr read on a thread of its own, so it can't fill up
+/// and block the program while its other output is read (or its input written)
+#[derive(Debug)]
+pub(crate) struct Process {
+    program: String,
+    child: Child,
+    stderr: Option<thread::JoinHandle<Vec<u8>>>,
+}
+
+impl Process {
+    /// Start reading the stderr of `child`, running `program`
+    pub(crate) fn new(program: &str, mut child: Child) -> Self {
+        let stderr = child.stderr.take().map(|mut stderr| {
+            thread::spawn(move || {
+                let mut message = Vec::new();
+                let _ = stderr.read_to_end(&mut message); // What was read still explains a failure best
+                message
+            })
+        });
+
+        Process { program: program.to_string(), child, stderr }
+    }
+
+    /// The program running, like `gzip`
+    pub(crate) fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Wait for the program to exit
+    ///
+    /// # Errors
+    ///
+    /// Will error with what the program said if it failed
+    pub(crate) fn wait(self) -> io::Result<()> {
+        let Process { program, mut child, stderr } = self;
+        let status = child.wait()?;
+        let message = stderr.and_then(|stderr| stderr.join().ok()).unwrap_or_default();
+        let message = String::from_utf8_lossy(&message);
+
+        if status.success() {
+            Ok(())
+        } else if message.trim().is_empty() {
+            Err(io::Error::other(format!("{program} failed ({status})")))
+        } else {
+            Err(io::Error::other(message.trim().to_string()))
+        }
+    }
+}
+
+/// A decompressor (or `--pre` command) running on a file, read like the file would be
+#[derive(Debug)]
+pub(crate) struct Decompressed {
+    process: Process,
+    stdout: ChildStdout,
+}
+
+impl Decompressed {
+    /// Start decompressing the file at `path`, or nothing if it isn't compressed
+    ///
+    /// # Errors
+    ///
+    /// Will error if the decompressor can't be run (like when it isn't installed)
+    pub(crate) fn open(path: &str) -> Option<io::Result<Self>> {
+        let (program, args) = decompressor(path)?.split_first()?;
+
+        Some(Self::start(program, spawn(program, &[args, &[path]].concat(), Stdio::null())))
+    }
+
+    /// Start the `--pre` command `command` on the file at `path`, which it gets as its argument and
+    /// on stdin
+    ///
+    /// # Errors
+    ///
+    /// Will error if the file can't be opened or the command can't be run
+    pub(crate) fn preprocess(command: &str, path: &str) -> io::Result<Self> {
+        let file = File::open(path)?;
+
+        Self::start(command, spawn(command, &[path], Stdio::from(file)))
+    }
+
+    /// Read the output of `child`, running `program`
+    fn start(program: &str, child: io::Result<Child>) -> io::Result<Self> {
+        child.and_then(|mut child| match child.stdout.take() {
+            Some(stdout) => Ok(Decompressed { process: Process::new(program, child), stdout }),
+            None => Err(io::Error::other(format!("no output from {program}"))),
+        })
+    }
+
+    /// The program decompressing, like `gzip` (or the `--pre` command)
+    pub(crate) fn program(&self) -> &str {
+        self.process.program()
+    }
+
+    /// Wait for the decompressor to exit
+    ///
+    /// # Errors
+    ///
+    /// Will error with what the decompressor said if it failed (like on a corrupt file)
+    pub(crate) fn finish(self) -> io::Result<()> {
+        let Decompressed { process, stdout } = self;
+        drop(stdout); // So it stops, rather than block on output nobody reads
+
+        process.wait()
+    }
+}
+
+impl Read for Decompressed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdout.read(buf)
+    }
+}
+
+/// Gzip-compressed `bytes` (like a `.tar.gz` in memory), decompressed
+///
+/// # Errors
+///
+/// Will error if `bytes` aren't gzip (or are damaged), or (with [`io::ErrorKind::FileTooLarge`])
+/// if decompressing gives more than `limit` bytes
+pub(crate) fn gunzip(bytes: &[u8], limit: u64) -> io::Result<Vec<u8>> {
+    read_at_most(GzDecoder::new(bytes), limit)
+}
+
+/// The deflate-compressed `data` of a zip member decompressed, checked against the CRC-32 and
+/// size the zip has for it
+///
+/// # Errors
+///
+/// Like [`gunzip`], and if the checks fail
+pub(crate) fn inflate(data: &[u8], crc: u32, size: u32, limit: u64) -> io::Result<Vec<u8>> {
+    let output = read_at_most(DeflateDecoder::new(data), limit)?;
+
+    let mut check = Crc::new();
+    check.update(&output);
+
+    if check.sum() != crc || output.len() as u64 != u64::from(size) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "damaged zip member (its CRC-32 or size doesn't match)"));
+    }
+
+    Ok(output)
+}
+
+/// Everything `reader` gives, or (with [`io::ErrorKind::FileTooLarge`]) an error once it's more
+/// than `limit` bytes
+pub(crate) fn read_at_most(reader: impl Read, limit: u64) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    reader.take(limit.saturating_add(1)).read_to_end(&mut output)?;
+
+    if output.len() as u64 > limit {
+        return Err(io::Error::new(io::ErrorKind::FileTooLarge, format!("more than {limit} bytes")));
+    }
+
+    Ok(output)
+}
+
+/// Start `program` with `args` and `stdin`, to read its output
+fn spawn(program: &str, args: &[&str], stdin: Stdio) -> io::Result<Child> {
+    Command::new(program)
+        .args(args)
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("can't run {program}: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompressor() {
+        assert_eq!(decompressor("logs/app.log.3.gz"), Some(&["gzip", "-d", "-c"][..]));
+        assert_eq!(decompressor("dump.SQL.XZ"), Some(&["xz", "-d", "-c"][..]));
+        assert_eq!(decompressor("app.log"), None);
+        assert_eq!(decompressor("gz"), None);
+    }
+
+    #[test]
+    fn test_gunzip() {
+        let gzip = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\x4b\xc9\x4f\xe7\x2a\x2c\xcd\x4c\xce\x56\x48\xcb\xaf\xe0\x02\x00\xf6\xc0\x6e\xfc\x0e\x00\x00\x00";
+
+        assert_eq!(gunzip(gzip, 100).unwrap(), b"dog\nquick fox\n");
+        assert!(gunzip(gzip, 5).is_err()); // Over the limit
+        assert!(gunzip(b"not gzip", 100).is_err());
+    }
+
+    #[test]
+    fn test_inflate() {
+        let deflated = b"\x4b\xc9\x4f\xe7\x2a\x2c\xcd\x4c\xce\x56\x48\xcb\xaf\xe0\x02\x00"; // The gzip above, without its header and trailer
+
+        assert_eq!(inflate(deflated, 0xfc6e_c0f6, 14, 100).unwrap(), b"dog\nquick fox\n");
+        assert!(inflate(deflated, 0xfc6e_c0f7, 14, 100).is_err()); // Wrong CRC-32
+        assert!(inflate(deflated, 0xfc6e_c0f6, 14, 5).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_loud_preprocessor() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = crate::temp::TempDir::new("pre");
+        let path = temp.write("a.txt", "quick fox\n");
+
+        // More on stderr than its pipe holds, before any output
+        let pre = temp.write("loud.sh", "#!/bin/sh\nhead -c 1000000 /dev/zero >&2\ncat \"$1\"\n");
+        std::fs::set_permissions(&pre, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut preprocessed = Decompressed::preprocess(&pre, &path).unwrap();
+        let mut output = String::new();
+        preprocessed.read_to_string(&mut output).unwrap();
+
+        assert_eq!(output, "quick fox\n");
+        preprocessed.finish().unwrap();
+    }
+
+    #[test]
+    fn test_open_uncompressed() {
+        assert!(Decompressed::open("./tests/test_2.txt").is_none());
+    }
+}
@@ -0,0 +1,132 @@
+//! Dropping result lines already printed from another file (`--global-dedupe`)
+
+// Standard library
+use std::collections::HashSet; // For exact dedupe
+use std::hash::{BuildHasher, BuildHasherDefault, DefaultHasher}; // For Bloom filter hashes
+
+// My stuff
+use crate::search::Match;
+
+/// How many bits each line sets in the Bloom filter
+///
+/// About 1% false positives at 10 bits per distinct line
+const HASHES: u64 = 7;
+
+/// Lines printed from the files searched so far
+#[derive(Debug)]
+pub(crate) enum SeenLines {
+    /// Every line, exactly
+    Exact(HashSet<String>),
+    /// Fixed size, but may wrongly think a line was seen (`--dedupe-bloom`)
+    Bloom(BloomFilter),
+}
+
+impl SeenLines {
+    /// Nothing seen yet, in a Bloom filter of `bloom_bytes` if given
+    pub(crate) fn new(bloom_bytes: Option<u64>) -> Self {
+        match bloom_bytes {
+            Some(bytes) => SeenLines::Bloom(BloomFilter::new(bytes)),
+            None => SeenLines::Exact(HashSet::new()),
+        }
+    }
+
+    /// `matches` from one file without the lines seen in earlier files, remembering the file's lines
+    ///
+    /// Repeats within the file are kept, since they're only remembered after the whole file
+    pub(crate) fn unseen(&mut self, mut matches: Vec<Match>) -> Vec<Match> {
+        let lines: Vec<String> = matches.iter().map(|found| found.line().to_string()).collect();
+
+        matches.retain(|found| !self.contains(found.line()));
+
+        for line in lines {
+            self.insert(line);
+        }
+
+        matches
+    }
+
+    fn contains(&self, line: &str) -> bool {
+        match self {
+            SeenLines::Exact(lines) => lines.contains(line),
+            SeenLines::Bloom(filter) => filter.contains(line),
+        }
+    }
+
+    fn insert(&mut self, line: String) {
+        match self {
+            SeenLines::Exact(lines) => {
+                lines.insert(line);
+            }
+            SeenLines::Bloom(filter) => filter.insert(&line),
+        }
+    }
+}
+
+/// Bloom filter of lines, using a fixed number of bytes
+#[derive(Debug)]
+pub(crate) struct BloomFilter {
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    /// Empty filter using `bytes` (at least 8)
+    fn new(bytes: u64) -> Self {
+        let words = usize::try_from(bytes.div_ceil(8)).unwrap_or(usize::MAX); // Allocating that much fails anyway
+
+        BloomFilter { bits: vec![0; words.max(1)] }
+    }
+
+    fn contains(&self, line: &str) -> bool {
+        self.positions(line).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    fn insert(&mut self, line: &str) {
+        for bit in self.positions(line).collect::<Vec<usize>>() {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// The bits for `line`, from two halves of one hash (double hashing)
+    fn positions(&self, line: &str) -> impl Iterator<Item = usize> {
+        // Fixed keys, so the same line always sets the same bits
+        let hash = BuildHasherDefault::<DefaultHasher>::default().hash_one(line);
+        let (first, second) = (hash, hash.rotate_left(32) | 1);
+        let len = self.bits.len() as u64 * 64;
+
+        #[allow(clippy::cast_possible_truncation)] // Below the number of bits, which fits
+        (0..HASHES).map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % len) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Argument, Searcher};
+
+    fn lines(matches: &[Match]) -> Vec<&str> {
+        matches.iter().map(Match::line).collect()
+    }
+
+    #[test]
+    fn test_unseen_across_files() {
+        let searcher = Searcher::new(Argument { pattern: Some(String::from("x")), ..Default::default() }).unwrap();
+
+        for mut seen in [SeenLines::new(None), SeenLines::new(Some(1024))] {
+            let first = seen.unseen(searcher.search_str("x 1\nx 1\nx 2\n"));
+            let second = seen.unseen(searcher.search_str("x 2\nx 3\n"));
+
+            assert_eq!(lines(&first), vec!["x 1", "x 1", "x 2"]); // Kept within a file
+            assert_eq!(lines(&second), vec!["x 3"]);
+        }
+    }
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut filter = BloomFilter::new(0); // Treated as 8
+
+        filter.insert("seen");
+
+        assert!(filter.contains("seen"));
+        assert_eq!(filter.bits.len(), 1);
+    }
+}
@@ -0,0 +1,94 @@
+//! Naming the columns matches are in, for CSV and TSV files (`--column-names`)
+//!
+//! The first line of the file is taken as its header row. Fields are split on the file's
+//! delimiter (going by its extension), with double quotes around fields that contain it, like
+//! spreadsheets write them. Quoted fields going over several lines aren't put back together,
+//! since lines are searched one at a time.
+
+// Standard library
+use std::ops::Range; // For fields and match spans
+use std::path::Path; // For file extensions
+
+/// The delimiter of the file at `path`, if it's a CSV or TSV file (going by its extension)
+pub(crate) fn delimiter(path: &str) -> Option<u8> {
+    let extension = Path::new(path).extension()?.to_str()?;
+
+    if extension.eq_ignore_ascii_case("csv") {
+        Some(b',')
+    } else if extension.eq_ignore_ascii_case("tsv") || extension.eq_ignore_ascii_case("tab") {
+        Some(b'\t')
+    } else {
+        None
+    }
+}
+
+/// Byte ranges of the fields of `line`, quotes included
+fn fields(line: &str, delimiter: u8) -> Vec<Range<usize>> {
+    let mut fields = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+
+    // Delimiters and quotes are ASCII, so these are char boundaries (and `""` toggles twice)
+    for (index, &byte) in line.as_bytes().iter().enumerate() {
+        if byte == b'"' {
+            quoted = !quoted;
+        } else if byte == delimiter && !quoted {
+            fields.push(start..index);
+            start = index + 1;
+        }
+    }
+
+    fields.push(start..line.len());
+    fields
+}
+
+/// `field` without the quotes around it (and with doubled quotes inside undone)
+fn unquote(field: &str) -> String {
+    match field.strip_prefix('"').and_then(|field| field.strip_suffix('"')) {
+        Some(inside) => inside.replace("\"\"", "\""),
+        None => field.to_string(),
+    }
+}
+
+/// Names (from the `header` row) of the columns of `line` that the matches at `spans` start in,
+/// in order without repeats, or their 1-based numbers (like `#7`) past the end of the header
+pub(crate) fn column_names(header: &str, line: &str, spans: &[Range<usize>], delimiter: u8) -> Vec<String> {
+    let names: Vec<String> = fields(header, delimiter).into_iter().map(|field| unquote(header[field].trim())).collect();
+    let fields = fields(line, delimiter);
+    let mut columns: Vec<usize> = Vec::new();
+
+    for span in spans {
+        if let Some(column) = fields.iter().position(|field| field.contains(&span.start) || field.end == span.start)
+            && !columns.contains(&column)
+        {
+            columns.push(column);
+        }
+    }
+
+    columns
+        .into_iter()
+        .map(|column| names.get(column).filter(|name| !name.is_empty()).cloned().unwrap_or_else(|| format!("#{}", column + 1)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delimiter() {
+        assert_eq!(delimiter("data/people.CSV"), Some(b','));
+        assert_eq!(delimiter("a.tar!export.tsv"), Some(b'\t'));
+        assert_eq!(delimiter("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_column_names() {
+        let header = "name,\"e-mail, work\",age";
+        let line = "\"Smith, Ann\",ann@example.com,30,extra";
+
+        assert_eq!(fields(line, b','), vec![0..12, 13..28, 29..31, 32..37]);
+        assert_eq!(column_names(header, line, &[1..6, 17..24, 32..37], b','), vec!["name", "e-mail, work", "#4"]);
+        assert_eq!(column_names(header, line, &[0..1, 3..4], b','), vec!["name"]); // Once per column
+        assert_eq!(column_names("a\tb", "x\ty", &[0..1, 2..3], b'\t'), vec!["a", "b"]);
+    }
+}
@@ -0,0 +1,94 @@
+//! Which device each file is on, so each gets its own queue (`--io-parallelism per-device`)
+//!
+//! With every thread taking the next file in one queue, a slow disk holds up as many threads as
+//! it has files waiting, and a fast one next to it sits idle. With a queue per device, and the
+//! threads starting on different ones, each is read as fast as it can be.
+
+// Standard library
+use std::collections::HashMap; // For devices of directories
+use std::path::Path; // For parent directories
+
+/// Indexes of `files` in one queue per device, in order, or one queue for all of them if the
+/// `roots` searched are all on one device (or devices can't be told apart here)
+pub(crate) fn queues(roots: &[String], files: &[String]) -> Vec<Vec<usize>> {
+    let mut devices: Vec<u64> = roots.iter().filter_map(|root| device(Path::new(root))).collect();
+    devices.sort_unstable();
+    devices.dedup();
+
+    if devices.len() < 2 {
+        return vec![(0..files.len()).collect()];
+    }
+
+    // Files in one directory are on one device, so each directory only has to be looked at once
+    let mut directories = HashMap::new();
+
+    group(files, |file| {
+        let directory = Path::new(file).parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        *directories.entry(directory.to_path_buf()).or_insert_with(|| device(directory))
+    })
+}
+
+/// Indexes of `files` grouped by the device `device` says each is on, in order of first file,
+/// with files whose device isn't known (which fail quickly) last
+fn group(files: &[String], mut device: impl FnMut(&str) -> Option<u64>) -> Vec<Vec<usize>> {
+    let mut groups: Vec<(u64, Vec<usize>)> = Vec::new();
+    let mut unknown = Vec::new();
+
+    for (index, file) in files.iter().enumerate() {
+        let Some(on) = device(file) else {
+            unknown.push(index);
+            continue;
+        };
+
+        match groups.iter_mut().find(|(known, _)| *known == on) {
+            Some((_, indexes)) => indexes.push(index),
+            None => groups.push((on, vec![index])),
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = groups.into_iter().map(|(_, indexes)| indexes).collect();
+
+    if !unknown.is_empty() {
+        groups.push(unknown);
+    }
+
+    groups
+}
+
+/// The ID of the device `path` is on
+#[cfg(unix)]
+fn device(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt; // For device IDs
+
+    std::fs::metadata(path).ok().map(|metadata| metadata.dev())
+}
+
+/// Devices can't be told apart without unstable APIs here, so everything is on one
+#[cfg(not(unix))]
+fn device(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_by_device() {
+        let files: Vec<String> = ["/hdd/a", "/nvme/b", "/hdd/c", "/nvme/d", "/gone/e"].iter().map(ToString::to_string).collect();
+        let device = |file: &str| match file.split('/').nth(1) {
+            Some("hdd") => Some(1),
+            Some("nvme") => Some(2),
+            _ => None,
+        };
+
+        assert_eq!(group(&files, device), vec![vec![0, 2], vec![1, 3], vec![4]]);
+    }
+
+    #[test]
+    fn test_queues_one_device() {
+        let files = vec![String::from("./tests/test_1.txt"), String::from("./tests/test_2.txt")];
+
+        assert_eq!(queues(&[String::from("./tests")], &files), vec![vec![0, 1]]);
+    }
+}
@@ -0,0 +1,180 @@
+//! Duplicate line detector (`--duplicates N`)
+
+// Standard library
+use std::cmp::Reverse; // For most repeated first
+use std::collections::BTreeMap; // For sorted grouping
+use std::error::Error; // For error handling
+use std::fs; // For file stuff
+use std::io::{self, Write}; // For writing the report
+
+// External crates
+use regex::Regex; // For regular expressions
+
+// My stuff
+use crate::{files_to_search, try_build_regex, warn_skipped, Argument};
+
+/// Where a line was seen
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Location {
+    file: String,
+    line_number: usize,
+}
+
+/// Lines -> every place they occur
+///
+/// Lines are compared with surrounding whitespace trimmed, so re-indented copies still count,
+/// and blank lines are skipped
+fn record_lines(
+    lines_seen: &mut BTreeMap<String, Vec<Location>>,
+    file: &str,
+    contents: &str,
+    regex_query: Option<&Regex>,
+) {
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || regex_query.is_some_and(|regex| !regex.is_match(line)) {
+            continue;
+        }
+
+        lines_seen.entry(line.to_string()).or_default().push(Location {
+            file: file.to_string(),
+            line_number: index + 1,
+        });
+    }
+}
+
+/// Lines that appear more than `max_occurrences` times, most repeated first
+fn find_duplicates(
+    lines_seen: BTreeMap<String, Vec<Location>>,
+    max_occurrences: usize,
+) -> Vec<(String, Vec<Location>)> {
+    let mut duplicates: Vec<(String, Vec<Location>)> = lines_seen
+        .into_iter()
+        .filter(|(_, locations)| locations.len() > max_occurrences)
+        .collect();
+
+    duplicates.sort_by_key(|(_, locations)| Reverse(locations.len())); // Stable, so ties stay alphabetical
+
+    duplicates
+}
+
+/// Print lines that appear more than N times across the searched files, with their locations
+///
+/// If a pattern is given, only lines matching it are considered
+///
+/// # Errors
+///
+/// Will error if a file is not readable or cannot be found (recursive mode skips them instead)
+///
+/// # Panics
+///
+/// Will panic if a regex query is invalid
+pub fn print_duplicates(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    let max_occurrences = arg.duplicates.unwrap_or_default();
+
+    let regex_query = arg
+        .has_pattern()
+        .then(|| try_build_regex(&arg.query(), arg.ignore_case(), arg.whole_word()))
+        .transpose()
+        .map_err(crate::error::Error::Pattern)?;
+
+    let mut lines_seen = BTreeMap::new();
+
+    for file in files_to_search(arg) {
+        let contents = match fs::read(&file) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(), // Searched like search_path does
+            Err(e) if !arg.recursive => return Err(e.into()),
+            Err(e) => {
+                warn_skipped(arg, &format!("{file}: {e}")); // Keep going, like recursive search does
+                continue;
+            }
+        };
+
+        record_lines(&mut lines_seen, &file, &contents, regex_query.as_ref());
+    }
+
+    let catalog = arg.lang().catalog();
+    let mut out = io::stdout().lock();
+
+    for (line, locations) in find_duplicates(lines_seen, max_occurrences) {
+        writeln!(out, "{}: {line}", catalog.times(locations.len()))?;
+
+        for location in locations {
+            writeln!(out, "  {}:{}", arg.display_path(&location.file), location.line_number)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_regex;
+
+    #[test]
+    fn test_find_duplicates_across_files() {
+        let mut lines_seen = BTreeMap::new();
+
+        record_lines(&mut lines_seen, "a.rs", "let x = 1;\nlet y = 2;\n", None);
+        record_lines(&mut lines_seen, "b.rs", "    let x = 1;\n", None);
+
+        let result = find_duplicates(lines_seen, 1);
+
+        assert_eq!(result, vec![(String::from("let x = 1;"), vec![
+            Location { file: String::from("a.rs"), line_number: 1 },
+            Location { file: String::from("b.rs"), line_number: 1 },
+        ])]);
+    }
+
+    #[test]
+    fn test_find_duplicates_most_repeated_first() {
+        let mut lines_seen = BTreeMap::new();
+
+        record_lines(&mut lines_seen, "a.txt", "a\nb\nb\na\nb\n", None);
+
+        let result: Vec<(String, usize)> = find_duplicates(lines_seen, 1)
+            .into_iter()
+            .map(|(line, locations)| (line, locations.len()))
+            .collect();
+
+        assert_eq!(result, vec![(String::from("b"), 3), (String::from("a"), 2)]);
+    }
+
+    #[test]
+    fn test_find_duplicates_threshold() {
+        let mut lines_seen = BTreeMap::new();
+
+        record_lines(&mut lines_seen, "a.txt", "a\na\nb\nb\nb\n", None);
+
+        let result = find_duplicates(lines_seen, 2);
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].0, "b");
+    }
+
+    #[test]
+    fn test_record_lines_skips_blank_and_non_matching_lines() {
+        let mut lines_seen = BTreeMap::new();
+        let regex_query = build_regex("key", false, false);
+
+        record_lines(&mut lines_seen, "a.ini", "key=1\n\n\nother=2\nkey=1\n", Some(&regex_query));
+
+        assert_eq!(lines_seen.keys().collect::<Vec<&String>>(), vec!["key=1"]);
+    }
+
+    #[test]
+    fn test_print_duplicates_dir() {
+        let arg = Argument {
+            files: vec![String::from("./tests")],
+            duplicates: Some(1),
+            recursive: true,
+            ..Default::default()
+        };
+
+        let result = print_duplicates(&arg);
+
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,157 @@
+//! Rewriting files with `--replace` or `--replace-rule` (`--write`)
+//!
+//! Files are picked and searched exactly like a search, so the same filters and ignore files
+//! apply. Each changed file is written to a temporary file next to it and renamed over it, so
+//! it's never left half written.
+
+// Standard library
+use std::error::Error; // For error handling
+use std::fs; // For file stuff
+use std::io::{self, Write}; // For file errors and the summary
+use std::path::Path; // For temporary file names
+
+// My stuff
+use crate::git::GitStatus;
+use crate::i18n::fill;
+use crate::{files_to_search, warn_skipped, Argument, Searcher};
+
+/// Replace the matches in every searched file, then print how many lines and files changed
+///
+/// Binary files and files that aren't valid UTF-8 are skipped with a warning, since rewriting them
+/// as text would corrupt them. So are files that would change but aren't clean in git, with
+/// `--require-clean-git`.
+///
+/// # Errors
+///
+/// Will error if a pattern is invalid, or a file can't be read or written (recursive mode
+/// skips unreadable files instead)
+pub fn write_replacements(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    let searcher = Searcher::new(arg.clone())?;
+
+    let mut git = arg.require_clean_git.then(GitStatus::default);
+    let (mut lines, mut files) = (0, 0);
+
+    for path in files_to_search(arg) {
+        let changed = match edit_file(&searcher, &path, git.as_mut()) {
+            Ok(changed) => changed,
+            Err(e) if !arg.recursive && e.kind() != io::ErrorKind::InvalidData => return Err(format!("{path}: {e}").into()),
+            Err(e) => {
+                warn_skipped(arg, &format!("{path}: {e}")); // Keep going, like recursive search does
+                continue;
+            }
+        };
+
+        lines += changed;
+        files += usize::from(changed > 0);
+    }
+
+    let catalog = arg.lang().catalog();
+    writeln!(io::stdout(), "{}", fill(catalog.write_summary, &[("lines", &lines.to_string()), ("files", &files.to_string())]))?;
+
+    Ok(())
+}
+
+/// Replace the matches in the file at `path`, returning how many lines changed
+///
+/// The file is only written if something changed (and it's clean, if `git` is given)
+fn edit_file(searcher: &Searcher, path: &str, git: Option<&mut GitStatus>) -> io::Result<usize> {
+    let bytes = fs::read(path)?;
+
+    if bytes.contains(&0) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "binary file, not rewritten"));
+    }
+
+    let contents = String::from_utf8(bytes)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8, not rewritten"))?;
+
+    let terminator = searcher.config().line_terminator();
+    let mut replacements = searcher.search_str(&contents).into_iter().peekable();
+
+    let mut edited = String::with_capacity(contents.len());
+    let mut changed = 0;
+
+    for (index, line) in contents.split_inclusive(terminator).enumerate() {
+        // Line endings (including \r\n) are kept as they were
+        let text = line.strip_suffix(terminator).unwrap_or(line);
+        let text = if terminator == '\n' { text.strip_suffix('\r').unwrap_or(text) } else { text };
+        let ending = &line[text.len()..];
+
+        match replacements.next_if(|found| found.line_number() == index + 1) {
+            Some(found) if found.line() != text => {
+                edited.push_str(found.line());
+                edited.push_str(ending);
+                changed += 1;
+            }
+            _ => edited.push_str(line),
+        }
+    }
+
+    if changed > 0 {
+        if let Some(git) = git
+            && let Some(refusal) = git.state(path)?.refusal()
+        {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, refusal));
+        }
+
+        write_atomically(Path::new(path), &edited)?;
+    }
+
+    Ok(changed)
+}
+
+/// Write `contents` to a temporary file next to `path` (with the same permissions) and rename it over `path`
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let temporary = path.with_file_name(format!(".{name}.simple-grep-{}", std::process::id()));
+
+    let written = fs::write(&temporary, contents)
+        .and_then(|()| fs::set_permissions(&temporary, fs::metadata(path)?.permissions()))
+        .and_then(|()| fs::rename(&temporary, path));
+
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary); // Don't leave it behind
+    }
+
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_edit_file_replaces_matching_lines() {
+        let temp = TempDir::new("edit");
+        let path = temp.write("a.txt", "user=alice\r\nother\nuser=bob");
+
+        let config = Argument {
+            pattern: Some(String::from(r"user=(\w+)")),
+            replace: Some(String::from("name=$1")),
+            ..Default::default()
+        };
+        let searcher = Searcher::new(config).unwrap();
+
+        let changed = edit_file(&searcher, &path, None).unwrap();
+        let changed_again = edit_file(&searcher, &path, None).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+
+        assert_eq!(changed, 2);
+        assert_eq!(changed_again, 0); // Nothing left to replace
+        assert_eq!(contents, "name=alice\r\nother\nname=bob");
+    }
+
+    #[test]
+    fn test_edit_file_skips_binary_files() {
+        let temp = TempDir::new("edit");
+        let path = temp.write("a.bin", b"a\0b");
+
+        let config = Argument { pattern: Some(String::from("a")), replace: Some(String::from("b")), ..Default::default() };
+
+        let result = edit_file(&Searcher::new(config).unwrap(), &path, None);
+        let contents = fs::read(&path).unwrap();
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(contents, b"a\0b");
+    }
+}
@@ -0,0 +1,115 @@
+//! Turning UTF-16 and Latin-1 files into UTF-8 before searching them (`--encoding`)
+
+// My stuff
+use crate::Encoding;
+
+pub(crate) const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+const UTF16LE_BOM: &[u8] = b"\xFF\xFE";
+const UTF16BE_BOM: &[u8] = b"\xFE\xFF";
+
+/// The encoding `bytes` are in: `encoding`, unless it's auto, in which case the byte order mark
+/// decides (UTF-8 without one)
+pub(crate) fn detect(bytes: &[u8], encoding: Encoding) -> Encoding {
+    match encoding {
+        Encoding::Auto if bytes.starts_with(UTF16LE_BOM) => Encoding::Utf16le,
+        Encoding::Auto if bytes.starts_with(UTF16BE_BOM) => Encoding::Utf16be,
+        Encoding::Auto => Encoding::Utf8,
+        encoding => encoding,
+    }
+}
+
+/// Name of `encoding` for saying text was decoded from it, or nothing for UTF-8
+pub(crate) fn name(encoding: Encoding) -> Option<&'static str> {
+    match encoding {
+        Encoding::Auto | Encoding::Utf8 => None,
+        Encoding::Utf16le => Some("UTF-16LE"),
+        Encoding::Utf16be => Some("UTF-16BE"),
+        Encoding::Latin1 => Some("Latin-1"),
+    }
+}
+
+/// `bytes` in `encoding` (or the detected one) as UTF-8, without a byte order mark
+///
+/// UTF-8 is only checked later, like files always were, so invalid bytes are replaced the same way
+/// whatever the encoding. UTF-16 that isn't valid (like an unpaired surrogate or an odd last byte)
+/// is replaced with U+FFFD.
+pub(crate) fn transcode(mut bytes: Vec<u8>, encoding: Encoding) -> Vec<u8> {
+    match detect(&bytes, encoding) {
+        Encoding::Auto | Encoding::Utf8 => {
+            if bytes.starts_with(UTF8_BOM) {
+                bytes.drain(..UTF8_BOM.len());
+            }
+
+            bytes
+        }
+        Encoding::Utf16le => utf16(bytes.strip_prefix(UTF16LE_BOM).unwrap_or(&bytes), u16::from_le_bytes),
+        Encoding::Utf16be => utf16(bytes.strip_prefix(UTF16BE_BOM).unwrap_or(&bytes), u16::from_be_bytes),
+        Encoding::Latin1 => latin1(&bytes).into_bytes(),
+    }
+}
+
+/// Latin-1 `bytes` as text: every byte is the character with that code point
+pub(crate) fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| char::from(byte)).collect()
+}
+
+/// UTF-16 `bytes` as UTF-8, with `unit` reading each code unit in the right byte order
+fn utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> Vec<u8> {
+    let chunks = bytes.chunks_exact(2);
+    let odd = !chunks.remainder().is_empty();
+
+    let mut text: String = char::decode_utf16(chunks.map(|pair| unit([pair[0], pair[1]])))
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+
+    if odd {
+        text.push(char::REPLACEMENT_CHARACTER);
+    }
+
+    text.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn test_detect_byte_order_marks() {
+        assert_eq!(detect(b"\xFF\xFEa\0", Encoding::Auto), Encoding::Utf16le);
+        assert_eq!(detect(b"\xFE\xFF\0a", Encoding::Auto), Encoding::Utf16be);
+        assert_eq!(detect(b"\xEF\xBB\xBFa", Encoding::Auto), Encoding::Utf8);
+        assert_eq!(detect(b"plain", Encoding::Auto), Encoding::Utf8);
+        assert_eq!(detect(b"\xFF\xFEa\0", Encoding::Latin1), Encoding::Latin1); // Forced
+    }
+
+    #[test]
+    fn test_transcode_utf16() {
+        let mut bytes = UTF16LE_BOM.to_vec();
+        bytes.extend(utf16le("café error\r\n🦀 ok\n"));
+
+        assert_eq!(transcode(bytes, Encoding::Auto), "café error\r\n🦀 ok\n".as_bytes());
+
+        let big_endian: Vec<u8> = "fox".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(transcode(big_endian, Encoding::Utf16be), b"fox"); // No BOM, but forced
+    }
+
+    #[test]
+    fn test_transcode_replaces_invalid_utf16() {
+        let mut bytes = utf16le("a");
+        bytes.extend([0x00, 0xD8]); // Unpaired surrogate
+        bytes.push(b'b'); // Odd last byte
+
+        assert_eq!(String::from_utf8(transcode(bytes, Encoding::Utf16le)).unwrap(), "a\u{fffd}\u{fffd}");
+    }
+
+    #[test]
+    fn test_transcode_utf8_and_latin1() {
+        assert_eq!(transcode(b"\xEF\xBB\xBFfox".to_vec(), Encoding::Auto), b"fox");
+        assert_eq!(transcode(b"caf\xe9".to_vec(), Encoding::Latin1), "café".as_bytes());
+        assert_eq!(transcode(b"caf\xe9".to_vec(), Encoding::Auto), b"caf\xe9"); // Replaced later
+    }
+}
@@ -0,0 +1,68 @@
+//! Errors returned by the library API
+
+// Standard library
+use std::fmt; // For displaying errors
+use std::io; // For I/O errors
+
+/// Everything that can go wrong while searching
+///
+/// New variants may be added in minor releases, so match with a wildcard arm
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The options don't make a valid search (e.g. unknown flag or no path)
+    Config(String),
+    /// A pattern is not valid regex
+    Pattern(regex::Error),
+    /// A file could not be read
+    Io {
+        /// Path of the file
+        path: String,
+        /// Why reading failed
+        source: io::Error,
+    },
+    /// Results could not be written
+    Output(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(message) => write!(f, "{message}"),
+            Error::Pattern(e) => write!(f, "invalid pattern: {e}"),
+            Error::Io { path, source } => write!(f, "{path}: {source}"),
+            Error::Output(e) => write!(f, "could not write results: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Config(_) => None,
+            Error::Pattern(e) => Some(e),
+            Error::Io { source, .. } | Error::Output(source) => Some(source),
+        }
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(e: regex::Error) -> Self {
+        Error::Pattern(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_error_names_the_path() {
+        let error = Error::Io {
+            path: String::from("a.txt"),
+            source: io::Error::new(io::ErrorKind::NotFound, "not found"),
+        };
+
+        assert_eq!(error.to_string(), "a.txt: not found");
+    }
+}
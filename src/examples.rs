@@ -0,0 +1,149 @@
+//! Example invocations for common tasks (`simple-grep examples` and `--help-long`)
+
+// Standard library
+use std::io::{self, Write}; // For printing
+
+// External crates
+use clap::CommandFactory; // For rendering the help
+
+// My stuff
+use crate::i18n::{localized_command, Lang};
+use crate::Cli;
+
+/// An invocation and what it's for, in English and Spanish
+struct Example {
+    command: &'static str,
+    en: &'static str,
+    es: &'static str,
+}
+
+/// Examples by topic, from the basics to the special modes
+const EXAMPLES: &[Example] = &[
+    Example { command: "simple-grep fox notes.txt", en: "Search a file", es: "Buscar en un archivo" },
+    Example {
+        command: "simple-grep -r -i todo src",
+        en: "Search a directory, ignoring case",
+        es: "Buscar en un directorio, sin distinguir mayúsculas",
+    },
+    Example {
+        command: "simple-grep -e error -e warning app.log",
+        en: "Search for several patterns",
+        es: "Buscar varios patrones",
+    },
+    Example {
+        command: "simple-grep -r -w -t rust unwrap .",
+        en: "Only whole words, only in Rust files",
+        es: "Solo palabras completas, solo en archivos de Rust",
+    },
+    Example {
+        command: "simple-grep -r --count-matches unsafe src",
+        en: "Count the matches in each file",
+        es: "Contar las coincidencias de cada archivo",
+    },
+    Example {
+        command: "simple-grep -r --heading --column fn src",
+        en: "Group results by file, with line and column",
+        es: "Agrupar los resultados por archivo, con línea y columna",
+    },
+    Example {
+        command: "simple-grep -r --vimgrep TODO .",
+        en: "Results for Vim's quickfix list",
+        es: "Resultados para la lista quickfix de Vim",
+    },
+    Example {
+        command: "simple-grep -r --json panic src",
+        en: "Results as JSON Lines, for scripts and editors",
+        es: "Resultados como JSON Lines, para scripts y editores",
+    },
+    Example {
+        command: "simple-grep -r --replace 'name=$1' 'user=(\\w+)' config",
+        en: "Preview a replacement with a capture group",
+        es: "Previsualizar un reemplazo con un grupo de captura",
+    },
+    Example {
+        command: "simple-grep -r --replace 'name=$1' --write 'user=(\\w+)' config",
+        en: "Apply it to the files",
+        es: "Aplicarlo a los archivos",
+    },
+    Example {
+        command: "simple-grep -r --min-entropy 4.5 --redact '[A-Za-z0-9]{32,}' .",
+        en: "Find likely secrets without printing them",
+        es: "Encontrar posibles secretos sin imprimirlos",
+    },
+    Example {
+        command: "simple-grep -r --only-binary ELF firmware",
+        en: "Hunt for strings in binary files, with hex windows",
+        es: "Buscar cadenas en archivos binarios, con ventanas hexadecimales",
+    },
+    Example {
+        command: "simple-grep --compare old new TODO",
+        en: "Compare the matches in two checkouts",
+        es: "Comparar las coincidencias de dos copias",
+    },
+    Example {
+        command: "simple-grep -r --duplicates 3 src",
+        en: "Find lines copied more than 3 times",
+        es: "Encontrar líneas copiadas más de 3 veces",
+    },
+    Example { command: "simple-grep -r --todos .", en: "Report TODO markers by owner", es: "Informe de marcas TODO por responsable" },
+    Example {
+        command: "simple-grep -r --sample-matches 20 --stats error logs",
+        en: "Get a feel for a huge number of matches",
+        es: "Hacerse una idea de una enorme cantidad de coincidencias",
+    },
+];
+
+/// The examples, one topic per paragraph
+fn examples_text(lang: Lang) -> String {
+    let examples: Vec<String> = EXAMPLES
+        .iter()
+        .map(|example| {
+            let about = if lang == Lang::Es { example.es } else { example.en };
+            format!("  # {about}\n  {}\n", example.command)
+        })
+        .collect();
+
+    format!("{}\n\n{}", lang.catalog().examples_heading, examples.join("\n"))
+}
+
+/// Print example invocations for common tasks
+///
+/// # Errors
+///
+/// Will error if they can't be written (like when stdout is closed)
+pub fn print_examples(lang: Lang) -> io::Result<()> {
+    write!(io::stdout().lock(), "{}", examples_text(lang))
+}
+
+/// Print the full help, followed by the examples
+///
+/// # Errors
+///
+/// Like [`print_examples`]
+pub fn print_long_help(lang: Lang) -> io::Result<()> {
+    writeln!(io::stdout().lock(), "{}", localized_command(Cli::command(), lang).render_long_help())?;
+    print_examples(lang)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_every_example_parses() {
+        for example in EXAMPLES {
+            // No quoted word has a space, so the shell would split them the same way
+            let result = Cli::try_parse_from(example.command.split(' ').map(|word| word.trim_matches('\'')));
+
+            assert!(result.is_ok(), "`{}` doesn't parse: {}", example.command, result.unwrap_err());
+        }
+    }
+
+    #[test]
+    fn test_examples_text() {
+        let result = examples_text(Lang::Es);
+
+        assert!(result.starts_with("Ejemplos:\n\n  # Buscar en un archivo\n  simple-grep fox notes.txt\n"));
+    }
+}
@@ -0,0 +1,289 @@
+//! Keeping the list of files a recursive search found between runs (`--cache-file-list`)
+//!
+//! With the list comes the modification time of every directory walked (and of the ignore files
+//! in them). Adding, removing or renaming a file changes its directory's time, so if none of
+//! them changed the list is still right, and checking takes one `stat` per directory instead of
+//! reading every one. Times within a couple of seconds of when the list was saved aren't
+//! trusted, since a directory could have changed again in the same tick of a coarse clock.
+//!
+//! Lists are kept in `$XDG_CACHE_HOME/simple-grep/file-lists` (or `~/.cache`, or the local app
+//! data folder on Windows), one per set of paths and options that decide which files are found.
+
+// Standard library
+use std::env; // For the cache directory and working directory
+use std::fmt::Write; // For writing lists
+use std::fs; // For reading and writing lists
+use std::io; // For errors saving lists
+use std::path::{Path, PathBuf}; // For cache paths
+use std::time::{Duration, SystemTime, UNIX_EPOCH}; // For modification times
+
+// My stuff
+use crate::ignore::IGNORE_FILES;
+use crate::{walk_files, Argument};
+
+/// First line of a list, so other formats (or other versions of it) aren't misread
+const HEADER: &str = "simple-grep file list 1";
+
+/// How close to saving a list a time can be and still be trusted
+const RACY: Duration = Duration::from_secs(2);
+
+/// Modification time of `path` in nanoseconds since the Unix epoch, or nothing if it's missing
+fn stamp(path: &Path) -> Option<u128> {
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+
+    Some(modified.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_nanos()))
+}
+
+fn now() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_nanos())
+}
+
+/// Whether `path` still has the time `then` it had when things were stamped at `written`, and
+/// it's old enough then to trust
+fn unchanged(path: &Path, then: Option<u128>, written: u128) -> bool {
+    let now = stamp(path);
+
+    now == then && now.is_none_or(|now| now + RACY.as_nanos() <= written)
+}
+
+/// The files a recursive search of `arg` finds, with the times of the directories walked (and
+/// ignore files in them), to tell later whether walking again could find others
+#[derive(Debug)]
+pub(crate) struct Walk {
+    pub(crate) files: Vec<String>,
+    stamps: Vec<(PathBuf, Option<u128>)>,
+    /// When the walk was done
+    walked: u128,
+}
+
+impl Walk {
+    pub(crate) fn new(arg: &Argument) -> Self {
+        // Roots that are directories are walked, and the rest (files, or missing ones) count too
+        let mut stamped: Vec<PathBuf> = arg.files.iter().map(PathBuf::from).filter(|root| !root.is_dir()).collect();
+        let files = walk_files(arg, |walked| {
+            stamped.push(walked.to_path_buf());
+            stamped.extend(IGNORE_FILES.iter().map(|name| walked.join(name)).filter(|ignore_file| ignore_file.exists()));
+        });
+
+        let stamps = stamped
+            .into_iter()
+            .map(|path| {
+                let when = stamp(&path);
+                (path, when)
+            })
+            .collect();
+
+        Walk { files, stamps, walked: now() }
+    }
+
+    /// Whether something the files depend on changed since the walk (or may have)
+    pub(crate) fn is_stale(&self) -> bool {
+        !self.stamps.iter().all(|(path, then)| unchanged(path, *then, self.walked))
+    }
+}
+
+/// Everything that decides which files a recursive search of `arg` finds
+fn key(arg: &Argument) -> String {
+    let cwd = env::current_dir().unwrap_or_default(); // Paths can be relative
+
+    format!(
+        "{:?}",
+        (cwd, &arg.files, arg.max_depth, arg.hidden, arg.no_ignore, arg.follow, arg.raw, &arg.include, &arg.exclude, &arg.file_type, &arg.type_not),
+    )
+}
+
+/// Where lists are kept
+fn cache_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+
+    Some(base.join("simple-grep").join("file-lists"))
+}
+
+/// Where the list for `key` is kept in `dir`, named by an FNV-1a hash of the key
+fn list_path(dir: &Path, key: &str) -> PathBuf {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for &byte in key.as_bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    dir.join(format!("{hash:016x}"))
+}
+
+/// The files a recursive search of `arg` finds, from the list saved by the last run if nothing
+/// changed since, and otherwise walked and saved for the next one
+///
+/// A list that can't be read or saved just means walking, like without the cache.
+pub(crate) fn cached_files(arg: &Argument) -> Vec<String> {
+    match cache_dir() {
+        Some(dir) => cached_files_in(arg, &dir),
+        None => walk_files(arg, |_| {}),
+    }
+}
+
+/// Walk the paths of `arg` and save the list for later searches with the same paths and options
+/// (`simple-grep index`), even if the saved one is still right, and give how many files it has
+///
+/// # Errors
+///
+/// Will error if there's nowhere to keep lists, or the list can't be saved
+pub(crate) fn index(arg: &Argument) -> io::Result<usize> {
+    let dir = cache_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory (set XDG_CACHE_HOME or HOME)"))?;
+
+    index_in(arg, &dir)
+}
+
+fn index_in(arg: &Argument, dir: &Path) -> io::Result<usize> {
+    let key = key(arg);
+    let walk = Walk::new(arg);
+    save(&list_path(dir, &key), &key, &walk)?;
+
+    Ok(walk.files.len())
+}
+
+fn cached_files_in(arg: &Argument, dir: &Path) -> Vec<String> {
+    let key = key(arg);
+    let path = list_path(dir, &key);
+
+    if let Some(files) = fs::read_to_string(&path).ok().and_then(|list| still_valid(&list, &key)) {
+        return files;
+    }
+
+    let walk = Walk::new(arg);
+    let _ = save(&path, &key, &walk); // Only a cache, so searching goes on without it
+
+    walk.files
+}
+
+/// The files in `list`, if it's for `key` and nothing it depends on changed since it was saved
+fn still_valid(list: &str, key: &str) -> Option<Vec<String>> {
+    let mut lines = list.lines();
+
+    if lines.next()? != HEADER {
+        return None;
+    }
+
+    let written: u128 = lines.next()?.strip_prefix("written ")?.parse().ok()?;
+
+    if lines.next()?.strip_prefix("key ")? != key {
+        return None; // A hash collision
+    }
+
+    let mut files = Vec::new();
+
+    for line in lines {
+        if let Some(file) = line.strip_prefix("file ") {
+            files.push(file.to_string());
+        } else {
+            let (when, path) = line.strip_prefix("stamp ")?.split_once(' ')?;
+            let then = if when == "-" { None } else { Some(when.parse().ok()?) };
+
+            if !unchanged(Path::new(path), then, written) {
+                return None;
+            }
+        }
+    }
+
+    Some(files)
+}
+
+/// Write the list, through a temporary file so another run never reads half of it
+fn save(path: &Path, key: &str, walk: &Walk) -> io::Result<()> {
+    let stamped: Vec<(String, Option<u128>)> = walk.stamps.iter().map(|(path, when)| (path.to_string_lossy().into_owned(), *when)).collect();
+
+    if key.contains('\n') || stamped.iter().map(|(path, _)| path).chain(&walk.files).any(|path| path.contains('\n')) {
+        return Ok(()); // Can't be written one per line
+    }
+
+    let mut list = format!("{HEADER}\nwritten {}\nkey {key}\n", walk.walked);
+
+    for (path, when) in stamped {
+        let when = when.map_or_else(|| String::from("-"), |when| when.to_string());
+        let _ = writeln!(list, "stamp {when} {path}"); // Writing to a String can't fail
+    }
+
+    for file in &walk.files {
+        let _ = writeln!(list, "file {file}");
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let temporary = path.with_extension(format!("tmp{}", std::process::id()));
+    fs::write(&temporary, list)?;
+    fs::rename(&temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_cached_files_revalidate() {
+        let root = TempDir::new("file-list");
+        let (tree, cache) = (root.join("tree"), root.join("cache"));
+        root.write("tree/sub/a.txt", "fox\n");
+
+        let arg = Argument::try_from_args(["-r", "fox", tree.to_str().unwrap()]).unwrap();
+        let key = key(&arg);
+        let list_path = list_path(&cache, &key);
+
+        let first = cached_files_in(&arg, &cache);
+        assert_eq!(first.len(), 1);
+
+        // Just saved, so its times are too new to trust
+        let list = fs::read_to_string(&list_path).unwrap();
+        assert_eq!(still_valid(&list, &key), None);
+
+        // As if saved well after anything last changed
+        let later = format!("written {}", now() + 10 * RACY.as_nanos());
+        let old: String = list.lines().map(|line| if line.starts_with("written ") { format!("{later}\n") } else { format!("{line}\n") }).collect();
+        assert_eq!(still_valid(&old, &key), Some(first.clone()));
+        assert_eq!(still_valid(&old, "other options"), None);
+
+        // A new file changes its directory's time
+        fs::write(&list_path, &old).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        fs::write(tree.join("sub").join("b.txt"), "fox\n").unwrap();
+        assert_eq!(still_valid(&fs::read_to_string(&list_path).unwrap(), &key), None);
+        assert_eq!(cached_files_in(&arg, &cache).len(), 2);
+    }
+
+    #[test]
+    fn test_index_in() {
+        let root = TempDir::new("index");
+        let (tree, cache) = (root.join("tree"), root.join("cache"));
+        root.write("tree/a.txt", "fox\n");
+        root.write("tree/sub/b.txt", "fox\n");
+
+        let arg = Argument::try_from_args(["-r", "fox", tree.to_str().unwrap()]).unwrap();
+        assert_eq!(index_in(&arg, &cache).unwrap(), 2);
+
+        let list = fs::read_to_string(list_path(&cache, &key(&arg))).unwrap();
+        assert_eq!(list.lines().filter(|line| line.starts_with("file ")).count(), 2);
+    }
+
+    #[test]
+    fn test_walk_is_stale() {
+        let root = TempDir::new("walk");
+        root.write("sub/a.txt", "fox\n");
+
+        let arg = Argument::try_from_args(["-r", "fox", &root.display()]).unwrap();
+        let mut walk = Walk::new(&arg);
+        assert!(walk.is_stale()); // Too new to trust
+
+        walk.walked += 10 * RACY.as_nanos(); // As if walked well after anything last changed
+        assert!(!walk.is_stale());
+
+        std::thread::sleep(Duration::from_millis(20));
+        root.write("sub/b.txt", "fox\n");
+        assert!(walk.is_stale());
+    }
+}
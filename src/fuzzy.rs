@@ -0,0 +1,260 @@
+//! Fuzzy matching (`--fuzzy`), for finding a half-remembered name
+//!
+//! A line matches when the characters of the query appear in it in order, like `srchcfg` in
+//! `SearchConfig`. Of all the ways they could appear, the best one is scored: every matched
+//! character scores, more at the start of a word (after punctuation or a space, or at a lowercase
+//! to uppercase change) and right after the previous one, and characters skipped between them
+//! cost a little. Only the best lines in all the files are printed, best first.
+
+// Standard library
+use std::cmp::Reverse; // For best first
+use std::error::Error; // For error handling
+use std::fs; // For reading files
+use std::io; // For printing
+use std::ops::Range; // For the matched characters
+
+// My stuff
+use crate::search::Match;
+use crate::{files_to_search, format_printer, warn_skipped, Argument, BinaryFiles};
+
+/// What each matched character scores
+const MATCH: i64 = 16;
+/// Extra for a character starting a word (twice that for the query's first character)
+const BOUNDARY: i64 = 8;
+/// Extra for a character starting a camelCase word or a number
+const CAMEL: i64 = 7;
+/// Least extra for a character right after the previous one, which otherwise gets as much as
+/// the start of their run
+const CONSECUTIVE: i64 = 4;
+/// Cost of skipping characters between matched ones, and of each one after the first
+const GAP_START: i64 = 3;
+const GAP_EXTENSION: i64 = 1;
+
+/// Lines longer than this many characters aren't scored, since it takes query length × line
+/// length (they're mostly minified code anyway)
+const MAX_LINE: usize = 4096;
+
+/// Score of a way to match that doesn't exist
+const NONE: i64 = i64::MIN / 2;
+
+/// A line and how well it matched
+#[derive(Debug, PartialEq, Eq)]
+struct Scored {
+    score: i64,
+    path: String,
+    found: Match,
+}
+
+/// Extra for matching `c`, coming after `previous` in the line
+fn bonus(previous: Option<char>, c: char) -> i64 {
+    match previous {
+        None => BOUNDARY,
+        Some(previous) if !previous.is_alphanumeric() && c.is_alphanumeric() => BOUNDARY,
+        Some(previous) if previous.is_lowercase() && c.is_uppercase() => CAMEL,
+        Some(previous) if previous.is_alphabetic() && c.is_numeric() => CAMEL,
+        Some(_) => 0,
+    }
+}
+
+/// `c` for comparing, lowercase when ignoring case
+fn fold(c: char, ignore_case: bool) -> char {
+    if ignore_case { c.to_lowercase().next().unwrap_or(c) } else { c }
+}
+
+/// The best score of `query` in `line`, with the byte ranges of the characters it matched
+/// (consecutive ones merged), or nothing if the query's characters aren't all in it in order
+fn score(query: &str, line: &str, ignore_case: bool) -> Option<(i64, Vec<Range<usize>>)> {
+    let query: Vec<char> = query.chars().map(|c| fold(c, ignore_case)).collect();
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let folded: Vec<char> = chars.iter().map(|&(_, c)| fold(c, ignore_case)).collect();
+
+    // Cheap check first, since most lines don't match at all
+    let mut rest = folded.iter();
+    if query.is_empty() || chars.len() > MAX_LINE || !query.iter().all(|q| rest.any(|c| c == q)) {
+        return None;
+    }
+
+    let (m, n) = (query.len(), chars.len());
+    let bonuses: Vec<i64> = (0..n).map(|j| bonus(j.checked_sub(1).map(|k| chars[k].1), chars[j].1)).collect();
+
+    // For the first i + 1 characters of the query with the last at j (at i * n + j): the best
+    // score, the bonus of the start of the run it ends and where the character before is
+    let mut scores = vec![NONE; m * n];
+    let mut runs = vec![0; m * n];
+    let mut from = vec![0; m * n];
+
+    for (i, &q) in query.iter().enumerate() {
+        let mut gap = (NONE, 0); // Best score (less the gap) and position before j - 1
+
+        for j in 0..n {
+            let at = i * n + j;
+
+            if i > 0 && j > 1 {
+                let skipping = scores[at - n - 2] - GAP_START;
+                gap = if skipping >= gap.0 - GAP_EXTENSION { (skipping, j - 2) } else { (gap.0 - GAP_EXTENSION, gap.1) };
+            }
+
+            if folded[j] != q {
+                continue;
+            }
+
+            if i == 0 {
+                (scores[at], runs[at]) = (MATCH + 2 * bonuses[j], bonuses[j]);
+                continue;
+            }
+
+            // Right after the character before, or after skipping some (preferring no gap on ties)
+            let consecutive = (j > 0 && scores[at - n - 1] > NONE / 2).then(|| {
+                let run = runs[at - n - 1];
+                (scores[at - n - 1] + MATCH + bonuses[j].max(run).max(CONSECUTIVE), run.max(bonuses[j]), j - 1)
+            });
+            let skipped = (gap.0 > NONE / 2).then_some((gap.0 + MATCH + bonuses[j], bonuses[j], gap.1));
+
+            if let Some(best) = [skipped, consecutive].into_iter().flatten().max_by_key(|&(score, _, _)| score) {
+                (scores[at], runs[at], from[at]) = best;
+            }
+        }
+    }
+
+    let (best, mut j) = (0..n).map(|j| (scores[(m - 1) * n + j], j)).max_by_key(|&(score, j)| (score, Reverse(j)))?;
+
+    // Walk back through which characters gave the best score
+    let mut positions = vec![j];
+    for i in (1..m).rev() {
+        j = from[i * n + j];
+        positions.push(j);
+    }
+
+    positions.reverse();
+
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    for j in positions {
+        let (start, c) = chars[j];
+
+        match spans.last_mut() {
+            Some(last) if last.end == start => last.end = start + c.len_utf8(),
+            _ => spans.push(start..start + c.len_utf8()),
+        }
+    }
+
+    Some((best, spans))
+}
+
+/// The best-scoring `limit` lines, best first (ties in search order)
+fn best(mut scored: Vec<Scored>, limit: usize) -> Vec<Scored> {
+    scored.sort_by_key(|scored| Reverse(scored.score)); // Stable, so ties stay in search order
+    scored.truncate(limit);
+    scored
+}
+
+/// Print the lines scoring best against the patterns (whichever fits a line best), best first
+///
+/// Matching is case insensitive unless a pattern has an uppercase letter (or with `-i`).
+///
+/// # Errors
+///
+/// Will error if a file is not readable or cannot be found (recursive mode skips them instead),
+/// or the results can't be written
+pub fn print_fuzzy(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    let queries: Vec<(&str, bool)> = arg
+        .pattern
+        .iter()
+        .chain(&arg.regexp)
+        .map(|query| (query.as_str(), arg.insensitive || !query.chars().any(char::is_uppercase)))
+        .collect();
+
+    let mut scored = Vec::new();
+
+    for file in files_to_search(arg) {
+        let bytes = match fs::read(&file) {
+            Ok(bytes) => bytes,
+            Err(e) if !arg.recursive => return Err(e.into()),
+            Err(e) => {
+                warn_skipped(arg, &format!("{file}: {e}")); // Keep going, like recursive search does
+                continue;
+            }
+        };
+
+        if arg.binary_mode() != BinaryFiles::Text && bytes.contains(&0) {
+            continue; // Binary lines aren't worth ranking
+        }
+
+        let contents = String::from_utf8_lossy(&bytes);
+
+        for (index, line) in contents.lines().enumerate() {
+            let best_query = queries.iter().filter_map(|&(query, ignore_case)| score(query, line, ignore_case)).max_by_key(|(score, _)| *score);
+
+            if let Some((score, spans)) = best_query {
+                let offset = (line.as_ptr().addr() - contents.as_ptr().addr()) as u64;
+                scored.push(Scored { score, path: file.clone(), found: Match::new(index + 1, offset, line.to_string(), spans) });
+            }
+        }
+
+        // Only ever keep a few more than needed
+        if scored.len() > arg.fuzzy_limit.saturating_mul(2).max(1024) {
+            scored = best(scored, arg.fuzzy_limit);
+        }
+    }
+
+    let mut printer = format_printer(arg, io::stdout().lock());
+
+    for Scored { path, found, .. } in best(scored, arg.fuzzy_limit) {
+        printer.file(&path, &[found])?;
+    }
+
+    printer.finish()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_subsequence() {
+        let (_, spans) = score("srchcfg", "let config = SearchConfig::new();", true).unwrap();
+
+        assert_eq!(spans, vec![13..14, 16..20, 22..23, 24..25]); // S, rchC, f and g of SearchConfig
+
+        assert_eq!(score("fgc", "SearchConfig", true), None); // Out of order
+        assert_eq!(score("", "anything", true), None);
+    }
+
+    #[test]
+    fn test_score_prefers_words_and_runs() {
+        let (word_start, _) = score("conf", "config = 1", true).unwrap();
+        let (word_starts, _) = score("conf", "c_o_n_f", true).unwrap();
+        let (inside, _) = score("conf", "reconfigure", true).unwrap();
+        let (scattered, _) = score("conf", "xcxxxxoxxxxnxxxxf", true).unwrap();
+
+        assert!(word_start > word_starts);
+        assert!(word_starts > inside);
+        assert!(inside > scattered);
+
+        let (camel, _) = score("sc", "SearchConfig", true).unwrap();
+        let (plain, _) = score("sc", "Searchconfig", true).unwrap();
+        assert!(camel > plain);
+    }
+
+    #[test]
+    fn test_score_case() {
+        assert!(score("Config", "config", false).is_none());
+        assert!(score("config", "CONFIG", true).is_some());
+
+        let (_, spans) = score("é", "café", true).unwrap();
+        assert_eq!(spans, vec![3..5]); // On character boundaries
+    }
+
+    #[test]
+    fn test_best_keeps_top_in_order() {
+        let scored = [(5, 1), (9, 2), (5, 3), (1, 4)]
+            .into_iter()
+            .map(|(score, line)| Scored { score, path: String::from("a.txt"), found: Match::new(line, 0, String::new(), Vec::new()) })
+            .collect();
+
+        let lines: Vec<usize> = best(scored, 3).iter().map(|scored| scored.found.line_number()).collect();
+
+        assert_eq!(lines, vec![2, 1, 3]);
+    }
+}
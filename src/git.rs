@@ -0,0 +1,180 @@
+//! Whether files are committed to git, so `--write --require-clean-git` only touches clean files
+
+// Standard library
+use std::collections::{HashMap, HashSet}; // For repositories and their files
+use std::fs; // For canonical paths
+use std::io; // For git errors
+use std::path::{Path, PathBuf}; // For file paths
+use std::process::Command; // For running git
+
+/// Where a file stands in git
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GitState {
+    /// Committed, with no staged or unstaged changes
+    Clean,
+    /// Committed, but changed since
+    Dirty,
+    /// In a repository, but never committed (untracked or ignored)
+    Untracked,
+    /// Not in a git repository
+    NoRepository,
+}
+
+impl GitState {
+    /// Why a file in this state isn't rewritten, or nothing if it's clean
+    pub(crate) fn refusal(self) -> Option<&'static str> {
+        match self {
+            GitState::Clean => None,
+            GitState::Dirty => Some("uncommitted changes in git, not rewritten"),
+            GitState::Untracked => Some("not committed to git, not rewritten"),
+            GitState::NoRepository => Some("not in a git repository, not rewritten"),
+        }
+    }
+}
+
+/// Tracked and changed files of one repository, relative to its top level
+#[derive(Debug, Default)]
+struct Repository {
+    tracked: HashSet<PathBuf>,
+    changed: HashSet<PathBuf>,
+}
+
+/// Asks git about files, running it once per directory and repository
+#[derive(Debug, Default)]
+pub(crate) struct GitStatus {
+    /// Top level of the repository each directory is in, if any
+    top_levels: HashMap<PathBuf, Option<PathBuf>>,
+    repositories: HashMap<PathBuf, Repository>,
+}
+
+impl GitStatus {
+    /// Where the file at `path` stands
+    ///
+    /// # Errors
+    ///
+    /// Will error if the file doesn't exist, or git can't be run
+    pub(crate) fn state(&mut self, path: &str) -> io::Result<GitState> {
+        let path = fs::canonicalize(path)?;
+        let directory = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+
+        let top_level = if let Some(top_level) = self.top_levels.get(&directory) {
+            top_level.clone()
+        } else {
+            let top_level = top_level(&directory)?;
+            self.top_levels.insert(directory, top_level.clone());
+            top_level
+        };
+
+        let Some(top_level) = top_level else {
+            return Ok(GitState::NoRepository);
+        };
+
+        if !self.repositories.contains_key(&top_level) {
+            let repository = Repository {
+                tracked: paths(&git(&top_level, &["ls-files", "-z"])?, false),
+                changed: paths(&git(&top_level, &["status", "--porcelain", "-z", "--untracked-files=no"])?, true),
+            };
+
+            self.repositories.insert(top_level.clone(), repository);
+        }
+
+        let repository = &self.repositories[&top_level];
+        let relative = path.strip_prefix(&top_level).unwrap_or(&path);
+
+        Ok(if !repository.tracked.contains(relative) {
+            GitState::Untracked
+        } else if repository.changed.contains(relative) {
+            GitState::Dirty
+        } else {
+            GitState::Clean
+        })
+    }
+}
+
+/// Top level of the repository `directory` is in, or nothing if it's not in one
+fn top_level(directory: &Path) -> io::Result<Option<PathBuf>> {
+    match git(directory, &["rev-parse", "--show-toplevel"]) {
+        Ok(output) => Ok(Some(fs::canonicalize(String::from_utf8_lossy(&output).trim_end())?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(e), // No git at all
+        Err(_) => Ok(None),
+    }
+}
+
+/// Output of git with `args`, run in `directory`
+fn git(directory: &Path, args: &[&str]) -> io::Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(args)
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("can't run git: {e}")))?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Paths in NUL-separated git output, without the `XY ` status codes if `status`
+fn paths(output: &[u8], status: bool) -> HashSet<PathBuf> {
+    let mut entries = output.split(|&byte| byte == 0).filter(|entry| !entry.is_empty());
+    let mut paths = HashSet::new();
+
+    while let Some(entry) = entries.next() {
+        let entry = String::from_utf8_lossy(entry);
+
+        if !status {
+            paths.insert(PathBuf::from(entry.as_ref()));
+            continue;
+        }
+
+        // Renames and copies are followed by where they came from, which changed too
+        if entry.starts_with(['R', 'C'])
+            && let Some(from) = entries.next()
+        {
+            paths.insert(PathBuf::from(String::from_utf8_lossy(from).as_ref()));
+        }
+
+        paths.insert(PathBuf::from(entry.get(3..).unwrap_or_default()));
+    }
+
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_status_paths() {
+        let result = paths(b" M src/lib.rs\0R  new.rs\0old.rs\0A  added.rs\0", true);
+
+        let expected: HashSet<PathBuf> = ["src/lib.rs", "new.rs", "old.rs", "added.rs"].iter().map(PathBuf::from).collect();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_git_states() {
+        let temp = TempDir::new("git");
+        let directory = temp.path();
+
+        for name in ["clean.txt", "dirty.txt", "untracked.txt"] {
+            temp.write(name, "a\n");
+        }
+
+        let run = |args: &[&str]| git(directory, args).unwrap();
+        run(&["init", "-q"]);
+        run(&["add", "clean.txt", "dirty.txt"]);
+        run(&["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-q", "-m", "test"]);
+        fs::write(directory.join("dirty.txt"), "b\n").unwrap();
+
+        let mut status = GitStatus::default();
+        let state = |status: &mut GitStatus, name: &str| status.state(&directory.join(name).to_string_lossy()).unwrap();
+
+        let results = [state(&mut status, "clean.txt"), state(&mut status, "dirty.txt"), state(&mut status, "untracked.txt")];
+
+        assert_eq!(results, [GitState::Clean, GitState::Dirty, GitState::Untracked]);
+    }
+}
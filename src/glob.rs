@@ -0,0 +1,367 @@
+//! Glob matching for file name filters (`--include`, `--exclude`) and ignore files
+//!
+//! Globs mean the same on every platform, rather than whatever the shell would expand them to:
+//!
+//! - `*` matches any run of characters in a name, and `?` any one character
+//! - `[abc]`, `[a-z]` and `[!a-z]` (or `[^a-z]`) match one character in (or not in) the class
+//! - `{a,b}` matches any of the alternatives, which can hold globs (and braces) too
+//! - `**` on its own between slashes matches any number of directories
+//! - `\` makes the next character literal, like `\*` or `\{`
+//!
+//! Braces are only expanded in globs from the command line: in ignore files they're literal,
+//! like in git. Globs are compiled once into a [`Glob`] and then matched against every file.
+
+// Standard library
+use std::convert::Infallible; // For globs always parsing
+use std::fmt; // For printing globs
+use std::iter; // For the `**` in front of path globs
+use std::str::FromStr; // For parsing globs from the command line
+
+/// What separates directories in the paths matched
+const SEPARATORS: [char; 2] = ['/', std::path::MAIN_SEPARATOR];
+
+/// One piece of a glob, matching part of a name
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// `*`
+    Star,
+    /// `?`
+    One,
+    /// `[...]`, with character ranges and whether it's negated
+    Class(Vec<(char, char)>, bool),
+    Literal(char),
+}
+
+/// One `/`-separated part of a glob
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    /// `**`, any number of directories
+    AnyDirs,
+    /// One name
+    Name(Vec<Token>),
+}
+
+impl Part {
+    fn new(pattern: &str) -> Self {
+        if pattern == "**" { Part::AnyDirs } else { Part::Name(tokenize(pattern)) }
+    }
+}
+
+/// A compiled glob
+#[derive(Clone, PartialEq)]
+pub(crate) struct Glob {
+    /// What it was compiled from
+    source: String,
+    /// The parts of each alternative the braces expand to
+    alternatives: Vec<Vec<Part>>,
+    /// Whether it matches the end of a path (it has a `/`) instead of the name
+    path: bool,
+}
+
+impl Glob {
+    /// A glob from the command line, with its braces expanded
+    pub(crate) fn new(pattern: &str) -> Self {
+        let path = pattern.contains('/');
+        let alternatives = expand_braces(pattern)
+            .iter()
+            .map(|pattern| {
+                if path {
+                    // Matches the end of the path
+                    iter::once(Part::AnyDirs).chain(pattern.split('/').filter(|part| !part.is_empty()).map(Part::new)).collect()
+                } else {
+                    vec![Part::Name(tokenize(pattern))]
+                }
+            })
+            .collect();
+
+        Glob { source: pattern.to_string(), alternatives, path }
+    }
+
+    /// A glob from an ignore file (with literal braces), matching paths from the ignore file's
+    /// directory if it's `anchored` and names otherwise
+    pub(crate) fn ignore_rule(pattern: &str, anchored: bool) -> Self {
+        let parts = pattern.split('/').map(Part::new);
+        let alternative = if anchored { parts.collect() } else { iter::once(Part::AnyDirs).chain(parts).collect() };
+
+        Glob { source: pattern.to_string(), alternatives: vec![alternative], path: true }
+    }
+
+    /// Whether the file at `path` matches: its name, or for a glob with a `/`, the end of its
+    /// path (so `src/**/*.rs` matches `./src/lib.rs` and `a/src/b/c.rs`)
+    pub(crate) fn is_match(&self, path: &str) -> bool {
+        let parts: Vec<&str> = if self.path {
+            path.split(SEPARATORS).filter(|part| !part.is_empty()).collect()
+        } else {
+            path.rsplit(SEPARATORS).take(1).collect()
+        };
+
+        self.components_match(&parts)
+    }
+
+    /// Whether the glob matches a path's components
+    pub(crate) fn components_match(&self, path: &[&str]) -> bool {
+        self.alternatives.iter().any(|parts| parts_match(parts, path))
+    }
+}
+
+/// Like it was written, since that's shorter than the compiled parts (and it's in file list
+/// cache keys)
+impl fmt::Debug for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.source)
+    }
+}
+
+/// For clap: any string is a glob from the command line
+impl FromStr for Glob {
+    type Err = Infallible;
+
+    fn from_str(pattern: &str) -> Result<Self, Infallible> {
+        Ok(Glob::new(pattern))
+    }
+}
+
+/// Whether path components match a glob's parts, where `**` matches any number of them
+fn parts_match(parts: &[Part], path: &[&str]) -> bool {
+    match parts.split_first() {
+        None => path.is_empty(),
+        Some((Part::AnyDirs, rest)) => (0..=path.len()).any(|skip| parts_match(rest, &path[skip..])),
+        Some((Part::Name(tokens), rest)) => path.split_first().is_some_and(|(name, path_rest)| {
+            tokens_match(tokens, &name.chars().collect::<Vec<_>>()) && parts_match(rest, path_rest)
+        }),
+    }
+}
+
+/// `pattern` with its braces expanded into every alternative, like a shell would
+///
+/// Braces without a comma in them (or a closing brace) are literal.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some((open, close, commas)) = outer_braces(pattern) else {
+        return vec![pattern.to_string()];
+    };
+
+    let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+    let mut starts = vec![open + 1];
+    starts.extend(commas.iter().map(|comma| comma + 1));
+    let ends = commas.iter().copied().chain([close]);
+
+    starts
+        .into_iter()
+        .zip(ends)
+        .flat_map(|(start, end)| expand_braces(&format!("{prefix}{}{suffix}", &pattern[start..end])))
+        .collect()
+}
+
+/// Where the first braces with alternatives in `pattern` open and close, and the commas between
+/// them at their level
+fn outer_braces(pattern: &str) -> Option<(usize, usize, Vec<usize>)> {
+    let mut chars = pattern.char_indices();
+    let mut open = None; // Where the braces open, and how deep inside them
+    let mut commas = Vec::new();
+
+    while let Some((at, c)) = chars.next() {
+        match (c, &mut open) {
+            ('\\', _) => {
+                chars.next();
+            }
+            ('{', None) => open = Some((at, 0)),
+            ('{', Some((_, depth))) => *depth += 1,
+            (',', Some((_, 0))) => commas.push(at),
+            ('}', Some((start, 0))) => {
+                if !commas.is_empty() {
+                    return Some((*start, at, commas));
+                }
+
+                open = None; // Literal, so look for the next ones
+            }
+            ('}', Some((_, depth))) => *depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// The tokens of a glob without braces
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let token = match chars[i] {
+            '*' => Token::Star,
+            '?' => Token::One,
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                Token::Literal(chars[i])
+            }
+            '[' => match class(&chars[i + 1..]) {
+                Some((token, len)) => {
+                    i += len;
+                    token
+                }
+                None => Token::Literal('['), // Not closed, so literal
+            },
+            c => Token::Literal(c),
+        };
+
+        tokens.push(token);
+        i += 1;
+    }
+
+    tokens
+}
+
+/// The class at the start of `chars` (just after its `[`), and how many characters it takes up
+/// with its `]`
+fn class(chars: &[char]) -> Option<(Token, usize)> {
+    let negated = matches!(chars.first(), Some('!' | '^'));
+    let mut i = usize::from(negated);
+    let mut ranges = Vec::new();
+
+    // A `]` right at the start is part of the class
+    while i < chars.len() && (chars[i] != ']' || i == usize::from(negated)) {
+        let start = chars[i];
+
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&end| end != ']') {
+            ranges.push((start, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((start, start));
+            i += 1;
+        }
+    }
+
+    (i < chars.len()).then_some((Token::Class(ranges, negated), i + 1))
+}
+
+/// Whether `name` matches `tokens`
+fn tokens_match(tokens: &[Token], name: &[char]) -> bool {
+    let (mut t, mut n) = (0, 0);
+
+    // Where to resume after the last `*` if the rest doesn't match
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        let matched = match tokens.get(t) {
+            Some(Token::Star) => {
+                backtrack = Some((t, n));
+                t += 1;
+                continue;
+            }
+            Some(Token::One) => true,
+            Some(Token::Class(ranges, negated)) => ranges.iter().any(|&(start, end)| (start..=end).contains(&name[n])) != *negated,
+            Some(Token::Literal(c)) => *c == name[n],
+            None => false,
+        };
+
+        if matched {
+            t += 1;
+            n += 1;
+        } else {
+            match backtrack {
+                // Let the `*` swallow one more character
+                Some((star, star_n)) => {
+                    t = star + 1;
+                    n = star_n + 1;
+                    backtrack = Some((star, star_n + 1));
+                }
+                None => return false,
+            }
+        }
+    }
+
+    // Trailing `*`s match the empty rest
+    tokens[t..].iter().all(|token| *token == Token::Star)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether `path` matches `pattern` from the command line
+    fn matches(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).is_match(path)
+    }
+
+    #[test]
+    fn test_glob_match_star() {
+        assert!(matches("*.rs", "lib.rs"));
+        assert!(matches("*.rs", ".rs"));
+        assert!(matches("*.min.js", "app.min.js"));
+        assert!(!matches("*.rs", "lib.rs.bak"));
+        assert!(!matches("*.min.js", "app.js"));
+    }
+
+    #[test]
+    fn test_glob_match_question_mark() {
+        assert!(matches("test_?.txt", "test_2.txt"));
+        assert!(!matches("test_?.txt", "test_22.txt"));
+    }
+
+    #[test]
+    fn test_glob_match_literal() {
+        assert!(matches("Cargo.toml", "Cargo.toml"));
+        assert!(!matches("Cargo.toml", "Cargo.lock"));
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+    }
+
+    #[test]
+    fn test_glob_match_multiple_stars() {
+        assert!(matches("*test*", "my_test_file.txt"));
+        assert!(matches("a*b*c", "aXXbYYc"));
+        assert!(!matches("a*b*c", "aXXcYYb"));
+        assert!(matches("**", "anything"));
+    }
+
+    #[test]
+    fn test_glob_match_classes() {
+        assert!(matches("test_[0-9].txt", "test_2.txt"));
+        assert!(!matches("test_[0-9].txt", "test_x.txt"));
+        assert!(matches("[!.]*", "visible"));
+        assert!(!matches("[^.]*", ".hidden"));
+        assert!(matches("[abc-]", "-")); // A `-` at the end is literal
+        assert!(matches("[]]", "]"));
+        assert!(matches("a[b", "a[b")); // Not closed
+    }
+
+    #[test]
+    fn test_glob_match_braces() {
+        assert!(matches("*.{rs,toml}", "Cargo.toml"));
+        assert!(matches("*.{rs,toml}", "lib.rs"));
+        assert!(!matches("*.{rs,toml}", "README.md"));
+        assert!(matches("{a,b{c,d}}.txt", "bd.txt"));
+        assert!(matches("{x}.txt", "{x}.txt")); // No alternatives
+        assert!(matches("{a,b}-{1,2}", "b-1"));
+        assert_eq!(expand_braces("{a,b{c,d}}"), vec!["a", "bc", "bd"]);
+    }
+
+    #[test]
+    fn test_glob_match_escapes() {
+        assert!(matches(r"\*.txt", "*.txt"));
+        assert!(!matches(r"\*.txt", "a.txt"));
+        assert!(matches(r"\{a,b\}", "{a,b}"));
+    }
+
+    #[test]
+    fn test_path_match() {
+        assert!(matches("*.rs", "./src/lib.rs"));
+        assert!(matches("src/**/*.rs", "./src/lib.rs"));
+        assert!(matches("src/**/*.rs", "a/src/b/c/main.rs"));
+        assert!(!matches("src/**/*.rs", "./tests/lib.rs"));
+        assert!(matches("**/tests/*", "tests/test_1.txt"));
+        assert!(matches("{src,tests}/*.rs", "tests/cli.rs"));
+        assert!(!matches("src/*.rs", "src/bin/main.rs")); // `*` stays in one directory
+    }
+
+    #[test]
+    fn test_ignore_rule() {
+        assert!(Glob::ignore_rule("*.log", false).components_match(&["logs", "a.log"]));
+        assert!(Glob::ignore_rule("build/*.o", true).components_match(&["build", "a.o"]));
+        assert!(!Glob::ignore_rule("build/*.o", true).components_match(&["src", "build", "a.o"]));
+        assert!(Glob::ignore_rule("{a,b}.txt", false).components_match(&["{a,b}.txt"])); // Braces are literal
+        assert!(!Glob::ignore_rule("{a,b}.txt", false).components_match(&["a.txt"]));
+    }
+}
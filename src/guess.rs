@@ -0,0 +1,137 @@
+//! Guessing the encoding of files that aren't UTF-8 and have no byte order mark (the
+//! `guess-encoding` feature)
+//!
+//! Only encodings `--encoding` can decode are guessed: UTF-16 (mostly ASCII or alphabetic text,
+//! which has NUL bytes as UTF-8) and Latin-1 (which has bytes that aren't valid UTF-8).
+
+// My stuff
+use crate::Encoding;
+use crate::encoding::UTF8_BOM;
+
+/// How sure a guess has to be to decode the file with it
+pub(crate) const CONFIDENT: f64 = 0.8;
+
+/// Fewer code units (or non-ASCII bytes) than this don't count as enough to go on, and lower
+/// the confidence
+const ENOUGH: usize = 8;
+
+/// What a file's encoding looks like, and how sure that is (0 to 1)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Guess {
+    pub(crate) encoding: Encoding,
+    pub(crate) confidence: f64,
+}
+
+/// The most likely encoding of `bytes`, unless they already read fine as UTF-8 (or say they're
+/// UTF-8 with a byte order mark)
+///
+/// `bytes` can be just the start of a file: a UTF-8 character cut off at the end is fine.
+pub(crate) fn guess(bytes: &[u8]) -> Option<Guess> {
+    if bytes.starts_with(UTF8_BOM) {
+        return None;
+    }
+
+    if bytes.contains(&0) {
+        return [utf16(bytes, Encoding::Utf16le, u16::from_le_bytes), utf16(bytes, Encoding::Utf16be, u16::from_be_bytes)]
+            .into_iter()
+            .max_by(|a, b| a.confidence.total_cmp(&b.confidence));
+    }
+
+    match std::str::from_utf8(bytes) {
+        Err(e) if e.error_len().is_some() => Some(latin1(bytes)),
+        _ => None, // Valid, or only cut off at the end
+    }
+}
+
+/// How much `bytes` look like UTF-16 in `encoding`, with `unit` reading each code unit
+#[allow(clippy::cast_precision_loss)] // Only loses precision past 2^52 code units
+fn utf16(bytes: &[u8], encoding: Encoding, unit: fn([u8; 2]) -> u16) -> Guess {
+    let chunks = bytes.chunks_exact(2);
+    let units = chunks.len() + chunks.remainder().len(); // An odd last byte counts against it
+
+    let plausible = char::decode_utf16(chunks.map(|pair| unit([pair[0], pair[1]])))
+        .filter(|c| c.as_ref().is_ok_and(|&c| is_text(c)))
+        .count();
+
+    Guess { encoding, confidence: ratio(plausible as f64, units) }
+}
+
+/// Whether `c` is likely in text: printable ASCII, whitespace, or a letter of an alphabet
+/// (leaving out the CJK ranges, which random bytes land in too often)
+fn is_text(c: char) -> bool {
+    matches!(c, ' '..='~' | '\t' | '\n' | '\r') || (('\u{A0}'..='\u{24FF}').contains(&c) && c.is_alphabetic())
+}
+
+/// How much the non-ASCII bytes of `bytes` look like Latin-1: letters count fully, symbols
+/// half, and C1 control codes (which are really Windows-1252 punctuation, most of the time) not
+/// at all
+fn latin1(bytes: &[u8]) -> Guess {
+    let mut score = 0.0;
+    let mut high = 0;
+
+    for &byte in bytes {
+        match byte {
+            b'\t' | b'\n' | b'\r' | 0x0C | 0x20..=0x7E => continue,
+            0x00..=0x1F | 0x7F => return Guess { encoding: Encoding::Latin1, confidence: 0.0 }, // Control codes aren't text
+            0x80..=0x9F => {}
+            0xD7 | 0xF7 | 0xA0..=0xBF => score += 0.5, // × and ÷ are symbols too
+            0xC0..=0xFF => score += 1.0,
+        }
+
+        high += 1;
+    }
+
+    Guess { encoding: Encoding::Latin1, confidence: ratio(score, high) }
+}
+
+/// `score` out of `total`, with totals under [`ENOUGH`] counted as ENOUGH
+#[allow(clippy::cast_precision_loss)] // Only loses precision past 2^52 bytes
+fn ratio(score: f64, total: usize) -> f64 {
+    score / total.max(ENOUGH) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn test_guess_utf16() {
+        let little = guess(&utf16le("error: disk full\r\nok\r\n")).unwrap();
+        assert_eq!(little.encoding, Encoding::Utf16le);
+        assert!(little.confidence >= CONFIDENT);
+
+        let big: Vec<u8> = "Привет, мир\n".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let big = guess(&big).unwrap();
+        assert_eq!(big.encoding, Encoding::Utf16be);
+        assert!(big.confidence >= CONFIDENT);
+    }
+
+    #[test]
+    fn test_guess_binary_is_not_confident() {
+        let elf = b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0\x03\0\x3e\0\x01\0\0\0\x10\x6b\0\0\0\0\0\0\xff\xfe\x01\x80";
+        assert!(guess(elf).unwrap().confidence < CONFIDENT);
+
+        assert!(guess(&utf16le("ok")).unwrap().confidence < CONFIDENT); // Too short to tell
+    }
+
+    #[test]
+    fn test_guess_latin1() {
+        let latin1 = guess(b"Caf\xe9 cr\xe8me br\xfbl\xe9e, na\xefve fa\xe7ade, d\xe9j\xe0 \xe0 la cr\xe9ation\n").unwrap();
+        assert_eq!(latin1.encoding, Encoding::Latin1);
+        assert!(latin1.confidence >= CONFIDENT);
+
+        // Windows-1252 curly quotes are C1 control codes in Latin-1
+        assert!(guess(b"\x93quoted\x94 and \x93more\x94 \x85").unwrap().confidence < CONFIDENT);
+    }
+
+    #[test]
+    fn test_no_guess_for_utf8() {
+        assert_eq!(guess("café\n".as_bytes()), None);
+        assert_eq!(guess(&"café".as_bytes()[..4]), None); // Cut off in the middle of é
+        assert_eq!(guess(b"\xEF\xBB\xBFcaf\xe9"), None); // Says it's UTF-8
+    }
+}
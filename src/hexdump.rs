@@ -0,0 +1,82 @@
+//! Hex windows around matches in binary files (`--only-binary`)
+
+// Standard library
+use std::fmt::Write; // For writing into strings
+use std::ops::Range; // For match spans
+
+/// Bytes per row, like `hexdump -C`
+const ROW: usize = 16;
+
+/// Most rows shown for one match, so a huge match doesn't dump the whole file
+const MAX_ROWS: usize = 4;
+
+/// `hexdump -C` style rows covering `span` of `bytes`
+///
+/// Whole rows, starting at multiples of 16 so offsets line up with other hex tools
+pub(crate) fn hex_rows(bytes: &[u8], span: &Range<usize>) -> Vec<String> {
+    let start = span.start - span.start % ROW;
+    let end = span.end.max(span.start + 1).next_multiple_of(ROW).min(start + ROW * MAX_ROWS).min(bytes.len());
+
+    bytes[start..end]
+        .chunks(ROW)
+        .enumerate()
+        .map(|(index, row)| hex_row(start + index * ROW, row))
+        .collect()
+}
+
+fn hex_row(offset: usize, row: &[u8]) -> String {
+    let mut line = format!("{offset:08x} ");
+
+    for index in 0..ROW {
+        if index % 8 == 0 {
+            line.push(' '); // Extra gap in the middle
+        }
+
+        match row.get(index) {
+            Some(byte) => {
+                let _ = write!(line, "{byte:02x} "); // Writing to a String can't fail
+            }
+            None => line.push_str("   "),
+        }
+    }
+
+    let ascii: String = row
+        .iter()
+        .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { char::from(byte) } else { '.' })
+        .collect();
+
+    let _ = write!(line, " |{ascii}|");
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_rows() {
+        let bytes = b"\x7fELF\x00\x01\x02\x03password\x00\x00\x00\x00tail";
+
+        let result = hex_rows(bytes, &(8..16));
+
+        assert_eq!(result, vec!["00000000  7f 45 4c 46 00 01 02 03  70 61 73 73 77 6f 72 64  |.ELF....password|"]);
+    }
+
+    #[test]
+    fn test_hex_rows_spanning_rows_and_short_last_row() {
+        let bytes = b"0123456789abcdefXYZ";
+
+        let result = hex_rows(bytes, &(14..18));
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[1], "00000010  58 59 5a                                          |XYZ|");
+    }
+
+    #[test]
+    fn test_hex_rows_limits_long_matches() {
+        let bytes = [b'a'; 1000];
+
+        assert_eq!(hex_rows(&bytes, &(0..1000)).len(), MAX_ROWS);
+    }
+}
@@ -0,0 +1,460 @@
+//! Translated user-facing messages (`--lang`, `LANG`)
+//!
+//! Each language is a catalog of static strings. English help comes from the doc comments on
+//! the options, other languages replace it through [`localized_command`].
+
+// Standard library
+use std::env; // For locale variables
+use std::ffi::OsString; // For raw command-line arguments
+
+// External crates
+use clap::ValueEnum; // For --lang values
+
+/// Language of messages and help
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    /// English
+    #[default]
+    En,
+    /// Spanish
+    Es,
+}
+
+/// Every translated message of a language
+#[derive(Debug)]
+pub(crate) struct Catalog {
+    pub(crate) application_error: &'static str,
+    pub(crate) no_path: &'static str,
+    pub(crate) compare_paths: &'static str,
+    #[cfg_attr(not(all(feature = "pcre2", unix)), allow(dead_code))] // Only checked with the pcre2 feature
+    pub(crate) pcre2_conflicts: &'static str,
+    pub(crate) patterns_ok: &'static str,
+    /// `{files}` is replaced with how many files `index` saved
+    pub(crate) indexed: &'static str,
+    /// `{address}` is replaced with where `serve` listens
+    pub(crate) serving: &'static str,
+    pub(crate) unassigned: &'static str,
+    /// `{count}` is replaced with the number of occurrences
+    pub(crate) times: &'static str,
+    pub(crate) write_summary: &'static str,
+    pub(crate) sampled_matches: &'static str,
+    pub(crate) debug_engine: &'static str,
+    /// `--debug` note about an encoding guess that wasn't used, see [`fill`] for the placeholders
+    #[cfg_attr(not(feature = "guess-encoding"), allow(dead_code))]
+    pub(crate) debug_guess: &'static str,
+    pub(crate) examples_heading: &'static str,
+    /// `--stats` trailer, see [`fill`] for the placeholders
+    pub(crate) stats: &'static str,
+    /// SIGUSR1 status, see [`fill`] for the placeholders
+    pub(crate) status: &'static str,
+    /// Column headings of the `--by-size` table: size, files, bytes, seconds and matches
+    pub(crate) stats_by_size: [&'static str; 5],
+    /// `{file}` is replaced with the path
+    pub(crate) binary_file_matches: &'static str,
+    /// `{len}` is replaced with the length of a match that was cut short
+    pub(crate) truncated_match: &'static str,
+    /// `--accessible` output, see [`fill`] for the placeholders
+    pub(crate) accessible_line: &'static str,
+    pub(crate) accessible_block: &'static str,
+    pub(crate) accessible_count: &'static str,
+    pub(crate) accessible_count_matches: &'static str,
+    pub(crate) accessible_binary: &'static str,
+    pub(crate) accessible_binary_match: &'static str,
+    pub(crate) accessible_summary: &'static str,
+    pub(crate) accessible_no_matches: &'static str,
+    /// `--tui` screen, see [`fill`] for the placeholders
+    pub(crate) tui_results: &'static str,
+    pub(crate) tui_searching: &'static str,
+    pub(crate) tui_keys: &'static str,
+    pub(crate) tui_no_preview: &'static str,
+    pub(crate) tui_terminal: &'static str,
+    about: &'static str,
+    usage_heading: &'static str,
+    arguments_heading: &'static str,
+    options_heading: &'static str,
+    commands_heading: &'static str,
+    /// Help for each argument (by clap id) and subcommand (by name)
+    help: &'static [(&'static str, &'static str)],
+}
+
+const EN: Catalog = Catalog {
+    application_error: "Application error",
+    no_path: "no file or directory to search was given",
+    compare_paths: "--compare searches OLD and NEW, so other paths can't be given",
+    pcre2_conflicts: "--engine pcre2 can't be used with --replace, --only-binary or --duplicates",
+    patterns_ok: "ok",
+    indexed: "{files} files indexed",
+    serving: "Listening on http://{address}",
+    unassigned: "(unassigned)",
+    times: "{count} times",
+    write_summary: "Changed lines: {lines}. Changed files: {files}.",
+    sampled_matches: "Showing {shown} of {total} matching lines.",
+    debug_engine: "debug: matching with {engine}",
+    debug_guess: "debug: {file} looks like {encoding}, but only {confidence}% sure, so it's searched as UTF-8",
+    examples_heading: "Examples:",
+    stats: "{lines} matched lines\n{matches} matches\n{files_with_matches} files with matches\n{files} files searched\n{bytes} bytes searched\n{seconds} seconds",
+    stats_by_size: ["size", "files", "bytes", "seconds", "matches"],
+    status: "{done}/{total} files searched, {lines} matching lines, now searching {file}",
+    binary_file_matches: "Binary file {file} matches",
+    truncated_match: "… ({len} bytes)",
+    accessible_line: "File {file}, line {line}: {text}",
+    accessible_block: "File {file}, lines {start} to {end}:",
+    accessible_count: "File {file}, matching lines: {count}.",
+    accessible_count_matches: "File {file}, matches: {count}.",
+    accessible_binary: "File {file} is binary and matches.",
+    accessible_binary_match: "File {file}, byte {offset}: {text}",
+    accessible_summary: "Matching lines: {count}. Files with matches: {files}.",
+    accessible_no_matches: "No matches found.",
+    tui_results: "{lines} matching lines",
+    tui_searching: "searching…",
+    tui_keys: "↑/↓ pick, Enter print, Ctrl-E edit, Esc quit",
+    tui_no_preview: "no preview: {error}",
+    tui_terminal: "--tui needs a terminal",
+    about: "Simple grep implementation written in Rust",
+    usage_heading: "Usage:",
+    arguments_heading: "Arguments",
+    options_heading: "Options",
+    commands_heading: "Commands",
+    help: &[], // Doc comments are already English
+};
+
+const ES: Catalog = Catalog {
+    application_error: "Error de la aplicación",
+    no_path: "no se indicó ningún archivo o directorio donde buscar",
+    compare_paths: "--compare busca en OLD y NEW, así que no se pueden indicar otras rutas",
+    pcre2_conflicts: "--engine pcre2 no se puede usar con --replace, --only-binary ni --duplicates",
+    patterns_ok: "correcto",
+    indexed: "{files} archivos indexados",
+    serving: "Escuchando en http://{address}",
+    unassigned: "(sin asignar)",
+    times: "{count} veces",
+    write_summary: "Líneas cambiadas: {lines}. Archivos cambiados: {files}.",
+    sampled_matches: "Mostrando {shown} de {total} líneas coincidentes.",
+    debug_engine: "depuración: buscando con {engine}",
+    debug_guess: "depuración: {file} parece {encoding}, pero solo con un {confidence}% de seguridad, así que se busca como UTF-8",
+    examples_heading: "Ejemplos:",
+    stats: "{lines} líneas coincidentes\n{matches} coincidencias\n{files_with_matches} archivos con coincidencias\n{files} archivos buscados\n{bytes} bytes buscados\n{seconds} segundos",
+    stats_by_size: ["tamaño", "archivos", "bytes", "segundos", "coincidencias"],
+    status: "{done}/{total} archivos buscados, {lines} líneas coincidentes, buscando ahora {file}",
+    binary_file_matches: "El archivo binario {file} coincide",
+    truncated_match: "… ({len} bytes)",
+    accessible_line: "Archivo {file}, línea {line}: {text}",
+    accessible_block: "Archivo {file}, líneas {start} a {end}:",
+    accessible_count: "Archivo {file}, líneas coincidentes: {count}.",
+    accessible_count_matches: "Archivo {file}, coincidencias: {count}.",
+    accessible_binary: "El archivo {file} es binario y coincide.",
+    accessible_binary_match: "Archivo {file}, byte {offset}: {text}",
+    accessible_summary: "Líneas coincidentes: {count}. Archivos con coincidencias: {files}.",
+    accessible_no_matches: "No se encontraron coincidencias.",
+    tui_results: "{lines} líneas coincidentes",
+    tui_searching: "buscando…",
+    tui_keys: "↑/↓ elegir, Enter mostrar, Ctrl-E editar, Esc salir",
+    tui_no_preview: "sin vista previa: {error}",
+    tui_terminal: "--tui necesita una terminal",
+    about: "Implementación sencilla de grep escrita en Rust",
+    usage_heading: "Uso:",
+    arguments_heading: "Argumentos",
+    options_heading: "Opciones",
+    commands_heading: "Comandos",
+    help: &[
+        ("pattern", "El patrón a buscar (admite regex)"),
+        ("files", "Los archivos (o directorios, con -r) donde buscar"),
+        ("regexp", "Patrón a buscar (se puede repetir, se imprimen las líneas que coincidan con cualquiera), que puede empezar por `-`"),
+        ("pattern_file", "Leer patrones de un archivo, uno por línea (se ignoran las líneas vacías)"),
+        ("smart_case", "Distinguir mayúsculas y minúsculas solo si un patrón tiene alguna mayúscula"),
+        ("insensitive", "No distinguir mayúsculas de minúsculas"),
+        ("auto_engine", "Elegir el motor más barato para los patrones: solo una búsqueda de subcadenas para los literales, sin la expresión regular, y PCRE2 para los que tienen lookaround o referencias hacia atrás (con la característica pcre2) (ver --debug)"),
+        ("engine", "Buscar con ENGINE: default (el crate regex, en tiempo lineal en la línea) o pcre2 (con lookaround y referencias hacia atrás, con backtracking; necesita la característica pcre2 y libpcre2-8)"),
+        ("debug", "Imprimir información de depuración en stderr, como qué motor busca los patrones"),
+        ("count", "Imprimir el número de líneas coincidentes de cada archivo"),
+        ("count_matches", "Imprimir el número de coincidencias de cada archivo (una línea con tres coincidencias cuenta como tres)"),
+        ("files_with_matches", "Mostrar solo las rutas de los archivos con coincidencias"),
+        ("min_matches", "Mostrar solo los archivos con al menos N líneas coincidentes (o N coincidencias, con --count-matches)"),
+        ("min_density", "Mostrar solo los archivos en los que coincide al menos PERCENT de las líneas, por ejemplo 50%"),
+        ("color", "Cuándo colorear las coincidencias (auto también sigue NO_COLOR, CLICOLOR_FORCE y FORCE_COLOR)"),
+        ("stats", "Imprimir estadísticas tras la búsqueda: líneas coincidentes, coincidencias, archivos con coincidencias, archivos y bytes buscados, y tiempo empleado"),
+        ("exit_summary", "Terminar con una línea en stderr para scripts, sea cual sea el formato de salida: simple-grep: matched=N files=N errors=N elapsed=SECONDSs"),
+        ("by_size", "Con --stats, imprimir también los archivos, bytes, tiempo y coincidencias de los archivos de hasta 4K, 64K, 1M y de más de 1M"),
+        ("sample_matches", "Imprimir solo N líneas coincidentes elegidas al azar de toda la búsqueda, y el total en stderr"),
+        ("column_names", "En archivos CSV y TSV, imprimir la fila de encabezado una vez encima de las coincidencias de cada archivo, y los nombres de las columnas donde están las coincidencias antes de cada línea"),
+        ("with_header_line", "Imprimir también las primeras N líneas de cada archivo con coincidencias una vez, antes de sus coincidencias (como la fila de encabezado de un CSV o un título, para entenderlas)"),
+        ("heading", "Imprimir la ruta del archivo una vez encima de sus resultados, en vez de antes de cada línea"),
+        ("column", "Imprimir el número de línea y la columna (desde 1) de la primera coincidencia antes de cada línea, como `12:5: ...`"),
+        ("vimgrep", "Imprimir cada coincidencia como `archivo:línea:columna:texto`, para la lista quickfix de Vim y otros editores"),
+        ("merge_adjacent", "Imprimir las líneas coincidentes consecutivas como un bloque bajo un único rango `inicio-fin:`"),
+        ("flexible_space", "Hacer que cada espacio del patrón coincida con cualquier secuencia de espacios en blanco, como para frases partidas o alineadas de otra forma"),
+        ("word", "Coincidir solo con palabras completas"),
+        ("line_regexp", "Coincidir solo si coincide la línea entera"),
+        ("starts_with", "Coincidir solo al principio de una palabra (como \\bPATTERN)"),
+        ("ends_with", "Coincidir solo al final de una palabra (como PATTERN\\b)"),
+        ("fixed_strings", "Tratar los patrones como texto literal en lugar de regex"),
+        ("recursive", "Buscar en directorios"),
+        ("cache_file_list", "Guardar en una caché los archivos encontrados y solo recorrer otra vez los directorios cuando alguno cambie (con -r, para sistemas de archivos lentos o de red)"),
+        ("max_filesize", "Omitir los archivos de más de SIZE (como 10M o 2GiB)"),
+        ("timeout", "Dejar de buscar tras DURATION (como 500ms, 30s o 2m), conservando los resultados obtenidos"),
+        ("threads", "Buscar en N archivos a la vez (uno por CPU por defecto), imprimiendo los resultados en el mismo orden"),
+        ("io_parallelism", "Dar a cada dispositivo su propia cola de archivos cuando las rutas buscadas están en varios, con los N hilos repartidos entre ellas (per-device), para que un disco lento no frene a uno rápido, o usar una sola cola (global)"),
+        ("sort", "Buscar (e imprimir) los archivos en este orden, en lugar del orden en que se encuentran"),
+        ("max_depth", "Bajar como mucho N directorios por debajo de cada ruta con -r (1 busca solo en los archivos que contiene directamente)"),
+        ("columns", "Buscar solo dentro de estas columnas de caracteres de cada línea (desde 1, inclusivas), p. ej. 1-80"),
+        ("unless_near", "Descartar las coincidencias que tengan cerca una línea que coincida con este patrón"),
+        ("within", "Cuántas líneas antes y después de una coincidencia cuentan como cerca para --unless-near"),
+        ("include", "Buscar solo en archivos cuyo nombre (o ruta, para globs con /) coincida con este glob, como '*.{rs,toml}' o 'src/**/*.rs' (se puede repetir)"),
+        ("file_type", "Buscar solo en archivos de este tipo, p. ej. rust o py (se puede repetir)"),
+        ("type_not", "Omitir los archivos de este tipo (se puede repetir, tiene prioridad sobre --type)"),
+        ("exclude", "Omitir los archivos cuyo nombre (o ruta, para globs con /) coincida con este glob (se puede repetir, tiene prioridad sobre --include)"),
+        ("canonical_paths", "Mostrar la ruta real de cada archivo, con los enlaces simbólicos resueltos"),
+        ("symlink_targets", "Mostrar los archivos encontrados a través de un enlace simbólico como `LINK -> TARGET`, para poder editar el archivo real"),
+        ("label_root", "Mostrar las rutas bajo PATH como NAME seguido del resto de la ruta (se puede repetir, para comparar varias raíces)"),
+        ("with_filename", "Imprimir el nombre del archivo en cada coincidencia (por defecto al buscar en más de un archivo)"),
+        ("no_filename", "No imprimir nunca nombres de archivo"),
+        ("null", "Terminar los nombres de archivo con un byte NUL en lugar de `:` (para xargs -0)"),
+        ("help", "Mostrar la ayuda"),
+        ("version", "Mostrar la versión"),
+        ("help_long", "Imprimir la ayuda completa, seguida de ejemplos de uso para tareas comunes"),
+        ("json", "Imprimir JSON legible por máquinas: resultados como JSON Lines, o detalles con --version"),
+        ("todos", "Informar de las marcas TODO/FIXME/HACK agrupadas por responsable y archivo"),
+        ("compare", "Comparar las coincidencias de dos árboles, mostrando las que solo están en OLD (-) o solo en NEW (+)"),
+        ("tui", "Buscar de forma interactiva: escribir el patrón, elegir un resultado con las flechas y pulsar Enter para mostrarlo (o Ctrl-E para abrirlo en $EDITOR)"),
+        ("fuzzy", "Mostrar las líneas más parecidas al patrón en vez de las que coinciden: sus caracteres en orden, mejor al inicio de palabras y juntos (como srchcfg para «search config»)"),
+        ("fuzzy_limit", "Cuántas de las mejores líneas muestra --fuzzy"),
+        ("output", "Escribir los resultados en SINK en vez de mostrarlos: shell muestra cada uno como la ruta y la línea entre comillas de shell separadas por un tabulador, para bucles while read, y sqlite:PATH los añade a un archivo de base de datos con sqlite3 (necesita la característica sqlite)"),
+        ("watch", "Buscar otra vez cada vez que cambien los archivos (comprobándolos periódicamente), limpiando la terminal y mostrando los nuevos resultados"),
+        ("watch_interval", "Cada cuánto comprueba --watch si han cambiado los archivos"),
+        ("duplicates", "Informar de las líneas (que coincidan con el patrón, si se da) que aparecen más de N veces, con sus ubicaciones"),
+        ("search_archives", "Buscar en los archivos dentro de archivadores .zip, .tar y .tar.gz, mostrados como archivo.zip!ruta/dentro.txt"),
+        ("archive_depth", "Abrir archivadores dentro de archivadores hasta N niveles, contando el exterior (con --search-archives)"),
+        ("archive_max_size", "Sacar como mucho SIZE de cada archivador, incluidos los anidados, y omitir el resto (y los archivadores de más de SIZE) con un aviso (con --search-archives)"),
+        ("search_zip", "Buscar en los archivos .gz, .bz2, .xz y .zst descomprimidos, con gzip, bzip2, xz o zstd (-z es --null-data, como en grep)"),
+        ("pre", "Buscar en lo que COMMAND imprime para cada archivo en lugar del archivo mismo, como pdftotext (se ejecuta con la ruta como argumento y el archivo en stdin)"),
+        ("pre_glob", "Ejecutar --pre solo en los archivos cuyo nombre (o ruta, para globs con /) coincide con este glob (se puede repetir)"),
+        ("record_size", "Dividir los archivos en registros de N bytes, que se buscan, numeran y cuentan como líneas (para archivos de registros de longitud fija sin saltos de línea)"),
+        ("null_data", "Las líneas terminan en NUL en lugar de salto de línea (p. ej. para la salida de `find -print0`), y las líneas coincidentes se imprimen igual"),
+        ("binary_files", "Cómo tratar los archivos binarios"),
+        ("encoding", "Codificación de los archivos (auto lee como UTF-16 los archivos con una marca de orden de bytes UTF-16)"),
+        ("output_buffer_lines", "Máximo de líneas de resultados retenidas mientras stdout tarda en aceptarlas, antes de que la búsqueda espere"),
+        ("only_binary", "Buscar solo en archivos binarios, mostrando una ventana hexadecimal alrededor de cada coincidencia (para buscar cadenas en firmware)"),
+        ("no_ignore", "Buscar también en los archivos ignorados por archivos .gitignore, .ignore y .simplegrepignore con -r"),
+        ("hidden", "Buscar también en archivos y directorios ocultos (que empiezan por .) con -r, que por defecto se omiten"),
+        ("follow", "Seguir los enlaces simbólicos con -r (los bucles de enlaces se omiten con un aviso)"),
+        ("full_matches", "Imprimir las coincidencias enteras, aunque sean enormes"),
+        ("global_dedupe", "No imprimir líneas de resultados ya impresas desde otro archivo"),
+        ("dedupe_bloom", "Recordar las líneas impresas para --global-dedupe en un filtro de Bloom de SIZE (como 64M), limitando la memoria (algunas líneas pueden descartarse por error)"),
+        ("line_cache", "Recordar las coincidencias de hasta N líneas distintas de cada archivo, para no volver a buscar en las líneas repetidas (como en los logs)"),
+        ("min_entropy", "Conservar solo las coincidencias cuyo texto tenga al menos esta entropía en bits por byte, para encontrar secretos (las claves aleatorias rondan 4 a 6, las palabras 2 a 3)"),
+        ("strings", "Buscar en las cadenas imprimibles de los archivos binarios (como la herramienta strings), una por línea"),
+        ("strings_min", "Longitud mínima de una cadena con --strings"),
+        ("raw", "Buscar en cada byte de cada archivo: sin archivos de ignorados, omisión de archivos ocultos, detección de binarios, filtros de tipo, smart case, color ni decodificación UTF-8 (los bytes se leen como Latin-1)"),
+        ("text", "Buscar en los archivos binarios como texto (igual que --binary-files=text)"),
+        ("replace", "Mostrar las coincidencias reemplazadas por TEMPLATE, que puede usar grupos de captura como $1 o $name (los archivos no se modifican)"),
+        ("replace_rule", "Reemplazar las coincidencias de PATTERN por TEMPLATE, para cada regla por orden, en una sola pasada por cada línea (se puede repetir)"),
+        ("replace_rules", "Leer reglas de --replace-rule de un archivo, una por línea (se ignoran las líneas vacías y las que empiezan por #)"),
+        ("preserve_case", "Reemplazar las coincidencias en su propio caso: con foo => bar, Foo pasa a Bar y FOO a BAR (para renombrar con -i)"),
+        ("write", "Reescribir los archivos con los reemplazos de --replace (o --replace-rule) en vez de mostrarlos, e indicar cuánto cambió"),
+        ("require_clean_git", "Con --write, solo reescribir archivos confirmados en git sin cambios pendientes (los demás se omiten con un aviso)"),
+        ("redact", "Ocultar cada coincidencia con **** en la salida, para informes compartibles de búsquedas de secretos"),
+        ("hash_lines", "Mostrar un hash con sal de cada línea coincidente, con su número de línea y las longitudes de las coincidencias, en vez de la línea (para compartir resultados sin el código)"),
+        ("hash_salt", "Sal para --hash-lines, para poder comparar los hashes entre ejecuciones (aleatoria por defecto)"),
+        ("no_messages", "No avisar de los archivos que no se pueden leer (el modo recursivo los omite)"),
+        ("accessible", "Salida para lectores de pantalla: sin color, cada resultado con su etiqueta y los totales en frases"),
+        ("record", "Guardar la búsqueda (opciones, archivos buscados con sus tamaños y fechas) en este archivo tar, para informar de errores"),
+        ("record_snippets", "Guardar también los resultados impresos con --record (pueden contener datos privados)"),
+        ("replay", "Repetir una búsqueda guardada con --record, sobre los mismos archivos"),
+        ("lang", "Idioma de los mensajes (por defecto, según LANG)"),
+        ("search", "Buscar un patrón en archivos (por defecto)"),
+        ("examples", "Imprimir ejemplos de uso para tareas comunes"),
+        ("check", "Comprobar que los patrones son válidos, sin buscar (todos los argumentos posicionales son patrones)"),
+        ("index", "Guardar los archivos de las rutas para búsquedas posteriores con --cache-file-list y las mismas opciones (todos los argumentos posicionales son rutas)"),
+        ("serve", "Responder búsquedas en las rutas por HTTP en /search?q=PATRÓN, como JSON Lines (todos los argumentos posicionales son rutas)"),
+        ("listen", "Dirección donde escuchar, como 127.0.0.1:7878 (el puerto 0 elige uno libre)"),
+        ("types", "Imprimir los tipos de archivo de --type y --type-not, con los globs de sus nombres"),
+    ],
+};
+
+impl Lang {
+    pub(crate) fn catalog(self) -> &'static Catalog {
+        match self {
+            Lang::En => &EN,
+            Lang::Es => &ES,
+        }
+    }
+
+    /// Language from the locale variables (`LC_ALL`, then `LC_MESSAGES`, then `LANG`), English if unset or unknown
+    #[must_use]
+    pub fn from_env() -> Lang {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Lang::from_locale(&value))
+            .unwrap_or_default()
+    }
+
+    /// Language of a locale like `es_ES.UTF-8`
+    fn from_locale(locale: &str) -> Option<Lang> {
+        let language = locale.split(['_', '.', '@', '-']).next().unwrap_or_default();
+
+        Lang::from_str(language, true).ok()
+    }
+
+    /// Language chosen with `--lang` in raw arguments, for localizing help before parsing
+    #[must_use]
+    pub fn from_args(args: &[OsString]) -> Option<Lang> {
+        let mut args = args.iter().map(|arg| arg.to_string_lossy());
+
+        while let Some(arg) = args.next() {
+            let value = match arg.strip_prefix("--lang") {
+                Some("") => args.next(),
+                Some(value) if value.starts_with('=') => Some(value[1..].to_string().into()),
+                _ => continue,
+            };
+
+            return value.and_then(|value| Lang::from_str(&value, true).ok());
+        }
+
+        None
+    }
+
+    /// Label before error messages, e.g. `Application error`
+    #[must_use]
+    pub fn application_error(self) -> &'static str {
+        self.catalog().application_error
+    }
+}
+
+impl Catalog {
+    /// `N times` in this language
+    pub(crate) fn times(&self, count: usize) -> String {
+        fill(self.times, &[("count", &count.to_string())])
+    }
+
+    fn help(&self, id: &str) -> Option<&'static str> {
+        self.help.iter().find(|(key, _)| *key == id).map(|(_, help)| *help)
+    }
+}
+
+/// `template` with each `{name}` replaced by its value
+///
+/// Done in one pass, so values (like matched lines) containing `{name}` are left alone
+pub(crate) fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        filled.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        let value = rest
+            .find('}')
+            .and_then(|close| values.iter().find(|(name, _)| *name == &rest[1..close]).map(|(_, value)| (close, value)));
+
+        if let Some((close, value)) = value {
+            filled.push_str(value);
+            rest = &rest[close + 1..];
+        } else {
+            filled.push('{');
+            rest = &rest[1..];
+        }
+    }
+
+    filled.push_str(rest);
+
+    filled
+}
+
+/// The command-line interface with help in `lang`
+///
+/// Messages generated by clap itself (like parse errors) stay in English
+#[must_use]
+pub fn localized_command(command: clap::Command, lang: Lang) -> clap::Command {
+    if lang == Lang::En {
+        return command;
+    }
+
+    let catalog = lang.catalog();
+
+    // Every command, so `search --help` is translated too
+    let localize = |command: clap::Command| {
+        let mut order = 0; // Headings would otherwise sort positionals by name
+
+        command
+            .help_template(format!(
+                "{{before-help}}{{about-with-newline}}\n{} {{usage}}\n\n{{all-args}}{{after-help}}",
+                catalog.usage_heading,
+            ))
+            .subcommand_help_heading(catalog.commands_heading)
+            .mut_args(|arg| {
+                let heading = if arg.is_positional() { catalog.arguments_heading } else { catalog.options_heading };
+                order += 1;
+                let arg = arg.help_heading(heading).display_order(order);
+
+                match catalog.help(arg.get_id().as_str()) {
+                    Some(help) => arg.help(help),
+                    None => arg,
+                }
+            })
+    };
+
+    let names: Vec<String> = command.get_subcommands().map(|sub| sub.get_name().to_string()).collect();
+
+    let mut command = localize(command.about(catalog.about));
+
+    for name in names {
+        command = command.mut_subcommand(&name, |sub| {
+            let about = catalog.help(&name);
+            let sub = localize(sub);
+
+            match about {
+                Some(about) => sub.about(about),
+                None => sub,
+            }
+        });
+    }
+
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_locale() {
+        assert_eq!(Lang::from_locale("es_ES.UTF-8"), Some(Lang::Es));
+        assert_eq!(Lang::from_locale("en_US"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("C"), None);
+    }
+
+    #[test]
+    fn test_from_args() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<OsString>>();
+
+        assert_eq!(Lang::from_args(&args(&["simple-grep", "--lang", "es", "x", "a"])), Some(Lang::Es));
+        assert_eq!(Lang::from_args(&args(&["simple-grep", "--lang=es"])), Some(Lang::Es));
+        assert_eq!(Lang::from_args(&args(&["simple-grep", "x", "a"])), None);
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(fill("File {file}: {text}", &[("file", "a"), ("text", "{file} {x}")]), "File a: {file} {x}");
+        assert_eq!(fill("{unknown} {", &[]), "{unknown} {");
+    }
+
+    #[test]
+    fn test_times() {
+        assert_eq!(Lang::En.catalog().times(3), "3 times");
+        assert_eq!(Lang::Es.catalog().times(3), "3 veces");
+    }
+
+    #[test]
+    fn test_every_spanish_help_is_for_a_real_argument() {
+        let command = <crate::Cli as clap::CommandFactory>::command();
+
+        for (id, _) in ES.help {
+            let known = command.get_subcommands().flat_map(clap::Command::get_arguments).any(|arg| arg.get_id() == id)
+                || command.find_subcommand(id).is_some();
+
+            assert!(known, "no argument or subcommand `{id}`");
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Ignore file rules for recursive search (turned off with `--no-ignore`)
+//!
+//! Each directory's `.gitignore`, `.ignore` and `.simplegrepignore` apply to everything below
+//! it. In one directory, `.ignore` wins over `.gitignore` and `.simplegrepignore` wins over
+//! both. Rules in deeper directories win over rules further up, like in git. Only directories
+//! inside the searched paths are read.
+
+// Standard library
+use std::fs; // For reading ignore files
+use std::path::{Path, PathBuf}; // For paths relative to an ignore file
+
+// External crates
+use walkdir::DirEntry; // For walked entries
+
+// My stuff
+use crate::glob::Glob;
+
+/// Ignore files read in each directory, from lowest to highest precedence
+///
+/// Rules are read in this order and the last match wins, so later files override earlier ones
+pub(crate) const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".simplegrepignore"];
+
+/// One line of an ignore file
+#[derive(Debug)]
+struct Rule {
+    glob: Glob, // Anchored if it has a slash, so it matches the path from the ignore file's directory instead of any name
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Rule {
+    /// The rule on `line`, if it isn't blank or a comment
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)), // For a literal `#` or `!`
+        };
+
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        let glob = Glob::ignore_rule(line.trim_start_matches('/'), line.contains('/'));
+
+        Some(Rule { glob, negated, dir_only })
+    }
+
+    /// Whether the rule matches `path` (components relative to the ignore file's directory)
+    fn matches(&self, path: &[&str], is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        self.glob.components_match(path)
+    }
+}
+
+/// Rules from one directory's ignore files, in order
+#[derive(Debug)]
+struct Level {
+    depth: usize,
+    dir: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl Level {
+    /// Whether `path` is ignored (`Some(true)`) or un-ignored by a `!` rule (`Some(false)`), if any rule matches
+    ///
+    /// The last matching rule wins
+    fn ignores(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        let relative: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+        let relative: Vec<&str> = relative.iter().map(String::as_str).collect();
+
+        self.rules.iter().rev().find(|rule| rule.matches(&relative, is_dir)).map(|rule| !rule.negated)
+    }
+}
+
+/// Ignore rules of the directories above the current entry of a walk
+///
+/// Entries have to be checked in walk order, since that's when directories' rules are read
+#[derive(Debug, Default)]
+pub(crate) struct Ignores {
+    levels: Vec<Level>,
+}
+
+impl Ignores {
+    /// Whether the entry is ignored, reading its rules if it's a directory that isn't
+    ///
+    /// The walked paths themselves (depth 0) are never ignored
+    pub(crate) fn is_ignored(&mut self, entry: &DirEntry) -> bool {
+        let depth = entry.depth();
+        self.levels.retain(|level| level.depth < depth); // Leave directories the walk is done with
+
+        let is_dir = entry.file_type().is_dir();
+
+        let ignored = depth > 0
+            && self
+                .levels
+                .iter()
+                .rev() // Deeper ignore files win
+                .find_map(|level| level.ignores(entry.path(), is_dir))
+                .unwrap_or(false);
+
+        if is_dir && !ignored {
+            let rules = read_rules(entry.path());
+
+            if !rules.is_empty() {
+                self.levels.push(Level { depth, dir: entry.path().to_path_buf(), rules });
+            }
+        }
+
+        ignored
+    }
+}
+
+/// Rules from the ignore files in `dir`, missing or unreadable ones counting as empty
+fn read_rules(dir: &Path) -> Vec<Rule> {
+    IGNORE_FILES
+        .iter()
+        .filter_map(|name| fs::read_to_string(dir.join(name)).ok())
+        .flat_map(|contents| contents.lines().filter_map(Rule::parse).collect::<Vec<_>>())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    fn level(contents: &str) -> Level {
+        Level { depth: 0, dir: PathBuf::from("repo"), rules: contents.lines().filter_map(Rule::parse).collect() }
+    }
+
+    #[test]
+    fn test_rule_parse_skips_blanks_and_comments() {
+        assert!(Rule::parse("").is_none());
+        assert!(Rule::parse("   ").is_none());
+        assert!(Rule::parse("# comment").is_none());
+        assert!(Rule::parse("\\#file").unwrap().matches(&["#file"], false));
+    }
+
+    #[test]
+    fn test_rules_names_anywhere() {
+        let rules = level("*.log\ntarget/\n");
+
+        assert_eq!(rules.ignores(Path::new("repo/a/b/debug.log"), false), Some(true));
+        assert_eq!(rules.ignores(Path::new("repo/crate/target"), true), Some(true));
+        assert_eq!(rules.ignores(Path::new("repo/target"), false), None); // A file, not a directory
+        assert_eq!(rules.ignores(Path::new("repo/main.rs"), false), None);
+    }
+
+    #[test]
+    fn test_rules_anchored_and_double_star() {
+        let rules = level("/build\ndocs/*.html\nsrc/**/gen\n");
+
+        assert_eq!(rules.ignores(Path::new("repo/build"), true), Some(true));
+        assert_eq!(rules.ignores(Path::new("repo/sub/build"), true), None);
+        assert_eq!(rules.ignores(Path::new("repo/docs/index.html"), false), Some(true));
+        assert_eq!(rules.ignores(Path::new("repo/docs/api/index.html"), false), None);
+        assert_eq!(rules.ignores(Path::new("repo/src/gen"), true), Some(true));
+        assert_eq!(rules.ignores(Path::new("repo/src/a/b/gen"), true), Some(true));
+    }
+
+    #[test]
+    fn test_read_rules_precedence() {
+        let temp = TempDir::new("ignore-files");
+        let dir = temp.path();
+        temp.write(".gitignore", "*.log\n");
+        temp.write(".ignore", "!keep.log\nvendor/\n");
+        temp.write(".simplegrepignore", "!vendor/\n");
+
+        let level = Level { depth: 0, dir: dir.to_path_buf(), rules: read_rules(dir) };
+
+        assert_eq!(level.ignores(&dir.join("debug.log"), false), Some(true));
+        assert_eq!(level.ignores(&dir.join("keep.log"), false), Some(false)); // .ignore over .gitignore
+        assert_eq!(level.ignores(&dir.join("vendor"), true), Some(false)); // .simplegrepignore over .ignore
+    }
+
+    #[test]
+    fn test_rules_last_match_wins() {
+        let rules = level("*.log\n!keep.log\n");
+
+        assert_eq!(rules.ignores(Path::new("repo/debug.log"), false), Some(true));
+        assert_eq!(rules.ignores(Path::new("repo/keep.log"), false), Some(false));
+    }
+}
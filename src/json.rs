@@ -0,0 +1,61 @@
+//! Minimal JSON writing helpers
+
+// Standard library
+use std::fmt::Write; // For writing into strings
+
+/// Quoted JSON string with special characters escaped
+pub(crate) fn string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+
+    escaped.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32); // Writing to a String can't fail
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+
+    escaped
+}
+
+/// JSON array of strings
+pub(crate) fn string_array(values: &[&str]) -> String {
+    let items: Vec<String> = values.iter().map(|value| string(value)).collect();
+
+    format!("[{}]", items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_escapes_special_characters() {
+        assert_eq!(string("plain"), "\"plain\"");
+        assert_eq!(string("a \"quote\""), "\"a \\\"quote\\\"\"");
+        assert_eq!(string("back\\slash"), "\"back\\\\slash\"");
+        assert_eq!(string("line\nbreak\ttab"), "\"line\\nbreak\\ttab\"");
+        assert_eq!(string("\u{1b}[0m"), "\"\\u001b[0m\"");
+    }
+
+    #[test]
+    fn test_string_keeps_unicode() {
+        assert_eq!(string("héllo ✓"), "\"héllo ✓\"");
+    }
+
+    #[test]
+    fn test_string_array() {
+        assert_eq!(string_array(&[]), "[]");
+        assert_eq!(string_array(&["a", "b\"c"]), "[\"a\",\"b\\\"c\"]");
+    }
+}
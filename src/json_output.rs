@@ -0,0 +1,367 @@
+//! JSON Lines output (`--json`), for editors and other tools
+//!
+//! One object per line, each with a `type`:
+//!
+//! - `begin`: a file with matches starts (`path`)
+//! - `header`: one of the file's first lines with `--with-header-line`, right after `begin`
+//!   (`path`, `line_number`, `line`)
+//! - `match`: a matching line (`path`, `line_number`, `line`, `spans` with `start`, `end` and `text`,
+//!   `record_offset` with `--record-size`, where the record starts in the file, and `columns` with
+//!   `--column-names`, the names of the columns the matches are in), or a match in a binary file
+//!   with `--only-binary` (`path`, `offset`, `text`)
+//! - `end`: the file is done (`path`, `matched_lines`, `matches`, and `binary` for binary files)
+//! - `progress`: how far the search has got, every so often (`files`, `bytes`, `elapsed_ms`)
+//! - `summary`: the totals, last (`files`, `files_with_matches`, `matched_lines`, `matches`, `bytes`, `elapsed_ms`)
+//!
+//! Records about a file also have `root` when it's under a `--label-root`, `target` when it
+//! was found through a symbolic link with `--symlink-targets`, and `decoded` when its contents
+//! were decompressed or transcoded before searching (the steps in order, like `["gzip","UTF-16LE"]`,
+//! since line numbers are then those of the decoded text). With `--hash-lines`, lines and
+//! match text are left out and each match has a `hash` instead.
+
+// Standard library
+use std::io::{self, Write}; // For writing results
+use std::ops::Range; // For match spans
+
+// My stuff
+use crate::delimited::{column_names, delimiter};
+use crate::error::Error;
+use crate::json;
+use crate::printer::{line_hash, Printer};
+use crate::search::{Match, Progress};
+use crate::{Argument, PathDisplay};
+
+/// Prints results as JSON Lines
+#[derive(Debug)]
+pub struct JsonPrinter<W> {
+    writer: W,
+    paths: PathDisplay,
+    hash_salt: Option<String>,
+    files_with_matches: usize,
+    matched_lines: usize,
+    matches: usize,
+    progress: Option<Progress>,
+    /// How the file about to be printed was decoded
+    decoded: Vec<String>,
+    /// First lines of the file about to be printed, with `--with-header-line` (or `--column-names`)
+    header: Vec<Match>,
+    column_names: bool,
+    record_size: Option<u64>,
+}
+
+impl<W: Write> JsonPrinter<W> {
+    /// Printer for the output options in `config`, writing to `writer`
+    pub fn new(config: &Argument, writer: W) -> Self {
+        JsonPrinter {
+            writer,
+            paths: config.path_display(),
+            hash_salt: config.hash_salt(),
+            files_with_matches: 0,
+            matched_lines: 0,
+            matches: 0,
+            progress: None,
+            decoded: Vec::new(),
+            header: Vec::new(),
+            column_names: config.column_names,
+            record_size: config.record_size,
+        }
+    }
+
+    /// Give back the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// `"path":...` and the `root`, `target` and `decoded` fields, for records about a file
+    fn path_fields(&self, path: &str) -> String {
+        let root = self.paths.root_label(path).map(|(root, _)| format!(",\"root\":{}", json::string(root)));
+        let target = self.paths.symlink_target(path).map(|target| format!(",\"target\":{}", json::string(&target)));
+        let decoded = (!self.decoded.is_empty()).then(|| {
+            let steps: Vec<String> = self.decoded.iter().map(|step| json::string(step)).collect();
+            format!(",\"decoded\":[{}]", steps.join(","))
+        });
+
+        format!(
+            "\"path\":{}{}{}{}",
+            json::string(&self.paths.resolve(path)),
+            root.unwrap_or_default(),
+            target.unwrap_or_default(),
+            decoded.unwrap_or_default(),
+        )
+    }
+
+    fn write_file(&mut self, path: &str, matches: &[Match], binary: bool) -> io::Result<()> {
+        if matches.is_empty() {
+            return Ok(());
+        }
+
+        let path_fields = self.path_fields(path);
+        let count: usize = matches.iter().map(|found| found.spans().len()).sum();
+
+        self.files_with_matches += 1;
+        self.matched_lines += matches.len();
+        self.matches += count;
+
+        writeln!(self.writer, "{{\"type\":\"begin\",{path_fields}}}")?;
+
+        let header = std::mem::take(&mut self.header);
+
+        for line in &header {
+            writeln!(
+                self.writer,
+                "{{\"type\":\"header\",{path_fields},\"line_number\":{},\"line\":{}}}",
+                line.line_number(),
+                json::string(line.line()),
+            )?;
+        }
+
+        // The columns of a CSV or TSV file are named by its first line
+        let columns = if self.column_names { delimiter(path).zip(header.first()) } else { None };
+
+        // Header lines that match are only written once, as header records
+        let header_end = header.last().map_or(0, Match::line_number);
+
+        // Binary files' lines aren't worth printing, like in text output
+        if !binary {
+            for found in matches.iter().filter(|found| found.line_number() > header_end) {
+                let names = columns.map(|(delimiter, header)| {
+                    let names: Vec<String> = column_names(header.line(), found.line(), found.spans(), delimiter).iter().map(|name| json::string(name)).collect();
+                    format!(",\"columns\":[{}]", names.join(","))
+                });
+
+                writeln!(self.writer, "{{\"type\":\"match\",{path_fields},{}{}}}", self.match_fields(found), names.unwrap_or_default())?;
+            }
+        }
+
+        let binary = if binary { ",\"binary\":true" } else { "" };
+
+        writeln!(
+            self.writer,
+            "{{\"type\":\"end\",{path_fields},\"matched_lines\":{},\"matches\":{count}{binary}}}",
+            matches.len(),
+        )
+    }
+
+    /// `line_number`, `line` and `spans` (or `hash` and `spans` without text, with `--hash-lines`)
+    fn match_fields(&self, found: &Match) -> String {
+        let spans: Vec<String> = found
+            .spans()
+            .iter()
+            .map(|span| match &self.hash_salt {
+                Some(_) => format!("{{\"start\":{},\"end\":{}}}", span.start, span.end),
+                None => format!(
+                    "{{\"start\":{},\"end\":{},\"text\":{}}}",
+                    span.start,
+                    span.end,
+                    json::string(&found.line()[span.clone()]),
+                ),
+            })
+            .collect();
+
+        let line = match &self.hash_salt {
+            Some(salt) => format!("\"hash\":\"{:016x}\"", line_hash(found, salt)),
+            None => format!("\"line\":{}", json::string(found.line())),
+        };
+
+        let record_offset = self.record_size.map(|size| format!(",\"record_offset\":{}", (found.line_number() as u64 - 1) * size));
+
+        format!("\"line_number\":{}{},{line},\"spans\":[{}]", found.line_number(), record_offset.unwrap_or_default(), spans.join(","))
+    }
+
+    fn write_binary_matches(&mut self, path: &str, bytes: &[u8], spans: &[Range<usize>]) -> io::Result<()> {
+        if spans.is_empty() {
+            return Ok(());
+        }
+
+        let path_fields = self.path_fields(path);
+
+        self.files_with_matches += 1;
+        self.matches += spans.len();
+
+        writeln!(self.writer, "{{\"type\":\"begin\",{path_fields}}}")?;
+
+        for span in spans {
+            let text = String::from_utf8_lossy(&bytes[span.clone()]);
+
+            writeln!(
+                self.writer,
+                "{{\"type\":\"match\",{path_fields},\"offset\":{},\"text\":{}}}",
+                span.start,
+                json::string(&text),
+            )?;
+        }
+
+        writeln!(self.writer, "{{\"type\":\"end\",{path_fields},\"matches\":{},\"binary\":true}}", spans.len())
+    }
+
+    fn write_summary(&mut self) -> io::Result<()> {
+        let (files, bytes, elapsed) = self
+            .progress
+            .map_or((0, 0, 0), |progress| (progress.files(), progress.bytes(), progress.elapsed().as_millis()));
+
+        writeln!(
+            self.writer,
+            "{{\"type\":\"summary\",\"files\":{files},\"files_with_matches\":{},\"matched_lines\":{},\"matches\":{},\"bytes\":{bytes},\"elapsed_ms\":{elapsed}}}",
+            self.files_with_matches,
+            self.matched_lines,
+            self.matches,
+        )
+    }
+}
+
+impl<W: Write> Printer for JsonPrinter<W> {
+    fn file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        let written = self.write_file(path, matches, false);
+        self.decoded.clear();
+        written.map_err(Error::Output)
+    }
+
+    fn binary_file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        let written = self.write_file(path, matches, true);
+        self.decoded.clear();
+        written.map_err(Error::Output)
+    }
+
+    fn binary_matches(&mut self, path: &str, bytes: &[u8], spans: &[Range<usize>]) -> Result<(), Error> {
+        self.decoded.clear(); // Offsets are in the raw bytes
+        self.write_binary_matches(path, bytes, spans).map_err(Error::Output)
+    }
+
+    fn decoded(&mut self, _path: &str, steps: &[&str]) -> Result<(), Error> {
+        self.decoded = steps.iter().map(ToString::to_string).collect();
+        Ok(())
+    }
+
+    fn header(&mut self, _path: &str, lines: &[Match]) -> Result<(), Error> {
+        self.header = lines.to_vec();
+        Ok(())
+    }
+
+    fn progress(&mut self, progress: &Progress) -> Result<(), Error> {
+        self.progress = Some(*progress);
+
+        writeln!(
+            self.writer,
+            "{{\"type\":\"progress\",\"files\":{},\"bytes\":{},\"elapsed_ms\":{}}}",
+            progress.files(),
+            progress.bytes(),
+            progress.elapsed().as_millis(),
+        )
+        .map_err(Error::Output)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.write_summary().and_then(|()| self.writer.flush()).map_err(Error::Output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_root_label, Searcher};
+
+    /// Every line `config` prints for each `(path, contents)`, summary included
+    fn print(config: Argument, files: &[(&str, &str)]) -> Vec<String> {
+        let mut printer = JsonPrinter::new(&config, Vec::new());
+        let searcher = Searcher::new(config).unwrap();
+
+        for (path, contents) in files {
+            printer.file(path, &searcher.search_str(contents)).unwrap();
+        }
+
+        printer.finish().unwrap();
+
+        String::from_utf8(printer.into_inner()).unwrap().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_json_records() {
+        let config = Argument { pattern: Some(String::from("o")), ..Default::default() };
+
+        let result = print(config, &[("a.txt", "one \"two\"\nsix\n"), ("b.txt", "six\n")]);
+
+        assert_eq!(result, vec![
+            r#"{"type":"begin","path":"a.txt"}"#,
+            r#"{"type":"match","path":"a.txt","line_number":1,"line":"one \"two\"","spans":[{"start":0,"end":1,"text":"o"},{"start":7,"end":8,"text":"o"}]}"#,
+            r#"{"type":"end","path":"a.txt","matched_lines":1,"matches":2}"#,
+            r#"{"type":"summary","files":0,"files_with_matches":1,"matched_lines":1,"matches":2,"bytes":0,"elapsed_ms":0}"#,
+        ]);
+    }
+
+    #[test]
+    fn test_json_decoded() {
+        let config = Argument { pattern: Some(String::from("x")), ..Default::default() };
+        let mut printer = JsonPrinter::new(&config, Vec::new());
+        let matches = Searcher::new(config).unwrap().search_str("x\n");
+
+        printer.decoded("a.gz", &["gzip", "UTF-16LE"]).unwrap();
+        printer.file("a.gz", &matches).unwrap();
+        printer.file("b.txt", &matches).unwrap();
+
+        let output = String::from_utf8(printer.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines[0], r#"{"type":"begin","path":"a.gz","decoded":["gzip","UTF-16LE"]}"#);
+        assert!(lines[1].starts_with(r#"{"type":"match","path":"a.gz","decoded":["gzip","UTF-16LE"],"line_number":1"#));
+        assert_eq!(lines[3], r#"{"type":"begin","path":"b.txt"}"#);
+    }
+
+    #[test]
+    fn test_json_record_offset() {
+        let config = Argument { pattern: Some(String::from("x")), record_size: Some(80), ..Default::default() };
+        let mut printer = JsonPrinter::new(&config, Vec::new());
+        let matches = Searcher::new(config).unwrap().search_str("a\nb\nx\n");
+
+        printer.file("a.dat", &matches).unwrap();
+
+        let output = String::from_utf8(printer.into_inner()).unwrap();
+        assert!(output.lines().nth(1).unwrap().contains(r#""line_number":3,"record_offset":160,"line":"x""#));
+    }
+
+    #[test]
+    fn test_json_root_label() {
+        let config = Argument {
+            pattern: Some(String::from("x")),
+            label_root: vec![parse_root_label("old=/work/a").unwrap()],
+            ..Default::default()
+        };
+
+        let result = print(config, &[("/work/a/lib.rs", "x\n")]);
+
+        assert_eq!(result[0], r#"{"type":"begin","path":"/work/a/lib.rs","root":"old"}"#);
+    }
+
+    #[test]
+    fn test_json_hash_lines() {
+        let config = Argument {
+            pattern: Some(String::from("secret")),
+            hash_lines: true,
+            hash_salt: Some(String::from("salt")),
+            ..Default::default()
+        };
+
+        let result = print(config, &[("a.txt", "my secret\n")]);
+
+        assert!(result[1].contains(r#""hash":""#));
+        assert!(result[1].ends_with(r#""spans":[{"start":3,"end":9}]}"#));
+        assert!(!result[1].contains("my secret"));
+    }
+
+    #[test]
+    fn test_json_progress_and_summary() {
+        let config = Argument {
+            pattern: Some(String::from("fox")),
+            files: vec![String::from("./tests/test_2.txt")],
+            ..Default::default()
+        };
+        let mut printer = JsonPrinter::new(&config, Vec::new());
+
+        Searcher::new(config).unwrap().run(&mut printer).unwrap();
+
+        let output = String::from_utf8(printer.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert!(lines[lines.len() - 2].starts_with(r#"{"type":"progress","files":1,"#));
+        assert!(lines[lines.len() - 1].starts_with(r#"{"type":"summary","files":1,"files_with_matches":1,"#));
+    }
+}
@@ -1,248 +1,2408 @@
+//! Simple grep implementation written in Rust
+//!
+//! The command-line tool is built on a small library. Programs embedding it should use
+//! [`prelude`], which is the stable part of the API:
+//!
+//! ```no_run
+//! use simple_grep::prelude::*;
+//!
+//! let config = SearchConfig::try_from_args(["-i", "fox", "notes.txt"])?;
+//! let searcher = Searcher::new(config)?;
+//!
+//! for found in searcher.search_path("notes.txt")? {
+//!     println!("{}: {}", found.line_number(), found.line());
+//! }
+//! # Ok::<(), Error>(())
+//! ```
+//!
+//! Everything else that's public exists for the `simple-grep` binary and may change in any release.
+
 #![warn(clippy::all, clippy::pedantic)] // Linting
 
 // Standard library
+use std::cmp::Reverse; // For newest and largest first
 use std::error::Error; // For error handling
 use std::fs; // For file stuff
+use std::hash::{BuildHasher, RandomState}; // For random salts
+use std::io::{self, IsTerminal, Write}; // For detecting a terminal, and printing
+use std::num::NonZeroUsize; // For thread counts
+use std::ops::Deref; // For reading a SearchConfig's options
+use std::path::{Component, Path, PathBuf}; // For file names
+use std::thread; // For counting CPUs
+use std::time::Duration; // For --timeout
 
 // External crates
-use clap::Parser; // For command-line argument parsing
+use clap::{Args, Parser, Subcommand, ValueEnum}; // For command-line argument parsing
 use walkdir::WalkDir; // For directory traversal
 use regex::Regex; // For regular expressions
+use regex_syntax::ast::{self, Ast}; // For finding spaces in patterns
+
+// My stuff
+mod accessible; // Screen reader output
+mod archive; // Files inside archives
+mod args; // Splitting argument strings
+mod cache; // LRU cache
+mod columns; // Column ranges
+mod compare; // Comparing two trees
+mod decompress; // Compressed files
+mod dedupe; // Dropping repeated result lines
+mod delimited; // Columns of CSV and TSV files
+mod devices; // Devices files are on
+mod duplicates; // Duplicate line detector
+mod edit; // Rewriting files
+mod encoding; // UTF-16 and Latin-1 files
+mod error; // Library errors
+mod examples; // Example invocations
+mod file_list; // Cached file lists
+mod fuzzy; // Fuzzy matching
+mod git; // Git status of files
+mod glob; // Glob matching for file filters
+#[cfg(feature = "guess-encoding")]
+mod guess; // Guessing encodings without a byte order mark
+mod hexdump; // Hex windows for binary matches
+mod i18n; // Translated messages
+mod ignore; // Ignore file rules
+mod json; // JSON writing helpers
+mod json_output; // JSON Lines results
+mod matcher; // Compiled patterns
+mod output; // Bounded stdout writer
+#[cfg(all(feature = "pcre2", unix))]
+mod pcre2; // Matching with PCRE2
+mod printer; // Printing results
+mod record; // Recording and replaying searches
+mod rules; // Several replacements in one pass
+mod sample; // Sampling matching lines
+mod search; // Searching files into matches
+mod session; // Paging through results
+mod shell; // Shell-quoted results
+#[cfg(feature = "sqlite")]
+mod sqlite; // Results in a SQLite database
+mod stats; // Search statistics
+mod status; // Status on SIGUSR1
+#[cfg(test)]
+mod temp; // Temporary directories for tests
+mod serve; // Searching over HTTP
+mod todos; // TODO/FIXME/HACK report
+mod tui; // Interactive search in the terminal
+mod types; // File types
+mod units; // Sizes and durations
+mod version; // Version information
+mod watch; // Searching again on changes
+
+use columns::ColumnRange;
+use glob::Glob;
+use matcher::Patterns;
+
+pub use accessible::AccessiblePrinter;
+pub use compare::print_comparison;
+pub use duplicates::print_duplicates;
+pub use edit::write_replacements;
+pub use examples::{print_examples, print_long_help};
+pub use fuzzy::print_fuzzy;
+pub use i18n::{localized_command, Lang};
+pub use json_output::JsonPrinter;
+pub use printer::{Printer, StandardPrinter};
+pub use record::replay;
+pub use search::{search_file, LineMatch, Match, Progress, Searcher};
+pub use session::{Hit, SearchSession};
+pub use status::report_status_on_signal;
+pub use serve::serve;
+pub use todos::print_todo_report;
+pub use tui::run_tui;
+pub use types::print_types;
+pub use version::print_version;
+pub use watch::watch;
+
+/// The stable library API
+///
+/// Items here follow semver: they are only removed or changed incompatibly in a new major
+/// version (a new minor version while the crate is `0.x`). [`Error`](prelude::Error) may gain
+/// variants in any release, and [`SearchConfig`] may gain options.
+pub mod prelude {
+    pub use crate::accessible::AccessiblePrinter;
+    pub use crate::error::Error;
+    pub use crate::json_output::JsonPrinter;
+    pub use crate::printer::{Printer, StandardPrinter};
+    pub use crate::search::{search_file, LineMatch, Match, Progress, Searcher};
+    pub use crate::session::{Hit, SearchSession};
+    pub use crate::SearchConfig;
+}
+
+/// Search options, as parsed from the command line
+///
+/// A config is made by parsing arguments (or from an [`Argument`]), so options can be added without
+/// breaking the programs that make one. The options are read through the [`Argument`] it derefs to.
+#[derive(Debug, Clone, Default)]
+pub struct SearchConfig(Argument);
+
+impl SearchConfig {
+    /// Parse command-line style arguments (without the program name) into a normalized config
+    ///
+    /// # Errors
+    ///
+    /// Like [`Argument::try_from_args`]
+    pub fn try_from_args<I, T>(args: I) -> Result<Self, error::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        Argument::try_from_args(args).map(SearchConfig)
+    }
+
+    /// Parse a command line in one string (arguments without the program name, quoted like in a
+    /// shell) into a normalized config
+    ///
+    /// Quotes and backslashes work like in a POSIX shell, but nothing is expanded.
+    ///
+    /// ```
+    /// use simple_grep::prelude::*;
+    ///
+    /// let config = SearchConfig::from_args_str("-i -e 'quick fox' notes.txt")?;
+    /// # Ok::<(), Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will error if a quote isn't closed, or like [`Argument::try_from_args`]
+    pub fn from_args_str(args: &str) -> Result<Self, error::Error> {
+        Self::try_from_args(args::split_args(args).map_err(error::Error::Config)?)
+    }
+
+    /// Parse options saved as a string (like a search profile in an editor), followed by more
+    /// arguments (like the pattern and paths), into a normalized config
+    ///
+    /// ```
+    /// use simple_grep::prelude::*;
+    ///
+    /// let profile = "-i -w --type rust";
+    /// let config = SearchConfig::from_profile(profile, ["-e", "unwrap", "src"])?;
+    /// # Ok::<(), Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will error if a quote in `profile` isn't closed, or like [`Argument::try_from_args`]
+    pub fn from_profile<I, T>(profile: &str, args: I) -> Result<Self, error::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let profile = args::split_args(profile).map_err(error::Error::Config)?;
+
+        Self::try_from_args(profile.into_iter().map(std::ffi::OsString::from).chain(args.into_iter().map(Into::into)))
+    }
+}
+
+impl From<Argument> for SearchConfig {
+    fn from(arg: Argument) -> Self {
+        SearchConfig(arg)
+    }
+}
+
+impl Deref for SearchConfig {
+    type Target = Argument;
+
+    fn deref(&self) -> &Argument {
+        &self.0
+    }
+}
+
+/// When to color matches
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color when printing to a terminal
+    #[default]
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+/// Order to search files in
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// By path
+    Path,
+    /// Most recently modified first
+    Modified,
+    /// Largest first
+    Size,
+}
+
+/// How to share threads between the devices searched (`--io-parallelism`)
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IoParallelism {
+    /// Threads for each device, when the paths searched are on more than one
+    #[default]
+    PerDevice,
+    /// The same threads for every file, wherever it is
+    Global,
+}
+
+/// How to treat binary files (files with a NUL byte)
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BinaryFiles {
+    /// Search them, but only say whether they match
+    #[default]
+    Binary,
+    /// Search and print them like text
+    Text,
+    /// Assume they don't match
+    WithoutMatch,
+}
+
+/// Encoding of the files to search
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-16 if there's a byte order mark, UTF-8 otherwise (unless the guess-encoding feature is sure it's UTF-16 or Latin-1)
+    #[default]
+    Auto,
+    /// UTF-8
+    #[value(name = "utf-8", alias = "utf8")]
+    Utf8,
+    /// UTF-16, little endian (what Windows tools usually write)
+    #[value(name = "utf-16le", alias = "utf16le")]
+    Utf16le,
+    /// UTF-16, big endian
+    #[value(name = "utf-16be", alias = "utf16be")]
+    Utf16be,
+    /// ISO-8859-1, one byte per character
+    #[value(name = "latin1", alias = "iso-8859-1")]
+    Latin1,
+}
+
+/// Where `--output` writes results instead of stdout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Sink {
+    /// Shell-quoted `path<TAB>line` records on stdout (`shell`)
+    Shell,
+    /// A database written with sqlite3 (`sqlite:PATH`)
+    #[cfg(feature = "sqlite")]
+    Sqlite(String),
+}
+
+/// Parse `shell` or `KIND:PATH` for `--output`
+///
+/// # Errors
+///
+/// Will error on an unknown kind, an empty path, or a kind this build has no feature for
+pub(crate) fn parse_sink(sink: &str) -> Result<Sink, String> {
+    match sink.split_once(':') {
+        None if sink == "shell" => Ok(Sink::Shell),
+        Some(("sqlite", "")) => Err(format!("invalid output `{sink}` (expected shell or sqlite:PATH)")),
+        #[cfg(feature = "sqlite")]
+        Some(("sqlite", path)) => Ok(Sink::Sqlite(path.to_string())),
+        #[cfg(not(feature = "sqlite"))]
+        Some(("sqlite", _)) => Err(String::from("sqlite output needs simple-grep built with the sqlite feature")),
+        _ => Err(format!("invalid output `{sink}` (expected shell or sqlite:PATH)")),
+    }
+}
+
+/// Which engine matches the patterns (`--engine`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum RegexEngine {
+    /// The regex crate, in time linear in the line
+    #[default]
+    Default,
+    /// PCRE2, for lookaround and backreferences
+    #[cfg(all(feature = "pcre2", unix))]
+    Pcre2,
+}
+
+/// Parse `default` or `pcre2` for `--engine`
+///
+/// # Errors
+///
+/// Will error on an unknown engine, or pcre2 in a build without the feature
+pub(crate) fn parse_engine(engine: &str) -> Result<RegexEngine, String> {
+    match engine {
+        "default" => Ok(RegexEngine::Default),
+        #[cfg(all(feature = "pcre2", unix))]
+        "pcre2" => Ok(RegexEngine::Pcre2),
+        #[cfg(not(all(feature = "pcre2", unix)))]
+        "pcre2" => Err(String::from("the pcre2 engine needs simple-grep built with the pcre2 feature (on Unix)")),
+        _ => Err(format!("invalid engine `{engine}` (expected default or pcre2)")),
+    }
+}
+
+/// A name to print instead of a root path (`--label-root NAME=PATH`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RootLabel {
+    name: String,
+    path: String,
+}
+
+/// Parse `NAME=PATH`
+///
+/// # Errors
+///
+/// Will error if there's no `=` or either side is empty
+pub(crate) fn parse_root_label(label: &str) -> Result<RootLabel, String> {
+    match label.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            Ok(RootLabel { name: name.to_string(), path: path.to_string() })
+        }
+        _ => Err(format!("invalid label `{label}` (expected NAME=PATH)")),
+    }
+}
+
+/// How to print paths (`--label-root`, `--canonical-paths`, `--symlink-targets`)
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PathDisplay {
+    label_root: Vec<RootLabel>,
+    canonical_paths: bool,
+    symlink_targets: bool,
+}
+
+impl PathDisplay {
+    /// `path` resolved with `--canonical-paths` or `--symlink-targets` and labeled with `--label-root`
+    pub(crate) fn display(&self, path: &str) -> String {
+        if let Some(target) = self.symlink_target(path) {
+            return format!("{} -> {}", self.labeled(path), self.labeled(&target));
+        }
+
+        self.labeled(&self.resolve(path))
+    }
+
+    /// `path`, made canonical with `--canonical-paths`
+    pub(crate) fn resolve(&self, path: &str) -> String {
+        if self.canonical_paths { canonical_path(path) } else { path.to_string() }
+    }
+
+    /// Where `path` really is, with `--symlink-targets` if it goes through a symbolic link
+    pub(crate) fn symlink_target(&self, path: &str) -> Option<String> {
+        (!self.canonical_paths && self.symlink_targets && through_symlink(path)).then(|| canonical_path(path))
+    }
 
+    /// The `--label-root` label of the root `path` is under (the longest, if several are) and the rest of the path
+    pub(crate) fn root_label(&self, path: &str) -> Option<(&str, String)> {
+        self.label_root
+            .iter()
+            .filter_map(|label| Some((label, strip_root(path, &label.path)?)))
+            .min_by_key(|(_, rest)| rest.components().count()) // The longest root leaves the least
+            .map(|(label, rest)| (label.name.as_str(), rest.to_string_lossy().into_owned()))
+    }
+
+    /// `NAME/rest` when `path` is under a `--label-root`, otherwise as is
+    fn labeled(&self, path: &str) -> String {
+        match self.root_label(path) {
+            Some((name, rest)) if rest.is_empty() => name.to_string(),
+            Some((name, rest)) => format!("{name}{}{rest}", std::path::MAIN_SEPARATOR),
+            None => path.to_string(),
+        }
+    }
+}
+
+// Command-line interface
+//
+// Searching is the default, so `simple-grep PATTERN PATH` works without naming a subcommand
 #[derive(Parser, Debug)]
-#[command(version)]
+#[command(about = "Simple grep implementation written in Rust")]
+#[command(version, disable_version_flag = true)] // Own version flag, so it can be combined with --json
+#[command(disable_help_flag = true)] // -h is --no-filename, like grep
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Search options when no subcommand is given
+    #[command(flatten)]
+    pub search: Argument,
+}
+
+/// Subcommands, which all share the search options
+#[derive(Subcommand, Debug)]
+#[command(disable_help_flag = true)]
+pub enum Command {
+    /// Search files for a pattern (the default)
+    Search(Argument),
+
+    /// Check that the patterns are valid, without searching (positional arguments are all patterns)
+    Check(Argument),
+
+    /// Print example invocations for common tasks
+    Examples,
+
+    /// Save the files under the paths (positional arguments are all paths) for later searches with --cache-file-list and the same options
+    Index(Argument),
+
+    /// Answer searches of the paths over HTTP at /search?q=PATTERN, as JSON Lines (positional arguments are all paths)
+    Serve(ServeArgs),
+
+    /// Print the file types for --type and --type-not, with the globs of their file names
+    Types,
+}
+
+/// Options of `serve`, on top of the search options
+#[derive(Args, Debug, Clone)]
+#[command(disable_help_flag = true)]
+pub struct ServeArgs {
+    /// Address to listen on, like 127.0.0.1:7878 (port 0 picks a free one)
+    #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1:7878")]
+    pub listen: String,
+
+    #[command(flatten)]
+    pub search: Argument,
+}
+
+#[derive(Args, Debug, Clone, Default)]
+#[command(group(clap::ArgGroup::new("replacement").multiple(true)))] // What --write applies
+#[allow(clippy::struct_excessive_bools)] // Flags are naturally bools
 pub struct Argument {
     /// The pattern to search for (includes regex)
-    pattern: String,
+    #[arg(required_unless_present_any = ["todos", "duplicates", "regexp", "pattern_file", "version", "help_long", "replay", "replace_rule", "replace_rules"])]
+    pattern: Option<String>,
+
+    /// The files (or directories, with -r) to search in
+    files: Vec<String>,
+
+    /// Pattern to search for (can be repeated, lines matching any pattern are printed), which may start with `-`
+    #[arg(short = 'e', long, value_name = "PATTERN", allow_hyphen_values = true)]
+    regexp: Vec<String>,
 
-    /// The file to search in
-    files: String,
+    /// Read patterns from a file, one per line (empty lines are ignored)
+    #[arg(short = 'f', long = "file", value_name = "FILE")]
+    pattern_file: Option<String>,
 
     /// Use case insensitive matching
     #[arg(default_value_t=false, short, long)] // Short and long refer to -i and --insensitive
     insensitive: bool,
 
+    /// Use case insensitive matching, unless a pattern has an uppercase letter
+    #[arg(default_value_t=false, short = 'S', long)]
+    smart_case: bool,
+
+    /// Pick the cheapest engine for the patterns: just a substring search for literal ones, skipping the regex, and PCRE2 for ones with lookaround or backreferences (with the pcre2 feature) (see --debug)
+    #[arg(default_value_t=false, long)]
+    auto_engine: bool,
+
+    /// Match with ENGINE: default (the regex crate, in time linear in the line) or pcre2 (with lookaround and backreferences, by backtracking; needs the pcre2 feature and libpcre2-8)
+    #[arg(long, value_name = "ENGINE", default_value = "default", value_parser = parse_engine)]
+    engine: RegexEngine,
+
+    /// Print debugging information on stderr, like which engine matches the patterns
+    #[arg(default_value_t=false, long)]
+    debug: bool,
+
     /// Print count of matching lines in file
     #[arg(default_value_t=false, short, long)] 
     count: bool,
 
+    /// Print count of matches in file (a line with three matches counts as three)
+    #[arg(default_value_t=false, long, conflicts_with = "count")]
+    count_matches: bool,
+
+    /// Only print the paths of files with matches
+    #[arg(
+        default_value_t=false,
+        short = 'l',
+        long,
+        conflicts_with_all = ["count", "count_matches", "merge_adjacent", "heading", "column", "sample_matches", "accessible", "json"]
+    )]
+    files_with_matches: bool,
+
+    /// Only print files with at least N matching lines (or N matches, with --count-matches)
+    #[arg(long, value_name = "N")]
+    min_matches: Option<usize>,
+
+    /// Only print files where at least PERCENT of the lines match, like 50%
+    #[arg(long, value_name = "PERCENT", value_parser = units::parse_percent)]
+    min_density: Option<f64>,
+
+    /// When to color matches (auto also follows `NO_COLOR`, `CLICOLOR_FORCE` and `FORCE_COLOR`)
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Print consecutive matching lines as one block under a single `start-end:` line range
+    #[arg(default_value_t=false, long)]
+    merge_adjacent: bool,
+
+    /// Print statistics after the search: matched lines, matches, files with matches, files and bytes searched, and time taken
+    #[arg(default_value_t=false, long, conflicts_with = "json")]
+    stats: bool,
+
+    /// With --stats, also print the files, bytes, time and matches for files up to 4K, 64K, 1M and over 1M
+    #[arg(default_value_t=false, long, requires = "stats")]
+    by_size: bool,
+
+    /// End with one line on stderr for scripts, whatever the output format: simple-grep: matched=N files=N errors=N elapsed=SECONDSs
+    #[arg(default_value_t=false, long)]
+    exit_summary: bool,
+
+    /// Only print N matching lines picked at random from the whole search, and the total on stderr
+    #[arg(long, value_name = "N", conflicts_with_all = ["count", "count_matches"])]
+    sample_matches: Option<usize>,
+
+    /// Also print the first N lines of each file with matches once, before its matches (like a CSV header row or a title, to make sense of them)
+    #[arg(long, value_name = "N", conflicts_with_all = ["count", "count_matches", "files_with_matches", "vimgrep", "merge_adjacent", "sample_matches", "hash_lines", "only_binary"])]
+    with_header_line: Option<usize>,
+
+    /// For CSV and TSV files, print the header row once above each file's matches, and the names of the columns the matches are in before each line
+    #[arg(default_value_t=false, long, conflicts_with_all = ["count", "count_matches", "files_with_matches", "vimgrep", "merge_adjacent", "sample_matches", "hash_lines", "only_binary"])]
+    column_names: bool,
+
+    /// Print the file path once above its results, instead of before every line
+    #[arg(default_value_t=false, long)]
+    heading: bool,
+
+    /// Print the line number and 1-based column of the first match before each line, like `12:5: ...`
+    #[arg(default_value_t=false, long, conflicts_with_all = ["count", "count_matches", "merge_adjacent"])]
+    column: bool,
+
+    /// Print each match as `file:line:column:text`, for Vim's quickfix list and other editors
+    #[arg(default_value_t=false, long, conflicts_with_all = ["count", "count_matches", "files_with_matches", "merge_adjacent", "heading", "accessible", "json"])]
+    vimgrep: bool,
+
     /// Match whole word
     #[arg(default_value_t=false, short, long)]
     word: bool,
 
+    /// Let each space in the pattern match any run of whitespace, like for phrases wrapped or aligned differently
+    #[arg(default_value_t=false, long)]
+    flexible_space: bool,
+
+    /// Only match if the whole line matches
+    #[arg(default_value_t=false, short = 'x', long)]
+    line_regexp: bool,
+
+    /// Only match at the start of a word (like \bPATTERN)
+    #[arg(default_value_t=false, long, conflicts_with_all = ["word", "line_regexp"])]
+    starts_with: bool,
+
+    /// Only match at the end of a word (like PATTERN\b)
+    #[arg(default_value_t=false, long, conflicts_with_all = ["word", "line_regexp"])]
+    ends_with: bool,
+
+    /// Treat patterns as literal strings instead of regex
+    #[arg(default_value_t=false, short = 'F', long)]
+    fixed_strings: bool,
+
     /// Search directory
     #[arg(default_value_t=false, short, long)]
     pub recursive: bool,
+
+    /// Keep the files found in a cache, and only walk the directories again when one of them changed (with -r, for slow or network file systems)
+    #[arg(default_value_t=false, long, requires = "recursive")]
+    cache_file_list: bool,
+
+    /// Skip files larger than SIZE (like 10M or 2GiB)
+    #[arg(long, value_name = "SIZE", value_parser = units::parse_size)]
+    max_filesize: Option<u64>,
+
+    /// Stop searching after DURATION (like 500ms, 30s or 2m), keeping the results so far
+    #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Search N files at once (one per CPU by default), still printing results in the same order
+    #[arg(short = 'j', long, value_name = "N")]
+    threads: Option<NonZeroUsize>,
+
+    /// Give each device its own queue of files when the paths searched are on several, with the N threads spread between them (per-device), so a slow disk doesn't hold up a fast one, or use one queue (global)
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = IoParallelism::PerDevice)]
+    io_parallelism: IoParallelism,
+
+    /// Search (and print) files in this order, instead of the order they're found in
+    #[arg(long, value_enum, value_name = "ORDER")]
+    sort: Option<SortBy>,
+
+    /// Descend at most N directories below each path with -r (1 searches only the files directly inside)
+    #[arg(long, value_name = "N", requires = "recursive")]
+    max_depth: Option<usize>,
+
+    /// Search files ignored by .gitignore, .ignore and .simplegrepignore files with -r
+    #[arg(default_value_t=false, long, requires = "recursive")]
+    no_ignore: bool,
+
+    /// Search hidden files and directories (starting with .) with -r, which are skipped by default
+    #[arg(default_value_t=false, long, requires = "recursive")]
+    hidden: bool,
+
+    /// Follow symbolic links with -r (symlink loops are skipped with a warning)
+    #[arg(default_value_t=false, short = 'L', long, requires = "recursive")]
+    follow: bool,
+
+    /// Print the real path of every file, with symbolic links resolved
+    #[arg(default_value_t=false, long)]
+    canonical_paths: bool,
+
+    /// Print files found through a symbolic link as `LINK -> TARGET`, so the real file can be edited
+    #[arg(default_value_t=false, long, conflicts_with = "canonical_paths")]
+    symlink_targets: bool,
+
+    /// Only search within these (1-based, inclusive) character columns of each line, e.g. 1-80
+    #[arg(long, value_name = "RANGE", value_parser = columns::parse_column_range)]
+    columns: Option<ColumnRange>,
+
+    /// Suppress matches that have a line matching this pattern nearby
+    #[arg(long, value_name = "PATTERN")]
+    unless_near: Option<String>,
+
+    /// How many lines before and after a match count as nearby for --unless-near
+    #[arg(default_value_t=0, long, value_name = "N", requires = "unless_near")]
+    within: usize,
+
+    /// Only search files whose name (or path, for globs with a /) matches this glob, like '*.{rs,toml}' or 'src/**/*.rs' (can be repeated)
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<Glob>,
+
+    /// Skip files whose name (or path, for globs with a /) matches this glob (can be repeated, wins over --include)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<Glob>,
+
+    /// Only search files of this type, e.g. rust or py (can be repeated)
+    #[arg(short = 't', long = "type", value_name = "TYPE", value_parser = types::parse_file_type)]
+    file_type: Vec<String>,
+
+    /// Skip files of this type (can be repeated, wins over --type)
+    #[arg(short = 'T', long = "type-not", value_name = "TYPE", value_parser = types::parse_file_type)]
+    type_not: Vec<String>,
+
+    /// Print paths under PATH as NAME followed by the rest of the path (can be repeated, for comparing several roots)
+    #[arg(long, value_name = "NAME=PATH", value_parser = parse_root_label)]
+    label_root: Vec<RootLabel>,
+
+    /// Print the file name for each match (the default when searching more than one file)
+    #[arg(default_value_t=false, short = 'H', long, overrides_with = "no_filename")]
+    with_filename: bool,
+
+    /// Never print file names
+    #[arg(default_value_t=false, short = 'h', long, overrides_with = "with_filename")]
+    no_filename: bool,
+
+    /// End file names with a NUL byte instead of `:` (for piping to xargs -0)
+    #[arg(default_value_t=false, short = '0', long)]
+    null: bool,
+
+    /// Lines end with NUL instead of newline (e.g. for `find -print0` output), matching lines are printed the same way
+    #[arg(default_value_t=false, short = 'z', long)]
+    null_data: bool,
+
+    /// Split files into records of N bytes, searched, numbered and counted like lines (for fixed-length record files without newlines)
+    #[arg(long, value_name = "N", conflicts_with_all = ["null_data", "write", "only_binary"], value_parser = clap::value_parser!(u64).range(1..))]
+    record_size: Option<u64>,
+
+    /// Search the files inside .zip, .tar and .tar.gz archives, printed like archive.zip!path/inside.txt
+    #[arg(default_value_t=false, long, conflicts_with_all = ["raw", "write"])]
+    search_archives: bool,
+
+    /// Open archives inside archives down to N levels, counting the outer one (with --search-archives)
+    #[arg(default_value_t=2, long, value_name = "N", requires = "search_archives", value_parser = clap::value_parser!(u64).range(1..))]
+    archive_depth: u64,
+
+    /// Take at most SIZE out of each archive, nested ones included, and skip the rest (and archives bigger than SIZE) with a warning (with --search-archives)
+    #[arg(default_value = "256M", long, value_name = "SIZE", requires = "search_archives", value_parser = units::parse_size)]
+    archive_max_size: u64,
+
+    /// Search .gz, .bz2, .xz and .zst files decompressed, with gzip, bzip2, xz or zstd (-z is --null-data, like in grep)
+    #[arg(default_value_t=false, long, conflicts_with_all = ["raw", "write"])]
+    search_zip: bool,
+
+    /// Search what COMMAND prints for each file instead of the file itself, like pdftotext (run with the path as its argument and the file on stdin)
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["raw", "write"])]
+    pre: Option<String>,
+
+    /// Only run --pre on files whose name (or path, for globs with a /) matches this glob (can be repeated)
+    #[arg(long, value_name = "GLOB", requires = "pre")]
+    pre_glob: Vec<Glob>,
+
+    /// How to treat binary files
+    #[arg(long, value_enum, value_name = "TYPE", default_value_t = BinaryFiles::Binary)]
+    binary_files: BinaryFiles,
+
+    /// Encoding of the files (auto reads files with a UTF-16 byte order mark as UTF-16)
+    #[arg(long, value_enum, value_name = "ENCODING", default_value_t = Encoding::Auto, conflicts_with = "raw")]
+    encoding: Encoding,
+
+    /// Only search binary files, printing a hex window around each match (for hunting strings in firmware)
+    #[arg(default_value_t=false, long, visible_alias = "skip-text", conflicts_with = "text")]
+    only_binary: bool,
+
+    /// Print huge matches in full, instead of a preview of their first 200 bytes
+    #[arg(default_value_t=false, long)]
+    pub full_matches: bool,
+
+    /// Don't print result lines already printed from another file
+    #[arg(default_value_t=false, long)]
+    global_dedupe: bool,
+
+    /// Remember the lines printed for --global-dedupe in a Bloom filter of SIZE (like 64M), bounding memory (a few lines may be wrongly dropped)
+    #[arg(long, value_name = "SIZE", requires = "global_dedupe", value_parser = units::parse_size)]
+    dedupe_bloom: Option<u64>,
+
+    /// Remember the matches of up to N distinct lines in each file, so repeated lines (like in logs) aren't matched again
+    #[arg(long, value_name = "N")]
+    line_cache: Option<usize>,
+
+    /// Only keep matches whose text has at least this much entropy in bits per byte, for finding secrets (random keys are around 4 to 6, words around 2 to 3)
+    #[arg(long, value_name = "BITS")]
+    min_entropy: Option<f64>,
+
+    /// Search the printable strings in binary files (like the strings tool), one per line
+    #[arg(default_value_t=false, long, conflicts_with_all = ["only_binary", "text"])]
+    strings: bool,
+
+    /// Shortest run of printable characters that counts as a string with --strings
+    #[arg(long, value_name = "N", default_value_t = 4, requires = "strings", value_parser = clap::value_parser!(u64).range(1..))]
+    strings_min: u64,
+
+    /// Search binary files as text (same as --binary-files=text)
+    #[arg(default_value_t=false, short = 'a', long)]
+    text: bool,
+
+    /// Search every byte of every file: no ignore files, hidden file skipping, binary detection, type filters, smart case, color or UTF-8 decoding (bytes are read as Latin-1)
+    #[arg(default_value_t=false, long, conflicts_with_all = ["only_binary", "strings"])]
+    raw: bool,
+
+    /// Most result lines to hold while stdout is slow to take them, before searching waits
+    #[arg(default_value_t=1024, long, value_name = "N")]
+    output_buffer_lines: usize,
+
+    /// Print matches replaced by TEMPLATE, which can use capture groups like $1 or $name (files aren't changed)
+    #[arg(long, value_name = "TEMPLATE", group = "replacement", conflicts_with_all = ["redact", "only_binary"])]
+    replace: Option<String>,
+
+    /// Replace matches of PATTERN with TEMPLATE, for each rule in turn, in one pass over each line (can be repeated)
+    #[arg(
+        long,
+        value_name = "PATTERN=>TEMPLATE",
+        value_parser = rules::parse_replace_rule,
+        group = "replacement",
+        conflicts_with_all = ["replace", "redact", "only_binary"]
+    )]
+    replace_rule: Vec<rules::ReplaceRule>,
+
+    /// Read --replace-rule rules from a file, one per line (empty lines and lines starting with # are ignored)
+    #[arg(long, value_name = "FILE", group = "replacement", conflicts_with_all = ["replace", "redact", "only_binary"])]
+    replace_rules: Option<String>,
+
+    /// Replace matches in their own case: with foo => bar, Foo becomes Bar and FOO becomes BAR (for renames with -i)
+    #[arg(default_value_t=false, long, requires = "replacement")]
+    preserve_case: bool,
+
+    /// Rewrite the files with the --replace (or --replace-rule) replacements instead of printing them, and say how much changed
+    #[arg(default_value_t=false, long, requires = "replacement")]
+    pub write: bool,
+
+    /// With --write, only rewrite files that are committed to git with no uncommitted changes (others are skipped with a warning)
+    #[arg(default_value_t=false, long, requires = "write")]
+    require_clean_git: bool,
+
+    /// Mask every match with **** in the output, for shareable reports of secret scans
+    #[arg(default_value_t=false, long)]
+    redact: bool,
+
+    /// Print a salted hash of each matching line, with its line number and match lengths, instead of the line (for sharing results without the code)
+    #[arg(default_value_t=false, long)]
+    hash_lines: bool,
+
+    /// Salt for --hash-lines, so hashes can be compared across runs (random by default)
+    #[arg(long, value_name = "SALT", requires = "hash_lines")]
+    hash_salt: Option<String>,
+
+    /// Don't warn about files that can't be read (recursive mode skips them)
+    #[arg(default_value_t=false, short = 's', long)]
+    no_messages: bool,
+
+    /// Output for screen readers: no color, every result labeled, and totals summarized in sentences
+    #[arg(default_value_t=false, long)]
+    accessible: bool,
+
+    /// Save the search (options, searched files and their sizes and times) to this tar file, for bug reports
+    #[arg(long, value_name = "FILE")]
+    record: Option<String>,
+
+    /// Also save the printed results with --record (they may contain private data)
+    #[arg(default_value_t=false, long, requires = "record")]
+    record_snippets: bool,
+
+    /// Rerun a search saved with --record, on the same files
+    #[arg(long, value_name = "FILE", conflicts_with = "record")]
+    pub replay: Option<String>,
+
+    /// Files to search instead of finding them, when replaying (or when `serve` found them already)
+    #[arg(skip)]
+    replay_files: Option<Vec<String>>,
+
+    /// Language of messages (defaults from LANG)
+    #[arg(long, value_enum, value_name = "LANG")]
+    lang: Option<Lang>,
+
+    /// Print help
+    #[arg(long, action = clap::ArgAction::Help)]
+    help: Option<bool>,
+
+    /// Print version
+    #[arg(default_value_t=false, short = 'V', long)]
+    pub version: bool,
+
+    /// Print the full help, followed by example invocations for common tasks
+    #[arg(default_value_t=false, long)]
+    pub help_long: bool,
+
+    /// Print machine-readable JSON: results as JSON Lines, or details with --version
+    #[arg(default_value_t=false, long, conflicts_with = "accessible")]
+    json: bool,
+
+    /// Report TODO/FIXME/HACK markers grouped by owner and file
+    #[arg(default_value_t=false, long)]
+    pub todos: bool,
+
+    /// Report lines (matching the pattern, if given) that appear more than N times, with locations
+    #[arg(long, value_name = "N")]
+    pub duplicates: Option<usize>,
+
+    /// Compare the matches in two trees, printing the ones only in OLD (-) or only in NEW (+)
+    #[arg(long, num_args = 2, value_names = ["OLD", "NEW"])]
+    pub compare: Vec<String>,
+
+    /// Search interactively: type the pattern, pick a result with the arrow keys, and press Enter to print it (or Ctrl-E to open it in $EDITOR)
+    #[arg(default_value_t=false, long, conflicts_with_all = ["write", "json", "todos", "duplicates", "compare"])]
+    pub tui: bool,
+
+    /// Write results into SINK instead of printing them: shell prints each as a shell-quoted path and line separated by a tab, for while read loops, and sqlite:PATH adds them to a database file with sqlite3 (needs the sqlite feature)
+    #[arg(long, value_name = "SINK", value_parser = parse_sink, conflicts_with_all = ["json", "accessible", "write", "tui", "todos", "duplicates", "compare"])]
+    output: Option<Sink>,
+
+    /// Search again whenever the files change (polling them), clearing the terminal and printing the new results
+    #[arg(default_value_t=false, long, conflicts_with_all = ["write", "tui", "todos", "duplicates", "compare", "record"])]
+    pub watch: bool,
+
+    /// How often --watch checks the files for changes
+    #[arg(default_value = "500ms", long, value_name = "DURATION", requires = "watch", value_parser = units::parse_duration)]
+    watch_interval: Duration,
+
+    /// Print the lines most like the pattern instead of lines matching it: its characters in order, best near word starts and together (like srchcfg for a search config)
+    #[arg(default_value_t=false, long, conflicts_with_all = ["fixed_strings", "word", "line_regexp", "replacement", "write", "tui", "todos", "duplicates", "compare", "output", "stats", "watch"])]
+    pub fuzzy: bool,
+
+    /// How many of the best lines --fuzzy prints
+    #[arg(default_value_t = 20, long, value_name = "N", requires = "fuzzy")]
+    fuzzy_limit: usize,
 }
 
-/// # Errors
-///
-/// Will error if a file is not readable or cannot be found
-/// 
-/// # Panics
-/// 
-/// Will panic if a regex query is invalid
-pub fn read_file_and_print_matches(arg: &Argument) -> Result<(), Box<dyn Error>> {
-    // Read file
-    let contents = fs::read_to_string(arg.files.clone())?; // Return error (dynamic) for caller to handle
+impl Argument {
+    /// Fix up positional arguments after parsing
+    ///
+    /// When the pattern comes from somewhere else (`-e`, `-f`), or the mode doesn't need one
+    /// (`--todos`, `--duplicates`), the path ends up in the pattern slot, since clap fills
+    /// positionals in order. Patterns from `-f` are read here and added to the `-e` patterns.
+    ///
+    /// # Errors
+    ///
+    /// Will error if the pattern file is not readable, or if the paths to search don't add up
+    pub fn normalize(&mut self) -> Result<(), Box<dyn Error>> {
+        self.fix_positionals()?;
+        self.read_pattern_file()?;
+        self.read_replace_rules()?;
+
+        #[cfg(all(feature = "pcre2", unix))]
+        if self.engine == RegexEngine::Pcre2 && self.needs_regex() {
+            return Err(self.lang().catalog().pcre2_conflicts.into());
+        }
 
-    // Print matching file contents
+        // Without a pattern, search for what the rules replace (in one regex, so without group names)
+        if !self.has_pattern() {
+            self.regexp = self.replace_rule.iter().map(|rule| rules::unnamed_groups(&rule.pattern)).collect();
+        }
 
-    if arg.count {
+        Ok(())
+    }
 
-        if arg.recursive {
-            // Print file path
-            print!("{}: ", arg.files);
+    /// Move a path out of the pattern slot, see [`Argument::normalize`]
+    fn fix_positionals(&mut self) -> Result<(), Box<dyn Error>> {
+        let pattern_elsewhere = !self.regexp.is_empty()
+            || self.pattern_file.is_some()
+            || !self.replace_rule.is_empty()
+            || self.replace_rules.is_some();
+        let pattern_optional = self.todos || self.tui || self.duplicates.is_some();
+
+        if (pattern_elsewhere || (pattern_optional && self.files.is_empty()))
+            && let Some(path) = self.pattern.take()
+        {
+            self.files.insert(0, path);
         }
 
-        let count = if arg.insensitive {
-            case_insensitive_line_matching(&arg.pattern, &contents, arg.word).len()
-        } else {
-            case_sensitive_line_matching(&arg.pattern, &contents, arg.word).len()
+        if !self.compare.is_empty() {
+            return if self.files.is_empty() { Ok(()) } else { Err(self.lang().catalog().compare_paths.into()) };
+        }
+
+        if self.files.is_empty() {
+            return Err(self.lang().catalog().no_path.into());
+        }
+
+        Ok(())
+    }
+
+    /// Parse command-line style arguments (without the program name) into a normalized config
+    ///
+    /// # Errors
+    ///
+    /// Will error if the arguments are invalid, name a subcommand other than `search`, or the
+    /// pattern file is not readable
+    pub fn try_from_args<I, T>(args: I) -> Result<Self, error::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let program = std::iter::once(std::ffi::OsString::from(env!("CARGO_PKG_NAME")));
+        let cli = Cli::try_parse_from(program.chain(args.into_iter().map(Into::into)))
+            .map_err(|e| error::Error::Config(e.to_string()))?;
+
+        let mut config = match cli.command {
+            None => cli.search,
+            Some(Command::Search(config)) => config,
+            Some(Command::Check(_)) => return Err(error::Error::Config(String::from("`check` is not a search"))),
+            Some(Command::Examples) => return Err(error::Error::Config(String::from("`examples` is not a search"))),
+            Some(Command::Index(_)) => return Err(error::Error::Config(String::from("`index` is not a search"))),
+            Some(Command::Serve(_)) => return Err(error::Error::Config(String::from("`serve` is not a search"))),
+            Some(Command::Types) => return Err(error::Error::Config(String::from("`types` is not a search"))),
         };
 
-        println!("{count}");
-        return Ok(());
+        config.normalize().map_err(|e| error::Error::Config(e.to_string()))?;
+
+        Ok(config)
     }
 
-    else {
-        match arg.insensitive {
-            true => case_insensitive_line_matching(&arg.pattern, &contents, arg.word),
-            false => case_sensitive_line_matching(&arg.pattern, &contents, arg.word),
+    /// Add the patterns from `-f` to the `-e` patterns
+    fn read_pattern_file(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(pattern_file) = self.pattern_file.take() {
+            let contents = fs::read_to_string(&pattern_file)
+                .map_err(|e| format!("{pattern_file}: {e}"))?;
+
+            // Skip empty lines (like a trailing newline), so they don't match everything
+            self.regexp.extend(
+                contents
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(String::from),
+            );
         }
-        .iter()
-        .for_each(|line| 
-            // Make matching lines bold red
 
-            if arg.insensitive {
-                // Bold red all occurrences regardless of case
+        Ok(())
+    }
 
-                if arg.recursive {
-                    // Print file path
-                    print!("{}: ", arg.files);
-                }
+    /// Add the rules from `--replace-rules` after the `--replace-rule` ones
+    fn read_replace_rules(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(rules_file) = self.replace_rules.take() {
+            let contents = fs::read_to_string(&rules_file)
+                .map_err(|e| format!("{rules_file}: {e}"))?;
 
-                let mut result = String::from(*line);
-                let lowercase_line = line.to_lowercase();
-                let lowercase_query = arg.pattern.to_lowercase();
+            for line in contents.lines().filter(|line| !line.is_empty() && !line.starts_with('#')) {
+                let rule = rules::parse_replace_rule(line).map_err(|e| format!("{rules_file}: {e}"))?;
+                self.replace_rule.push(rule);
+            }
+        }
 
-                // Find all occurrences of query in line
-                let mut start = 0;
+        Ok(())
+    }
 
-                while let Some(index) = lowercase_line[start..].find(&lowercase_query) {
-                    let index = index + start;
-                    let end = index + arg.pattern.len();
+    /// Compiled `--replace-rule` rules, with the same matching options as the patterns
+    fn replace_rules(&self) -> Result<Option<rules::Rules>, regex::Error> {
+        if self.replace_rule.is_empty() {
+            return Ok(None);
+        }
 
-                    // Replace query with bold red query
-                    result = result.replace(&line[index..end], &format!("\x1b[1;31m{}\x1b[0m", &line[index..end]));
+        let source = |pattern: &str| regex_source(&self.word_edges(self.pattern_source(pattern)), self.ignore_case(), self.whole_word());
 
-                    // Move start to end of query
-                    start = end;
-                }
-                
-                println!("{result}");
-            }
-            
-            else {
+        rules::Rules::new(&self.replace_rule, source).map(Some)
+    }
 
-                if arg.recursive {
-                    // Print file path
-                    print!("{}: ", arg.files);
-                }
+    /// Whether any pattern was given (positional, `-e` or `-f`)
+    fn has_pattern(&self) -> bool {
+        self.pattern.is_some() || !self.regexp.is_empty()
+    }
 
-                let regex_query = Regex::new(&arg.pattern).unwrap();
+    /// Whether to match case insensitively, with `-i` or when `-S` finds no uppercase letters
+    fn ignore_case(&self) -> bool {
+        let uppercase = || self.pattern.iter().chain(&self.regexp).any(|pattern| has_uppercase(pattern, self.fixed_strings));
 
-                // Bold red matching parts of line
-                let result = regex_query.replace_all(line, "\x1b[1;31m$0\x1b[0m".to_string());
-        
-                println!("{result}");
-            }
-        
-        
-        );
+        self.insensitive || (self.smart_case && !self.raw && !uppercase())
     }
 
-    Ok(()) // Ok if sucessful
-}
+    /// Whether the options build their own regex from the patterns, so PCRE2 can't match them
+    #[cfg(all(feature = "pcre2", unix))]
+    fn needs_regex(&self) -> bool {
+        self.replace.is_some() || self.only_binary || self.duplicates.is_some()
+    }
 
+    /// The pattern to match lines against
+    ///
+    /// Multiple patterns are combined so a line matching any of them matches
+    fn query(&self) -> String {
+        let patterns: Vec<String> = self
+            .pattern
+            .iter()
+            .chain(&self.regexp)
+            .map(|pattern| self.pattern_source(pattern))
+            .collect();
+
+        let query = match patterns.as_slice() {
+            [] => return String::from(r"[^\s\S]"), // Nothing to match (e.g. empty pattern file)
+            [pattern] => pattern.clone(),
+            _ => patterns
+                .iter()
+                .map(|pattern| format!("(?:{pattern})"))
+                .collect::<Vec<String>>()
+                .join("|"),
+        };
 
-/// # Panics
-/// 
-/// Will panic if a file is not readable or cannot be found
-/// # Errors
-/// 
-/// Will ignore errors
-pub fn read_dir_and_print_matches(arg: &Argument) -> Result<(), Box<dyn Error>> {
-    
-
-    // Skip directories owner doesn't have permission to acess
-    for entry in WalkDir::new(arg.files.clone()).into_iter().filter_map(std::result::Result::ok) { 
-        let path = entry.path();
-
-        if path.is_file() {
-            let file = path.to_str().unwrap().to_string(); // Convert path to string
-
-            let new_argument = Argument {
-                pattern: arg.pattern.clone(),
-                files: file,
-                insensitive: arg.insensitive,
-                count: arg.count,
-                word: arg.word,
-                recursive: true,
-            };
+        // Only match if the whole line matches
+        if self.line_regexp {
+            return format!("^(?:{query})$");
+        }
+
+        self.word_edges(query)
+    }
+
+    /// Regex source for one pattern: escaped with `-F`, with flexible whitespace with `--flexible-space`
+    fn pattern_source(&self, pattern: &str) -> String {
+        let source = if self.fixed_strings {
+            regex::escape(pattern) // Escaped literals become a plain substring search
+        } else {
+            pattern.to_string()
+        };
+
+        if self.flexible_space { flexible_space(&source) } else { source }
+    }
 
-            // Read file
-            let _ = read_file_and_print_matches(&new_argument); // Ignore errors
+    /// `pattern` anchored to the start or end of a word, with `--starts-with` and `--ends-with`
+    fn word_edges(&self, pattern: String) -> String {
+        match (self.starts_with, self.ends_with) {
+            (false, false) => pattern,
+            (true, false) => format!(r"\b(?:{pattern})"), // Group so alternations are all anchored
+            (false, true) => format!(r"(?:{pattern})\b"),
+            (true, true) => format!(r"\b(?:{pattern})\b"),
         }
     }
 
-    Ok(()) // Ok if sucessful
-}
+    /// The `--unless-near` pattern, escaped with `-F`
+    fn near_query(&self, near: &str) -> String {
+        if self.fixed_strings { regex::escape(near) } else { near.to_string() }
+    }
 
-fn case_sensitive_line_matching<'a> (query: &str, contents: &'a str, whole_word: bool) -> Vec<&'a str> {
+    /// Language of messages, `--lang` or else from the environment
+    #[must_use]
+    pub fn lang(&self) -> Lang {
+        self.lang.unwrap_or_else(Lang::from_env)
+    }
 
-    let mut regex_query = Regex::new(query).unwrap();
+    /// Whether to print the file name before each result
+    ///
+    /// `-H` and `-h` decide, otherwise it's printed when more than one file could be searched
+    /// (like in an archive, with `--search-archives`)
+    fn show_filename(&self) -> bool {
+        self.with_filename || (!self.no_filename && (self.recursive || self.search_archives || self.files.len() > 1))
+    }
 
-    // Only match if query is a whole word in the line
-    if whole_word {
-        regex_query = Regex::new(&format!(r"\b{query}\b")).unwrap();
+    /// Whether to color output, checking for a terminal in auto mode
+    fn use_color(&self) -> bool {
+        if self.raw {
+            return false;
+        }
+
+        match self.color {
+            ColorChoice::Always => {
+                enable_ansi_escapes(); // Asked for, so used even if the console can't show them
+                true
+            }
+            ColorChoice::Never => false,
+            ColorChoice::Auto => auto_color(|name| std::env::var_os(name), || io::stdout().is_terminal() && enable_ansi_escapes()),
+        }
     }
 
-    contents
-        .lines()
-        .filter(|line| regex_query.is_match(line))
-        .collect()
-}
+    /// How to treat binary files, `-a` meaning as text
+    fn binary_mode(&self) -> BinaryFiles {
+        if self.text || self.raw { BinaryFiles::Text } else { self.binary_files }
+    }
+
+    /// The options for how to print paths
+    pub(crate) fn path_display(&self) -> PathDisplay {
+        PathDisplay {
+            label_root: self.label_root.clone(),
+            canonical_paths: self.canonical_paths,
+            symlink_targets: self.symlink_targets,
+        }
+    }
 
-fn case_insensitive_line_matching<'a> (query: &str, contents: &'a str, whole_word: bool) -> Vec<&'a str> {
+    /// How to print `path`, see [`PathDisplay::display`]
+    pub(crate) fn display_path(&self, path: &str) -> String {
+        self.path_display().display(path)
+    }
 
-    let mut regex_query = Regex::new(&format!(r"(?i){query}")).unwrap(); // Case insensitive
-    
-    // Oonly match if query is a whole word in the line
-    if whole_word {
-        regex_query = Regex::new(&format!(r"(?i)\b{query}\b")).unwrap();
+    /// Salt to hash lines with for `--hash-lines`, or `None` to print them
+    fn hash_salt(&self) -> Option<String> {
+        if !self.hash_lines {
+            return None;
+        }
+
+        // Randomly seeded, so the salt is different every run
+        let random = || format!("{:016x}", RandomState::new().hash_one(0));
+
+        Some(self.hash_salt.clone().unwrap_or_else(random))
     }
 
-    contents
-    .lines()
-    .filter(|line| regex_query.is_match(line))
-    .collect()
+    /// How many files to search at once, one per CPU unless `-j` was given
+    fn threads(&self) -> usize {
+        self.threads.or_else(|| thread::available_parallelism().ok()).map_or(1, NonZeroUsize::get)
+    }
+
+    /// What ends a line, NUL with `-z`
+    fn line_terminator(&self) -> char {
+        if self.null_data { '\0' } else { '\n' }
+    }
+
+    /// Whether to only match whole words (whole line matching makes this redundant)
+    fn whole_word(&self) -> bool {
+        self.word && !self.line_regexp
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Whether to color in auto mode, from the environment variables `var` gives and whether stdout
+/// is a terminal
+///
+/// `NO_COLOR` (set and not empty) turns color off and `CLICOLOR_FORCE` or `FORCE_COLOR` (set and
+/// not `0`) turns it on even when piped, like CI logs want. `NO_COLOR` wins if both are set.
+fn auto_color(var: impl Fn(&str) -> Option<std::ffi::OsString>, terminal: impl FnOnce() -> bool) -> bool {
+    if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
 
-    /* Test read file and print matches */
-    #[test]
-    fn test_read_file_success() {
-        let arg = Argument {
-            pattern: String::from("query"),
-            files: String::from("./tests/test_poem.txt"),
-            insensitive: false, // Path is based on cwd (not executable location)
-            count: false,
-            word: false,
-            recursive: false,
-        };
+    if ["CLICOLOR_FORCE", "FORCE_COLOR"].iter().any(|name| var(name).is_some_and(|value| value != "0")) {
+        return true;
+    }
 
-        let result = read_file_and_print_matches(&arg);
+    terminal() // Escapes would corrupt piped output
+}
 
-        assert!(result.is_ok());
+/// Turn on escape sequences in the Windows console (off by default in cmd and older PowerShell),
+/// returning whether it can show them
+///
+/// Consoles that can't (before Windows 10) get no color rather than garbage. Other systems always can.
+fn enable_ansi_escapes() -> bool {
+    anstyle_query::windows::enable_ansi_colors().unwrap_or(true)
+}
+
+/// Search every given file (that passes the file filters) and print matches
+///
+/// To get the matches instead, search with a [`Searcher`] (like [`Searcher::search_path`] for one
+/// file), or hand [`Searcher::run`] a [`Printer`] of your own.
+///
+/// # Errors
+///
+/// Will error if a file is not readable or cannot be found, or a pattern is invalid
+pub fn read_file_and_print_matches(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    if arg.record.is_some() {
+        return record::record(arg);
     }
 
-    #[test]
-    fn test_read_file_error() {
-        let arg = Argument {
-            pattern: String::from("query"),
-            files: String::from("nonexistent_file.nonsense"),
-            insensitive: false,
-            count: false,
-            word: false,
-            recursive: false,
-        };
+    let mut output = output::BoundedOutput::stdout(arg.output_buffer_lines);
+    let result = print_matches(arg, &mut output);
 
-        let result = read_file_and_print_matches(&arg);
+    output.finish()?;
+    result?;
 
-        assert!(result.is_err());
+    Ok(()) // Ok if sucessful
+}
+
+/// The printer for the chosen output format (`--accessible`, `--json` or text), writing to `writer`
+pub(crate) fn format_printer<'a, W: io::Write + 'a>(arg: &Argument, writer: W) -> Box<dyn Printer + 'a> {
+    if arg.accessible {
+        Box::new(AccessiblePrinter::new(arg, writer))
+    } else if arg.json {
+        Box::new(JsonPrinter::new(arg, writer))
+    } else if matches!(arg.output, Some(Sink::Shell)) {
+        Box::new(shell::ShellPrinter::new(arg, writer))
+    } else {
+        Box::new(StandardPrinter::new(arg, writer))
     }
+}
 
-    /* Test read dir and print matches */
+/// Search with `arg` and print the results to `writer`, in the chosen output format
+pub(crate) fn print_matches<W: io::Write>(arg: &Argument, mut writer: W) -> Result<(), error::Error> {
+    let mut stats = stats::Stats::default();
 
-    #[test]
-    fn test_read_dir_success() {
-        let arg = Argument {
-            pattern: String::from("query"),
-            files: String::from("./tests"),
-            insensitive: false, // Path is based on cwd (not executable location)
-            count: false,
-            word: false,
-            recursive: true,
-        };
+    #[cfg(feature = "sqlite")]
+    let mut database = match &arg.output {
+        Some(Sink::Sqlite(path)) => Some(sqlite::Database::open(path, arg).map_err(error::Error::Output)?),
+        Some(Sink::Shell) | None => None,
+    };
 
-        let result = read_dir_and_print_matches(&arg);
+    let mut printer = format_printer(arg, &mut writer);
 
-        assert!(result.is_ok());
+    #[cfg(feature = "sqlite")]
+    if let Some(database) = &mut database {
+        printer = Box::new(sqlite::SqlitePrinter::new(arg, database.writer()));
+    }
+
+    if let Some(size) = arg.sample_matches {
+        let seed = RandomState::new().hash_one(0); // Different every run
+        printer = Box::new(sample::SamplingPrinter::new(printer, arg.lang().catalog(), size, seed));
+    }
+
+    if arg.stats || arg.exit_summary {
+        printer = Box::new(stats::StatsPrinter::new(printer, &mut stats)); // Outside sampling, so everything counts
+    }
+
+    let searched = Searcher::new(arg.clone()).and_then(|searcher| {
+        if arg.debug {
+            eprintln!("{}: {}", env!("CARGO_PKG_NAME"), i18n::fill(arg.lang().catalog().debug_engine, &[("engine", searcher.engine_name())]));
+        }
+
+        searcher.run(printer.as_mut())
+    });
+    drop(printer);
+
+    #[cfg(feature = "sqlite")]
+    if let Some(database) = database {
+        database.close().map_err(error::Error::Output)?; // What went wrong in sqlite3, before a broken pipe writing to it
+    }
+
+    searched?;
+
+    if arg.stats {
+        stats.write(&mut writer, arg.lang().catalog(), arg.by_size).map_err(error::Error::Output)?;
+    }
+
+    if arg.exit_summary {
+        writer.flush().map_err(error::Error::Output)?; // So the summary comes after the results in a terminal
+        eprintln!("{}", stats.summary_line());
+    }
+
+    Ok(())
+}
+
+/// Search every file under the given directories (that passes the file filters) and print matches
+///
+/// # Errors
+///
+/// Will error if a pattern is invalid, unreadable files are ignored
+pub fn read_dir_and_print_matches(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    read_file_and_print_matches(arg) // Recursive mode skips unreadable files
+}
+
+/// Warn on stderr that a file was skipped because it couldn't be read, unless `-s` was given
+pub(crate) fn warn_skipped(arg: &Argument, error: &dyn std::fmt::Display) {
+    if !arg.no_messages {
+        eprintln!("{}: {error}", env!("CARGO_PKG_NAME"));
+    }
+}
+
+/// Paths of every file to search
+///
+/// In recursive mode this walks each directory, otherwise it's just the given files.
+/// Either way only files passing the file filters are kept (unless replaying a recording).
+pub(crate) fn files_to_search(arg: &Argument) -> Vec<String> {
+    // A replay searches exactly what was searched when recording
+    if let Some(files) = &arg.replay_files {
+        return files.clone();
+    }
+
+    sized_and_sorted(arg, find_files(arg))
+}
+
+/// `files` without the ones over `--max-filesize`, in `--sort` order
+pub(crate) fn sized_and_sorted(arg: &Argument, mut files: Vec<String>) -> Vec<String> {
+    if let Some(max) = arg.max_filesize {
+        files.retain(|file| fs::metadata(file).is_ok_and(|metadata| metadata.len() <= max)); // Unreadable ones too, since a size can't be checked
+    }
+
+    if let Some(order) = arg.sort {
+        sort_files(&mut files, order);
+    }
+
+    files
+}
+
+/// Paths of every file to search in the order found, see [`files_to_search`]
+fn find_files(arg: &Argument) -> Vec<String> {
+    if !arg.recursive {
+        return arg
+            .files
+            .iter()
+            .filter(|file| passes_file_filters(arg, file))
+            .cloned()
+            .collect();
+    }
+
+    if arg.cache_file_list {
+        return file_list::cached_files(arg);
+    }
+
+    walk_files(arg, |_| {})
+}
+
+/// Every file under the given directories that passes the file filters, calling `walked` with
+/// each directory walked
+pub(crate) fn walk_files(arg: &Argument, mut walked: impl FnMut(&Path)) -> Vec<String> {
+    arg.files
+        .iter()
+        .flat_map(|file| {
+            let walk = WalkDir::new(file).follow_links(arg.follow); // walkdir detects loops when following
+
+            let walk = match arg.max_depth {
+                Some(depth) => walk.max_depth(depth),
+                None => walk,
+            };
+
+            let mut ignores = ignore::Ignores::default();
+
+            // Paths given explicitly are searched even if hidden, like `.` or `.config`
+            walk.into_iter().filter_entry(move |entry| {
+                (arg.hidden || arg.raw || entry.depth() == 0 || !is_hidden(entry.file_name()))
+                    && (arg.no_ignore || arg.raw || !ignores.is_ignored(entry))
+            })
+        })
+        .filter_map(|entry| match entry {
+            Ok(entry) => {
+                if entry.file_type().is_dir() {
+                    walked(entry.path());
+                }
+
+                Some(entry)
+            }
+            // Unreadable directories (and ones gone since) are skipped like unreadable files
+            Err(e) if e.io_error().is_some() => {
+                let path = e.path().map_or_else(String::new, |path| path.to_string_lossy().into_owned());
+
+                if let Some(source) = e.into_io_error() {
+                    warn_skipped(arg, &error::Error::Io { path, source });
+                }
+
+                None
+            }
+            Err(e) => {
+                warn_skipped(arg, &e); // Says why a loop is skipped
+                None
+            }
+        })
+        .filter(|entry| entry.path().is_file())
+        .filter(|entry| passes_file_filters(arg, &entry.path().to_string_lossy()))
+        .map(|entry| entry.path().to_string_lossy().into_owned()) // Convert path to string
+        .collect()
+}
+
+/// Sort `files` for `--sort`, keeping the order found for ties (and files that can't be read)
+fn sort_files(files: &mut [String], order: SortBy) {
+    match order {
+        SortBy::Path => files.sort_by(|a, b| Path::new(a).cmp(Path::new(b))), // By component, so a directory's files stay together
+        SortBy::Modified => {
+            files.sort_by_cached_key(|file| Reverse(fs::metadata(file).and_then(|metadata| metadata.modified()).ok()));
+        }
+        SortBy::Size => files.sort_by_cached_key(|file| Reverse(fs::metadata(file).map(|metadata| metadata.len()).ok())),
+    }
+}
+
+/// The rest of `path` after `root`, compared by component (so `a` isn't a root of `ab`) ignoring `.`s
+pub(crate) fn strip_root(path: &str, root: &str) -> Option<PathBuf> {
+    let not_current = |component: &Component| *component != Component::CurDir;
+
+    let mut rest = Path::new(path).components().filter(not_current);
+
+    for component in Path::new(root).components().filter(not_current) {
+        if rest.next()? != component {
+            return None;
+        }
+    }
+
+    Some(rest.collect())
+}
+
+/// `path` with symbolic links, `.` and `..` resolved (or as is if it can't be)
+fn canonical_path(path: &str) -> String {
+    fs::canonicalize(path).map_or_else(|_| path.to_string(), |real| real.to_string_lossy().into_owned())
+}
+
+/// Whether `path` or a directory it's in is a symbolic link
+fn through_symlink(path: &str) -> bool {
+    Path::new(path)
+        .ancestors()
+        .any(|ancestor| fs::symlink_metadata(ancestor).is_ok_and(|metadata| metadata.file_type().is_symlink()))
+}
+
+/// Whether a file or directory name is hidden (starts with .)
+fn is_hidden(file_name: &std::ffi::OsStr) -> bool {
+    file_name.as_encoded_bytes().starts_with(b".")
+}
+
+/// Whether the file at `path` gets past `--include`, `--exclude`, `--type` and `--type-not`
+///
+/// With no includes (or types) every file is included, and excludes take precedence. Globs
+/// match the file name, or the end of the path if they have a `/`; types go by the name.
+fn passes_file_filters(arg: &Argument, path: &str) -> bool {
+    let file_name = Path::new(path).file_name().map_or(path.into(), |name| name.to_string_lossy());
+    let file_name = file_name.as_ref();
+
+    let included = arg.include.is_empty()
+        || arg.include.iter().any(|include| include.is_match(path));
+
+    let typed = arg.raw || arg.file_type.is_empty() || arg.file_type.iter().any(|name| types::is_type(name, file_name));
+
+    let excluded = arg.exclude.iter().any(|exclude| exclude.is_match(path))
+        || (!arg.raw && arg.type_not.iter().any(|name| types::is_type(name, file_name)));
+
+    included && typed && !excluded
+}
+
+/// Lines of `contents` without their terminator
+///
+/// Like `str::lines`, a final terminator doesn't start another (empty) line, and `\r\n` counts as a newline
+pub(crate) fn split_lines(contents: &str, terminator: char) -> impl Iterator<Item = &str> {
+    contents
+        .split_terminator(terminator)
+        .map(move |line| if terminator == '\n' { line.strip_suffix('\r').unwrap_or(line) } else { line })
+}
+
+/// Whether a pattern has an uppercase letter, not counting escapes like `\S` or `\W` (unless it's a literal)
+fn has_uppercase(pattern: &str, literal: bool) -> bool {
+    let mut chars = pattern.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' && !literal {
+            chars.next(); // Skip what's escaped
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Check that the patterns compile, for the `check` subcommand
+///
+/// Positional arguments are all treated as patterns, since there's nothing to search
+///
+/// # Errors
+///
+/// Will error if the pattern file is not readable or a pattern is invalid
+pub fn check_patterns(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    let mut arg = arg.clone();
+
+    arg.regexp.append(&mut arg.files);
+    arg.read_pattern_file()?;
+    arg.read_replace_rules()?;
+
+    Patterns::new(&arg).map_err(error::Error::Pattern)?;
+    arg.replace_rules().map_err(error::Error::Pattern)?;
+
+    writeln!(io::stdout().lock(), "{}", arg.lang().catalog().patterns_ok)?;
+    Ok(())
+}
+
+/// Whether an error from one of the functions above is from stdout being closed (like by
+/// `| head`), which isn't worth reporting
+pub fn is_broken_pipe(error: &(dyn Error + 'static)) -> bool {
+    let mut error = Some(error);
+
+    while let Some(current) = error {
+        if current.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) {
+            return true;
+        }
+
+        error = current.source();
+    }
+
+    false
+}
+
+/// Save the files under the paths for `--cache-file-list` (`simple-grep index`), and print how
+/// many there are
+///
+/// Positional arguments are all treated as paths, since there's nothing to search for. The
+/// list is only used by searches with the same paths and options that decide which files are
+/// found (like `--hidden` or `--include`).
+///
+/// # Errors
+///
+/// Will error if no path was given, or the list can't be saved
+pub fn build_index(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    let mut arg = arg.clone();
+
+    if let Some(path) = arg.pattern.take() {
+        arg.files.insert(0, path);
+    }
+
+    if arg.files.is_empty() {
+        return Err(arg.lang().catalog().no_path.into());
+    }
+
+    let files = file_list::index(&arg)?;
+
+    writeln!(io::stdout().lock(), "{}", i18n::fill(arg.lang().catalog().indexed, &[("files", &files.to_string())]))?;
+    Ok(())
+}
+
+/// Exit code for an error from one of the functions above: 2 for an invalid pattern, 1 otherwise
+pub fn exit_code(error: &(dyn Error + 'static)) -> i32 {
+    let invalid_pattern = error.is::<regex::Error>() || matches!(error.downcast_ref(), Some(error::Error::Pattern(_)));
+
+    if invalid_pattern { 2 } else { 1 }
+}
+
+/// Regex equivalent of the matching options, for tests
+///
+/// # Panics
+///
+/// Will panic if the query is invalid regex
+#[cfg(test)]
+pub(crate) fn build_regex(query: &str, insensitive: bool, whole_word: bool) -> Regex {
+    try_build_regex(query, insensitive, whole_word).unwrap()
+}
+
+/// Regex equivalent of the matching options, or an error if the query is invalid
+pub(crate) fn try_build_regex(query: &str, insensitive: bool, whole_word: bool) -> Result<Regex, regex::Error> {
+    Regex::new(&regex_source(query, insensitive, whole_word))
+}
+
+/// Regex source for the matching options, shared by the text and byte regexes
+pub(crate) fn regex_source(query: &str, insensitive: bool, whole_word: bool) -> String {
+    let mut pattern = query.to_string();
+
+    // Only match if there's no word character right before or after, like grep, so it also works
+    // for phrases that start or end with a space or punctuation (where `\b` would want a word next)
+    if whole_word {
+        pattern = format!(r"\b{{start-half}}(?:{pattern})\b{{end-half}}"); // Group so alternations stay inside the boundaries
+    }
+
+    if insensitive {
+        pattern = format!(r"(?i){pattern}");
+    }
+
+    pattern
+}
+
+/// `source` with each run of literal whitespace matching any run of whitespace (`\s+`)
+///
+/// Whitespace in classes, escaped or repeated is left alone, and so is invalid regex (for
+/// compiling it to report the error).
+fn flexible_space(source: &str) -> String {
+    /// Spans of the whitespace literals in `ast` that stand for themselves
+    fn whitespace(ast: &Ast, spans: &mut Vec<std::ops::Range<usize>>) {
+        match ast {
+            Ast::Literal(literal) if literal.kind == ast::LiteralKind::Verbatim && literal.c.is_whitespace() => {
+                spans.push(literal.span.start.offset..literal.span.end.offset);
+            }
+            Ast::Group(group) => whitespace(&group.ast, spans),
+            Ast::Alternation(alternation) => alternation.asts.iter().for_each(|ast| whitespace(ast, spans)),
+            Ast::Concat(concat) => concat.asts.iter().for_each(|ast| whitespace(ast, spans)),
+            _ => {}
+        }
+    }
+
+    let Ok(ast) = ast::parse::Parser::new().parse(source) else {
+        return source.to_string();
+    };
+
+    let mut spans = Vec::new();
+    whitespace(&ast, &mut spans);
+
+    let mut flexible = String::with_capacity(source.len());
+    let mut last = 0;
+    let mut run_end = None; // Where the whitespace last replaced ends
+
+    for span in spans {
+        if run_end != Some(span.start) {
+            flexible.push_str(&source[last..span.start]);
+            flexible.push_str(r"\s+");
+        }
+
+        last = span.end;
+        run_end = Some(span.end);
+    }
+
+    flexible.push_str(&source[last..]);
+    flexible
+}
+
+/// Drop matches that have a line matching `near_regex` within `within` lines of them (including the match itself)
+pub(crate) fn suppress_near_matches<'a>(
+    matches: Vec<(usize, &'a str)>,
+    contents: &str,
+    near_regex: &Regex,
+    within: usize,
+    terminator: char,
+) -> Vec<(usize, &'a str)> {
+    // Sorted, so each window is a binary search
+    let near_lines: Vec<usize> = split_lines(contents, terminator)
+        .enumerate()
+        .filter(|(_, line)| near_regex.is_match(line))
+        .map(|(index, _)| index + 1)
+        .collect();
+
+    matches
+        .into_iter()
+        .filter(|(line_number, _)| {
+            let window_start = line_number.saturating_sub(within);
+            let first_near = near_lines.partition_point(|&near| near < window_start);
+
+            near_lines
+                .get(first_near)
+                .is_none_or(|&near| near > line_number + within)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use temp::TempDir;
+
+    /// Matching lines of `contents` for `query`, matching case
+    fn case_sensitive_line_matching<'a>(query: &str, contents: &'a str, whole_word: bool) -> Vec<(usize, &'a str)> {
+        Patterns::from_regex(build_regex(query, false, whole_word), None, '\n').matching_lines(contents)
+    }
+
+    /// Matching lines of `contents` for `query`, ignoring case
+    fn case_insensitive_line_matching<'a>(query: &str, contents: &'a str, whole_word: bool) -> Vec<(usize, &'a str)> {
+        Patterns::from_regex(build_regex(query, true, whole_word), None, '\n').matching_lines(contents)
+    }
+
+    /* Test read file and print matches */
+    #[test]
+    fn test_read_file_success() {
+        let arg = Argument {
+            pattern: Some(String::from("query")),
+            files: vec![String::from("./tests/test_poem.txt")],
+            insensitive: false, // Path is based on cwd (not executable location)
+            count: false,
+            word: false,
+            recursive: false,
+            ..Default::default()
+        };
+
+        let result = read_file_and_print_matches(&arg);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_read_file_error() {
+        let arg = Argument {
+            pattern: Some(String::from("query")),
+            files: vec![String::from("nonexistent_file.nonsense")],
+            insensitive: false,
+            count: false,
+            word: false,
+            recursive: false,
+            ..Default::default()
+        };
+
+        let result = read_file_and_print_matches(&arg);
+
+        assert!(result.is_err());
+    }
+
+    /* Test normalize */
+
+    #[test]
+    fn test_normalize_moves_path_when_regexp_given() {
+        let mut arg = Argument {
+            pattern: Some(String::from("./tests/test_poem.txt")),
+            regexp: vec![String::from("nobody")],
+            ..Default::default()
+        };
+
+        arg.normalize().unwrap();
+
+        assert_eq!(arg.pattern, None);
+        assert_eq!(arg.files, vec!["./tests/test_poem.txt"]);
+    }
+
+    #[test]
+    fn test_normalize_keeps_every_path_when_regexp_given() {
+        let mut arg = Argument {
+            pattern: Some(String::from("a.txt")),
+            files: vec![String::from("b.txt"), String::from("c.txt")],
+            regexp: vec![String::from("nobody")],
+            ..Default::default()
+        };
+
+        arg.normalize().unwrap();
+
+        assert_eq!(arg.files, vec!["a.txt", "b.txt", "c.txt"]);
+    }
+
+    #[test]
+    fn test_normalize_reads_pattern_file() {
+        let mut arg = Argument {
+            pattern: Some(String::from("./tests/test_2.txt")),
+            regexp: vec![String::from("dog")],
+            pattern_file: Some(String::from("./tests/test_patterns.txt")),
+            ..Default::default()
+        };
+
+        arg.normalize().unwrap();
+
+        assert_eq!(arg.regexp, vec!["dog", "fox", "cat"]); // Empty line skipped
+    }
+
+    #[test]
+    fn test_normalize_missing_pattern_file() {
+        let mut arg = Argument {
+            pattern: Some(String::from("./tests/test_2.txt")),
+            pattern_file: Some(String::from("nonexistent_file.nonsense")),
+            ..Default::default()
+        };
+
+        assert!(arg.normalize().is_err());
+    }
+
+    #[test]
+    fn test_normalize_no_path() {
+        let mut arg = Argument {
+            regexp: vec![String::from("dog")],
+            ..Default::default()
+        };
+
+        assert!(arg.normalize().is_err());
+    }
+
+    /* Test query */
+
+    #[test]
+    fn test_query_combines_patterns() {
+        let arg = Argument {
+            regexp: vec![String::from("fox"), String::from("cat|dog")],
+            ..Default::default()
+        };
+
+        let result = case_sensitive_line_matching(&arg.query(), "a fox
+a dog
+a bird
+", false);
+
+        assert_eq!(result, vec![(1, "a fox"), (2, "a dog")]);
+    }
+
+    #[test]
+    fn test_query_no_patterns_matches_nothing() {
+        let arg = Argument::default();
+
+        let result = case_sensitive_line_matching(&arg.query(), "a fox
+
+", false);
+
+        assert_eq!(result, Vec::<(usize, &str)>::new());
+    }
+
+    #[test]
+    fn test_query_fixed_strings() {
+        let arg = Argument {
+            pattern: Some(String::from("a.b(")),
+            fixed_strings: true,
+            ..Default::default()
+        };
+
+        let result = case_sensitive_line_matching(&arg.query(), "a.b(c)
+axb(
+", false);
+
+        assert_eq!(result, vec![(1, "a.b(c)")]);
+    }
+
+    #[test]
+    fn test_query_fixed_strings_with_multiple_patterns() {
+        let arg = Argument {
+            regexp: vec![String::from("1+1"), String::from("[x]")],
+            fixed_strings: true,
+            ..Default::default()
+        };
+
+        let result = case_insensitive_line_matching(&arg.query(), "1+1=2
+11
+[X] done
+x
+", false);
+
+        assert_eq!(result, vec![(1, "1+1=2"), (3, "[X] done")]);
+    }
+
+    #[test]
+    fn test_query_line_regexp() {
+        let arg = Argument {
+            pattern: Some(String::from("the end")),
+            line_regexp: true,
+            ..Default::default()
+        };
+
+        let result = case_insensitive_line_matching(&arg.query(), "The End\nnot the end\nthe end.\n", true);
+
+        assert_eq!(result, vec![(1, "The End")]);
+    }
+
+    #[test]
+    fn test_query_line_regexp_with_fixed_strings_and_multiple_patterns() {
+        let arg = Argument {
+            regexp: vec![String::from("a.c"), String::from("x|y")],
+            line_regexp: true,
+            fixed_strings: true,
+            ..Default::default()
+        };
+
+        let result = case_sensitive_line_matching(&arg.query(), "a.c\nabc\nx|y\nx\na.c!\n", false);
+
+        assert_eq!(result, vec![(1, "a.c"), (3, "x|y")]);
+    }
+
+    #[test]
+    fn test_query_starts_and_ends_with() {
+        let arg = |starts_with, ends_with| Argument {
+            regexp: vec![String::from("old_"), String::from("_v1")],
+            fixed_strings: true,
+            starts_with,
+            ends_with,
+            ..Default::default()
+        };
+        let contents = "old_api\nbold_api\nparse_v1\nparse_v12\n";
+
+        let result = case_sensitive_line_matching(&arg(true, false).query(), contents, false);
+        assert_eq!(result, vec![(1, "old_api")]);
+
+        let result = case_insensitive_line_matching(&arg(false, true).query(), "PARSE_V1\nPARSE_V12\n", false);
+        assert_eq!(result, vec![(1, "PARSE_V1")]);
+
+        let arg = Argument { pattern: Some(String::from("fox")), ..arg(true, true) };
+        let result = case_sensitive_line_matching(&arg.query(), "fox\nfoxes\n", false);
+        assert_eq!(result, vec![(1, "fox")]);
+
+        assert!(Cli::try_parse_from(["simple-grep", "--starts-with", "-w", "x", "a.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_whole_word_matching_with_alternation() {
+        let result = case_sensitive_line_matching("(?:the)|(?:fox)", "foxes
+there
+the end
+", true);
+
+        assert_eq!(result, vec![(3, "the end")]);
+    }
+
+    #[test]
+    fn test_whole_word_phrases() {
+        let result = case_sensitive_line_matching(r"\(x\) or", "call (x) or not\ncall(x) or\n(x) order\n", true);
+
+        assert_eq!(result, vec![(1, "call (x) or not")]); // No word character right outside, whatever the edges are
+    }
+
+    #[test]
+    fn test_flexible_space() {
+        assert_eq!(flexible_space("quick brown  fox"), r"quick\s+brown\s+fox");
+        assert_eq!(flexible_space(" fox"), r"\s+fox");
+        assert_eq!(flexible_space("[a b] (c d|e f)"), r"[a b]\s+(c\s+d|e\s+f)");
+        assert_eq!(flexible_space(r"a\ b a +"), r"a\ b\s+a +"); // Escaped and repeated stay
+        assert_eq!(flexible_space("(?x) a b"), "(?x) a b"); // Spaces mean nothing there
+        assert_eq!(flexible_space("(a b"), "(a b"); // Invalid, for the error
+    }
+
+    #[test]
+    fn test_flexible_space_phrase() {
+        let config = Argument::try_from_args(["-w", "-F", "--flexible-space", "quick brown", "a.txt"]).unwrap();
+        let searcher = Searcher::new(config).unwrap();
+
+        let lines: Vec<String> = searcher.search_str("the quick   brown fox\nthe quick\tbrown\nquick brownie\n").iter().map(|found| found.line().to_string()).collect();
+
+        assert_eq!(lines, vec!["the quick   brown fox", "the quick\tbrown"]);
+    }
+
+    /* Test subcommands */
+
+    #[test]
+    fn test_cli_defaults_to_search() {
+        let cli = Cli::try_parse_from(["simple-grep", "-i", "fox", "a.txt"]).unwrap();
+
+        assert!(cli.command.is_none());
+        assert_eq!(cli.search.pattern.as_deref(), Some("fox"));
+        assert_eq!(cli.search.files, vec!["a.txt"]);
+        assert!(cli.search.insensitive);
+    }
+
+    #[test]
+    fn test_cli_subcommands_share_search_options() {
+        let cli = Cli::try_parse_from(["simple-grep", "search", "-w", "fox", "a.txt"]).unwrap();
+
+        assert!(matches!(cli.command, Some(Command::Search(arg)) if arg.word && arg.files == ["a.txt"]));
+    }
+
+    #[test]
+    fn test_cli_pattern_can_be_a_subcommand_name_with_e() {
+        let cli = Cli::try_parse_from(["simple-grep", "-e", "x", "check"]).unwrap();
+
+        assert!(cli.command.is_none());
+        assert_eq!(cli.search.pattern.as_deref(), Some("check"));
+    }
+
+    #[test]
+    fn test_cli_patterns_starting_with_a_dash() {
+        let with_e = Cli::try_parse_from(["simple-grep", "-e", "-rf", "-e", "--force", "-i", "a.sh"]).unwrap();
+        let after_separator = Cli::try_parse_from(["simple-grep", "-i", "--", "-rf", "-v.sh"]).unwrap();
+
+        assert_eq!(with_e.search.regexp, vec!["-rf", "--force"]);
+        assert!(with_e.search.insensitive); // Options after the patterns still work
+        assert_eq!(after_separator.search.pattern.as_deref(), Some("-rf"));
+        assert_eq!(after_separator.search.files, vec!["-v.sh"]);
+    }
+
+    #[test]
+    fn test_try_from_args() {
+        let config = SearchConfig::try_from_args(["-w", "-e", "fox", "a.txt", "b.txt"]).unwrap();
+
+        assert!(config.word);
+        assert_eq!(config.regexp, vec!["fox"]);
+        assert_eq!(config.files, vec!["a.txt", "b.txt"]);
+
+        assert!(SearchConfig::try_from_args(["--no-such-flag", "x", "a.txt"]).is_err());
+        assert!(SearchConfig::try_from_args(["check", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_from_args_str() {
+        let config = SearchConfig::from_args_str("-w -e 'quick fox' a.txt").unwrap();
+
+        assert!(config.word);
+        assert_eq!(config.regexp, vec!["quick fox"]);
+        assert_eq!(config.files, vec!["a.txt"]);
+
+        let config = SearchConfig::from_profile("-i --type rust", ["-e", "fox", "src"]).unwrap();
+
+        assert!(config.insensitive);
+        assert_eq!(config.regexp, vec!["fox"]);
+        assert_eq!(config.files, vec!["src"]);
+
+        assert!(matches!(SearchConfig::from_args_str("-e 'fox a.txt"), Err(error::Error::Config(_))));
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let invalid: Box<dyn Error> = Box::new(error::Error::Pattern(try_build_regex("(", false, false).unwrap_err()));
+        assert_eq!(exit_code(&*invalid), 2);
+
+        let config = SearchConfig::try_from_args(["--duplicates", "2", "-e", "(", "a.txt"]).unwrap();
+        assert_eq!(exit_code(&*duplicates::print_duplicates(&config).unwrap_err()), 2);
+
+        let missing: Box<dyn Error> = Box::new(error::Error::Config(String::from("no path")));
+        assert_eq!(exit_code(&*missing), 1);
+    }
+
+    #[test]
+    fn test_is_broken_pipe() {
+        let closed = || io::Error::new(io::ErrorKind::BrokenPipe, "closed");
+
+        assert!(is_broken_pipe(&closed()));
+        assert!(is_broken_pipe(&error::Error::Output(closed()))); // Found through the source
+        assert!(!is_broken_pipe(&error::Error::Output(io::Error::other("disk full"))));
+    }
+
+    #[test]
+    fn test_check_patterns() {
+        let valid = Argument {
+            pattern: Some(String::from("a+b")),
+            files: vec![String::from("c|d")],
+            ..Default::default()
+        };
+        let invalid = Argument {
+            pattern: Some(String::from("ok")),
+            files: vec![String::from("(")],
+            ..Default::default()
+        };
+
+        assert!(check_patterns(&valid).is_ok());
+        assert!(check_patterns(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_cli_serve_options() {
+        let cli = Cli::try_parse_from(["simple-grep", "serve", "-r", "src"]).unwrap();
+        let Some(Command::Serve(args)) = cli.command else { panic!("not serve") };
+
+        assert_eq!(args.listen, "127.0.0.1:7878");
+        assert!(args.search.recursive);
+        assert_eq!(args.search.pattern.as_deref(), Some("src")); // Taken as a path by serve
+    }
+
+    /* Test read dir and print matches */
+
+    #[test]
+    fn test_read_dir_success() {
+        let arg = Argument {
+            pattern: Some(String::from("query")),
+            files: vec![String::from("./tests")],
+            insensitive: false, // Path is based on cwd (not executable location)
+            count: false,
+            word: false,
+            recursive: true,
+            ..Default::default()
+        };
+
+        let result = read_dir_and_print_matches(&arg);
+
+        assert!(result.is_ok());
+
+    }
+
+    /* Test suppress near matches */
+
+    #[test]
+    fn test_suppress_near_matches_same_line() {
+        let contents = "x.unwrap() // checked: fine\ny.unwrap()\n";
+        let matches = case_sensitive_line_matching("unwrap", contents, false);
+
+        let result = suppress_near_matches(matches, contents, &build_regex("// checked:", false, false), 0, '\n');
+
+        assert_eq!(result, vec![(2, "y.unwrap()")]);
+    }
+
+    #[test]
+    fn test_suppress_near_matches_within_window() {
+        let contents = "// checked: ok\na.unwrap()\n\nb.unwrap()\n\n\nc.unwrap()\n// checked: ok\n";
+        let matches = case_sensitive_line_matching("unwrap", contents, false);
+
+        let result = suppress_near_matches(matches, contents, &build_regex("// checked:", false, false), 1, '\n');
+
+        assert_eq!(result, vec![(4, "b.unwrap()")]);
+    }
+
+    #[test]
+    fn test_suppress_near_matches_no_near_lines() {
+        let contents = "a.unwrap()\nb.unwrap()\n";
+        let matches = case_sensitive_line_matching("unwrap", contents, false);
+
+        let result = suppress_near_matches(matches.clone(), contents, &build_regex("checked", false, false), 5, '\n');
+
+        assert_eq!(result, matches);
+    }
+
+    /* Test column ranges */
+
+    #[test]
+    fn test_split_lines() {
+        assert_eq!(split_lines("a\r\nb\n\nc\n", '\n').collect::<Vec<&str>>(), vec!["a", "b", "", "c"]);
+        assert_eq!(split_lines("a\nb\0c\r\0", '\0').collect::<Vec<&str>>(), vec!["a\nb", "c\r"]);
+        assert_eq!(split_lines("", '\0').count(), 0);
+    }
+
+    /* Test file filters */
+
+    #[test]
+    fn test_passes_file_filters_include() {
+        let arg = Argument {
+            include: vec![Glob::new("*.rs"), Glob::new("*.toml")],
+            ..Default::default()
+        };
+
+        assert!(passes_file_filters(&arg, "lib.rs"));
+        assert!(passes_file_filters(&arg, "Cargo.toml"));
+        assert!(!passes_file_filters(&arg, "README.md"));
+    }
+
+    #[test]
+    fn test_passes_file_filters_path_globs() {
+        let arg = Argument {
+            include: vec![Glob::new("src/**/*.{rs,toml}")],
+            exclude: vec![Glob::new("**/generated/*")],
+            ..Default::default()
+        };
+
+        assert!(passes_file_filters(&arg, "./src/lib.rs"));
+        assert!(passes_file_filters(&arg, "./src/parser/Cargo.toml"));
+        assert!(!passes_file_filters(&arg, "./src/generated/ast.rs"));
+        assert!(!passes_file_filters(&arg, "./tests/cli.rs"));
+    }
+
+    #[test]
+    fn test_passes_file_filters_exclude_wins() {
+        let arg = Argument {
+            include: vec![Glob::new("*.js")],
+            exclude: vec![Glob::new("*.min.js")],
+            ..Default::default()
+        };
+
+        assert!(passes_file_filters(&arg, "app.js"));
+        assert!(!passes_file_filters(&arg, "app.min.js"));
+    }
+
+    #[test]
+    fn test_passes_file_filters_types() {
+        let arg = Argument {
+            file_type: vec![String::from("rust"), String::from("toml")],
+            type_not: vec![String::from("toml")],
+            ..Default::default()
+        };
+
+        assert!(passes_file_filters(&arg, "lib.rs"));
+        assert!(!passes_file_filters(&arg, "Cargo.toml")); // --type-not wins
+        assert!(!passes_file_filters(&arg, "README.md"));
+    }
+
+    #[test]
+    fn test_passes_file_filters_no_filters() {
+        assert!(passes_file_filters(&Argument::default(), "anything"));
+    }
+
+    #[test]
+    fn test_files_to_search_with_include() {
+        let arg = Argument {
+            files: vec![String::from("./tests")],
+            recursive: true,
+            include: vec![Glob::new("test_?.txt")],
+            ..Default::default()
+        };
+
+        let result = files_to_search(&arg);
+
+        assert_eq!(result.len(), 1);
+        assert!(result[0].ends_with("test_2.txt"));
+    }
+
+    #[test]
+    fn test_files_to_search_max_depth() {
+        let top = Argument { files: vec![String::from("./src")], recursive: true, max_depth: Some(1), ..Default::default() };
+        let none = Argument { files: vec![String::from("./src")], recursive: true, max_depth: Some(0), ..Default::default() };
+
+        assert!(files_to_search(&top).iter().any(|file| file.ends_with("lib.rs")));
+        assert!(files_to_search(&none).is_empty()); // Only the directory itself, which isn't a file
+    }
+
+    #[test]
+    fn test_files_to_search_skips_hidden() {
+        let temp = TempDir::new("hidden");
+        let dir = temp.join(".hidden");
+
+        for file in [".hidden/.git/config", ".hidden/.env", ".hidden/a.txt"] {
+            temp.write(file, "a\n");
+        }
+
+        let files = vec![dir.to_string_lossy().into_owned()];
+        let default = Argument { files: files.clone(), recursive: true, ..Default::default() };
+        let hidden = Argument { files, recursive: true, hidden: true, ..Default::default() };
+
+        let not_hidden = files_to_search(&default);
+        let with_hidden = files_to_search(&hidden);
+
+        assert_eq!(not_hidden.len(), 1); // The hidden directory itself was given, so it's searched
+        assert!(not_hidden[0].ends_with("a.txt"));
+        assert_eq!(with_hidden.len(), 3);
+    }
+
+    #[test]
+    fn test_files_to_search_respects_gitignore() {
+        let dir = TempDir::new("gitignore");
+        dir.write(".gitignore", "target/\n*.log\n!keep.log\n");
+        dir.write("sub/.gitignore", "secret.txt\n");
+
+        for file in ["a.txt", "debug.log", "keep.log", "target/out.txt", "sub/secret.txt", "sub/b.txt"] {
+            dir.write(file, "a\n");
+        }
+
+        let files = vec![dir.display()];
+        let default = Argument { files: files.clone(), recursive: true, ..Default::default() };
+        let no_ignore = Argument { files, recursive: true, no_ignore: true, ..Default::default() };
+
+        let mut searched: Vec<String> = files_to_search(&default)
+            .iter()
+            .map(|file| Path::new(file).strip_prefix(dir.path()).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect();
+        searched.sort();
+        let all = files_to_search(&no_ignore);
+
+        assert_eq!(searched, vec!["a.txt", "keep.log", "sub/b.txt"]);
+        assert_eq!(all.len(), 6);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_files_to_search_follow_with_loop() {
+        let dir = TempDir::new("follow");
+        dir.write("real/a.txt", "a\n");
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.join("real/loop")).unwrap();
+
+        let files = vec![dir.display()];
+        let follow = Argument { files: files.clone(), recursive: true, follow: true, no_messages: true, ..Default::default() };
+        let no_follow = Argument { files, recursive: true, ..Default::default() };
+
+        let followed = files_to_search(&follow);
+        let not_followed = files_to_search(&no_follow);
+
+        assert_eq!(followed.len(), 2); // real/a.txt and link/a.txt, without going around the loop
+        assert_eq!(not_followed.len(), 1);
+    }
+
+    #[test]
+    fn test_files_to_search_max_filesize() {
+        let arg = Argument {
+            files: vec![String::from("./tests/test_2.txt"), String::from("./tests/test_gpl_3.txt")],
+            max_filesize: Some(units::parse_size("1K").unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(files_to_search(&arg), vec!["./tests/test_2.txt"]);
+    }
+
+    #[test]
+    fn test_files_to_search_sorted() {
+        let sorted = |order| {
+            let arg = Argument {
+                files: vec![String::from("./tests/test_poem.txt"), String::from("./tests/test_2.txt"), String::from("./tests/test_gpl_3.txt")],
+                sort: Some(order),
+                ..Default::default()
+            };
+
+            files_to_search(&arg)
+        };
+
+        assert_eq!(sorted(SortBy::Path), vec!["./tests/test_2.txt", "./tests/test_gpl_3.txt", "./tests/test_poem.txt"]);
+        assert_eq!(sorted(SortBy::Size)[0], "./tests/test_gpl_3.txt"); // The license is by far the largest
+    }
+
+    /* Test smart case */
+
+    #[test]
+    fn test_smart_case() {
+        let smart = |pattern: &str| Argument { pattern: Some(String::from(pattern)), smart_case: true, ..Default::default() };
+
+        assert!(smart("fox").ignore_case());
+        assert!(!smart("Fox").ignore_case());
+        assert!(smart(r"\Sfox\W").ignore_case()); // Escapes aren't letters to match
+        assert!(!Argument { fixed_strings: true, ..smart(r"\S") }.ignore_case());
+        assert!(Argument { insensitive: true, ..smart("Fox") }.ignore_case()); // -i always wins
+    }
+
+    /* Test root labels */
+
+    #[test]
+    fn test_parse_root_label() {
+        assert_eq!(
+            parse_root_label("old=/src/a"),
+            Ok(RootLabel { name: String::from("old"), path: String::from("/src/a") }),
+        );
+        assert!(parse_root_label("old").is_err());
+        assert!(parse_root_label("=/src/a").is_err());
+    }
+
+    #[test]
+    fn test_display_path_uses_longest_root() {
+        let arg = Argument {
+            label_root: vec![parse_root_label("all=/src").unwrap(), parse_root_label("new=/src/b").unwrap()],
+            ..Default::default()
+        };
+
+        assert_eq!(arg.display_path("/src/a/lib.rs"), format!("all{}a/lib.rs", std::path::MAIN_SEPARATOR));
+        assert_eq!(arg.display_path("/src/b/lib.rs"), format!("new{}lib.rs", std::path::MAIN_SEPARATOR));
+        assert_eq!(arg.display_path("/src/b"), "new");
+        assert_eq!(arg.display_path("/srcs/lib.rs"), "/srcs/lib.rs");
+        assert_eq!(strip_root("src/lib.rs", "./src/"), Some(PathBuf::from("lib.rs")));
+        assert_eq!(arg.path_display().root_label("/src/b/lib.rs"), Some(("new", String::from("lib.rs"))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_display_path_symlinks() {
+        let dir = TempDir::new("symlink-paths");
+        dir.write("real/a.txt", "a\n");
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+
+        let link = dir.join("link/a.txt").to_string_lossy().into_owned();
+        let real = fs::canonicalize(dir.join("real/a.txt")).unwrap().to_string_lossy().into_owned();
+        let plain = dir.join("real/a.txt").to_string_lossy().into_owned();
+
+        let canonical = Argument { canonical_paths: true, ..Default::default() };
+        let targets = Argument { symlink_targets: true, ..Default::default() };
+
+        let results = (canonical.display_path(&link), targets.display_path(&link), targets.display_path(&plain));
+
+        assert_eq!(results.0, real);
+        assert_eq!(results.1, format!("{link} -> {real}"));
+        assert_eq!(results.2, plain); // Not through a link
+    }
+
+    /* Test show filename */
+
+    #[test]
+    fn test_show_filename_defaults() {
+        let single = Argument { files: vec![String::from("a")], ..Default::default() };
+        let multiple = Argument { files: vec![String::from("a"), String::from("b")], ..Default::default() };
+        let recursive = Argument { files: vec![String::from("a")], recursive: true, ..Default::default() };
+
+        assert!(!single.show_filename());
+        assert!(multiple.show_filename());
+        assert!(recursive.show_filename());
+    }
+
+    #[test]
+    fn test_show_filename_flags() {
+        let with_filename = Argument { files: vec![String::from("a")], with_filename: true, ..Default::default() };
+        let no_filename = Argument { recursive: true, no_filename: true, ..Default::default() };
+        let archive = Argument { files: vec![String::from("a.zip")], search_archives: true, ..Default::default() };
+
+        assert!(with_filename.show_filename());
+        assert!(!no_filename.show_filename());
+        assert!(archive.show_filename());
+    }
+
+    #[test]
+    fn test_filename_flags_last_one_wins() {
+        let arg = Cli::try_parse_from(["simple-grep", "-H", "-h", "x", "a", "b"]).unwrap().search;
+
+        assert!(!arg.show_filename());
+    }
+
+    /* Test use color */
+
+    #[test]
+    fn test_use_color_always_and_never() {
+        let always = Argument { color: ColorChoice::Always, ..Default::default() };
+        let never = Argument { color: ColorChoice::Never, ..Default::default() };
+
+        assert!(always.use_color());
+        assert!(!never.use_color());
+    }
+
+    #[test]
+    fn test_auto_color_environment() {
+        let auto = |vars: &[(&str, &str)], terminal: bool| {
+            let var = |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.into());
+            auto_color(var, || terminal)
+        };
+
+        assert!(auto(&[], true));
+        assert!(!auto(&[], false));
+        assert!(!auto(&[("NO_COLOR", "1")], true));
+        assert!(auto(&[("NO_COLOR", "")], true)); // Empty doesn't count
+        assert!(auto(&[("CLICOLOR_FORCE", "1")], false));
+        assert!(auto(&[("FORCE_COLOR", "true")], false));
+        assert!(!auto(&[("CLICOLOR_FORCE", "0")], false));
+        assert!(!auto(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")], true));
+    }
+
+    #[test]
+    fn test_files_to_search_filters_explicit_files() {
+        let arg = Argument {
+            files: vec![
+                String::from("./tests/test_2.txt"),
+                String::from("./tests/test_poem.txt"),
+                String::from("./src/lib.rs"),
+            ],
+            include: vec![Glob::new("*.txt")],
+            exclude: vec![Glob::new("*poem*")],
+            ..Default::default()
+        };
+
+        let result = files_to_search(&arg);
+
+        assert_eq!(result, vec!["./tests/test_2.txt"]);
+    }
+
+    #[test]
+    fn test_read_file_multiple_files() {
+        let arg = Argument {
+            pattern: Some(String::from("the")),
+            files: vec![String::from("./tests/test_2.txt"), String::from("./tests/test_poem.txt")],
+            ..Default::default()
+        };
 
+        assert!(arg.show_filename());
+        assert!(read_file_and_print_matches(&arg).is_ok());
     }
 
     /* Test case sensitive line matching */
@@ -254,7 +2414,7 @@ mod tests {
 
         let result = case_sensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, vec!["Jumps over the lazy dog"]);
+        assert_eq!(result, vec![(2, "Jumps over the lazy dog")]);
     }
 
     #[test]
@@ -264,7 +2424,7 @@ mod tests {
 
         let result = case_sensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, Vec::<&str>::new());
+        assert_eq!(result, Vec::<(usize, &str)>::new());
     }
 
     #[test]
@@ -274,7 +2434,7 @@ mod tests {
 
         let result = case_sensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, vec!["Jumps over the lazy dog", "the end"]);
+        assert_eq!(result, vec![(2, "Jumps over the lazy dog"), (3, "the end")]);
     }
 
     #[test]
@@ -284,7 +2444,7 @@ mod tests {
 
         let result = case_sensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, vec!["hello"]);
+        assert_eq!(result, vec![(1, "hello")]);
     }
 
     #[test]
@@ -294,7 +2454,7 @@ mod tests {
 
         let result = case_sensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, vec!["the", "the", "the"]);
+        assert_eq!(result, vec![(1, "the"), (2, "the"), (3, "the")]);
     }
 
     #[test]
@@ -304,7 +2464,7 @@ mod tests {
 
         let result = case_sensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, Vec::<&str>::new());
+        assert_eq!(result, Vec::<(usize, &str)>::new());
     }
 
     #[test]
@@ -314,7 +2474,7 @@ mod tests {
 
         let result = case_sensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, contents.lines().collect::<Vec<&str>>());
+        assert_eq!(result, contents.lines().enumerate().map(|(index, line)| (index + 1, line)).collect::<Vec<(usize, &str)>>());
     }
 
     #[test]
@@ -324,7 +2484,7 @@ mod tests {
 
         let result = case_sensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, Vec::<&str>::new());
+        assert_eq!(result, Vec::<(usize, &str)>::new());
     }
 
     #[test]
@@ -334,7 +2494,7 @@ mod tests {
 
         let result = case_sensitive_line_matching(query, contents, true);
 
-        assert_eq!(result, vec!["the quick brown fox"]);
+        assert_eq!(result, vec![(1, "the quick brown fox")]);
     }
 
     /* Test case insensitive line matching */
@@ -346,7 +2506,7 @@ mod tests {
 
         let result = case_insensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, vec!["The quick brown fox", "Jumps over the lazy dog"]);
+        assert_eq!(result, vec![(1, "The quick brown fox"), (2, "Jumps over the lazy dog")]);
     }
 
     #[test]
@@ -356,7 +2516,7 @@ mod tests {
 
         let result = case_insensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, Vec::<&str>::new());
+        assert_eq!(result, Vec::<(usize, &str)>::new());
     }
 
     #[test]
@@ -366,7 +2526,7 @@ mod tests {
 
         let result = case_insensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, vec!["The quick brown fox","Jumps over the lazy dog", "the end"]);
+        assert_eq!(result, vec![(1, "The quick brown fox"), (2, "Jumps over the lazy dog"), (3, "the end")]);
     }
 
     #[test]
@@ -376,7 +2536,7 @@ mod tests {
 
         let result = case_insensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, vec!["hello"]);
+        assert_eq!(result, vec![(1, "hello")]);
     }
 
     #[test]
@@ -386,7 +2546,7 @@ mod tests {
 
         let result = case_insensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, vec!["the", "the", "The"]);
+        assert_eq!(result, vec![(1, "the"), (2, "the"), (3, "The")]);
     }
 
     #[test]
@@ -396,7 +2556,7 @@ mod tests {
 
         let result = case_insensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, Vec::<&str>::new());
+        assert_eq!(result, Vec::<(usize, &str)>::new());
     }
 
     #[test]
@@ -406,7 +2566,7 @@ mod tests {
 
         let result = case_insensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, contents.lines().collect::<Vec<&str>>());
+        assert_eq!(result, contents.lines().enumerate().map(|(index, line)| (index + 1, line)).collect::<Vec<(usize, &str)>>());
     }
 
     #[test]
@@ -416,7 +2576,7 @@ mod tests {
 
         let result = case_insensitive_line_matching(query, contents, false);
 
-        assert_eq!(result, Vec::<&str>::new());
+        assert_eq!(result, Vec::<(usize, &str)>::new());
     }
 
     #[test]
@@ -426,6 +2586,6 @@ mod tests {
 
         let result = case_insensitive_line_matching(query, contents, true);
 
-        assert_eq!(result, vec!["The quick brown fox"]);
+        assert_eq!(result, vec![(1, "The quick brown fox")]);
     }
 }
\ No newline at end of file
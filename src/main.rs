@@ -2,36 +2,125 @@
 
 
 // Standard library
+use std::env; // For command-line arguments
+use std::error::Error; // For reporting errors
 use std::process; // For exiting
 // use std::path::PathBuf; // For file paths
 
 // External creates
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 
 // My stuff
-use simple_grep::Argument; // Import Config struct from lib.rs
+use simple_grep::{Argument, Cli, Command, Lang}; // Import Config struct from lib.rs
 
 
 fn main() {
     
-    let config = Argument::parse(); // Parse command-line arguments w/ clap
+    // Help is translated before parsing, so --lang has to be picked out by hand
+    let args: Vec<_> = env::args_os().collect();
+    let lang = Lang::from_args(&args).unwrap_or_else(Lang::from_env);
+    simple_grep::report_status_on_signal(lang); // Before any thread starts
 
-    match &config.recursive {
-        true => {
-            // Handle error
-            if let Err(e) = simple_grep::read_dir_and_print_matches(&config) {
-                eprintln!("Application error: {e}"); // Print to stderr
+    // Parse command-line arguments w/ clap
+    let matches = simple_grep::localized_command(Cli::command(), lang).get_matches_from(args);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
-                process::exit(1);
-            }
-        },
-        false => {
+    match cli.command {
+        Some(Command::Check(config)) => {
             // Handle error
-            if let Err(e) = simple_grep::read_file_and_print_matches(&config) {
-                eprintln!("Application error: {e}"); // Print to stderr
-
-                process::exit(1);
+            if let Err(e) = simple_grep::check_patterns(&config) {
+                fail(config.lang(), &*e);
+            }
+        }
+        Some(Command::Examples) => {
+            if let Err(e) = simple_grep::print_examples(lang) {
+                fail(lang, &e);
+            }
+        }
+        Some(Command::Index(config)) => {
+            if let Err(e) = simple_grep::build_index(&config) {
+                fail(config.lang(), &*e);
+            }
+        }
+        Some(Command::Serve(args)) => {
+            if let Err(e) = simple_grep::serve(&args) {
+                fail(args.search.lang(), &*e);
             }
         }
+        Some(Command::Types) => {
+            if let Err(e) = simple_grep::print_types() {
+                fail(lang, &e);
+            }
+        }
+        Some(Command::Search(config)) => search(config),
+        None => search(cli.search),
+    }
+}
+
+/// Report `e` in `lang` and exit, quietly if stdout was closed (like by `| head`)
+fn fail(lang: Lang, e: &(dyn Error + 'static)) -> ! {
+    if simple_grep::is_broken_pipe(e) {
+        process::exit(0);
+    }
+
+    eprintln!("{}: {e}", lang.application_error()); // Print to stderr
+
+    process::exit(simple_grep::exit_code(e));
+}
+
+fn search(mut config: Argument) {
+    if config.help_long {
+        if let Err(e) = simple_grep::print_long_help(config.lang()) {
+            fail(config.lang(), &e);
+        }
+
+        return;
+    }
+
+    if config.version {
+        if let Err(e) = simple_grep::print_version(&config) {
+            fail(config.lang(), &e);
+        }
+
+        return;
+    }
+
+    // Replays bring their own options
+    if config.replay.is_some() {
+        if let Err(e) = simple_grep::replay(&config) {
+            fail(config.lang(), &*e);
+        }
+
+        return;
+    }
+
+    // Handle error
+    if let Err(e) = config.normalize() {
+        fail(config.lang(), &*e);
+    }
+
+    let result = if config.tui {
+        simple_grep::run_tui(&config)
+    } else if config.todos {
+        simple_grep::print_todo_report(&config)
+    } else if config.duplicates.is_some() {
+        simple_grep::print_duplicates(&config)
+    } else if !config.compare.is_empty() {
+        simple_grep::print_comparison(&config)
+    } else if config.write {
+        simple_grep::write_replacements(&config)
+    } else if config.fuzzy {
+        simple_grep::print_fuzzy(&config)
+    } else if config.watch {
+        simple_grep::watch(&config)
+    } else if config.recursive {
+        simple_grep::read_dir_and_print_matches(&config)
+    } else {
+        simple_grep::read_file_and_print_matches(&config)
+    };
+
+    // Handle error
+    if let Err(e) = result {
+        fail(config.lang(), &*e);
     }
 }
@@ -0,0 +1,609 @@
+//! The patterns, compiled once per search with the matching options
+//!
+//! Compiling a regex costs far more than matching a typical line with it, so a search builds
+//! its [`Patterns`] up front and uses them for every file (and every line).
+//!
+//! When every match starts with one of a few literal strings (like `TODO` or `ERROR code=\d+`),
+//! those are searched for in the whole file first, with memchr or Aho-Corasick, and only the
+//! lines they're found in are split off and checked with the regex. That skips the per-line
+//! work for all the other lines, which is most of the time in a big file with few matches.
+//!
+//! With `--auto-engine`, patterns that are only literal strings (like `TODO`, or `-F` patterns)
+//! skip the regex altogether. With `-i` too, ASCII literals are searched for ignoring ASCII
+//! case, rather than for every case variant. Match spans still come from the regex, but only for
+//! the (usually few) lines that match.
+//!
+//! With `--engine pcre2` (and the `pcre2` feature), PCRE2 matches instead of the regex crate,
+//! line by line, so patterns can use lookaround and backreferences. With `--auto-engine`, PCRE2
+//! is used for patterns the regex crate rejects for having them. Without the feature, there's
+//! no fallback, and those patterns are errors as usual.
+//!
+//! Everything else (searching, printing, replacing) only asks the [`Patterns`] whether a line
+//! matches and where, and they ask their [`Matcher`]. So another engine (like fuzzy matching) is
+//! another implementation of the trait, without touching the rest: how it finds a match in a
+//! line, every match's span, and its name for `--debug`.
+
+// Standard library
+use std::fmt; // For matchers' debug output
+use std::ops::Range; // For spans
+use std::sync::Arc; // For sharing the matcher between searches
+
+// External crates
+use aho_corasick::AhoCorasick; // For several literals
+use memchr::memmem; // For one literal
+use regex::Regex; // For matching lines
+use regex_syntax::hir::literal::{Extractor, Literal}; // For finding literal patterns
+use regex_syntax::hir::Hir; // For looking at patterns
+
+// My stuff
+use crate::columns::ColumnRange;
+#[cfg(all(feature = "pcre2", unix))]
+use crate::pcre2::Pcre2;
+use crate::search::match_spans;
+use crate::{regex_source, split_lines, try_build_regex, Argument};
+
+/// Most literals to search for before the regex
+///
+/// With more, like the case variants of `-i error`, a substring search finds so many candidates
+/// that the regex alone is faster.
+const MAX_LITERALS: usize = 4;
+
+/// Where a match is in a line, in bytes
+pub(crate) type Span = Range<usize>;
+
+/// Finds the patterns' matches in a line (or the part of it `--columns` leaves)
+pub(crate) trait Matcher: fmt::Debug + Send + Sync {
+    /// The first match in `line`, if any (which can be empty, like for `x*`)
+    fn find(&self, line: &str) -> Option<Span>;
+
+    /// Whether `line` has a match
+    fn is_match(&self, line: &str) -> bool {
+        self.find(line).is_some()
+    }
+
+    /// Spans of the matches in `line`, in order without overlapping, skipping empty ones
+    fn spans(&self, line: &str) -> Vec<Span>;
+
+    /// Name for `--debug`
+    fn name(&self) -> &'static str;
+
+    /// The regex the matches are the matches of, unless there's none (like with PCRE2, which
+    /// can't replace)
+    fn regex(&self) -> Option<&Regex>;
+
+    /// Literals one of which is in every matching line, to find the lines worth matching in the
+    /// whole of a file (without splitting every line)
+    fn prefilter(&self) -> Option<&Literals> {
+        None
+    }
+}
+
+/// The regex crate, which picks between its own DFAs and NFAs
+#[derive(Debug)]
+struct RegexMatcher(Regex);
+
+impl Matcher for RegexMatcher {
+    fn find(&self, line: &str) -> Option<Span> {
+        self.0.find(line).map(|found| found.range())
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.0.is_match(line)
+    }
+
+    fn spans(&self, line: &str) -> Vec<Span> {
+        match_spans(line, &self.0, None)
+    }
+
+    fn name(&self) -> &'static str {
+        "regex"
+    }
+
+    fn regex(&self) -> Option<&Regex> {
+        Some(&self.0)
+    }
+}
+
+/// The regex, but only on lines with one of the literals every match starts with
+#[derive(Debug)]
+struct PrefilteredMatcher {
+    literals: Literals,
+    regex: Regex,
+}
+
+impl Matcher for PrefilteredMatcher {
+    fn find(&self, line: &str) -> Option<Span> {
+        self.literals.find(line.as_bytes())?;
+        self.regex.find(line).map(|found| found.range())
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.literals.find(line.as_bytes()).is_some() && self.regex.is_match(line)
+    }
+
+    fn spans(&self, line: &str) -> Vec<Span> {
+        match_spans(line, &self.regex, None)
+    }
+
+    fn name(&self) -> &'static str {
+        match self.literals {
+            Literals::One(_) => "regex with a memchr prefilter",
+            Literals::Many(_) => "regex with an Aho-Corasick prefilter",
+        }
+    }
+
+    fn regex(&self) -> Option<&Regex> {
+        Some(&self.regex)
+    }
+
+    fn prefilter(&self) -> Option<&Literals> {
+        Some(&self.literals)
+    }
+}
+
+/// The literals alone, since they're exactly what the regex matches (`--auto-engine`), which
+/// still finds the spans in matching lines
+#[derive(Debug)]
+struct LiteralMatcher {
+    literals: Literals,
+    regex: Regex,
+}
+
+impl Matcher for LiteralMatcher {
+    fn find(&self, line: &str) -> Option<Span> {
+        self.literals.find(line.as_bytes())?;
+        self.regex.find(line).map(|found| found.range()) // The one the regex would pick, if literals overlap
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.literals.find(line.as_bytes()).is_some()
+    }
+
+    fn spans(&self, line: &str) -> Vec<Span> {
+        match_spans(line, &self.regex, None)
+    }
+
+    fn name(&self) -> &'static str {
+        match self.literals {
+            Literals::One(_) => "memchr substring search",
+            Literals::Many(_) => "Aho-Corasick multi-substring search",
+        }
+    }
+
+    fn regex(&self) -> Option<&Regex> {
+        Some(&self.regex)
+    }
+
+    fn prefilter(&self) -> Option<&Literals> {
+        Some(&self.literals)
+    }
+}
+
+/// Literals ignoring ASCII case, since that's all their case is (`--auto-engine` with `-i`),
+/// with the regex for the spans like [`LiteralMatcher`]
+#[derive(Debug)]
+struct CaseInsensitiveMatcher {
+    literals: Literals,
+    regex: Regex,
+}
+
+impl CaseInsensitiveMatcher {
+    /// Matcher for `hir` (the patterns without `-i`) when it's only literals that ignoring case
+    /// can't make match anything but ASCII, with the case-insensitive `regex`
+    fn new(hir: &Hir, regex: Regex) -> Option<Self> {
+        let prefixes = Extractor::new().extract(hir);
+        let literals = prefixes.literals().filter(|literals| !literals.is_empty() && literals.len() <= MAX_LITERALS)?;
+
+        // The Kelvin sign folds to k and the long s to s, so those need Unicode case folding
+        let ascii = |literal: &Literal| !literal.is_empty() && literal.as_bytes().iter().all(|byte| byte.is_ascii() && !b"kKsS".contains(byte));
+
+        if !prefixes.is_exact() || !hir.properties().look_set().is_empty() || !literals.iter().all(ascii) {
+            return None;
+        }
+
+        let automaton = AhoCorasick::builder().ascii_case_insensitive(true).build(literals.iter().map(Literal::as_bytes)).ok()?;
+
+        Some(CaseInsensitiveMatcher { literals: Literals::Many(automaton), regex })
+    }
+}
+
+impl Matcher for CaseInsensitiveMatcher {
+    fn find(&self, line: &str) -> Option<Span> {
+        self.literals.find(line.as_bytes())?;
+        self.regex.find(line).map(|found| found.range())
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.literals.find(line.as_bytes()).is_some()
+    }
+
+    fn spans(&self, line: &str) -> Vec<Span> {
+        match_spans(line, &self.regex, None)
+    }
+
+    fn name(&self) -> &'static str {
+        "ASCII case-insensitive Aho-Corasick search"
+    }
+
+    fn regex(&self) -> Option<&Regex> {
+        Some(&self.regex)
+    }
+
+    fn prefilter(&self) -> Option<&Literals> {
+        Some(&self.literals)
+    }
+}
+
+/// PCRE2 (`--engine pcre2`)
+#[cfg(all(feature = "pcre2", unix))]
+impl Matcher for Pcre2 {
+    fn find(&self, line: &str) -> Option<Span> {
+        Pcre2::find(self, line)
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        Pcre2::is_match(self, line)
+    }
+
+    fn spans(&self, line: &str) -> Vec<Span> {
+        self.find_iter(line).into_iter().filter(|found| !found.is_empty()).collect()
+    }
+
+    fn name(&self) -> &'static str {
+        "PCRE2"
+    }
+
+    fn regex(&self) -> Option<&Regex> {
+        None
+    }
+}
+
+/// The cheapest matcher that matches exactly what `regex` (compiled from `source`) does, only
+/// skipping the regex altogether if `auto`
+fn pick(regex: Regex, source: &str, auto: bool) -> Arc<dyn Matcher> {
+    let Ok(hir) = regex_syntax::parse(source) else {
+        return Arc::new(RegexMatcher(regex));
+    };
+
+    // Every match starts with one of these (or is one of them, if they're all exact)
+    let prefixes = Extractor::new().extract(&hir);
+
+    let Some(literals) = prefixes.literals().filter(|literals| !literals.is_empty() && literals.iter().all(|literal| !literal.is_empty())) else {
+        return Arc::new(RegexMatcher(regex)); // Nothing required (or nothing matches, which is already fast)
+    };
+
+    let Some(literals) = Literals::new(literals) else {
+        return Arc::new(RegexMatcher(regex));
+    };
+
+    // Anchors and word boundaries count as empty exact literals, but aren't
+    if auto && prefixes.is_exact() && hir.properties().look_set().is_empty() {
+        Arc::new(LiteralMatcher { literals, regex })
+    } else {
+        Arc::new(PrefilteredMatcher { literals, regex })
+    }
+}
+
+/// Whether `source` only fails to parse for using lookaround or backreferences, which the regex
+/// crate doesn't have
+#[cfg(all(feature = "pcre2", unix))]
+fn needs_backtracking(source: &str) -> bool {
+    use regex_syntax::ast::{parse::Parser, ErrorKind};
+
+    Parser::new()
+        .parse(source)
+        .is_err_and(|error| matches!(error.kind(), ErrorKind::UnsupportedLookAround | ErrorKind::UnsupportedBackreference))
+}
+
+/// Substring search for one or more literal strings
+#[derive(Debug, Clone)]
+pub(crate) enum Literals {
+    /// With memchr
+    One(Box<memmem::Finder<'static>>), // Boxed, since it's much bigger than the other
+    /// With Aho-Corasick
+    Many(AhoCorasick),
+}
+
+impl Literals {
+    /// Search for `literals`, unless there are too many for it to beat the regex
+    fn new(literals: &[Literal]) -> Option<Self> {
+        match literals {
+            [literal] => Some(Literals::One(Box::new(memmem::Finder::new(literal.as_bytes()).into_owned()))),
+            _ if literals.len() <= MAX_LITERALS => AhoCorasick::new(literals.iter().map(Literal::as_bytes)).ok().map(Literals::Many),
+            _ => None,
+        }
+    }
+
+    /// Start of a literal in `haystack`, the one that ends first if they overlap
+    pub(crate) fn find(&self, haystack: &[u8]) -> Option<usize> {
+        match self {
+            Literals::One(finder) => finder.find(haystack),
+            Literals::Many(automaton) => automaton.find(haystack).map(|found| found.start()),
+        }
+    }
+}
+
+/// Finds the lines matching the patterns, respecting `-i`, `-w`, `--columns` and `-z`
+#[derive(Debug, Clone)]
+pub(crate) struct Patterns {
+    matcher: Arc<dyn Matcher>,
+    columns: Option<ColumnRange>,
+    terminator: char,
+}
+
+impl Patterns {
+    /// Patterns and options of `arg`
+    ///
+    /// # Errors
+    ///
+    /// Will error if a pattern is invalid
+    pub(crate) fn new(arg: &Argument) -> Result<Self, regex::Error> {
+        let query = arg.query();
+        let patterns = |matcher: Arc<dyn Matcher>| Patterns { matcher, columns: arg.columns, terminator: arg.line_terminator() };
+
+        #[cfg(all(feature = "pcre2", unix))]
+        if arg.engine == crate::RegexEngine::Pcre2 {
+            return Ok(patterns(Arc::new(Pcre2::new(&query, arg.ignore_case(), arg.whole_word())?)));
+        }
+
+        let source = regex_source(&query, arg.ignore_case(), arg.whole_word());
+
+        let regex = match try_build_regex(&query, arg.ignore_case(), arg.whole_word()) {
+            // Lookaround and backreferences are what PCRE2 has and the regex crate doesn't
+            #[cfg(all(feature = "pcre2", unix))]
+            Err(_) if arg.auto_engine && !arg.needs_regex() && needs_backtracking(&source) => {
+                return Ok(patterns(Arc::new(Pcre2::new(&query, arg.ignore_case(), arg.whole_word())?)));
+            }
+            result => result?,
+        };
+
+        if arg.auto_engine
+            && arg.ignore_case()
+            && let Ok(hir) = regex_syntax::parse(&regex_source(&query, false, arg.whole_word()))
+            && let Some(matcher) = CaseInsensitiveMatcher::new(&hir, regex.clone())
+        {
+            return Ok(patterns(Arc::new(matcher)));
+        }
+
+        Ok(patterns(pick(regex, &source, arg.auto_engine)))
+    }
+
+    /// Patterns of an already compiled `regex`
+    #[cfg(test)]
+    pub(crate) fn from_regex(regex: Regex, columns: Option<ColumnRange>, terminator: char) -> Self {
+        Patterns { matcher: Arc::new(RegexMatcher(regex)), columns, terminator }
+    }
+
+    /// The compiled patterns, unless PCRE2 matches them (which can't replace)
+    pub(crate) fn regex(&self) -> Option<&Regex> {
+        self.matcher.regex()
+    }
+
+    /// Name of the engine checking lines, for `--debug`
+    pub(crate) fn engine_name(&self) -> &'static str {
+        self.matcher.name()
+    }
+
+    /// Whether `line` matches (in the `--columns` range, if given)
+    pub(crate) fn is_match(&self, line: &str) -> bool {
+        self.matcher.is_match(self.columns.map_or(line, |columns| columns.slice(line)))
+    }
+
+    /// Spans of the matches in `line` (in the `--columns` range, if given), skipping empty ones
+    pub(crate) fn spans(&self, line: &str) -> Vec<Span> {
+        let (start, end) = self.columns.map_or((0, line.len()), |columns| columns.byte_range(line));
+
+        self.matcher.spans(&line[start..end]).into_iter().map(|found| start + found.start..start + found.end).collect()
+    }
+
+    /// Matching lines of `contents` with their (1-based) line numbers
+    pub(crate) fn matching_lines<'a>(&self, contents: &'a str) -> Vec<(usize, &'a str)> {
+        if let Some(literals) = self.matcher.prefilter() {
+            return self.prefiltered_lines(literals, contents);
+        }
+
+        split_lines(contents, self.terminator)
+            .enumerate()
+            .filter(|(_, line)| self.is_match(line))
+            .map(|(index, line)| (index + 1, line))
+            .collect()
+    }
+
+    /// Like [`Patterns::matching_lines`], but only looking at the lines where `literals` finds
+    /// something in the whole of `contents`, and skipping the rest without splitting them
+    fn prefiltered_lines<'a>(&self, literals: &Literals, contents: &'a str) -> Vec<(usize, &'a str)> {
+        let bytes = contents.as_bytes();
+        let terminator = if self.terminator == '\n' { b'\n' } else { 0 };
+
+        let mut lines = Vec::new();
+        let (mut line_number, mut counted) = (1, 0); // The line number of the byte at `counted`
+        let mut from = 0;
+
+        while let Some(found) = literals.find(&bytes[from..]).map(|found| from + found) {
+            let start = memchr::memrchr(terminator, &bytes[..found]).map_or(0, |end| end + 1);
+            let end = memchr::memchr(terminator, &bytes[found..]).map_or(bytes.len(), |end| found + end);
+
+            line_number += memchr::memchr_iter(terminator, &bytes[counted..start]).count();
+            counted = start;
+
+            // Terminators are ASCII, so these are char boundaries
+            let line = &contents[start..end];
+            let line = if terminator == b'\n' { line.strip_suffix('\r').unwrap_or(line) } else { line };
+
+            if self.is_match(line) {
+                lines.push((line_number, line));
+            }
+
+            from = end + 1;
+
+            if from >= bytes.len() {
+                break;
+            }
+        }
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::columns;
+
+    #[test]
+    fn test_matching_lines_in_columns() {
+        let arg = Argument {
+            pattern: Some(String::from("^id")),
+            columns: Some(columns::parse_column_range("5-10").unwrap()),
+            ..Default::default()
+        };
+        let contents = "id  id=1\nab  xx=2 id\n";
+
+        let result = Patterns::new(&arg).unwrap().matching_lines(contents);
+
+        assert_eq!(result, vec![(1, "id  id=1")]);
+    }
+
+    #[test]
+    fn test_matching_lines_null_data() {
+        let arg = Argument {
+            pattern: Some(String::from("^b")),
+            null_data: true,
+            ..Default::default()
+        };
+        let contents = "a\nb\0b c\0\0";
+
+        let result = Patterns::new(&arg).unwrap().matching_lines(contents);
+
+        assert_eq!(result, vec![(2, "b c")]);
+    }
+
+    #[test]
+    fn test_matching_lines_smart_case() {
+        let arg = Argument { pattern: Some(String::from("fox")), smart_case: true, ..Default::default() };
+
+        assert_eq!(Patterns::new(&arg).unwrap().matching_lines("Fox\nfox\nFOX\n").len(), 3);
+    }
+
+    #[test]
+    fn test_matching_lines_strips_carriage_returns() {
+        let arg = Argument { pattern: Some(String::from("b$")), ..Default::default() };
+
+        assert_eq!(Patterns::new(&arg).unwrap().matching_lines("a\r\nb\r\n"), vec![(2, "b")]);
+    }
+
+    /// Engine `--auto-engine` picks for `arg`
+    fn engine(arg: Argument) -> &'static str {
+        Patterns::new(&Argument { auto_engine: true, ..arg }).unwrap().engine_name()
+    }
+
+    fn pattern(pattern: &str) -> Argument {
+        Argument { pattern: Some(String::from(pattern)), ..Default::default() }
+    }
+
+    #[test]
+    fn test_auto_engine_picks() {
+        assert_eq!(engine(pattern("TODO")), "memchr substring search");
+        assert_eq!(engine(pattern("TODO|FIXME")), "Aho-Corasick multi-substring search");
+        assert_eq!(engine(Argument { insensitive: true, ..pattern("ab") }), "ASCII case-insensitive Aho-Corasick search");
+        assert_eq!(engine(Argument { insensitive: true, ..pattern("todo|fixme") }), "ASCII case-insensitive Aho-Corasick search"); // Not 16 case variants
+        assert_eq!(engine(Argument { insensitive: true, ..pattern("ks") }), "regex"); // The Kelvin sign is a k and ſ an s, so 9 case variants
+        assert_eq!(engine(Argument { insensitive: true, word: true, ..pattern("ab") }), "regex with an Aho-Corasick prefilter");
+        assert_eq!(engine(Argument { fixed_strings: true, ..pattern("a.b") }), "memchr substring search");
+        assert_eq!(engine(pattern("a.b")), "regex with a memchr prefilter");
+        assert_eq!(engine(Argument { word: true, ..pattern("fox") }), "regex with a memchr prefilter"); // Boundaries aren't literals
+        assert_eq!(engine(pattern("^fox")), "regex with a memchr prefilter");
+        assert_eq!(engine(pattern(r"\w+@example")), "regex");
+        assert_eq!(engine(pattern("")), "regex");
+    }
+
+    #[test]
+    fn test_only_k_and_s_fold_to_other_than_ascii() {
+        use regex_syntax::hir::{Class, HirKind};
+
+        for letter in 'a'..='z' {
+            let HirKind::Class(Class::Unicode(class)) = regex_syntax::parse(&format!("(?i){letter}")).unwrap().into_kind() else {
+                panic!("{letter} isn't a class when ignoring case");
+            };
+
+            assert_eq!(class.ranges().iter().all(|range| range.end().is_ascii()), !"ks".contains(letter), "{letter}");
+        }
+    }
+
+    #[test]
+    fn test_prefilter_without_auto_engine() {
+        let engine = |pattern_: &str| Patterns::new(&pattern(pattern_)).unwrap().engine_name();
+
+        assert_eq!(engine("TODO"), "regex with a memchr prefilter"); // Only --auto-engine skips the regex
+        assert_eq!(engine(r"(?:TODO|FIXME)\(\w+\)"), "regex with an Aho-Corasick prefilter");
+        assert_eq!(engine(r"\d+"), "regex");
+    }
+
+    #[test]
+    fn test_prefiltered_lines_match_every_line() {
+        let contents = "id=1 x\r\nfoo\n\nid= id=22\nabc id=3\nid=4";
+
+        for (pattern_, lines) in [
+            (r"id=\d+", vec![(1, "id=1 x"), (4, "id= id=22"), (5, "abc id=3"), (6, "id=4")]),
+            (r"^id=\d", vec![(1, "id=1 x"), (6, "id=4")]),
+            ("x$", vec![(1, "id=1 x")]), // Without the \r
+            (r"o\nf", vec![]), // Literals across lines never match
+        ] {
+            let arg = pattern(pattern_);
+            let matcher = Patterns::new(&arg).unwrap();
+
+            assert_ne!(matcher.engine_name(), "regex");
+            assert_eq!(matcher.matching_lines(contents), lines, "{pattern_}");
+        }
+    }
+
+    #[test]
+    fn test_prefiltered_lines_null_data_and_columns() {
+        let null_data = Argument { null_data: true, ..pattern("b+") };
+        let columns = Argument { columns: columns::parse_column_range("3-").ok(), ..pattern("ab") };
+
+        assert_eq!(Patterns::new(&null_data).unwrap().matching_lines("a\nb\0x\0bb\0"), vec![(1, "a\nb"), (3, "bb")]);
+        assert_eq!(Patterns::new(&columns).unwrap().matching_lines("ab\nxxab\n"), vec![(2, "xxab")]);
+    }
+
+    #[test]
+    fn test_auto_engine_matches_like_regex() {
+        let contents = "TODO: x\nfixme later\nnothing\nKelvin \u{212a}\n";
+
+        for arg in [
+            pattern("TODO"),
+            pattern("TODO|fixme"),
+            Argument { insensitive: true, ..pattern("k") }, // Including the Kelvin sign
+            Argument { insensitive: true, ..pattern("todo|FIXME") },
+            Argument { columns: columns::parse_column_range("3-").ok(), ..pattern("DO") },
+        ] {
+            let auto = Patterns::new(&Argument { auto_engine: true, ..arg.clone() }).unwrap();
+
+            assert_ne!(auto.engine_name(), "regex");
+            assert_eq!(auto.matching_lines(contents), Patterns::new(&arg).unwrap().matching_lines(contents));
+        }
+    }
+
+    #[test]
+    fn test_auto_engine_lookaround() {
+        let lookbehind = Argument { auto_engine: true, ..pattern(r"(?<=foo)bar") };
+        let result = Patterns::new(&lookbehind);
+
+        #[cfg(all(feature = "pcre2", unix))]
+        assert_eq!(result.unwrap().matching_lines("foobar
+bar
+"), vec![(1, "foobar")]);
+        #[cfg(not(all(feature = "pcre2", unix)))]
+        assert!(result.is_err());
+
+        assert!(Patterns::new(&pattern(r"(?<=foo)bar")).is_err()); // Only picked with --auto-engine
+        assert!(Patterns::new(&Argument { auto_engine: true, ..pattern("(") }).is_err()); // Just invalid
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let arg = Argument { pattern: Some(String::from("(")), ..Default::default() };
+
+        assert!(Patterns::new(&arg).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! Writing results to stdout through a bounded channel (`--output-buffer-lines`)
+//!
+//! Searching hands lines to a writer thread. When stdout is a slow consumer, the channel fills
+//! up and searching waits, instead of buffering everything in memory.
+
+// Standard library
+use std::io::{self, Write}; // For writing results
+use std::mem; // For taking the pending line
+use std::sync::mpsc::{self, SyncSender}; // For the bounded channel
+use std::thread::{self, JoinHandle}; // For the writer thread
+
+/// Writer sending each line (ended by newline or NUL) to a thread that writes it to stdout
+pub(crate) struct BoundedOutput {
+    sender: Option<SyncSender<Vec<u8>>>,
+    pending: Vec<u8>,
+    writer: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl BoundedOutput {
+    /// Output to stdout, holding at most `capacity` lines that haven't been written yet
+    pub(crate) fn stdout(capacity: usize) -> Self {
+        BoundedOutput::new(capacity, io::stdout())
+    }
+
+    fn new<W: Write + Send + 'static>(capacity: usize, mut out: W) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(capacity);
+
+        let writer = thread::spawn(move || {
+            for line in receiver {
+                out.write_all(&line)?; // Returning drops the receiver, so senders see the error
+            }
+
+            out.flush()
+        });
+
+        BoundedOutput { sender: Some(sender), pending: Vec::new(), writer: Some(writer) }
+    }
+
+    /// Send the pending partial line
+    fn send_pending(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let line = mem::take(&mut self.pending);
+
+        match &self.sender {
+            Some(sender) if sender.send(line).is_ok() => Ok(()),
+            _ => Err(io::Error::new(io::ErrorKind::BrokenPipe, "output closed")),
+        }
+    }
+
+    /// Write everything that's left and wait for the writer thread
+    ///
+    /// Errors from writing to stdout are returned here, rather than the less useful error
+    /// sending gets once the thread has stopped
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        let sent = self.send_pending();
+
+        self.sender = None; // Closing the channel ends the thread
+        let written = self.writer.take().map_or(Ok(()), |writer| writer.join().unwrap_or(Ok(())));
+
+        written.and(sent)
+    }
+}
+
+impl Write for BoundedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+
+        while let Some(end) = rest.iter().position(|&byte| byte == b'\n' || byte == 0) {
+            self.pending.extend_from_slice(&rest[..=end]);
+            self.send_pending()?;
+
+            rest = &rest[end + 1..];
+        }
+
+        self.pending.extend_from_slice(rest);
+
+        Ok(buf.len())
+    }
+
+    /// Nothing is held back except a partial line, which is sent too
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_pending()
+    }
+}
+
+impl Drop for BoundedOutput {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.send_pending(); // Errors are only reported through finish
+            self.sender = None;
+
+            if let Some(writer) = self.writer.take() {
+                let _ = writer.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Writer collecting into a shared buffer, so the test can see what the thread wrote
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_bounded_output_writes_everything_in_order() {
+        let shared = Shared::default();
+        let mut output = BoundedOutput::new(1, shared.clone());
+
+        for number in 0..100 {
+            write!(output, "line ").unwrap();
+            writeln!(output, "{number}").unwrap();
+        }
+        write!(output, "partial").unwrap();
+        output.finish().unwrap();
+
+        let written = String::from_utf8(shared.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = written.split('\n').collect();
+
+        assert_eq!(lines.len(), 101);
+        assert_eq!(lines[42], "line 42");
+        assert_eq!(lines[100], "partial");
+    }
+
+    #[test]
+    fn test_bounded_output_reports_write_errors() {
+        let mut output = BoundedOutput::new(0, Closed);
+
+        let _ = writeln!(output, "a"); // May or may not see the error yet
+        let _ = writeln!(output, "b");
+
+        assert_eq!(output.finish().unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+}
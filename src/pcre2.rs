@@ -0,0 +1,271 @@
+//! Matching with PCRE2 (`--engine pcre2`), for lookaround and backreferences
+//!
+//! The regex crate matches in time linear in the line, which can't be done with lookaround or
+//! backreferences, so it has neither. PCRE2 has both, by backtracking (which takes exponential
+//! time on some patterns, so it's only used when asked for). The library is loaded the first time
+//! a pattern is compiled, from wherever the system keeps `libpcre2-8`, so building doesn't need
+//! it and searches with the default engine never touch it.
+
+// Standard library
+use std::cell::RefCell; // For match data per thread
+use std::ffi::{c_int, c_void, CStr}; // For calling PCRE2
+use std::fmt; // For debugging patterns
+use std::ops::Range; // For match spans
+use std::ptr; // For null contexts
+use std::sync::{Arc, OnceLock}; // For sharing patterns and the library
+
+/// Compile options
+const CASELESS: u32 = 0x0000_0008;
+const DOLLAR_ENDONLY: u32 = 0x0000_0010; // `$` only at the end, like the regex crate
+const UCP: u32 = 0x0002_0000; // Unicode `\w`, `\d`, `\s` and `\b`, like the regex crate
+const UTF: u32 = 0x0008_0000;
+/// Match option, since lines are already valid UTF-8
+const NO_UTF_CHECK: u32 = 0x4000_0000;
+/// Compile the whole pattern to machine code
+const JIT_COMPLETE: u32 = 0x0000_0001;
+
+/// Names the library goes by on Linux and the BSDs, then macOS
+const LIBRARIES: [&CStr; 4] = [c"libpcre2-8.so.0", c"libpcre2-8.so", c"libpcre2-8.0.dylib", c"libpcre2-8.dylib"];
+
+type Compile = unsafe extern "C" fn(*const u8, usize, u32, *mut c_int, *mut usize, *mut c_void) -> *mut c_void;
+type ErrorMessage = unsafe extern "C" fn(c_int, *mut u8, usize) -> c_int;
+type JitCompile = unsafe extern "C" fn(*mut c_void, u32) -> c_int;
+type MatchDataCreate = unsafe extern "C" fn(u32, *mut c_void) -> *mut c_void;
+type Find = unsafe extern "C" fn(*const c_void, *const u8, usize, usize, u32, *mut c_void, *mut c_void) -> c_int;
+type OvectorPointer = unsafe extern "C" fn(*mut c_void) -> *mut usize;
+type Free = unsafe extern "C" fn(*mut c_void);
+
+/// The functions used from the library
+struct Library {
+    compile: Compile,
+    error_message: ErrorMessage,
+    jit_compile: JitCompile,
+    match_data_create: MatchDataCreate,
+    find: Find,
+    ovector_pointer: OvectorPointer,
+    match_data_free: Free,
+    code_free: Free,
+}
+
+/// The library, loaded the first time it's needed
+fn library() -> Result<&'static Library, regex::Error> {
+    static LIBRARY: OnceLock<Option<Library>> = OnceLock::new();
+
+    LIBRARY
+        .get_or_init(load)
+        .as_ref()
+        .ok_or_else(|| regex::Error::Syntax(String::from("the pcre2 engine needs the PCRE2 library (libpcre2-8), which wasn't found")))
+}
+
+fn load() -> Option<Library> {
+    // SAFETY: the names are NUL-terminated, and the library is never closed (its functions are
+    // kept for good)
+    let handle = LIBRARIES
+        .iter()
+        .map(|name| unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) })
+        .find(|handle| !handle.is_null())?;
+
+    let symbol = |name: &CStr| {
+        // SAFETY: `handle` is an open library and `name` is NUL-terminated
+        let address = unsafe { libc::dlsym(handle, name.as_ptr()) };
+        (!address.is_null()).then_some(address)
+    };
+
+    // SAFETY: each symbol is the PCRE2 function of that name, which has the signature of its type
+    unsafe {
+        Some(Library {
+            compile: std::mem::transmute::<*mut c_void, Compile>(symbol(c"pcre2_compile_8")?),
+            error_message: std::mem::transmute::<*mut c_void, ErrorMessage>(symbol(c"pcre2_get_error_message_8")?),
+            jit_compile: std::mem::transmute::<*mut c_void, JitCompile>(symbol(c"pcre2_jit_compile_8")?),
+            match_data_create: std::mem::transmute::<*mut c_void, MatchDataCreate>(symbol(c"pcre2_match_data_create_8")?),
+            find: std::mem::transmute::<*mut c_void, Find>(symbol(c"pcre2_match_8")?),
+            ovector_pointer: std::mem::transmute::<*mut c_void, OvectorPointer>(symbol(c"pcre2_get_ovector_pointer_8")?),
+            match_data_free: std::mem::transmute::<*mut c_void, Free>(symbol(c"pcre2_match_data_free_8")?),
+            code_free: std::mem::transmute::<*mut c_void, Free>(symbol(c"pcre2_code_free_8")?),
+        })
+    }
+}
+
+/// PCRE2's message for error `code`
+fn error_message(library: &Library, code: c_int) -> String {
+    let mut buffer = [0; 256];
+
+    // SAFETY: the buffer is as long as it's said to be
+    let length = unsafe { (library.error_message)(code, buffer.as_mut_ptr(), buffer.len()) };
+
+    usize::try_from(length).map_or_else(|_| format!("error {code}"), |length| String::from_utf8_lossy(&buffer[..length]).into_owned())
+}
+
+/// A compiled pattern
+struct Code {
+    library: &'static Library,
+    pointer: *mut c_void,
+}
+
+// SAFETY: compiled patterns aren't changed by matching, and PCRE2 allows matching one on several
+// threads at once (each with its own match data)
+unsafe impl Send for Code {}
+unsafe impl Sync for Code {}
+
+impl Drop for Code {
+    fn drop(&mut self) {
+        // SAFETY: the pattern came from pcre2_compile and is freed only here
+        unsafe { (self.library.code_free)(self.pointer) };
+    }
+}
+
+/// Where PCRE2 writes where a match is, one for each thread since matching changes it
+struct MatchData {
+    library: &'static Library,
+    pointer: *mut c_void,
+}
+
+impl MatchData {
+    fn new(library: &'static Library) -> Option<Self> {
+        // SAFETY: room for one match (which is all that's read), with the default allocator
+        let pointer = unsafe { (library.match_data_create)(1, ptr::null_mut()) };
+
+        (!pointer.is_null()).then_some(MatchData { library, pointer })
+    }
+}
+
+impl Drop for MatchData {
+    fn drop(&mut self) {
+        // SAFETY: the match data came from pcre2_match_data_create and is freed only here
+        unsafe { (self.library.match_data_free)(self.pointer) };
+    }
+}
+
+thread_local! {
+    static MATCH_DATA: RefCell<Option<MatchData>> = const { RefCell::new(None) };
+}
+
+/// Patterns compiled with PCRE2, cheap to clone
+#[derive(Clone)]
+pub(crate) struct Pcre2 {
+    source: String,
+    code: Arc<Code>,
+}
+
+impl fmt::Debug for Pcre2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pcre2").field("source", &self.source).finish_non_exhaustive()
+    }
+}
+
+impl Pcre2 {
+    /// Compile `query` (already in PCRE2 syntax, which the regex crate's mostly is), ignoring case
+    /// or only matching whole words like the default engine
+    ///
+    /// # Errors
+    ///
+    /// Will error if the library can't be loaded or the pattern is invalid
+    pub(crate) fn new(query: &str, ignore_case: bool, whole_word: bool) -> Result<Self, regex::Error> {
+        let library = library()?;
+
+        // Like `\b{start-half}` and `\b{end-half}`, which PCRE2 doesn't have
+        let source = if whole_word { format!(r"(?<!\w)(?:{query})(?!\w)") } else { query.to_string() };
+        let options = UTF | UCP | DOLLAR_ENDONLY | if ignore_case { CASELESS } else { 0 };
+        let (mut code, mut offset) = (0, 0);
+
+        // SAFETY: the pattern is as long as it's said to be, and the error code and offset are
+        // valid places to write
+        let pointer = unsafe { (library.compile)(source.as_ptr(), source.len(), options, &raw mut code, &raw mut offset, ptr::null_mut()) };
+
+        if pointer.is_null() {
+            return Err(regex::Error::Syntax(format!("{source}\nerror at byte {offset}: {}", error_message(library, code))));
+        }
+
+        // SAFETY: a compiled pattern. Without the JIT (like on some platforms) it still matches,
+        // only slower
+        unsafe { (library.jit_compile)(pointer, JIT_COMPLETE) };
+
+        Ok(Pcre2 { source, code: Arc::new(Code { library, pointer }) })
+    }
+
+    /// The first match in `haystack` at or after byte `start` (a char boundary)
+    ///
+    /// Errors matching, like hitting PCRE2's limit on backtracking, count as no match.
+    fn find_at(&self, haystack: &str, start: usize) -> Option<Range<usize>> {
+        let library = self.code.library;
+
+        MATCH_DATA.with_borrow_mut(|data| {
+            let data = match data {
+                Some(data) => data,
+                None => data.insert(MatchData::new(library)?),
+            };
+
+            // SAFETY: the subject is valid UTF-8 as long as it's said to be, `start` is in it on
+            // a char boundary, and the match data is this thread's
+            let found = unsafe {
+                (library.find)(self.code.pointer, haystack.as_ptr(), haystack.len(), start, NO_UTF_CHECK, data.pointer, ptr::null_mut())
+            };
+
+            if found < 0 {
+                return None;
+            }
+
+            // SAFETY: after a match, the match data holds its start and end
+            let ovector = unsafe { std::slice::from_raw_parts((library.ovector_pointer)(data.pointer), 2) };
+
+            Some(ovector[0].min(ovector[1])..ovector[1]) // `\K` in a lookahead can put the start after the end
+        })
+    }
+
+    /// The first match in `haystack`
+    pub(crate) fn find(&self, haystack: &str) -> Option<Range<usize>> {
+        self.find_at(haystack, 0)
+    }
+
+    /// Whether anything in `haystack` matches
+    pub(crate) fn is_match(&self, haystack: &str) -> bool {
+        self.find_at(haystack, 0).is_some()
+    }
+
+    /// Every match in `haystack`, in order without overlapping
+    pub(crate) fn find_iter(&self, haystack: &str) -> Vec<Range<usize>> {
+        let mut found = Vec::new();
+        let mut start = 0;
+
+        while start <= haystack.len()
+            && let Some(span) = self.find_at(haystack, start)
+        {
+            // Past an empty match by a whole character, so the next search starts on a boundary
+            start = if span.is_empty() { span.end + haystack[span.end..].chars().next().map_or(1, char::len_utf8) } else { span.end };
+            found.push(span);
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookaround_and_backreferences() {
+        let lookbehind = Pcre2::new(r"(?<=foo)bar", false, false).unwrap();
+        assert!(lookbehind.is_match("foobar"));
+        assert!(!lookbehind.is_match("bazbar"));
+        assert_eq!(lookbehind.find_iter("foobar barfoobar"), vec![3..6, 13..16]);
+
+        let doubled = Pcre2::new(r"\b(\w+) \1\b", true, false).unwrap();
+        assert_eq!(doubled.find_iter("it was the The end"), vec![7..14]);
+        assert!(!doubled.is_match("the then"));
+
+        assert!(Pcre2::new(r"(a", false, false).unwrap_err().to_string().contains("byte 2"));
+    }
+
+    #[test]
+    fn test_whole_words_and_unicode() {
+        let word = Pcre2::new("fox", false, true).unwrap();
+        assert_eq!(word.find_iter("fox foxes a.fox"), vec![0..3, 12..15]);
+
+        let empty = Pcre2::new("x*", false, false).unwrap();
+        assert_eq!(empty.find_iter("éx"), vec![0..0, 2..3, 3..3]); // Never inside a character
+
+        assert!(Pcre2::new(r"^\w+$", false, false).unwrap().is_match("café"));
+        assert!(!Pcre2::new("end$", false, false).unwrap().is_match("end\n"));
+    }
+}
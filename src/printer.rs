@@ -0,0 +1,714 @@
+//! Printing search results
+
+// Standard library
+use std::borrow::Cow; // For marked paths
+use std::io::{self, Write}; // For writing results
+use std::ops::Range; // For match spans
+use std::time::Duration; // For search times
+
+// My stuff
+use crate::delimited::{column_names, delimiter};
+use crate::error::Error;
+use crate::hexdump::hex_rows;
+use crate::i18n::{fill, Catalog};
+use crate::search::{Match, Progress};
+use crate::{Argument, PathDisplay};
+
+/// Receives the results of a search, one file at a time
+pub trait Printer {
+    /// Called with every searched file's matches (possibly none), in search order
+    ///
+    /// # Errors
+    ///
+    /// Implementations should return [`Error::Output`] if writing fails
+    fn file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error>;
+
+    /// Called instead of [`Printer::file`] for binary files, whose lines aren't worth printing
+    ///
+    /// Does nothing by default
+    ///
+    /// # Errors
+    ///
+    /// Implementations should return [`Error::Output`] if writing fails
+    fn binary_file(&mut self, _path: &str, _matches: &[Match]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called instead of [`Printer::file`] with `--only-binary`, with the spans of the matches in the raw bytes
+    ///
+    /// Does nothing by default
+    ///
+    /// # Errors
+    ///
+    /// Implementations should return [`Error::Output`] if writing fails
+    fn binary_matches(&mut self, _path: &str, _bytes: &[u8], _spans: &[Range<usize>]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called before the results of each file that could be read, with its size and how long
+    /// reading and searching it took
+    ///
+    /// Does nothing by default
+    ///
+    /// # Errors
+    ///
+    /// Implementations should return [`Error::Output`] if writing fails
+    fn searched(&mut self, _path: &str, _bytes: u64, _took: Duration) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called right before [`Printer::file`] (or [`Printer::binary_file`]) for a file whose
+    /// contents were decoded before searching, with each step in order (like `gzip`, then
+    /// `UTF-16LE`), since its line numbers are those of the decoded text rather than of the bytes
+    /// on disk
+    ///
+    /// Does nothing by default
+    ///
+    /// # Errors
+    ///
+    /// Implementations should return [`Error::Output`] if writing fails
+    fn decoded(&mut self, _path: &str, _steps: &[&str]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called right before [`Printer::file`] for a text file with matches, with its first lines
+    /// (`--with-header-line`), which have no spans
+    ///
+    /// Does nothing by default
+    ///
+    /// # Errors
+    ///
+    /// Implementations should return [`Error::Output`] if writing fails
+    fn header(&mut self, _path: &str, _lines: &[Match]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called for each file skipped because it couldn't be read, when searching recursively (the
+    /// warning on stderr is printed anyway, unless `-s` was given)
+    ///
+    /// Does nothing by default
+    ///
+    /// # Errors
+    ///
+    /// Implementations should return [`Error::Output`] if writing fails
+    fn skipped(&mut self, _path: &str, _error: &Error) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called every so often during a search, and once more with the totals before [`Printer::finish`]
+    ///
+    /// Does nothing by default
+    ///
+    /// # Errors
+    ///
+    /// Implementations should return [`Error::Output`] if writing fails
+    fn progress(&mut self, _progress: &Progress) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called once after the last file
+    ///
+    /// # Errors
+    ///
+    /// Implementations should return [`Error::Output`] if writing fails
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Prints results the way the command-line tool does
+#[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)] // Flags are naturally bools
+pub struct StandardPrinter<W> {
+    writer: W,
+    catalog: &'static Catalog,
+    show_filename: bool,
+    null: bool,
+    color: bool,
+    count: bool,
+    count_matches: bool,
+    files_with_matches: bool,
+    merge_adjacent: bool,
+    vimgrep: bool,
+    heading: bool,
+    column: bool,
+    headings_printed: usize,
+    line_terminator: char,
+    hash_salt: Option<String>,
+    full_matches: bool,
+    paths: PathDisplay,
+    /// Whether the file about to be printed was decoded, so its locations are marked `[decoded]`
+    decoded: bool,
+    /// First lines of the file about to be printed, with `--with-header-line` (or `--column-names`)
+    header: Vec<Match>,
+    column_names: bool,
+}
+
+impl<W: Write> StandardPrinter<W> {
+    /// Printer for the output options in `config`, writing to `writer`
+    pub fn new(config: &Argument, writer: W) -> Self {
+        StandardPrinter {
+            writer,
+            catalog: config.lang().catalog(),
+            show_filename: config.show_filename(),
+            null: config.null,
+            color: config.use_color(),
+            count: config.count,
+            count_matches: config.count_matches,
+            files_with_matches: config.files_with_matches,
+            merge_adjacent: config.merge_adjacent,
+            vimgrep: config.vimgrep,
+            heading: config.heading,
+            column: config.column,
+            headings_printed: 0,
+            line_terminator: config.line_terminator(),
+            hash_salt: config.hash_salt(),
+            full_matches: config.full_matches,
+            paths: config.path_display(),
+            decoded: false,
+            header: Vec::new(),
+            column_names: config.column_names,
+        }
+    }
+
+    /// Give back the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_file(&mut self, path: &str, matches: &[Match], decoded: bool) -> io::Result<()> {
+        if self.files_with_matches {
+            return self.write_path(path, !matches.is_empty());
+        }
+
+        if self.count {
+            writeln!(self.writer, "{}{}", self.filename_prefix(path, ": "), matches.len())?;
+            return Ok(());
+        }
+
+        if self.count_matches {
+            let count: usize = matches.iter().map(|found| found.spans().len()).sum();
+
+            writeln!(self.writer, "{}{count}", self.filename_prefix(path, ": "))?;
+            return Ok(());
+        }
+
+        // Line numbers of decoded text aren't those of the file (paths alone, above, still are)
+        let path = if decoded { Cow::Owned(format!("{path}[decoded]")) } else { Cow::Borrowed(path) };
+
+        if self.vimgrep {
+            // One line per match, always with the file and a 1-based byte column
+            for found in matches {
+                for span in found.spans() {
+                    writeln!(self.writer, "{path}:{}:{}:{}", found.line_number(), span.start + 1, found.line())?;
+                }
+            }
+
+            return Ok(());
+        }
+
+        // The path once above the file's results, with a blank line between files
+        let heading = self.heading && self.show_filename;
+
+        if heading && !matches.is_empty() {
+            if self.headings_printed > 0 {
+                writeln!(self.writer)?;
+            }
+
+            writeln!(self.writer, "{path}")?;
+            self.headings_printed += 1;
+        }
+
+        let (block_prefix, line_prefix) = if heading {
+            (String::new(), String::new())
+        } else {
+            (self.filename_prefix(&path, ":"), self.filename_prefix(&path, ": "))
+        };
+
+        // Set off with `-` like context lines in grep, since they aren't matches
+        let header_prefix = if heading { String::new() } else { self.filename_prefix(&path, "- ") };
+
+        let header = std::mem::take(&mut self.header);
+
+        for line in &header {
+            write!(self.writer, "{header_prefix}{}{}", line.line(), self.line_terminator)?;
+        }
+
+        // The columns of a CSV or TSV file are named by its first line
+        let columns = if self.column_names { delimiter(&path).zip(header.first()) } else { None };
+
+        // Header lines that match are only printed once, as the header
+        let header_end = header.last().map_or(0, Match::line_number);
+
+        if self.merge_adjacent {
+            // One prefix per run of consecutive matching lines
+            for block in adjacent_blocks(matches) {
+                writeln!(self.writer, "{}{}:", block_prefix, block_range(block))?;
+
+                for found in block {
+                    write!(self.writer, "{}{}", self.format_line(found), self.line_terminator)?;
+                }
+            }
+
+            return Ok(());
+        }
+
+        for found in matches.iter().filter(|found| found.line_number() > header_end) {
+            // `line:column: ` of the first match, 1-based in bytes
+            let position = match found.spans().first() {
+                Some(span) if self.column => format!("{}:{}: ", found.line_number(), span.start + 1),
+                _ => String::new(),
+            };
+
+            let names = columns.map_or_else(String::new, |(delimiter, header)| {
+                format!("[{}] ", column_names(header.line(), found.line(), found.spans(), delimiter).join(","))
+            });
+
+            write!(
+                self.writer,
+                "{}{}{}{}{}",
+                line_prefix,
+                position,
+                names,
+                self.format_line(found),
+                self.line_terminator,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Each match's offset followed by a hex window around it
+    fn write_binary_matches(&mut self, path: &str, bytes: &[u8], spans: &[Range<usize>]) -> io::Result<()> {
+        if self.files_with_matches {
+            return self.write_path(path, !spans.is_empty());
+        }
+
+        if self.count || self.count_matches {
+            return writeln!(self.writer, "{}{}", self.filename_prefix(path, ": "), spans.len());
+        }
+
+        for span in spans {
+            writeln!(self.writer, "{}{:#010x}:", self.filename_prefix(path, ": "), span.start)?;
+
+            for row in hex_rows(bytes, span) {
+                writeln!(self.writer, "  {row}")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Just the path, on its own line (or ended by NUL with `--null`), if the file `matched`
+    fn write_path(&mut self, path: &str, matched: bool) -> io::Result<()> {
+        if !matched {
+            return Ok(());
+        }
+
+        write!(self.writer, "{path}{}", if self.null { '\0' } else { '\n' })
+    }
+
+    /// File path to print before a result, ended by `separator` (or NUL with `--null`)
+    ///
+    /// Empty when file names aren't shown
+    fn filename_prefix(&self, path: &str, separator: &str) -> String {
+        if !self.show_filename {
+            return String::new();
+        }
+
+        if self.null {
+            format!("{path}\0") // Paths can contain anything but NUL, so this is safe for xargs -0
+        } else {
+            format!("{path}{separator}")
+        }
+    }
+
+    /// The matching line, highlighted when coloring (or hashed with `--hash-lines`)
+    fn format_line(&self, found: &Match) -> String {
+        if let Some(salt) = &self.hash_salt {
+            return hashed_line(found, salt);
+        }
+
+        let (line, spans) = if self.full_matches {
+            (found.line().to_string(), found.spans().to_vec())
+        } else {
+            truncate_matches(found.line(), found.spans(), self.catalog.truncated_match)
+        };
+
+        if self.color { highlight(&line, &spans) } else { line }
+    }
+}
+
+impl<W: Write> Printer for StandardPrinter<W> {
+    fn file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        let decoded = std::mem::take(&mut self.decoded);
+        self.write_file(&self.paths.display(path), matches, decoded).map_err(Error::Output)
+    }
+
+    fn binary_file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        self.decoded = false; // Binary files' lines aren't printed
+
+        // Counts and paths are still meaningful
+        if self.count || self.count_matches || self.files_with_matches {
+            return self.file(path, matches);
+        }
+
+        if matches.is_empty() {
+            return Ok(());
+        }
+
+        let path = self.paths.display(path);
+
+        writeln!(self.writer, "{}", fill(self.catalog.binary_file_matches, &[("file", &path)])).map_err(Error::Output)
+    }
+
+    fn binary_matches(&mut self, path: &str, bytes: &[u8], spans: &[Range<usize>]) -> Result<(), Error> {
+        self.decoded = false; // Offsets are in the raw bytes
+        self.write_binary_matches(&self.paths.display(path), bytes, spans).map_err(Error::Output)
+    }
+
+    fn decoded(&mut self, _path: &str, _steps: &[&str]) -> Result<(), Error> {
+        self.decoded = true;
+        Ok(())
+    }
+
+    fn header(&mut self, _path: &str, lines: &[Match]) -> Result<(), Error> {
+        self.header = lines.to_vec();
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.writer.flush().map_err(Error::Output)
+    }
+}
+
+/// How many bytes of a longer match are printed, unless `--full-matches`
+const MATCH_PREVIEW: usize = 200;
+
+/// `line` with every match longer than [`MATCH_PREVIEW`] cut short and followed by `note` (with its `{len}`), and the spans of what's left
+///
+/// Keeps terminals responsive when a pattern accidentally matches megabytes of a minified file
+pub(crate) fn truncate_matches(line: &str, spans: &[Range<usize>], note: &str) -> (String, Vec<Range<usize>>) {
+    if spans.iter().all(|span| span.len() <= MATCH_PREVIEW) {
+        return (line.to_string(), spans.to_vec());
+    }
+
+    let mut truncated = String::new();
+    let mut previews = Vec::with_capacity(spans.len());
+    let mut last = 0;
+
+    for span in spans {
+        truncated.push_str(&line[last..span.start]);
+
+        let end = if span.len() > MATCH_PREVIEW { line.floor_char_boundary(span.start + MATCH_PREVIEW) } else { span.end };
+        let start = truncated.len();
+
+        truncated.push_str(&line[span.start..end]);
+        previews.push(start..truncated.len());
+
+        if end < span.end {
+            truncated.push_str(&fill(note, &[("len", &span.len().to_string())]));
+        }
+
+        last = span.end;
+    }
+
+    truncated.push_str(&line[last..]);
+
+    (truncated, previews)
+}
+
+/// Bold red the spans of line
+fn highlight(line: &str, spans: &[Range<usize>]) -> String {
+    let mut highlighted = String::with_capacity(line.len());
+    let mut last = 0;
+
+    for span in spans {
+        highlighted.push_str(&line[last..span.start]);
+        highlighted.push_str("\x1b[1;31m");
+        highlighted.push_str(&line[span.clone()]);
+        highlighted.push_str("\x1b[0m");
+        last = span.end;
+    }
+
+    highlighted.push_str(&line[last..]);
+
+    highlighted
+}
+
+/// `line:hash:match lengths` standing in for a line with `--hash-lines`, like `12:5f0c7e8a1b2d3c4e:3,5`
+///
+/// The hash is 64-bit FNV-1a of the salt and line, which is stable across versions. It isn't
+/// cryptographic: short lines can be guessed by hashing candidates with the same salt.
+pub(crate) fn hashed_line(found: &Match, salt: &str) -> String {
+    let lengths: Vec<String> = found.spans().iter().map(|span| span.len().to_string()).collect();
+
+    format!("{}:{:016x}:{}", found.line_number(), line_hash(found, salt), lengths.join(","))
+}
+
+/// 64-bit FNV-1a of `salt` and the line, see [`hashed_line`]
+pub(crate) fn line_hash(found: &Match, salt: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for &byte in salt.as_bytes().iter().chain(&[0]).chain(found.line().as_bytes()) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    hash
+}
+
+/// Split matches into runs of consecutive line numbers
+fn adjacent_blocks(matches: &[Match]) -> Vec<&[Match]> {
+    matches
+        .chunk_by(|previous, next| next.line_number() == previous.line_number() + 1)
+        .collect()
+}
+
+/// `start-end` line range of a block (or just the line number for a single line)
+fn block_range(block: &[Match]) -> String {
+    match (block.first(), block.last()) {
+        (Some(start), Some(end)) if start.line_number() != end.line_number() => {
+            format!("{}-{}", start.line_number(), end.line_number())
+        }
+        (Some(start), _) => start.line_number().to_string(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{columns, ColorChoice, Searcher};
+
+    /// Everything `config` prints for `contents` searched as `path`
+    fn print(config: Argument, path: &str, contents: &str) -> String {
+        let mut printer = StandardPrinter::new(&config, Vec::new());
+        let matches = Searcher::new(config).unwrap().search_str(contents);
+
+        printer.file(path, &matches).unwrap();
+
+        String::from_utf8(printer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_print_lines() {
+        let config = Argument { pattern: Some(String::from("o")), ..Default::default() };
+
+        assert_eq!(print(config, "a.txt", "one\ntwo\nsix\n"), "one\ntwo\n");
+    }
+
+    #[test]
+    fn test_print_count_and_count_matches() {
+        let count = Argument { pattern: Some(String::from("o")), count: true, ..Default::default() };
+        let count_matches = Argument { pattern: Some(String::from("o")), count_matches: true, ..Default::default() };
+
+        assert_eq!(print(count, "a.txt", "oo\nxo\n"), "2\n");
+        assert_eq!(print(count_matches, "a.txt", "oo\nxo\n"), "3\n");
+    }
+
+    #[test]
+    fn test_print_files_with_matches() {
+        let config = || Argument { pattern: Some(String::from("o")), files_with_matches: true, ..Default::default() };
+
+        assert_eq!(print(config(), "a.txt", "oo\nxo\n"), "a.txt\n");
+        assert_eq!(print(config(), "a.txt", "x\n"), "");
+        assert_eq!(print(Argument { null: true, ..config() }, "a.txt", "o\n"), "a.txt\0");
+    }
+
+    #[test]
+    fn test_print_hash_lines() {
+        let config = |salt: &str| Argument {
+            pattern: Some(String::from("o")),
+            hash_lines: true,
+            hash_salt: Some(String::from(salt)),
+            ..Default::default()
+        };
+
+        let result = print(config("pepper"), "a.txt", "one\nsix\ntwo ox\n");
+        let lines: Vec<&str> = result.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("1:") && lines[0].ends_with(":1"));
+        assert!(lines[1].starts_with("3:") && lines[1].ends_with(":1,1"));
+        assert!(!result.contains("one"));
+        assert_eq!(print(config("pepper"), "a.txt", "one\n"), format!("{}\n", lines[0])); // Same salt, same hash
+        assert_ne!(print(config("salt"), "a.txt", "one\n"), format!("{}\n", lines[0]));
+    }
+
+    #[test]
+    fn test_print_null_data() {
+        let config = Argument { pattern: Some(String::from("o")), null_data: true, ..Default::default() };
+
+        assert_eq!(print(config, "a.txt", "one\0two\0six\0"), "one\0two\0");
+    }
+
+    #[test]
+    fn test_print_decoded() {
+        let config = Argument { pattern: Some(String::from("o")), with_filename: true, column: true, ..Default::default() };
+        let mut printer = StandardPrinter::new(&config, Vec::new());
+        let matches = Searcher::new(config).unwrap().search_str("one\n");
+
+        printer.decoded("a.gz", &["gzip"]).unwrap();
+        printer.file("a.gz", &matches).unwrap();
+        printer.file("b.txt", &matches).unwrap();
+
+        assert_eq!(String::from_utf8(printer.into_inner()).unwrap(), "a.gz[decoded]: 1:1: one\nb.txt: 1:1: one\n");
+
+        let config = Argument { pattern: Some(String::from("o")), files_with_matches: true, ..Default::default() };
+        let mut printer = StandardPrinter::new(&config, Vec::new());
+
+        printer.decoded("a.gz", &["gzip"]).unwrap();
+        printer.file("a.gz", &matches).unwrap();
+
+        assert_eq!(String::from_utf8(printer.into_inner()).unwrap(), "a.gz\n"); // Paths alone stay usable
+    }
+
+    #[test]
+    fn test_print_binary_file() {
+        let config = Argument { pattern: Some(String::from("o")), lang: Some(crate::Lang::En), ..Default::default() };
+        let mut printer = StandardPrinter::new(&config, Vec::new());
+        let searcher = Searcher::new(config).unwrap();
+
+        printer.binary_file("a.bin", &searcher.search_str("o\0")).unwrap();
+        printer.binary_file("b.bin", &searcher.search_str("x\0")).unwrap();
+
+        assert_eq!(String::from_utf8(printer.into_inner()).unwrap(), "Binary file a.bin matches\n");
+    }
+
+    #[test]
+    fn test_print_binary_matches() {
+        let config = Argument { with_filename: true, ..Default::default() };
+        let mut printer = StandardPrinter::new(&config, Vec::new());
+
+        let span = 2..5;
+        printer.binary_matches("fw.bin", b"\x00\x01key=abc", &[span]).unwrap();
+
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            "fw.bin: 0x00000002:\n  00000000  00 01 6b 65 79 3d 61 62  63                       |..key=abc|\n",
+        );
+    }
+
+    #[test]
+    fn test_filename_prefix() {
+        let shown = StandardPrinter::new(&Argument { recursive: true, ..Default::default() }, io::sink());
+        let null = StandardPrinter::new(&Argument { recursive: true, null: true, ..Default::default() }, io::sink());
+        let hidden = StandardPrinter::new(&Argument::default(), io::sink());
+
+        assert_eq!(shown.filename_prefix("a b.txt", ": "), "a b.txt: ");
+        assert_eq!(null.filename_prefix("a b.txt", ": "), "a b.txt\0");
+        assert_eq!(hidden.filename_prefix("a b.txt", ": "), "");
+    }
+
+    #[test]
+    fn test_print_merge_adjacent() {
+        let config = Argument {
+            pattern: Some(String::from("x")),
+            merge_adjacent: true,
+            with_filename: true,
+            ..Default::default()
+        };
+
+        assert_eq!(print(config, "a.txt", "x1\nx2\n\nx4\n"), "a.txt:1-2:\nx1\nx2\na.txt:4:\nx4\n");
+    }
+
+    #[test]
+    fn test_highlight_only_inside_columns() {
+        let config = Argument {
+            pattern: Some(String::from("ab")),
+            color: ColorChoice::Always,
+            columns: Some(columns::parse_column_range("3-4").unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(print(config, "a.txt", "abab\n"), "ab\x1b[1;31mab\x1b[0m\n");
+    }
+
+    #[test]
+    fn test_truncate_matches() {
+        let line = format!("a{}é{}b", "x".repeat(199), "y".repeat(300));
+        let spans = [1..line.len() - 1, line.len() - 1..line.len()];
+
+        let (truncated, previews) = truncate_matches(&line, &spans, " ({len})");
+
+        // Cut before the é, which doesn't fit
+        assert_eq!(truncated, format!("a{} (501)b", "x".repeat(199)));
+        assert_eq!(previews, vec![1..200, 206..207]);
+    }
+
+    #[test]
+    fn test_print_heading() {
+        let config = Argument {
+            pattern: Some(String::from("o")),
+            heading: true,
+            with_filename: true,
+            color: ColorChoice::Never,
+            ..Default::default()
+        };
+        let searcher = Searcher::new(config.clone()).unwrap();
+        let mut printer = StandardPrinter::new(&config, Vec::new());
+
+        for (path, contents) in [("a.txt", "one\ntwo\n"), ("b.txt", "six\n"), ("c.txt", "four\n")] {
+            printer.file(path, &searcher.search_str(contents)).unwrap();
+        }
+
+        let result = String::from_utf8(printer.into_inner()).unwrap();
+
+        assert_eq!(result, "a.txt\none\ntwo\n\nc.txt\nfour\n");
+    }
+
+    #[test]
+    fn test_print_column() {
+        let config = Argument { pattern: Some(String::from("o")), column: true, color: ColorChoice::Never, ..Default::default() };
+
+        let result = print(config, "a.txt", "six\ntwo one\n");
+
+        assert_eq!(result, "2:3: two one\n");
+    }
+
+    #[test]
+    fn test_print_vimgrep() {
+        let config = Argument { pattern: Some(String::from("o")), vimgrep: true, ..Default::default() };
+
+        let result = print(config, "a.txt", "one two\nsix\n");
+
+        assert_eq!(result, "a.txt:1:1:one two\na.txt:1:7:one two\n");
+    }
+
+    #[test]
+    fn test_print_full_matches() {
+        let line = "x".repeat(300);
+        let config = Argument { pattern: Some(String::from("x+")), color: ColorChoice::Never, ..Default::default() };
+
+        let previewed = print(config.clone(), "a.txt", &line);
+        let full = print(Argument { full_matches: true, ..config }, "a.txt", &line);
+
+        assert_eq!(previewed, format!("{}… (300 bytes)\n", "x".repeat(200)));
+        assert_eq!(full, format!("{line}\n"));
+    }
+
+    #[test]
+    fn test_highlight_whole_line() {
+        let result = highlight("abxab", &[0..2, 3..5]);
+
+        assert_eq!(result, "\x1b[1;31mab\x1b[0mx\x1b[1;31mab\x1b[0m");
+    }
+
+    #[test]
+    fn test_adjacent_blocks() {
+        let config = Argument { pattern: Some(String::from("x")), ..Default::default() };
+        let matches = Searcher::new(config).unwrap().search_str("x\nx\nx\n\n\n\nx\n\nx\nx\n");
+
+        let result: Vec<String> = adjacent_blocks(&matches).into_iter().map(block_range).collect();
+
+        assert_eq!(result, vec!["1-3", "7", "9-10"]);
+    }
+
+    #[test]
+    fn test_adjacent_blocks_no_matches() {
+        let result = adjacent_blocks(&[]);
+
+        assert!(result.is_empty());
+    }
+}
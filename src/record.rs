@@ -0,0 +1,244 @@
+//! Recording searches for bug reports (`--record`) and rerunning them (`--replay`)
+//!
+//! A recording is a tar file with:
+//!
+//! - `args`: the command-line arguments, each ended by NUL
+//! - `patterns`: the patterns searched for (after reading `-f`), each ended by NUL
+//! - `files`: every searched file as `size<TAB>modified<TAB>path`, each ended by NUL
+//! - `config.txt`: the version, working directory and effective options, for people to read
+//! - `output`: what was printed, only with `--record-snippets`
+
+// Standard library
+use std::collections::HashMap; // For archive entries by name
+use std::env; // For the arguments and working directory
+use std::error::Error; // For error handling
+use std::fs::{self, File}; // For file stuff
+use std::io::{self, Read, Write}; // For reading and writing
+use std::time::UNIX_EPOCH; // For modification times
+
+// External crates
+use clap::Parser; // For parsing the recorded arguments
+
+// My stuff
+use crate::{files_to_search, print_matches, Argument, Cli, Command};
+
+/// Search and print like normal, and save the search to the `--record` file
+///
+/// The recording is written even if the search fails, since that's usually what's being reported
+pub(crate) fn record(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    let path = arg.record.clone().unwrap_or_default();
+
+    let mut arg = arg.clone();
+    arg.record = None;
+
+    let files = files_to_search(&arg);
+    arg.replay_files = Some(files.clone()); // Walk once, so the recording has exactly what was searched
+
+    let mut output = Vec::new();
+    let result = print_matches(&arg, Tee(io::stdout().lock(), &mut output));
+
+    write_recording(&path, &arg, &files, arg.record_snippets.then_some(output.as_slice()))
+        .map_err(|e| format!("{path}: {e}"))?;
+
+    Ok(result?)
+}
+
+fn write_recording(path: &str, arg: &Argument, files: &[String], output: Option<&[u8]>) -> io::Result<()> {
+    let mut builder = tar::Builder::new(File::create(path)?);
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let patterns: Vec<&String> = arg.pattern.iter().chain(&arg.regexp).collect();
+
+    let file_records: String = files.iter().map(|file| file_record(file)).collect();
+
+    let cwd = env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_default();
+    let config = format!(
+        "{} {}\nworking directory: {cwd}\n\n{arg:#?}\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+    );
+
+    append(&mut builder, "args", nul_terminated(&args).as_bytes())?;
+    append(&mut builder, "patterns", nul_terminated(&patterns).as_bytes())?;
+    append(&mut builder, "files", file_records.as_bytes())?;
+    append(&mut builder, "config.txt", config.as_bytes())?;
+
+    if let Some(output) = output {
+        append(&mut builder, "output", output)?;
+    }
+
+    builder.into_inner()?.flush()
+}
+
+fn append(builder: &mut tar::Builder<File>, name: &str, data: &[u8]) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+
+    builder.append_data(&mut header, name, data)
+}
+
+/// `size<TAB>modified<TAB>path` and NUL, with `-` for whatever can't be found out
+fn file_record(path: &str) -> String {
+    let metadata = fs::metadata(path).ok();
+
+    let size = metadata.as_ref().map_or(String::from("-"), |metadata| metadata.len().to_string());
+    let modified = metadata
+        .and_then(|metadata| metadata.modified().ok())
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(String::from("-"), |modified| modified.as_secs().to_string());
+
+    format!("{size}\t{modified}\t{path}\0")
+}
+
+fn nul_terminated<T: AsRef<str>>(values: &[T]) -> String {
+    values.iter().flat_map(|value| [value.as_ref(), "\0"]).collect()
+}
+
+/// Rerun the search saved in the `--replay` file, on the recorded files
+///
+/// Files that changed since recording are warned about, and so is output that differs from a
+/// recording made with `--record-snippets`
+///
+/// # Errors
+///
+/// Will error if the recording can't be read or the search fails
+pub fn replay(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    let path = arg.replay.clone().unwrap_or_default();
+
+    let entries = read_recording(&path).map_err(|e| format!("{path}: {e}"))?;
+    let entry = |name: &str| {
+        entries
+            .get(name)
+            .map(|data| String::from_utf8_lossy(data).into_owned())
+            .ok_or_else(|| format!("{path}: not a recording (no `{name}`)"))
+    };
+
+    let config = recorded_config(&entry("args")?, &entry("patterns")?)?;
+
+    let mut files = Vec::new();
+
+    for record in entry("files")?.split_terminator('\0') {
+        let mut fields = record.splitn(3, '\t');
+        let (Some(_), Some(_), Some(file)) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(format!("{path}: bad file record `{record}`").into());
+        };
+
+        if file_record(file).trim_end_matches('\0') != record {
+            eprintln!("{}: {file} changed since the recording", env!("CARGO_PKG_NAME"));
+        }
+
+        files.push(file.to_string());
+    }
+
+    let config = Argument { replay_files: Some(files), ..config };
+
+    let mut output = Vec::new();
+    print_matches(&config, Tee(io::stdout().lock(), &mut output))?;
+
+    if entries.get("output").is_some_and(|recorded| *recorded != output) {
+        eprintln!("{}: the results differ from the recording", env!("CARGO_PKG_NAME"));
+    }
+
+    Ok(())
+}
+
+/// Config from the recorded arguments, with the recorded patterns (so `-f` files aren't read again)
+fn recorded_config(args: &str, patterns: &str) -> Result<Argument, Box<dyn Error>> {
+    let program = std::iter::once(env!("CARGO_PKG_NAME"));
+    let cli = Cli::try_parse_from(program.chain(args.split_terminator('\0')))?;
+
+    let mut config = match cli.command {
+        None => cli.search,
+        Some(Command::Search(config)) => config,
+        Some(Command::Check(_) | Command::Examples | Command::Index(_) | Command::Serve(_) | Command::Types) => return Err("the recording isn't of a search".into()),
+    };
+
+    config.fix_positionals()?;
+
+    config.pattern = None;
+    config.pattern_file = None;
+    config.regexp = patterns.split_terminator('\0').map(String::from).collect();
+    config.record = None;
+    config.record_snippets = false;
+
+    Ok(config)
+}
+
+/// Every file in the tar at `path`, by name
+fn read_recording(path: &str) -> io::Result<HashMap<String, Vec<u8>>> {
+    let mut archive = tar::Archive::new(File::open(path)?);
+    let mut entries = HashMap::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+
+        entries.insert(name, data);
+    }
+
+    Ok(entries)
+}
+
+/// Writes to both writers
+struct Tee<A, B>(A, B);
+
+impl<A: Write, B: Write> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_all(buf)?;
+        self.1.write_all(buf)?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()?;
+        self.1.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_recording_round_trip() {
+        let temp = TempDir::new("record");
+        let path = temp.join("a.tar").to_string_lossy().into_owned();
+
+        let arg = Argument {
+            pattern: Some(String::from("the")),
+            files: vec![String::from("./tests/test_2.txt")],
+            ..Default::default()
+        };
+
+        write_recording(&path, &arg, &arg.files, Some(b"output")).unwrap();
+        let entries = read_recording(&path).unwrap();
+
+        assert_eq!(entries["patterns"], b"the\0");
+        assert_eq!(entries["files"], file_record("./tests/test_2.txt").as_bytes());
+        assert_eq!(entries["output"], b"output");
+        assert!(String::from_utf8_lossy(&entries["config.txt"]).starts_with("simple-grep "));
+    }
+
+    #[test]
+    fn test_recorded_config_uses_recorded_patterns() {
+        let config = recorded_config("-w\0-f\0patterns.txt\0a.txt\0b.txt\0", "fox\0cat\0").unwrap();
+
+        assert!(config.word);
+        assert_eq!(config.pattern, None);
+        assert_eq!(config.pattern_file, None);
+        assert_eq!(config.regexp, vec!["fox", "cat"]);
+        assert_eq!(config.files, vec!["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn test_file_record_missing_file() {
+        assert_eq!(file_record("nonexistent_file.nonsense"), "-\t-\tnonexistent_file.nonsense\0");
+    }
+}
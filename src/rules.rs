@@ -0,0 +1,234 @@
+//! Several replacements applied to each line in one pass (`--replace-rule` and `--replace-rules`),
+//! and matching the case of what's replaced (`--preserve-case`)
+
+// Standard library
+use std::ops::Range; // For replacement spans
+
+// External crates
+use regex::Regex; // For rule patterns
+use regex_syntax::ast::{self, Ast}; // For unnaming groups
+
+/// A `PATTERN=>TEMPLATE` rule: matches of the pattern are replaced with the template, which can use
+/// capture groups like `--replace`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ReplaceRule {
+    pub(crate) pattern: String,
+    pub(crate) template: String,
+}
+
+/// Rule from `PATTERN=>TEMPLATE`, split at the first `=>`
+///
+/// # Errors
+///
+/// Errors with a message for clap if there's no `=>` or the pattern is empty
+pub(crate) fn parse_replace_rule(rule: &str) -> Result<ReplaceRule, String> {
+    match rule.split_once("=>") {
+        Some((pattern, template)) if !pattern.is_empty() => {
+            Ok(ReplaceRule { pattern: pattern.to_string(), template: template.to_string() })
+        }
+        _ => Err(format!("invalid rule `{rule}` (expected PATTERN=>TEMPLATE, like 'colour=>color')")),
+    }
+}
+
+/// Rules compiled into one regex, so each line is scanned once whatever the number of rules
+///
+/// Where rules overlap, the first one listed wins, and replaced text is never matched again, so
+/// rules like `a=>b` and `b=>a` swap.
+#[derive(Debug, Clone)]
+pub(crate) struct Rules {
+    /// Every rule as one alternation, each in its own group
+    combined: Regex,
+    /// Each rule's regex (for its own capture groups), template, and group in `combined`
+    rules: Vec<(Regex, String, usize)>,
+}
+
+impl Rules {
+    /// Compile `rules`, turning each pattern into a regex source with `source`
+    ///
+    /// # Errors
+    ///
+    /// Will error if a pattern is invalid
+    pub(crate) fn new(rules: &[ReplaceRule], source: impl Fn(&str) -> String) -> Result<Self, regex::Error> {
+        let mut compiled = Vec::with_capacity(rules.len());
+        let mut branches = Vec::with_capacity(rules.len());
+        let mut group = 1;
+
+        for rule in rules {
+            let source = source(&rule.pattern);
+            let regex = Regex::new(&source)?;
+
+            compiled.push((regex.clone(), rule.template.clone(), group));
+            branches.push(format!("({})", unnamed_groups(&source))); // Rules can name groups alike
+
+            // The wrapping group, then the rule's own groups
+            group += regex.captures_len();
+        }
+
+        Ok(Rules { combined: Regex::new(&branches.join("|"))?, rules: compiled })
+    }
+
+    /// `line` with every rule applied, and the spans of the replacements
+    ///
+    /// With `preserve_case`, each replacement takes the case of what it replaces (see [`match_case`])
+    pub(crate) fn apply(&self, line: &str, preserve_case: bool) -> (String, Vec<Range<usize>>) {
+        let mut replaced = String::with_capacity(line.len());
+        let mut spans = Vec::new();
+        let mut last = 0;
+
+        for captures in self.combined.captures_iter(line) {
+            let Some(found) = captures.get(0).filter(|found| !found.is_empty()) else {
+                continue; // Nothing to replace
+            };
+
+            // Only the rule that matched has its group set
+            let Some((regex, template, _)) = self.rules.iter().find(|(_, _, group)| captures.get(*group).is_some()) else {
+                continue;
+            };
+
+            let Some(own) = regex.captures_at(line, found.start()) else {
+                continue;
+            };
+
+            replaced.push_str(&line[last..found.start()]);
+
+            let replacement_start = replaced.len();
+            own.expand(template, &mut replaced);
+
+            if preserve_case {
+                let replacement = match_case(found.as_str(), &replaced[replacement_start..]);
+                replaced.replace_range(replacement_start.., &replacement);
+            }
+
+            spans.push(replacement_start..replaced.len());
+
+            last = found.end();
+        }
+
+        replaced.push_str(&line[last..]);
+
+        (replaced, spans)
+    }
+}
+
+/// `source` with its named groups unnamed, keeping their numbers
+pub(crate) fn unnamed_groups(source: &str) -> String {
+    let Ok(mut parsed) = ast::parse::Parser::new().parse(source) else {
+        return source.to_string(); // Invalid, which compiling it reports
+    };
+
+    unname(&mut parsed);
+    parsed.to_string()
+}
+
+fn unname(parsed: &mut Ast) {
+    match parsed {
+        Ast::Group(group) => {
+            if let ast::GroupKind::CaptureName { name, .. } = &group.kind {
+                group.kind = ast::GroupKind::CaptureIndex(name.index);
+            }
+
+            unname(&mut group.ast);
+        }
+        Ast::Repetition(repetition) => unname(&mut repetition.ast),
+        Ast::Alternation(alternation) => alternation.asts.iter_mut().for_each(unname),
+        Ast::Concat(concat) => concat.asts.iter_mut().for_each(unname),
+        _ => {}
+    }
+}
+
+/// `replacement` in the case of `matched`: all upper case if it is, capitalized if only its first
+/// letter is upper case, and as written otherwise (like for lower case or camelCase)
+///
+/// So the template is written the way a lower case match should be replaced, and `foo` => `bar`
+/// also turns `Foo` into `Bar` and `FOO` into `BAR`.
+pub(crate) fn match_case(matched: &str, replacement: &str) -> String {
+    let mut letters = matched.chars().filter(|c| c.is_alphabetic());
+
+    let Some(first) = letters.next() else {
+        return replacement.to_string(); // No case to match
+    };
+
+    let rest: Vec<char> = letters.collect();
+
+    if first.is_uppercase() && rest.iter().all(|c| c.is_uppercase()) && !rest.is_empty() {
+        replacement.to_uppercase()
+    } else if first.is_uppercase() && rest.iter().all(|c| !c.is_uppercase()) {
+        let mut chars = replacement.chars();
+        chars.next().map_or_else(String::new, |c| c.to_uppercase().chain(chars).collect())
+    } else {
+        replacement.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(rules: &[&str]) -> Rules {
+        let rules: Vec<ReplaceRule> = rules.iter().map(|rule| parse_replace_rule(rule).unwrap()).collect();
+
+        Rules::new(&rules, str::to_string).unwrap()
+    }
+
+    #[test]
+    fn test_parse_replace_rule() {
+        assert_eq!(
+            parse_replace_rule("a=>b=>c"),
+            Ok(ReplaceRule { pattern: String::from("a"), template: String::from("b=>c") })
+        );
+        assert_eq!(parse_replace_rule("x=>"), Ok(ReplaceRule { pattern: String::from("x"), template: String::new() }));
+        assert!(parse_replace_rule("a=b").unwrap_err().starts_with("invalid rule `a=b`"));
+        assert!(parse_replace_rule("=>b").is_err());
+    }
+
+    #[test]
+    fn test_rules_apply_in_one_pass() {
+        let (line, spans) = rules(&["cat=>dog", "dog=>cat"]).apply("cat chases dog", false);
+
+        assert_eq!(line, "dog chases cat");
+        assert_eq!(spans, vec![0..3, 11..14]);
+    }
+
+    #[test]
+    fn test_rules_capture_groups() {
+        let (line, _) = rules(&[r"(\w+)=(\d+)=>$2=$1", r"old_(?<name>\w+)=>new_$name"]).apply("x=1 old_api y=2", false);
+
+        assert_eq!(line, "1=x new_api 2=y");
+    }
+
+    #[test]
+    fn test_rules_same_group_names() {
+        let (line, _) = rules(&[r"old_(?P<x>\w+)=>new_$x", r"(?P<x>\d+)px=>${x}rem"]).apply("old_api 12px", false);
+
+        assert_eq!(line, "new_api 12rem");
+        assert_eq!(unnamed_groups(r"(?P<x>a)(?<y>b(c))"), "(a)(b(c))");
+    }
+
+    #[test]
+    fn test_match_case() {
+        assert_eq!(match_case("foo", "bar"), "bar");
+        assert_eq!(match_case("Foo", "bar"), "Bar");
+        assert_eq!(match_case("FOO", "bar"), "BAR");
+        assert_eq!(match_case("FOO_BAR", "new_name"), "NEW_NAME");
+        assert_eq!(match_case("fooBar", "newName"), "newName"); // Mixed, so as written
+        assert_eq!(match_case("F", "bar"), "Bar"); // One letter counts as capitalized
+        assert_eq!(match_case("éte", "été"), "été");
+        assert_eq!(match_case("123", "bar"), "bar");
+    }
+
+    #[test]
+    fn test_rules_preserve_case() {
+        let rules: Vec<ReplaceRule> = vec![parse_replace_rule("(?i)colour=>color").unwrap()];
+        let (line, spans) = Rules::new(&rules, str::to_string).unwrap().apply("Colour, COLOUR and colour", true);
+
+        assert_eq!(line, "Color, COLOR and color");
+        assert_eq!(spans, vec![0..5, 7..12, 17..22]);
+    }
+
+    #[test]
+    fn test_first_rule_wins() {
+        let (line, _) = rules(&["foo=>1", "foobar=>2"]).apply("foobar", false);
+
+        assert_eq!(line, "1bar");
+    }
+}
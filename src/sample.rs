@@ -0,0 +1,198 @@
+//! Random sample of the matching lines of a whole search (`--sample-matches N`)
+
+// Standard library
+use std::ops::Range; // For match spans
+
+// My stuff
+use crate::error::Error;
+use crate::i18n::{fill, Catalog};
+use crate::printer::Printer;
+use crate::search::{Match, Progress};
+
+/// Keeps a uniform random sample of the matching lines it's given, and prints them with the
+/// printer it wraps once the search is done
+///
+/// Uses reservoir sampling, so memory is bounded by the sample size however many lines match.
+/// The sample is printed in search order, followed by the total on stderr (so it doesn't get
+/// mixed into `--json` output).
+pub(crate) struct SamplingPrinter<'a> {
+    inner: Box<dyn Printer + 'a>,
+    catalog: &'static Catalog,
+    size: usize,
+    /// Every file with matches so far, and whether it's binary
+    files: Vec<(String, bool)>,
+    /// Sampled lines, with the index of their file
+    sample: Vec<(usize, Match)>,
+    matching_lines: usize,
+    random: SplitMix64,
+}
+
+/// Small, fast random numbers (splitmix64), the same for the same seed
+#[derive(Debug, Clone)]
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    /// Random number below `bound`
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut mixed = self.0;
+        mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        mixed ^= mixed >> 31;
+
+        #[allow(clippy::cast_possible_truncation)] // Below bound, which is a usize
+        let index = ((u128::from(mixed) * bound as u128) >> 64) as usize;
+
+        index
+    }
+}
+
+impl<'a> SamplingPrinter<'a> {
+    /// Sample `size` lines for `inner`, picking them with a random number generator seeded by `seed`
+    pub(crate) fn new(inner: Box<dyn Printer + 'a>, catalog: &'static Catalog, size: usize, seed: u64) -> Self {
+        SamplingPrinter {
+            inner,
+            catalog,
+            size,
+            files: Vec::new(),
+            sample: Vec::with_capacity(size),
+            matching_lines: 0,
+            random: SplitMix64::new(seed),
+        }
+    }
+
+    fn add(&mut self, path: &str, matches: &[Match], binary: bool) {
+        if matches.is_empty() {
+            return;
+        }
+
+        let file = self.files.len();
+        self.files.push((path.to_string(), binary));
+
+        for found in matches {
+            self.matching_lines += 1;
+
+            // Keep the first N, then replace one with decreasing probability (algorithm R)
+            if self.sample.len() < self.size {
+                self.sample.push((file, found.clone()));
+            } else {
+                let index = self.random.below(self.matching_lines);
+
+                if index < self.size {
+                    self.sample[index] = (file, found.clone());
+                }
+            }
+        }
+    }
+}
+
+impl Printer for SamplingPrinter<'_> {
+    fn file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        self.add(path, matches, false);
+        Ok(())
+    }
+
+    fn binary_file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        self.add(path, matches, true);
+        Ok(())
+    }
+
+    fn binary_matches(&mut self, path: &str, bytes: &[u8], spans: &[Range<usize>]) -> Result<(), Error> {
+        self.inner.binary_matches(path, bytes, spans) // Not lines, so not sampled
+    }
+
+    fn progress(&mut self, progress: &Progress) -> Result<(), Error> {
+        self.inner.progress(progress)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        let mut sample = std::mem::take(&mut self.sample);
+        sample.sort_by_key(|(file, found)| (*file, found.line_number()));
+
+        let shown = sample.len();
+
+        // One call per file, like a normal search
+        for group in sample.chunk_by(|(a, _), (b, _)| a == b) {
+            let (path, binary) = &self.files[group[0].0];
+            let matches: Vec<Match> = group.iter().map(|(_, found)| found.clone()).collect();
+
+            if *binary {
+                self.inner.binary_file(path, &matches)?;
+            } else {
+                self.inner.file(path, &matches)?;
+            }
+        }
+
+        self.inner.finish()?;
+
+        let (shown, total) = (shown.to_string(), self.matching_lines.to_string());
+        eprintln!("{}", fill(self.catalog.sampled_matches, &[("shown", &shown), ("total", &total)]));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Argument, Lang, Searcher};
+
+    /// Records what it's given
+    struct Recorder<'a>(&'a mut Vec<(String, usize)>);
+
+    impl Printer for Recorder<'_> {
+        fn file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+            self.0.extend(matches.iter().map(|found| (path.to_string(), found.line_number())));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sample_is_in_search_order() {
+        let searcher = Searcher::new(Argument { pattern: Some(String::from("x")), ..Default::default() }).unwrap();
+        let contents = "x\n".repeat(100);
+        let mut results = Vec::new();
+
+        let mut sampler = SamplingPrinter::new(Box::new(Recorder(&mut results)), Lang::En.catalog(), 5, 1);
+
+        for path in ["a.txt", "b.txt"] {
+            sampler.file(path, &searcher.search_str(&contents)).unwrap();
+        }
+
+        sampler.finish().unwrap();
+        assert_eq!(sampler.matching_lines, 200);
+        drop(sampler);
+
+        let mut sorted = results.clone();
+        sorted.sort();
+        sorted.dedup();
+
+        assert_eq!(results.len(), 5);
+        assert_eq!(results, sorted);
+    }
+
+    #[test]
+    fn test_sample_smaller_than_size_keeps_everything() {
+        let searcher = Searcher::new(Argument { pattern: Some(String::from("x")), ..Default::default() }).unwrap();
+        let mut results = Vec::new();
+
+        let mut sampler = SamplingPrinter::new(Box::new(Recorder(&mut results)), Lang::En.catalog(), 5, 1);
+        sampler.file("a.txt", &searcher.search_str("x\ny\nx\n")).unwrap();
+        sampler.finish().unwrap();
+        drop(sampler);
+
+        assert_eq!(results, vec![(String::from("a.txt"), 1), (String::from("a.txt"), 3)]);
+    }
+
+    #[test]
+    fn test_below_stays_in_bounds() {
+        let mut random = SplitMix64::new(42);
+
+        assert!((1..1000).all(|bound| random.below(bound) < bound));
+    }
+}
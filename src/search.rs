@@ -0,0 +1,1900 @@
+//! Searching files into matches, separate from printing them
+
+// Standard library
+use std::borrow::Cow; // For decoded lines
+use std::collections::BTreeMap; // For putting results back in order
+use std::fs; // For reading files
+use std::io::{BufRead, BufReader, Read}; // For streaming big files
+use std::ops::Range; // For match spans
+use std::sync::{mpsc, Condvar, Mutex, PoisonError}; // For sending results back, a bounded number at a time
+use std::thread; // For searching files in parallel
+use std::time::{Duration, Instant}; // For progress
+
+// External crates
+use regex::Regex; // For regular expressions
+
+// My stuff
+use crate::archive::{self, Limits};
+use crate::cache::LruCache;
+use crate::columns::ColumnRange;
+use crate::decompress::Decompressed;
+use crate::dedupe::SeenLines;
+use crate::delimited;
+use crate::devices;
+use crate::encoding;
+use crate::error::Error;
+#[cfg(feature = "guess-encoding")]
+use crate::guess;
+#[cfg(feature = "guess-encoding")]
+use crate::i18n::fill;
+use crate::matcher::Patterns;
+use crate::printer::Printer;
+use crate::rules::{match_case, Rules};
+use crate::status::STATUS;
+use crate::{BinaryFiles, Encoding, IoParallelism, SearchConfig, files_to_search, regex_source, split_lines, suppress_near_matches, try_build_regex, warn_skipped};
+
+/// A matching line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    line_number: usize,
+    byte_offset: u64,
+    line: String,
+    spans: Vec<Range<usize>>,
+}
+
+impl Match {
+    /// Match of `spans` in `line`, which have to keep to the invariants of [`Match::spans`]
+    pub(crate) fn new(line_number: usize, byte_offset: u64, line: String, spans: Vec<Range<usize>>) -> Self {
+        Match { line_number, byte_offset, line, spans }
+    }
+
+    /// 1-based line number
+    #[must_use]
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// Where the line starts, in bytes from the start of the file
+    ///
+    /// For files searched as they are (UTF-8, even with invalid bytes replaced, or with `--raw`)
+    /// it's in the file's own bytes, byte order mark included. For files decoded before
+    /// searching (like UTF-16, Latin-1 or compressed ones) it's in the decoded UTF-8 instead.
+    #[must_use]
+    pub fn byte_offset(&self) -> u64 {
+        self.byte_offset
+    }
+
+    /// The line, without its line ending
+    #[must_use]
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Byte ranges of each match in the line, with the invariants of [`Searcher::match_spans`]
+    ///
+    /// With `--replace`, `--replace-rule` or `--redact`, they're the ranges of the replacements in
+    /// the replaced line instead, which are empty where a replacement is.
+    #[must_use]
+    pub fn spans(&self) -> &[Range<usize>] {
+        &self.spans
+    }
+}
+
+/// A matching line: its number, where it starts in the file, the line and the match spans
+pub type LineMatch = Match;
+
+/// Matching lines of the file at `path`, for programs that want matches rather than output
+///
+/// A [`Searcher`] is the matcher, with the patterns compiled and checked once, so this is
+/// [`Searcher::search_path`] under another name: `search_file(&searcher, path)` is
+/// `searcher.search_path(path)`, and a [`LineMatch`] is a [`Match`].
+///
+/// ```no_run
+/// use simple_grep::prelude::*;
+///
+/// let searcher = Searcher::new(SearchConfig::try_from_args(["fox", "notes.txt"])?)?;
+///
+/// for found in search_file(&searcher, "notes.txt")? {
+///     println!("{} (byte {}): {}", found.line_number(), found.byte_offset(), found.line());
+/// }
+/// # Ok::<(), Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Will error if the file is not readable or cannot be found
+pub fn search_file(searcher: &Searcher, path: &str) -> Result<Vec<LineMatch>, Error> {
+    searcher.search_path(path)
+}
+
+/// How far a search has got, for showing progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    files: usize,
+    bytes: u64,
+    elapsed: Duration,
+}
+
+impl Progress {
+    /// Files searched so far (read, whether or not they matched)
+    #[must_use]
+    pub fn files(&self) -> usize {
+        self.files
+    }
+
+    /// Bytes read so far
+    #[must_use]
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Time since the search started
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Lines -> their spans if they match
+type LineCache = LruCache<Option<Vec<Range<usize>>>>;
+
+/// What searching a file found, for [`Searcher::run`] to print
+enum Found {
+    /// Matching lines, and whether the file is binary
+    Lines(Vec<Match>, bool),
+    /// Matches in the raw bytes of a binary file, with `--only-binary`
+    Bytes(Vec<u8>, Vec<Range<usize>>),
+    /// Nothing to print, like for a text file with `--only-binary`
+    Nothing,
+    /// The files in an archive, with `--search-archives`, each with its path inside and what was found in it
+    Members(Vec<(String, Searched)>),
+}
+
+impl Found {
+    /// How many matching lines (or binary matches) were found
+    fn matched_lines(&self) -> usize {
+        match self {
+            Found::Lines(matches, _) => matches.len(),
+            Found::Bytes(_, spans) => spans.len(),
+            Found::Nothing => 0,
+            Found::Members(members) => members.iter().filter_map(|(_, searched)| searched.found.as_ref().ok()).map(Found::matched_lines).sum(),
+        }
+    }
+}
+
+/// A searched file: how many bytes were read (if it could be), what was found, how long it took,
+/// how its contents were decoded before searching (like `gzip`, then `UTF-16LE`), and its first
+/// lines with `--with-header-line`
+struct Searched {
+    read: Option<u64>,
+    found: Result<Found, Error>,
+    took: Duration,
+    decoded: Vec<String>,
+    header: Vec<Match>,
+}
+
+impl Searched {
+    /// Searched contents that weren't decoded, not timed yet
+    fn new(read: Option<u64>, found: Result<Found, Error>) -> Self {
+        Searched { read, found, took: Duration::ZERO, decoded: Vec::new(), header: Vec::new() }
+    }
+}
+
+/// A file's contents as text
+struct Text {
+    contents: String,
+    binary: bool,
+    /// Where each line starts in the file, when it's not where it starts in the text (like after
+    /// invalid UTF-8 was replaced) and the file wasn't decoded from another encoding
+    line_starts: Option<Vec<u64>>,
+}
+
+impl Text {
+    /// Give `matches` the offsets of their lines in the file, rather than in the text searched
+    fn file_offsets(&self, matches: &mut [Match]) {
+        let Some(line_starts) = &self.line_starts else { return };
+
+        for found in matches {
+            if let Some(&start) = line_starts.get(found.line_number - 1) {
+                found.byte_offset = start;
+            }
+        }
+    }
+}
+
+/// What [`Searcher::run`] keeps track of while printing
+struct RunState<'a> {
+    printer: &'a mut dyn Printer,
+    start: Instant,
+    last_report: Instant,
+    progress: Progress,
+    seen: Option<SeenLines>,
+}
+
+/// Hands out files to the threads from each queue in turn, only as far as a bounded number
+/// of files past the last one printed, so results held up by a slow file don't pile up
+struct Window<'a> {
+    queues: &'a [Vec<usize>],
+    state: Mutex<WindowState>,
+    moved: Condvar,
+    size: usize,
+}
+
+/// What a [`Window`] keeps track of, behind its lock
+struct WindowState {
+    /// How many files have been printed
+    printed: usize,
+    /// Whether searching has stopped (printing failed, or it timed out)
+    stopped: bool,
+    /// The next file of each queue to hand out
+    nexts: Vec<usize>,
+}
+
+impl<'a> Window<'a> {
+    fn new(queues: &'a [Vec<usize>], size: usize) -> Self {
+        let state = WindowState { printed: 0, stopped: false, nexts: vec![0; queues.len()] };
+
+        Window { queues, state: Mutex::new(state), moved: Condvar::new(), size }
+    }
+
+    /// The file for a thread to search next, trying the queue `first` first, or [`None`] once
+    /// there are none left (or searching stopped)
+    ///
+    /// Waits while every file left is too far ahead. Queues are in order, so the next file to
+    /// print is always at the front of one and never waits.
+    fn take(&self, first: usize) -> Option<usize> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        loop {
+            if state.stopped {
+                return None;
+            }
+
+            let mut left = false;
+
+            for queue in (0..self.queues.len()).map(|offset| (first + offset) % self.queues.len()) {
+                let Some(&index) = self.queues[queue].get(state.nexts[queue]) else { continue };
+
+                if index < state.printed + self.size {
+                    state.nexts[queue] += 1;
+                    return Some(index);
+                }
+
+                left = true;
+            }
+
+            if !left {
+                return None;
+            }
+
+            state = self.moved.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Move the window past the first `printed` files
+    fn printed(&self, printed: usize) {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).printed = printed;
+        self.moved.notify_all();
+    }
+
+    /// Let every waiting thread go, without handing out any more files
+    fn stop(&self) {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).stopped = true;
+        self.moved.notify_all();
+    }
+}
+
+/// How many files [`Window`] lets each thread get ahead of printing
+const IN_FLIGHT_PER_THREAD: usize = 4;
+
+/// Files at least this big are searched a line at a time instead of being read whole
+const STREAM_THRESHOLD: u64 = 64 << 20;
+
+/// How often [`Searcher::run`] reports progress, at most
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Searches files with a config, checked up front
+#[derive(Debug, Clone)]
+pub struct Searcher {
+    config: SearchConfig,
+    matcher: Patterns,
+    near_regex: Option<Regex>,                 // For --unless-near
+    bytes_regex: Option<regex::bytes::Regex>, // For --only-binary
+    rules: Option<Rules>,                      // For --replace-rule
+}
+
+impl Searcher {
+    /// Searcher for a (normalized) config
+    ///
+    /// # Errors
+    ///
+    /// Will error if a pattern is invalid
+    pub fn new(config: impl Into<SearchConfig>) -> Result<Self, Error> {
+        let config = config.into();
+        let matcher = Patterns::new(&config)?;
+
+        let near_regex = match &config.unless_near {
+            Some(near) => Some(try_build_regex(&config.near_query(near), config.ignore_case(), false)?),
+            None => None,
+        };
+
+        let bytes_regex = if config.only_binary {
+            Some(regex::bytes::Regex::new(&regex_source(&config.query(), config.ignore_case(), config.whole_word()))?)
+        } else {
+            None
+        };
+
+        let rules = config.replace_rules()?;
+
+        Ok(Searcher { config, matcher, near_regex, bytes_regex, rules })
+    }
+
+    /// Name of the engine matching the patterns, for `--debug`
+    pub(crate) fn engine_name(&self) -> &'static str {
+        self.matcher.engine_name()
+    }
+
+    /// The config being searched with
+    #[must_use]
+    pub fn config(&self) -> &SearchConfig {
+        &self.config
+    }
+
+    /// Matching lines of `contents`
+    #[must_use]
+    pub fn search_str(&self, contents: &str) -> Vec<Match> {
+        // Remembers whether repeated lines match and where, with --line-cache
+        let mut cache = self.config.line_cache.map(LruCache::new);
+
+        let mut lines = match &mut cache {
+            Some(cache) => self.cached_matching_lines(contents, cache),
+            None => self.matcher.matching_lines(contents),
+        };
+
+        // Drop matches that have the other pattern nearby
+        if let Some(near_regex) = &self.near_regex {
+            lines = suppress_near_matches(lines, contents, near_regex, self.config.within, self.config.line_terminator());
+        }
+
+        lines
+            .into_iter()
+            .filter_map(|(line_number, line)| {
+                let cached = cache.as_mut().and_then(|cache| cache.get(line).cloned().flatten());
+                let spans = cached.unwrap_or_else(|| self.line_spans(line));
+
+                self.line_match(line_number, offset_in(contents, line), line, spans)
+            })
+            .collect()
+    }
+
+    /// Result for a matching line with match `spans`, with the replacements applied
+    ///
+    /// Nothing if `--min-entropy` filtered out every match
+    fn line_match(&self, line_number: usize, byte_offset: u64, line: &str, spans: Vec<Range<usize>>) -> Option<Match> {
+        let (line, spans) = if let Some(rules) = &self.rules {
+            rules.apply(line, self.config.preserve_case)
+        } else if let Some(template) = &self.config.replace
+            && let Some(regex) = self.matcher.regex() // Always, since --replace conflicts with PCRE2
+        {
+            replace(line, &spans, regex, self.config.columns, template, self.config.preserve_case)
+        } else if self.config.redact {
+            redact(line, &spans)
+        } else {
+            (line.to_string(), spans)
+        };
+
+        (self.config.min_entropy.is_none() || !spans.is_empty()).then_some(Match { line_number, byte_offset, line, spans })
+    }
+
+    /// Like [`Patterns::matching_lines`], but looking up lines seen before in `cache` instead of matching them again
+    fn cached_matching_lines<'a>(&self, contents: &'a str, cache: &mut LineCache) -> Vec<(usize, &'a str)> {
+        split_lines(contents, self.config.line_terminator())
+            .enumerate()
+            .filter(|(_, line)| {
+                if let Some(spans) = cache.get(line) {
+                    return spans.is_some();
+                }
+
+                let spans = self.matcher.is_match(line).then(|| self.line_spans(line));
+                let matched = spans.is_some();
+
+                cache.insert(line, spans);
+                matched
+            })
+            .map(|(index, line)| (index + 1, line))
+            .collect()
+    }
+
+    /// Byte ranges of the matches in `line`, as used to highlight it
+    ///
+    /// These hold for every line, and are relied on by the printers:
+    ///
+    /// - every span is non-empty, and starts and ends on a `char` boundary of `line`, so
+    ///   `&line[span]` never panics
+    /// - spans are in order and don't overlap (each ends at or before the next one starts)
+    /// - with `--columns`, every span is inside the column range
+    ///
+    /// Lines that don't match have no spans.
+    #[must_use]
+    pub fn match_spans(&self, line: &str) -> Vec<Range<usize>> {
+        self.line_spans(line)
+    }
+
+    /// Spans of the matches in a matching line
+    fn line_spans(&self, line: &str) -> Vec<Range<usize>> {
+        self.entropy_filter(line.as_bytes(), self.matcher.spans(line))
+    }
+
+    /// The spans with at least `--min-entropy`, or all of them without it
+    fn entropy_filter(&self, haystack: &[u8], mut spans: Vec<Range<usize>>) -> Vec<Range<usize>> {
+        if let Some(min_entropy) = self.config.min_entropy {
+            spans.retain(|span| entropy(&haystack[span.clone()]) >= min_entropy);
+        }
+
+        spans
+    }
+
+    /// Matching lines of the file at `path`
+    ///
+    /// Binary files are searched with invalid UTF-8 replaced, or have no matches with
+    /// `--binary-files=without-match`
+    ///
+    /// # Errors
+    ///
+    /// Will error if the file is not readable or cannot be found
+    pub fn search_path(&self, path: &str) -> Result<Vec<Match>, Error> {
+        let text = self.read(path)?;
+
+        if text.binary && self.config.binary_mode() == BinaryFiles::WithoutMatch {
+            return Ok(Vec::new());
+        }
+
+        let mut matches = self.search_str(&text.contents);
+        text.file_offsets(&mut matches);
+
+        Ok(matches)
+    }
+
+    /// Contents of the file at `path` as text
+    fn read(&self, path: &str) -> Result<Text, Error> {
+        let bytes = fs::read(path).map_err(|source| Error::Io { path: path.to_string(), source })?;
+        let encoding = self.file_encoding(path, &bytes);
+
+        Ok(self.decode(bytes, encoding))
+    }
+
+    /// The encoding of the file at `path`, going by its contents (or their start) `bytes`: the
+    /// one given, the one its byte order mark says, or (with the `guess-encoding` feature) a
+    /// confident enough guess when it doesn't read as UTF-8
+    ///
+    /// Guesses that aren't confident enough are left out, and reported with `--debug`.
+    fn file_encoding(&self, path: &str, bytes: &[u8]) -> Encoding {
+        // Records are counted in bytes, so they're searched as they are
+        if self.config.record_size.is_some() {
+            return if self.config.encoding == Encoding::Latin1 { Encoding::Latin1 } else { Encoding::Utf8 };
+        }
+
+        let encoding = encoding::detect(bytes, self.config.encoding);
+
+        #[cfg(feature = "guess-encoding")]
+        if self.config.encoding == Encoding::Auto
+            && encoding == Encoding::Utf8
+            && !self.config.null_data // NUL bytes end lines there, rather than being half of UTF-16
+            && let Some(guess) = guess::guess(bytes)
+        {
+            if guess.confidence >= guess::CONFIDENT {
+                return guess.encoding;
+            }
+
+            if self.config.debug {
+                let confidence = format!("{:.0}", guess.confidence * 100.0);
+                let name = encoding::name(guess.encoding).unwrap_or_default();
+
+                let catalog = self.config.lang().catalog();
+                eprintln!("{}: {}", env!("CARGO_PKG_NAME"), fill(catalog.debug_guess, &[("file", path), ("encoding", name), ("confidence", &confidence)]));
+            }
+        }
+
+        #[cfg(not(feature = "guess-encoding"))]
+        let _ = path;
+
+        encoding
+    }
+
+    /// Whether file contents count as binary
+    fn is_binary(&self, bytes: &[u8]) -> bool {
+        !self.config.null_data && is_binary(bytes) // NUL is the line terminator with -z, so it doesn't mean binary there
+    }
+
+    /// File contents as text
+    ///
+    /// Invalid UTF-8 (like a Latin-1 log) is replaced with U+FFFD, so the rest of the file is
+    /// still searched.
+    fn decode(&self, bytes: Vec<u8>, encoding: Encoding) -> Text {
+        // Files searched as they are keep the offsets of their lines in the file
+        let undecoded = self.config.raw || matches!(encoding::detect(&bytes, encoding), Encoding::Auto | Encoding::Utf8);
+        let bom = if undecoded && !self.config.raw && bytes.starts_with(encoding::UTF8_BOM) { encoding::UTF8_BOM.len() } else { 0 };
+
+        let bytes = match self.config.record_size {
+            Some(size) => records(&bytes, usize::try_from(size).unwrap_or(usize::MAX)),
+            None => bytes,
+        };
+
+        // The bytes too, when the text isn't just them
+        let (contents, binary, bytes) = if self.config.raw {
+            (encoding::latin1(&bytes), false, Some(bytes)) // Every byte is one character
+        } else {
+            let bytes = encoding::transcode(bytes, encoding);
+            let binary = self.is_binary(&bytes);
+
+            if binary && self.config.strings {
+                let contents = extract_strings(&bytes, self.config.strings_min); // Searched like any text file
+                return Text { contents, binary: false, line_starts: None };
+            }
+
+            match String::from_utf8(bytes) {
+                Ok(contents) => (contents, binary, None),
+                Err(e) => (String::from_utf8_lossy(e.as_bytes()).into_owned(), binary, Some(e.into_bytes())),
+            }
+        };
+
+        let line_starts = if !undecoded {
+            None
+        } else if let Some(size) = self.config.record_size {
+            Some((0..split_lines(&contents, '\n').count() as u64).map(|index| index * size).collect())
+        } else {
+            let bytes = bytes.as_deref().unwrap_or(contents.as_bytes());
+            (bom > 0 || bytes.len() != contents.len()).then(|| self.line_starts(bytes, bom))
+        };
+
+        Text { contents, binary, line_starts }
+    }
+
+    /// Where each line of `bytes` starts in the file they're from, after a byte order mark of
+    /// `bom` bytes
+    fn line_starts(&self, bytes: &[u8], bom: usize) -> Vec<u64> {
+        let terminator = if self.config.null_data { 0 } else { b'\n' };
+
+        std::iter::once(0).chain(memchr::memchr_iter(terminator, bytes).map(|at| (bom + at + 1) as u64)).collect()
+    }
+
+    /// Search every file in the config (that passes the file filters) and hand the results to `printer`
+    ///
+    /// Files are searched on several threads (see `-j`), but handed over in order, so the
+    /// output is the same however many threads there are.
+    ///
+    /// Unreadable files are skipped when searching recursively, with a warning unless `-s` was given
+    ///
+    /// # Errors
+    ///
+    /// Will error if a given file can't be read (outside recursive mode) or printing fails
+    pub fn run(&self, printer: &mut dyn Printer) -> Result<(), Error> {
+        let files = files_to_search(&self.config);
+        let start = Instant::now();
+        STATUS.start(files.len());
+
+        let mut state = RunState {
+            printer,
+            start,
+            last_report: start,
+            progress: Progress { files: 0, bytes: 0, elapsed: Duration::ZERO },
+            seen: self.config.global_dedupe.then(|| SeenLines::new(self.config.dedupe_bloom)),
+        };
+
+        let threads = self.config.threads().min(files.len());
+
+        let searched = if threads > 1 {
+            self.run_parallel(&files, threads, &mut state)?
+        } else {
+            self.run_sequential(&files, &mut state)?
+        };
+
+        if searched < files.len() {
+            warn_skipped(&self.config, &format!("timed out, {} files not searched", files.len() - searched));
+        }
+
+        state.progress.elapsed = start.elapsed();
+        state.printer.progress(&state.progress)?; // Always once at the end, with the totals
+
+        state.printer.finish()
+    }
+
+    /// Search and print `files` one at a time, returning how many were searched before `--timeout`
+    fn run_sequential(&self, files: &[String], state: &mut RunState) -> Result<usize, Error> {
+        for (index, path) in files.iter().enumerate() {
+            if self.timed_out(state.start) {
+                return Ok(index);
+            }
+
+            self.print(state, path, self.search_file(path))?;
+        }
+
+        Ok(files.len())
+    }
+
+    /// Search `files` on `threads` threads and print them in order, returning how many were searched before `--timeout`
+    fn run_parallel(&self, files: &[String], threads: usize, state: &mut RunState) -> Result<usize, Error> {
+        let queues = match self.config.io_parallelism {
+            IoParallelism::PerDevice => devices::queues(&self.config.files, files),
+            IoParallelism::Global => vec![(0..files.len()).collect()],
+        };
+
+        let start = state.start;
+        let window = Window::new(&queues, threads * IN_FLIGHT_PER_THREAD);
+
+        thread::scope(|scope| {
+            // Only files in the window are searched, so there are never more results than that waiting
+            let (sender, receiver) = mpsc::sync_channel(window.size);
+            let window = &window;
+
+            // The threads start on different devices' queues, so a slow one doesn't hold all of them up
+            for thread in 0..threads {
+                let sender = sender.clone();
+
+                scope.spawn(move || {
+                    while let Some(index) = window.take(thread) {
+                        // The file taken is left unsearched, so nobody waits for it to be printed
+                        if self.timed_out(start) {
+                            window.stop();
+                            break;
+                        }
+
+                        if sender.send((index, self.search_file(&files[index]))).is_err() {
+                            break; // Printing failed, so the rest won't be printed
+                        }
+                    }
+                });
+            }
+
+            drop(sender); // So receiving stops once every thread is done
+
+            // Results come in any order, so they wait here until it's their turn
+            let mut waiting = BTreeMap::new();
+            let mut printed = 0;
+
+            for (index, searched) in receiver {
+                waiting.insert(index, searched);
+
+                while let Some(searched) = waiting.remove(&printed) {
+                    if let Err(e) = self.print(state, &files[printed], searched) {
+                        window.stop();
+                        return Err(e);
+                    }
+
+                    printed += 1;
+                }
+
+                window.printed(printed);
+            }
+
+            // After a timeout, files searched past one that wasn't are still printed (in order)
+            let searched = printed + waiting.len();
+
+            for (index, searched) in waiting {
+                self.print(state, &files[index], searched)?;
+            }
+
+            Ok(searched)
+        })
+    }
+
+    /// Whether `--timeout` has passed since `start`
+    fn timed_out(&self, start: Instant) -> bool {
+        self.config.timeout.is_some_and(|timeout| start.elapsed() >= timeout)
+    }
+
+    /// Read and search the file at `path`, timing it
+    fn search_file(&self, path: &str) -> Searched {
+        let start = Instant::now();
+        STATUS.searching(path);
+        let searched = self.read_and_search(path);
+
+        Searched { took: start.elapsed(), ..searched }
+    }
+
+    /// Read and search the file at `path`, a line at a time if it's big
+    fn read_and_search(&self, path: &str) -> Searched {
+        let io_error = |source| Searched::new(None, Err(Error::Io { path: path.to_string(), source }));
+
+        if let Some(command) = self.preprocessor(path) {
+            return match Decompressed::preprocess(command, path) {
+                Ok(preprocessed) => self.search_decompressed(path, preprocessed),
+                Err(source) => io_error(source),
+            };
+        }
+
+        if self.config.search_archives && archive::is_archive(path) {
+            return match archive::read(path, self.config.archive_max_size) {
+                Ok(bytes) => self.search_archive(path, &bytes),
+                Err(source) => io_error(source),
+            };
+        }
+
+        if self.config.search_zip
+            && let Some(decompressed) = Decompressed::open(path)
+        {
+            return match decompressed {
+                Ok(decompressed) => self.search_decompressed(path, decompressed),
+                Err(source) => io_error(source),
+            };
+        }
+
+        if self.can_stream() && fs::metadata(path).is_ok_and(|metadata| metadata.len() >= STREAM_THRESHOLD) {
+            let mut reader = match fs::File::open(path) {
+                Ok(file) => BufReader::new(file),
+                Err(source) => return io_error(source),
+            };
+
+            // UTF-16 is turned into UTF-8 whole, below
+            let encoding = self.peek_encoding(path, &mut reader);
+            if !is_utf16(encoding) {
+                return self.stream(path, reader, encoding);
+            }
+        }
+
+        match fs::read(path) {
+            Ok(bytes) => self.search_bytes(path, bytes),
+            Err(source) => io_error(source),
+        }
+    }
+
+    /// The `--pre` command to run on the file at `path`, if any (and `--pre-glob` doesn't leave it out)
+    fn preprocessor(&self, path: &str) -> Option<&str> {
+        let command = self.config.pre.as_deref()?;
+
+        (self.config.pre_glob.is_empty() || self.config.pre_glob.iter().any(|glob| glob.is_match(path))).then_some(command)
+    }
+
+    /// Search `bytes` (of the file at `path`) read whole
+    fn search_bytes(&self, path: &str, bytes: Vec<u8>) -> Searched {
+        let encoding = self.file_encoding(path, &bytes);
+        let read = bytes.len() as u64;
+        let (found, header) = self.find(path, bytes, encoding);
+
+        Searched { decoded: self.decoding(encoding), header, ..Searched::new(Some(read), Ok(found)) }
+    }
+
+    /// The name of `encoding` that contents are decoded from, if it isn't UTF-8 (and they aren't
+    /// searched as they are, with `--raw`)
+    fn decoding(&self, encoding: Encoding) -> Vec<String> {
+        if self.config.raw {
+            return Vec::new();
+        }
+
+        encoding::name(encoding).into_iter().map(String::from).collect()
+    }
+
+    /// Search each file in the archive at `path` with contents `bytes`
+    fn search_archive(&self, path: &str, bytes: &[u8]) -> Searched {
+        let depth = usize::try_from(self.config.archive_depth).unwrap_or(usize::MAX);
+        let mut limits = Limits { depth, size: self.config.archive_max_size };
+
+        let found = archive::members(path, bytes, &mut limits)
+            .map(|members| {
+                let members = members.into_iter().map(|member| {
+                    let searched = match member.contents {
+                        Ok(contents) => self.search_bytes(&format!("{path}!{}", member.name), contents),
+                        Err(source) => Searched::new(None, Err(Error::Io { path: format!("{path}!{}", member.name), source })),
+                    };
+
+                    (member.name, searched)
+                });
+
+                Found::Members(members.collect())
+            })
+            .map_err(|source| Error::Io { path: path.to_string(), source });
+
+        Searched::new(None, found)
+    }
+
+    /// Search what `decompressed` gives, a line at a time when possible (whatever its size, since
+    /// that isn't known before it's all decompressed)
+    fn search_decompressed(&self, path: &str, mut decompressed: Decompressed) -> Searched {
+        let io_error = |source| Err(Error::Io { path: path.to_string(), source });
+        let mut reader = BufReader::new(&mut decompressed);
+
+        let encoding = self.peek_encoding(path, &mut reader);
+
+        let searched = if self.can_stream() && !is_utf16(encoding) {
+            self.stream(path, reader, encoding)
+        } else {
+            let mut bytes = Vec::new();
+
+            match reader.read_to_end(&mut bytes) {
+                Ok(_) => self.search_bytes(path, bytes),
+                Err(source) => Searched::new(None, io_error(source)),
+            }
+        };
+
+        let decoded = [decompressed.program().to_string()].into_iter().chain(searched.decoded).collect();
+
+        match decompressed.finish() {
+            Ok(()) => Searched { decoded, ..searched },
+            Err(source) => Searched { found: io_error(source), decoded, ..searched },
+        }
+    }
+
+    /// Whether files can be searched a line at a time, which they can't when matches depend on
+    /// nearby lines or the whole file
+    fn can_stream(&self) -> bool {
+        self.bytes_regex.is_none()
+            && !self.config.strings
+            && self.config.unless_near.is_none()
+            && !matches!(self.config.encoding, Encoding::Utf16le | Encoding::Utf16be)
+    }
+
+    /// The encoding of what `reader` (reading the file at `path`) reads, going by the start of it
+    fn peek_encoding(&self, path: &str, reader: &mut impl BufRead) -> Encoding {
+        match reader.fill_buf() {
+            Ok(start) => self.file_encoding(path, start),
+            Err(_) => self.config.encoding, // Reading again fails the same way
+        }
+    }
+
+    /// Search `reader` a line at a time, so memory use grows with the matches but not the file
+    ///
+    /// Gives the same results as reading it whole: whether it's binary is only decided at the end,
+    /// since a NUL byte anywhere makes the whole file binary.
+    fn stream(&self, path: &str, mut reader: impl BufRead, encoding: Encoding) -> Searched {
+        let io_error = |source| Error::Io { path: path.to_string(), source };
+        let terminator = if self.config.null_data { 0 } else { b'\n' };
+
+        let mut matches = Vec::new();
+        let mut header = Vec::new();
+        let mut buffer = Vec::new();
+        let (mut read, mut lines, mut binary) = (0, 0, false);
+        let mut offset = 0; // Of the line in the text searched, as if it was read whole
+        let header_lines = self.header_lines(path);
+        let undecoded = self.config.raw || matches!(encoding, Encoding::Auto | Encoding::Utf8); // So offsets are in the file
+
+        for line_number in 1.. {
+            buffer.clear();
+
+            let next = match self.config.record_size {
+                Some(size) => (&mut reader).take(size).read_to_end(&mut buffer),
+                None => reader.read_until(terminator, &mut buffer),
+            };
+
+            let file_offset = read;
+
+            match next {
+                Ok(0) => break,
+                Ok(len) => (read, lines) = (read + len as u64, line_number),
+                Err(source) => return Searched::new(Some(read), Err(io_error(source))),
+            }
+
+            let mut bytes = if self.config.record_size.is_some() {
+                as_line(&mut buffer);
+                &buffer[..]
+            } else {
+                buffer.strip_suffix(&[terminator]).unwrap_or(&buffer)
+            };
+
+            if terminator == b'\n' && self.config.record_size.is_none() {
+                bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+            }
+
+            // Records are searched as if each ended with a newline
+            let ending = if self.config.record_size.is_some() { 1 } else { buffer.len() - bytes.len() };
+
+            let line: Cow<str> = if self.config.raw {
+                Cow::Owned(encoding::latin1(bytes)) // Every byte is one character
+            } else if encoding == Encoding::Latin1 {
+                binary |= self.is_binary(bytes);
+                Cow::Owned(encoding::latin1(bytes))
+            } else {
+                if line_number == 1 {
+                    bytes = bytes.strip_prefix(encoding::UTF8_BOM).unwrap_or(bytes);
+                }
+
+                binary |= self.is_binary(bytes);
+                String::from_utf8_lossy(bytes)
+            };
+
+            let line_offset = if undecoded { file_offset } else { offset };
+            offset += (line.len() + ending) as u64;
+
+            if line_number <= header_lines {
+                header.push(Match::new(line_number, line_offset, line.to_string(), Vec::new()));
+            }
+
+            if self.matcher.is_match(&line)
+                && let Some(found) = self.line_match(line_number, line_offset, &line, self.line_spans(&line))
+            {
+                matches.push(found);
+            }
+        }
+
+        let found = if (binary && self.config.binary_mode() == BinaryFiles::WithoutMatch) || !self.enough_matches(&matches, || lines) {
+            Ok(Found::Nothing)
+        } else {
+            Ok(Found::Lines(matches, binary))
+        };
+
+        Searched { decoded: self.decoding(encoding), header, ..Searched::new(Some(read), found) }
+    }
+
+    /// What to print for a file with contents `bytes`, and its first lines with `--with-header-line`
+    fn find(&self, path: &str, mut bytes: Vec<u8>, encoding: Encoding) -> (Found, Vec<Match>) {
+        // Only binary files, searched as bytes so offsets are exact
+        if let Some(bytes_regex) = &self.bytes_regex {
+            if !self.is_binary(&bytes) {
+                return (Found::Nothing, Vec::new());
+            }
+
+            let spans = self.entropy_filter(&bytes, byte_spans(&bytes, bytes_regex));
+
+            if self.config.redact {
+                // Same length, so offsets still line up
+                for span in &spans {
+                    bytes[span.clone()].fill(b'*');
+                }
+            }
+
+            return (Found::Bytes(bytes, spans), Vec::new());
+        }
+
+        let text = self.decode(bytes, encoding);
+        let (contents, binary) = (&text.contents, text.binary);
+
+        if binary && self.config.binary_mode() == BinaryFiles::WithoutMatch {
+            return (Found::Nothing, Vec::new());
+        }
+
+        let mut matches = self.search_str(contents);
+        text.file_offsets(&mut matches);
+
+        if !self.enough_matches(&matches, || split_lines(contents, self.config.line_terminator()).count()) {
+            return (Found::Nothing, Vec::new());
+        }
+
+        let mut header: Vec<Match> = split_lines(contents, self.config.line_terminator())
+            .take(self.header_lines(path))
+            .enumerate()
+            .map(|(index, line)| Match::new(index + 1, offset_in(contents, line), line.to_string(), Vec::new()))
+            .collect();
+        text.file_offsets(&mut header);
+
+        (Found::Lines(matches, binary), header)
+    }
+
+    /// How many of the first lines of the file at `path` to print before its matches: those asked
+    /// for with `--with-header-line`, or a CSV or TSV file's header row with `--column-names`
+    fn header_lines(&self, path: &str) -> usize {
+        let header_row = usize::from(self.config.column_names && delimited::delimiter(path).is_some());
+
+        self.config.with_header_line.unwrap_or(0).max(header_row)
+    }
+
+    /// Whether a file's `matches` pass `--min-matches` and `--min-density`, out of its number of `lines`
+    fn enough_matches(&self, matches: &[Match], lines: impl FnOnce() -> usize) -> bool {
+        let found = if self.config.count_matches {
+            matches.iter().map(|found| found.spans.len()).sum()
+        } else {
+            matches.len()
+        };
+
+        if self.config.min_matches.is_some_and(|min_matches| found < min_matches) {
+            return false;
+        }
+
+        let Some(min_density) = self.config.min_density else {
+            return true;
+        };
+
+        let lines = lines();
+
+        #[allow(clippy::cast_precision_loss)] // Only loses precision past 2^52 lines
+        let density = matches.len() as f64 * 100.0 / lines as f64;
+
+        lines > 0 && density >= min_density
+    }
+
+    /// Count a searched file and hand what was found to the printer, in search order
+    fn print(&self, state: &mut RunState, path: &str, searched: Searched) -> Result<(), Error> {
+        if state.last_report.elapsed() >= PROGRESS_INTERVAL {
+            state.progress.elapsed = state.start.elapsed();
+            state.printer.progress(&state.progress)?;
+            state.last_report = Instant::now();
+        }
+
+        STATUS.searched(searched.found.as_ref().map_or(0, Found::matched_lines));
+
+        self.print_searched(state, path, searched)
+    }
+
+    /// Hand what was found in the file at `path` (or an archive member) to the printer
+    fn print_searched(&self, state: &mut RunState, path: &str, searched: Searched) -> Result<(), Error> {
+        if let Some(bytes) = searched.read {
+            state.progress.files += 1;
+            state.progress.bytes += bytes;
+            state.printer.searched(path, bytes, searched.took)?;
+        }
+
+        let (matches, binary) = match searched.found {
+            Ok(Found::Lines(matches, binary)) => (matches, binary),
+            Ok(Found::Bytes(bytes, spans)) => return state.printer.binary_matches(path, &bytes, &spans),
+            Ok(Found::Nothing) => return Ok(()),
+            Ok(Found::Members(members)) => {
+                for (name, member) in members {
+                    self.print_searched(state, &format!("{path}!{name}"), member)?;
+                }
+
+                return Ok(());
+            }
+            Err(e) => return self.skip(state, path, e),
+        };
+
+        let matches = match &mut state.seen {
+            Some(seen) => seen.unseen(matches),
+            None => matches,
+        };
+
+        if !searched.decoded.is_empty() {
+            let steps: Vec<&str> = searched.decoded.iter().map(String::as_str).collect();
+            state.printer.decoded(path, &steps)?;
+        }
+
+        if binary && self.config.binary_mode() == BinaryFiles::Binary {
+            state.printer.binary_file(path, &matches)
+        } else {
+            if !matches.is_empty() && !searched.header.is_empty() {
+                state.printer.header(path, &searched.header)?;
+            }
+
+            state.printer.file(path, &matches)
+        }
+    }
+
+    /// Skip a file that couldn't be read when searching recursively (with a warning), otherwise fail
+    fn skip(&self, state: &mut RunState, path: &str, error: Error) -> Result<(), Error> {
+        if !self.config.recursive {
+            return Err(error);
+        }
+
+        state.printer.skipped(path, &error)?;
+        warn_skipped(&self.config, &error); // Keep going
+        Ok(())
+    }
+}
+
+/// `line` with the matches at `spans` replaced by `template` (expanding `$1`, `$name`...), and the spans of the replacements
+///
+/// Captures are found the same way as [`match_spans`], so they line up with the spans. With
+/// `preserve_case`, each replacement takes the case of the match (see [`match_case`]).
+fn replace(
+    line: &str,
+    spans: &[Range<usize>],
+    regex: &Regex,
+    columns: Option<ColumnRange>,
+    template: &str,
+    preserve_case: bool,
+) -> (String, Vec<Range<usize>>) {
+    let (start, end) = columns.map_or((0, line.len()), |columns| columns.byte_range(line));
+
+    let mut replaced = String::with_capacity(line.len());
+    let mut replaced_spans = Vec::with_capacity(spans.len());
+    let mut last = 0;
+
+    for captures in regex.captures_iter(&line[start..end]) {
+        let found = captures.get(0).map_or(0..0, |found| start + found.start()..start + found.end());
+
+        if !spans.contains(&found) {
+            continue; // Empty, or filtered out (like by --min-entropy)
+        }
+
+        replaced.push_str(&line[last..found.start]);
+
+        let replacement_start = replaced.len();
+        captures.expand(template, &mut replaced);
+
+        if preserve_case {
+            let replacement = match_case(&line[found.clone()], &replaced[replacement_start..]);
+            replaced.replace_range(replacement_start.., &replacement);
+        }
+
+        replaced_spans.push(replacement_start..replaced.len());
+
+        last = found.end;
+    }
+
+    replaced.push_str(&line[last..]);
+
+    (replaced, replaced_spans)
+}
+
+/// Text to replace each match with for `--redact`
+const REDACTED: &str = "****";
+
+/// `line` with every span replaced by `****`, and the spans of the replacements
+///
+/// Every match gets the same mask, so the length of a secret isn't given away either
+fn redact(line: &str, spans: &[Range<usize>]) -> (String, Vec<Range<usize>>) {
+    let mut redacted = String::with_capacity(line.len());
+    let mut redacted_spans = Vec::with_capacity(spans.len());
+    let mut last = 0;
+
+    for span in spans {
+        redacted.push_str(&line[last..span.start]);
+        redacted_spans.push(redacted.len()..redacted.len() + REDACTED.len());
+        redacted.push_str(REDACTED);
+        last = span.end;
+    }
+
+    redacted.push_str(&line[last..]);
+
+    (redacted, redacted_spans)
+}
+
+/// Whether file contents look binary, which is when they have a NUL byte
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0)
+}
+
+/// `bytes` split into records of `size` bytes, one per line
+fn records(bytes: &[u8], size: usize) -> Vec<u8> {
+    let mut lines = Vec::with_capacity(bytes.len() + bytes.len() / size + 1);
+
+    for record in bytes.chunks(size) {
+        let start = lines.len();
+        lines.extend_from_slice(record);
+        as_line(&mut lines[start..]);
+        lines.push(b'\n');
+    }
+
+    lines
+}
+
+/// A record made into one line, with the newlines and carriage returns in it searched as spaces
+/// (so it keeps its length)
+fn as_line(record: &mut [u8]) {
+    for byte in record.iter_mut().filter(|byte| matches!(byte, b'\n' | b'\r')) {
+        *byte = b' ';
+    }
+}
+
+/// Where `line` (a slice of `contents`) starts in it
+fn offset_in(contents: &str, line: &str) -> u64 {
+    (line.as_ptr().addr() - contents.as_ptr().addr()) as u64
+}
+
+/// Whether `encoding` is UTF-16, which is turned into UTF-8 whole rather than a line at a time
+fn is_utf16(encoding: Encoding) -> bool {
+    matches!(encoding, Encoding::Utf16le | Encoding::Utf16be)
+}
+
+/// Runs of at least `min_len` printable ASCII characters (and tabs), one per line, like the strings tool
+fn extract_strings(bytes: &[u8], min_len: u64) -> String {
+    let min_len = usize::try_from(min_len).unwrap_or(usize::MAX).max(1); // Default configs have 0
+
+    let mut strings = String::new();
+
+    for run in bytes.split(|&byte| !(byte.is_ascii_graphic() || byte == b' ' || byte == b'\t')) {
+        if run.len() >= min_len {
+            strings.extend(run.iter().map(|&byte| char::from(byte)));
+            strings.push('\n');
+        }
+    }
+
+    strings
+}
+
+/// Shannon entropy of `bytes` in bits per byte, from 0 (one repeated byte) to 8 (every byte value equally often)
+#[allow(clippy::cast_precision_loss)] // Only loses precision past 2^52 bytes
+fn entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0_usize; 256];
+
+    for &byte in bytes {
+        counts[usize::from(byte)] += 1;
+    }
+
+    let len = bytes.len() as f64;
+
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let probability = count as f64 / len;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+/// Byte ranges of the (non-empty) matches in raw file contents
+fn byte_spans(bytes: &[u8], bytes_regex: &regex::bytes::Regex) -> Vec<Range<usize>> {
+    bytes_regex
+        .find_iter(bytes)
+        .filter(|found| !found.is_empty())
+        .map(|found| found.range())
+        .collect()
+}
+
+/// Byte ranges of the (non-empty) matches in line, respecting `--columns`
+pub(crate) fn match_spans(line: &str, regex_query: &Regex, columns: Option<ColumnRange>) -> Vec<Range<usize>> {
+    let (start, end) = columns.map_or((0, line.len()), |columns| columns.byte_range(line));
+
+    regex_query
+        .find_iter(&line[start..end])
+        .filter(|found| !found.is_empty())
+        .map(|found| start + found.start()..start + found.end())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::SplitMix64;
+    use crate::temp::TempDir;
+    use crate::{build_regex, columns, Argument, StandardPrinter};
+    use std::num::NonZeroUsize;
+
+    /// Deterministic random numbers for property tests
+    struct Random(SplitMix64);
+
+    impl Random {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0.below(bound)
+        }
+
+        fn pick<'a>(&mut self, choices: &[&'a str]) -> &'a str {
+            choices[self.below(choices.len())]
+        }
+
+        /// Line of up to 12 pieces mixing ASCII and 2, 3 and 4-byte characters (and a combining accent)
+        fn line(&mut self) -> String {
+            const PIECES: &[&str] = &["a", "b", "k", " ", "-", "é", "ß", "ſ", "日本", "🦀", "\u{301}", "\u{212a}", "İ"];
+
+            (0..self.below(13)).map(|_| self.pick(PIECES)).collect()
+        }
+    }
+
+    /// Check the invariants of [`Searcher::match_spans`] for `spans` of `line`, inside `bounds`
+    /// (spans can be empty if `replaced`)
+    fn assert_span_invariants(line: &str, spans: &[Range<usize>], bounds: (usize, usize), replaced: bool, context: &str) {
+        for (index, span) in spans.iter().enumerate() {
+            assert!(!span.is_empty() || replaced, "empty span {span:?} in {line:?} ({context})");
+            assert!(
+                line.is_char_boundary(span.start) && line.is_char_boundary(span.end),
+                "span {span:?} splits a character of {line:?} ({context})"
+            );
+            assert!(span.start >= bounds.0 && span.end <= bounds.1, "span {span:?} outside {bounds:?} in {line:?} ({context})");
+
+            if let Some(next) = spans.get(index + 1) {
+                assert!(span.end <= next.start, "spans {span:?} and {next:?} overlap in {line:?} ({context})");
+            }
+        }
+    }
+
+    #[test]
+    fn test_match_spans_invariants() {
+        const PATTERNS: &[&str] = &["a", "b+", "é|日", ".", r"\w+", "k", "ß", "🦀.", "a*", r"\b", "[^a]", "(?:a|ab)", "\u{301}", ".?"];
+        let mut random = Random(SplitMix64::new(7));
+
+        for _ in 0..300 {
+            let pattern = random.pick(PATTERNS);
+            let columns = (random.below(3) == 0).then(|| columns::parse_column_range(&format!("{}-{}", random.below(4) + 1, random.below(6) + 4)).unwrap());
+
+            let config = Argument {
+                pattern: Some(String::from(pattern)),
+                insensitive: random.below(2) == 0,
+                word: random.below(4) == 0,
+                columns,
+                ..Default::default()
+            };
+            let context = format!("{pattern:?}, -i {}, -w {}, --columns {columns:?}", config.insensitive, config.word);
+            let searcher = Searcher::new(config).unwrap();
+
+            for _ in 0..50 {
+                let line = random.line();
+                let bounds = columns.map_or((0, line.len()), |columns| columns.byte_range(&line));
+
+                assert_span_invariants(&line, &searcher.match_spans(&line), bounds, false, &context);
+            }
+        }
+    }
+
+    #[test]
+    fn test_replaced_and_previewed_spans_invariants() {
+        let mut random = Random(SplitMix64::new(11));
+
+        let configs = [
+            Argument { pattern: Some(String::from(r"\w")), replace: Some(String::from("日$0")), ..Default::default() },
+            Argument { pattern: Some(String::from(".")), replace: Some(String::new()), ..Default::default() },
+            Argument { pattern: Some(String::from("[^ ]+")), redact: true, ..Default::default() },
+            Argument::try_from_args(["--replace-rule", "é=>e", "--replace-rule", "k|ß=>🦀", "a.txt"]).unwrap(),
+        ];
+
+        for config in configs {
+            let searcher = Searcher::new(config).unwrap();
+
+            for _ in 0..500 {
+                let contents = random.line().repeat(random.below(40) + 1);
+
+                for found in searcher.search_str(&contents) {
+                    let line = found.line();
+                    assert_span_invariants(line, found.spans(), (0, line.len()), true, "replaced");
+
+                    let (preview, spans) = crate::printer::truncate_matches(line, found.spans(), "…");
+                    assert_span_invariants(&preview, &spans, (0, preview.len()), true, "previewed");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_match_spans_counts_every_match() {
+        let regex_query = build_regex("the", true, false);
+
+        let result = match_spans("The cat sat on the mat by the door", &regex_query, None);
+
+        assert_eq!(result, vec![0..3, 15..18, 26..29]);
+    }
+
+    #[test]
+    fn test_match_spans_in_columns() {
+        let regex_query = build_regex("ab", false, false);
+        let columns = columns::parse_column_range("3-6").unwrap();
+
+        let result = match_spans("abababab", &regex_query, Some(columns));
+
+        assert_eq!(result, vec![2..4, 4..6]);
+    }
+
+    #[test]
+    fn test_match_spans_ignores_empty_matches() {
+        let regex_query = build_regex("x*", false, false);
+
+        let result = match_spans("axxbx", &regex_query, None);
+
+        assert_eq!(result, vec![1..3, 4..5]);
+    }
+
+    #[test]
+    fn test_searcher_search_str() {
+        let config = Argument {
+            pattern: Some(String::from("fox")),
+            ..Default::default()
+        };
+
+        let result = Searcher::new(config).unwrap().search_str("a fox\na dog\nfox and fox\n");
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[1].line_number(), 3);
+        assert_eq!(result[1].line(), "fox and fox");
+        assert_eq!(result[1].spans(), &[0..3, 8..11]);
+    }
+
+    #[test]
+    fn test_line_cache_gives_the_same_matches() {
+        let contents = "GET /a 200\nGET /b 500\nGET /a 200\n\nGET /b 500\nGET /a 200\n";
+
+        for columns in [None, Some(columns::parse_column_range("5-").unwrap())] {
+            let config = Argument { pattern: Some(String::from("/a|500")), columns, ..Default::default() };
+            let cached = Argument { line_cache: Some(1), ..config.clone() }; // Tiny, so entries get dropped
+
+            assert_eq!(Searcher::new(cached).unwrap().search_str(contents), Searcher::new(config).unwrap().search_str(contents));
+        }
+    }
+
+    #[test]
+    fn test_searcher_invalid_pattern() {
+        let config = Argument {
+            pattern: Some(String::from("(")),
+            ..Default::default()
+        };
+
+        assert!(matches!(Searcher::new(config), Err(Error::Pattern(_))));
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(is_binary(b"ELF\0\x01"));
+        assert!(!is_binary("plain text ✓\n".as_bytes()));
+    }
+
+    #[test]
+    fn test_replace_with_captures() {
+        let regex = build_regex(r"(?P<key>\w+)=(\d+)", false, false);
+        let line = "a=1 b=22 c";
+
+        let (replaced, spans) = replace(line, &match_spans(line, &regex, None), &regex, None, "$2:$key", false);
+
+        assert_eq!(replaced, "1:a 22:b c");
+        assert_eq!(spans, vec![0..3, 4..8]);
+    }
+
+    #[test]
+    fn test_pre() {
+        let temp = TempDir::new("pre");
+        let path = temp.write("a.txt", "fox\n");
+
+        let search = |args: &[&str]| {
+            let config = Argument::try_from_args(args.iter().copied().chain([path.as_str()])).unwrap();
+            Searcher::new(config).unwrap().search_file(&path)
+        };
+
+        let Searched { found, decoded, .. } = search(&["--pre", "base64", "Zm94"]);
+        let skipped = search(&["--pre", "base64", "--pre-glob", "*.md", "Zm94"]).found;
+        let failed = search(&["--pre", "false", "fox"]).found;
+
+        assert!(matches!(found, Ok(Found::Lines(matches, false)) if matches.len() == 1 && matches[0].line() == "Zm94Cg=="));
+        assert_eq!(decoded, vec!["base64"]);
+        assert!(matches!(skipped, Ok(Found::Lines(matches, false)) if matches.is_empty())); // Searched as it is, since the glob doesn't match
+        assert!(matches!(failed, Err(Error::Io { source, .. }) if source.to_string() == "false failed (exit status: 1)"));
+    }
+
+    #[test]
+    fn test_search_zip() {
+        let temp = TempDir::new("search-zip");
+        let path = temp.write("a.log.gz", b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\x4b\xc9\x4f\xe7\x2a\x2c\xcd\x4c\xce\x56\x48\xcb\xaf\xe0\x02\x00\xf6\xc0\x6e\xfc\x0e\x00\x00\x00");
+
+        let searcher = Searcher::new(Argument::try_from_args(["--search-zip", "fox", &path]).unwrap()).unwrap();
+        let Searched { found, decoded, .. } = searcher.search_file(&path);
+
+        let compressed = Searcher::new(Argument::try_from_args(["fox", &path]).unwrap()).unwrap().search_file(&path).found;
+
+        assert!(matches!(&found, Ok(Found::Lines(matches, false)) if matches.len() == 1 && matches[0].line() == "quick fox"));
+        assert!(matches!(compressed, Ok(Found::Lines(matches, true)) if matches.is_empty())); // Binary without --search-zip
+        assert_eq!(decoded, vec!["gzip"]);
+    }
+
+    #[test]
+    fn test_search_zip_corrupt_file() {
+        let temp = TempDir::new("search-zip");
+        let path = temp.write("a.txt.gz", "not gzip at all\n");
+
+        let searcher = Searcher::new(Argument::try_from_args(["--search-zip", "gzip", &path]).unwrap()).unwrap();
+        let found = searcher.search_file(&path).found;
+
+        assert!(matches!(found, Err(Error::Io { .. })));
+    }
+
+    #[test]
+    fn test_replace_preserve_case() {
+        let config = Argument::try_from_args(["-i", "--replace", "bar", "--preserve-case", "foo", "a.txt"]).unwrap();
+
+        let found = Searcher::new(config).unwrap().search_str("foo(Foo, FOO)\n");
+
+        assert_eq!(found[0].line(), "bar(Bar, BAR)");
+        assert_eq!(found[0].spans(), &[0..3, 4..7, 9..12]);
+    }
+
+    #[test]
+    fn test_replace_only_kept_spans_in_columns() {
+        let regex = build_regex("ab", false, false);
+        let columns = columns::parse_column_range("3-").unwrap();
+
+        let kept = 4..6; // As if 2..4 had been filtered out
+
+        let (replaced, _) = replace("ababab", std::slice::from_ref(&kept), &regex, Some(columns), "X", false);
+
+        assert_eq!(replaced, "ababX");
+    }
+
+    #[test]
+    fn test_searcher_applies_replace_rules() {
+        let config = Argument::try_from_args(["-i", "--replace-rule", "cat=>dog", "--replace-rule", "dog=>cat", "a.txt"]).unwrap();
+
+        let result = Searcher::new(config).unwrap().search_str("Cat chases dog
+nothing
+");
+
+        assert_eq!(result.len(), 1); // The rules are the pattern
+        assert_eq!(result[0].line(), "dog chases cat");
+        assert_eq!(result[0].spans(), &[0..3, 11..14]);
+
+        // Both naming a group `x`
+        let config = Argument::try_from_args(["--replace-rule", r"old_(?P<x>\w+)=>new_$x", "--replace-rule", r"(?P<x>\d+)px=>${x}rem", "a.txt"]).unwrap();
+        let result = Searcher::new(config).unwrap().search_str("old_api 12px\n");
+
+        assert_eq!(result[0].line(), "new_api 12rem");
+    }
+
+    #[test]
+    fn test_redact() {
+        let (line, spans) = redact("api_key=hunter2 token=ab", &[8..15, 22..24]);
+
+        assert_eq!(line, "api_key=**** token=****");
+        assert_eq!(spans, vec![8..12, 19..23]);
+    }
+
+    #[test]
+    fn test_searcher_redacts_matches() {
+        let config = Argument { pattern: Some(String::from("hunter[0-9]")), redact: true, ..Default::default() };
+
+        let result = Searcher::new(config).unwrap().search_str("api_key=hunter2\nnothing\n");
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].line(), "api_key=****");
+        assert_eq!(result[0].spans()[0], 8..12);
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert!(entropy(b"aaaa").abs() < f64::EPSILON);
+        assert!((entropy(b"abab") - 1.0).abs() < f64::EPSILON);
+        assert!((entropy(b"0123456789abcdef") - 4.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_min_entropy_filters_matches() {
+        let config = Argument {
+            pattern: Some(String::from(r"secret=\S+")),
+            min_entropy: Some(3.5),
+            ..Default::default()
+        };
+
+        let result = Searcher::new(config).unwrap().search_str("secret=secret\nsecret=Zm9vYmFyLXRva2Vu\n");
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].line_number(), 2);
+    }
+
+    #[test]
+    fn test_extract_strings() {
+        let bytes = b"\x7fELF\x00\x01ab\x00password=hunter2\x00\xff\tkey val\n";
+
+        assert_eq!(extract_strings(bytes, 4), "password=hunter2\n\tkey val\n");
+        assert_eq!(extract_strings(bytes, 2), "ELF\nab\npassword=hunter2\n\tkey val\n");
+    }
+
+    #[test]
+    fn test_strings_searches_binary_files() {
+        let temp = TempDir::new("strings");
+        let path = temp.write("a.bin", b"\x00\x01\x02password=hunter2\x00\x03");
+
+        let config = Argument { pattern: Some(String::from("password")), strings: true, strings_min: 4, ..Default::default() };
+        let result = Searcher::new(config).unwrap().search_path(&path);
+
+        assert_eq!(result.unwrap()[0].line(), "password=hunter2");
+    }
+
+    #[test]
+    fn test_raw_reads_bytes_as_latin1() {
+        let temp = TempDir::new("raw");
+        let path = temp.write("a.bin", b"\xff\0key\nother");
+
+        let config = Argument { pattern: Some(String::from(r"^\xFF\x00key$")), raw: true, ..Default::default() };
+        let result = Searcher::new(config).unwrap().search_path(&path);
+
+        assert_eq!(result.unwrap().len(), 1);
+    }
+
+    /// What reading `bytes` whole finds, and what streaming them finds
+    fn whole_and_streamed(config: Argument, bytes: &[u8]) -> (Result<Found, Error>, Result<Found, Error>) {
+        let searcher = Searcher::new(config).unwrap();
+        let encoding = searcher.file_encoding("a.txt", bytes);
+        let streamed = searcher.stream("a.txt", std::io::Cursor::new(bytes), encoding);
+
+        assert_eq!(streamed.read, Some(bytes.len() as u64));
+
+        (Ok(searcher.find("a.txt", bytes.to_vec(), encoding).0), streamed.found)
+    }
+
+    #[test]
+    fn test_min_matches_and_density() {
+        let config = || Argument { pattern: Some(String::from("o")), ..Default::default() };
+        let contents = b"oo\nx\nxo\nx\n"; // 2 of 4 lines match, with 3 matches
+
+        let found = |config: Argument| {
+            let (whole, streamed) = whole_and_streamed(config, contents);
+            let found = matches!(whole.unwrap(), Found::Lines(..));
+
+            assert_eq!(found, matches!(streamed.unwrap(), Found::Lines(..)));
+            found
+        };
+
+        assert!(found(Argument { min_matches: Some(2), ..config() }));
+        assert!(!found(Argument { min_matches: Some(3), ..config() }));
+        assert!(found(Argument { min_matches: Some(3), count_matches: true, ..config() }));
+        assert!(found(Argument { min_density: Some(50.0), ..config() }));
+        assert!(!found(Argument { min_density: Some(51.0), ..config() }));
+    }
+
+    #[test]
+    fn test_stream_matches_reading_whole() {
+        let config = || Argument { pattern: Some(String::from("o")), ..Default::default() };
+        let columns = Argument { columns: columns::parse_column_range("2-").ok(), ..config() };
+
+        for (config, bytes) in [
+            (config(), &b"one\r\ntwo\nthree\nfour"[..]),
+            (Argument { binary_files: BinaryFiles::Binary, ..config() }, b"one\ntwo\0\nthree\n"),
+            (Argument { replace: Some(String::from("0")), ..config() }, b"foo\nbar\n"),
+            (columns, b"oops\nno\n"),
+            (config(), b"\xEF\xBB\xBFone\ntwo\n"),
+            (Argument { encoding: Encoding::Latin1, ..config() }, b"caf\xe9 o\n"),
+            (Argument { record_size: Some(4), ..config() }, b"one\none\rtwo\r\n\xFF\xFEno"),
+        ] {
+            let (whole, streamed) = whole_and_streamed(config, bytes);
+
+            match (whole.unwrap(), streamed.unwrap()) {
+                (Found::Lines(whole, whole_binary), Found::Lines(streamed, streamed_binary)) => {
+                    assert_eq!(whole, streamed);
+                    assert_eq!(whole_binary, streamed_binary);
+                }
+                _ => panic!("expected lines"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_byte_offsets() {
+        let searcher = Searcher::new(Argument { pattern: Some(String::from("o")), ..Default::default() }).unwrap();
+        let matches = searcher.search_str("one\r\nsix\nfoo bar\n\ntwo");
+
+        let offsets: Vec<(usize, u64)> = matches.iter().map(|found| (found.line_number(), found.byte_offset())).collect();
+        assert_eq!(offsets, vec![(1, 0), (3, 9), (5, 18)]);
+    }
+
+    #[test]
+    fn test_byte_offsets_in_the_file() {
+        let offsets = |config: Argument, bytes: &[u8]| {
+            let offsets = |found| {
+                let Ok(Found::Lines(matches, _)) = found else { panic!("expected lines") };
+                matches.iter().map(Match::byte_offset).collect::<Vec<u64>>()
+            };
+
+            let (whole, streamed) = whole_and_streamed(config, bytes);
+            let whole = offsets(whole);
+            assert_eq!(offsets(streamed), whole);
+
+            whole
+        };
+        let config = |encoding| Argument { pattern: Some(String::from("o")), encoding, ..Default::default() };
+
+        // Neither invalid UTF-8 (replaced while searching) nor a byte order mark moves the lines after them
+        assert_eq!(offsets(config(Encoding::Utf8), b"a\xffo\nfox\n"), vec![0, 4]);
+        assert_eq!(offsets(config(Encoding::Utf8), b"\xef\xbb\xbfone\ntwo\n"), vec![0, 7]);
+        assert_eq!(offsets(Argument { raw: true, ..config(Encoding::Auto) }, b"\xe9o\nfox\n"), vec![0, 3]);
+
+        // Decoded files count in the text searched
+        assert_eq!(offsets(config(Encoding::Latin1), b"\xe9o\nfox\n"), vec![0, 4]);
+
+        // The same through the library
+        let temp = TempDir::new("offsets");
+        let path = temp.write("a.txt", b"\xef\xbb\xbfone\ntwo\n");
+        let found = search_file(&Searcher::new(config(Encoding::Utf8)).unwrap(), &path).unwrap();
+
+        assert_eq!(found.iter().map(LineMatch::byte_offset).collect::<Vec<u64>>(), vec![0, 7]);
+    }
+
+    #[test]
+    fn test_record_size() {
+        let config = Argument { pattern: Some(String::from("^B.*9$")), record_size: Some(6), ..Default::default() };
+        let (found, _) = whole_and_streamed(config, b"A00001B0\n009B00019C00001B9");
+
+        let Ok(Found::Lines(matches, false)) = found else { panic!("expected lines") };
+        let records: Vec<(usize, &str)> = matches.iter().map(|found| (found.line_number(), found.line())).collect();
+
+        assert_eq!(records, vec![(2, "B0 009"), (3, "B00019"), (5, "B9")]); // The short last record counts too
+    }
+
+    #[test]
+    fn test_stream_decides_at_the_end() {
+        let config = || Argument { pattern: Some(String::from("o")), ..Default::default() };
+
+        // Invalid UTF-8 is text unless a NUL turns up later
+        let (_, invalid) = whole_and_streamed(config(), b"caf\xe9 o\nok\n");
+        let (_, binary) = whole_and_streamed(config(), b"caf\xe9 o\nok\0\n");
+
+        assert!(matches!(invalid, Ok(Found::Lines(matches, false)) if matches.len() == 2));
+        assert!(matches!(binary, Ok(Found::Lines(matches, true)) if matches.len() == 2));
+    }
+
+    #[test]
+    fn test_utf16_files() {
+        let temp = TempDir::new("utf16");
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("café ok\r\nerror\r\nok 🦀\r\n".encode_utf16().flat_map(u16::to_le_bytes));
+        let path = temp.write("a.log", bytes);
+
+        let searcher = Searcher::new(Argument { pattern: Some(String::from("ok")), ..Default::default() }).unwrap();
+
+        let result = searcher.search_path(&path);
+
+        let lines: Vec<(usize, String)> = result.unwrap().iter().map(|found| (found.line_number(), found.line().to_string())).collect();
+        assert_eq!(lines, vec![(1, String::from("café ok")), (3, String::from("ok 🦀"))]);
+    }
+
+    #[test]
+    #[cfg(feature = "guess-encoding")]
+    fn test_guessed_encoding() {
+        let searcher = Searcher::new(Argument { pattern: Some(String::from("déjà")), ..Default::default() }).unwrap();
+        let Searched { found, decoded, .. } = searcher.search_bytes("a.txt", b"caf\xe9 cr\xe8me\nd\xe9j\xe0 vu, na\xefve fa\xe7ade \xe0 la cr\xe9ation\n".to_vec());
+
+        assert!(matches!(found, Ok(Found::Lines(matches, false)) if matches.len() == 1 && matches[0].line_number() == 2));
+        assert_eq!(decoded, vec!["Latin-1"]);
+
+        // Not sure enough, so searched as UTF-8 like before
+        assert!(searcher.search_bytes("a.txt", b"\x93d\xe9j\xe0\x94\n".to_vec()).decoded.is_empty());
+    }
+
+    #[test]
+    fn test_decoding() {
+        let searcher = |encoding| Searcher::new(Argument { pattern: Some(String::from("x")), encoding, ..Default::default() }).unwrap();
+
+        assert_eq!(searcher(Encoding::Auto).search_bytes("a.txt", b"\xFF\xFEx\0\n\0".to_vec()).decoded, vec!["UTF-16LE"]);
+        assert_eq!(searcher(Encoding::Latin1).search_bytes("a.txt", b"caf\xe9 x\n".to_vec()).decoded, vec!["Latin-1"]);
+        assert!(searcher(Encoding::Auto).search_bytes("a.txt", b"x\n".to_vec()).decoded.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_replaced() {
+        let temp = TempDir::new("latin1");
+        let path = temp.write("a.log", b"caf\xe9 ok\nplain ok\n");
+
+        let searcher = Searcher::new(Argument { pattern: Some(String::from("ok")), ..Default::default() }).unwrap();
+
+        let result = searcher.search_path(&path);
+
+        let lines: Vec<String> = result.unwrap().iter().map(|found| found.line().to_string()).collect();
+        assert_eq!(lines, vec!["caf\u{fffd} ok", "plain ok"]);
+    }
+
+    /// Printer keeping the progress it was given
+    #[derive(Default)]
+    struct ProgressPrinter(Vec<Progress>);
+
+    impl Printer for ProgressPrinter {
+        fn file(&mut self, _path: &str, _matches: &[Match]) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn progress(&mut self, progress: &Progress) -> Result<(), Error> {
+            self.0.push(*progress);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_run_in_parallel_keeps_order() {
+        let output = |threads| {
+            let config = Argument {
+                pattern: Some(String::from("fn")),
+                files: vec![String::from("./src"), String::from("./tests")],
+                recursive: true,
+                threads: NonZeroUsize::new(threads),
+                ..Default::default()
+            };
+            let mut printer = StandardPrinter::new(&config, Vec::new());
+
+            Searcher::new(config).unwrap().run(&mut printer).unwrap();
+
+            String::from_utf8(printer.into_inner()).unwrap()
+        };
+
+        let sequential = output(1);
+
+        assert!(!sequential.is_empty());
+        assert_eq!(output(8), sequential);
+    }
+
+    #[test]
+    fn test_window() {
+        let queues = [vec![0, 2, 4], vec![1, 3]];
+        let window = Window::new(&queues, 2);
+
+        assert_eq!((window.take(1), window.take(1)), (Some(1), Some(0)));
+
+        thread::scope(|scope| {
+            let waiting = scope.spawn(|| window.take(0));
+
+            window.printed(1); // Lets the file at 2 in
+            assert_eq!(waiting.join().unwrap(), Some(2));
+
+            let waiting = scope.spawn(|| window.take(1));
+
+            window.stop();
+            assert_eq!(waiting.join().unwrap(), None);
+        });
+
+        // Every file is handed out once, then none
+        let window = Window::new(&queues, 10);
+        let mut taken: Vec<usize> = std::iter::from_fn(|| window.take(0)).collect();
+        taken.sort_unstable();
+
+        assert_eq!(taken, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_run_search_archives() {
+        let temp = TempDir::new("archive");
+        let path = temp.join("a.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&path).unwrap());
+
+        for (name, contents) in [("a.txt", "dog\n"), ("docs/b.txt", "quick fox\n")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, contents.as_bytes()).unwrap();
+        }
+
+        builder.finish().unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let config = Argument::try_from_args(["--search-archives", "fox", &path]).unwrap();
+        let mut printer = StandardPrinter::new(&config, Vec::new());
+        Searcher::new(config).unwrap().run(&mut printer).unwrap();
+
+        assert_eq!(String::from_utf8(printer.into_inner()).unwrap(), format!("{path}!docs/b.txt: quick fox\n"));
+    }
+
+    #[test]
+    fn test_with_header_line() {
+        let temp = TempDir::new("header");
+        let path = temp.write("a.csv", "name,age\nann,30\nbob,41\n");
+
+        let config = Argument::try_from_args(["--with-header-line", "1", "-H", "bob", &path, "./tests/test_2.txt"]).unwrap();
+        let searcher = Searcher::new(config.clone()).unwrap();
+
+        // Read whole or a line at a time, the same first lines
+        let contents = fs::read(&path).unwrap();
+        let whole = searcher.search_bytes(&path, contents.clone());
+        let streamed = searcher.stream(&path, std::io::Cursor::new(contents), Encoding::Utf8);
+        assert_eq!(whole.header, vec![Match::new(1, 0, String::from("name,age"), Vec::new())]);
+        assert_eq!(streamed.header, whole.header);
+
+        // Only printed for files with matches
+        let mut printer = StandardPrinter::new(&config, Vec::new());
+        searcher.run(&mut printer).unwrap();
+
+        assert_eq!(String::from_utf8(printer.into_inner()).unwrap(), format!("{path}- name,age\n{path}: bob,41\n"));
+
+        // Once, as the header, when it matches too
+        let config = Argument::try_from_args(["--with-header-line", "1", "-e", "name", "-e", "bob", &path]).unwrap();
+        let mut printer = StandardPrinter::new(&config, Vec::new());
+        Searcher::new(config).unwrap().run(&mut printer).unwrap();
+
+        assert_eq!(String::from_utf8(printer.into_inner()).unwrap(), "name,age\nbob,41\n");
+    }
+
+    #[test]
+    fn test_column_names() {
+        let temp = TempDir::new("column-names");
+        let path = temp.write("a.tsv", "name\tage\nann\t30\nbob\t41\n");
+
+        let config = Argument::try_from_args(["--column-names", "-e", "name", "-e", "41", &path]).unwrap();
+        let mut printer = StandardPrinter::new(&config, Vec::new());
+        Searcher::new(config).unwrap().run(&mut printer).unwrap();
+
+        // The header row matching too is still only printed once
+        assert_eq!(String::from_utf8(printer.into_inner()).unwrap(), "name\tage\n[age] bob\t41\n");
+    }
+
+    #[test]
+    fn test_run_stops_at_timeout() {
+        let config = Argument {
+            pattern: Some(String::from("fox")),
+            files: vec![String::from("./tests/test_2.txt")],
+            timeout: Some(Duration::ZERO),
+            no_messages: true,
+            ..Default::default()
+        };
+        let mut printer = ProgressPrinter(Vec::new());
+
+        Searcher::new(config).unwrap().run(&mut printer).unwrap();
+
+        assert_eq!(printer.0.last().unwrap().files(), 0);
+    }
+
+    #[test]
+    fn test_run_reports_progress_totals() {
+        let files = vec![String::from("./tests/test_2.txt"), String::from("./Cargo.toml")];
+        let bytes: u64 = files.iter().map(|file| fs::metadata(file).unwrap().len()).sum();
+
+        let searcher = Searcher::new(Argument { pattern: Some(String::from("x")), files, ..Default::default() }).unwrap();
+        let mut printer = ProgressPrinter::default();
+        searcher.run(&mut printer).unwrap();
+
+        let last = printer.0.last().unwrap();
+        assert_eq!((last.files(), last.bytes()), (2, bytes));
+    }
+
+    #[test]
+    fn test_searcher_missing_file() {
+        let searcher = Searcher::new(Argument::default()).unwrap();
+
+        assert!(matches!(searcher.search_path("nonexistent_file.nonsense"), Err(Error::Io { .. })));
+    }
+}
@@ -0,0 +1,489 @@
+//! Searching over HTTP (`simple-grep serve`)
+//!
+//! `GET /search?q=PATTERN` searches the paths given to `serve`, with the options given to it, for
+//! PATTERN and answers with the results as JSON Lines, like `--json`. Requests are read on a
+//! thread each, so a slow client doesn't hold up the others, but answered one at a time (each
+//! search uses every thread anyway), and connections are closed after each one.
+//!
+//! With `-r`, the files found by walking the paths are kept for the next search, and only found
+//! again once a directory changed (like `--watch` and `--cache-file-list` do).
+//!
+//! `GET /metrics` reports the searches answered, how long they took, the bytes searched and how
+//! often the files found were reused, in the Prometheus text format.
+
+// Standard library
+use std::error::Error; // For reporting errors
+use std::fmt::Write as _; // For writing metrics
+use std::io::{self, BufRead, BufReader, Read, Take, Write}; // For reading requests and writing responses
+use std::net::{TcpListener, TcpStream}; // For the server
+use std::sync::mpsc; // For handing requests to the thread answering them
+use std::thread; // For reading requests
+use std::time::{Duration, Instant}; // For timeouts and latency
+
+// My stuff
+use crate::file_list::Walk;
+use crate::i18n::fill;
+use crate::json_output::JsonPrinter;
+use crate::search::Searcher;
+use crate::stats::{Stats, StatsPrinter};
+use crate::{sized_and_sorted, Argument, ServeArgs};
+
+/// How long a client gets to send its whole request (or to read each part of the response)
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most bytes of the request line and headers read, so a client can't make them grow forever
+const MAX_HEAD: u64 = 8 << 10;
+
+/// Latency histogram buckets: their label, and the longest search in them
+const LATENCY_BUCKETS: [(&str, Duration); 9] = [
+    ("0.001", Duration::from_millis(1)),
+    ("0.005", Duration::from_millis(5)),
+    ("0.01", Duration::from_millis(10)),
+    ("0.05", Duration::from_millis(50)),
+    ("0.1", Duration::from_millis(100)),
+    ("0.5", Duration::from_millis(500)),
+    ("1", Duration::from_secs(1)),
+    ("5", Duration::from_secs(5)),
+    ("10", Duration::from_secs(10)),
+];
+
+/// What `/metrics` reports, since the server started
+#[derive(Debug, Default)]
+struct Metrics {
+    /// Searches answered with results
+    searches: u64,
+    /// How many of them took at most each bucket's time (but more than the one before), and
+    /// longer than all of them last
+    latency: [u64; LATENCY_BUCKETS.len() + 1],
+    latency_sum: Duration,
+    bytes: u64,
+    /// Searches with `-r` that reused the files found by the last walk
+    cache_hits: u64,
+    /// And ones that walked again
+    cache_misses: u64,
+}
+
+impl Metrics {
+    fn searched(&mut self, took: Duration, bytes: u64) {
+        let bucket = LATENCY_BUCKETS.iter().position(|(_, longest)| took <= *longest).unwrap_or(LATENCY_BUCKETS.len());
+
+        self.searches += 1;
+        self.latency[bucket] += 1;
+        self.latency_sum += took;
+        self.bytes += bytes;
+    }
+
+    /// The metrics in the Prometheus text format
+    fn text(&self) -> String {
+        let mut text = String::new();
+        let lookups = self.cache_hits + self.cache_misses;
+        #[allow(clippy::cast_precision_loss)] // Counts that big don't need every digit
+        let ratio = if lookups == 0 { f64::NAN } else { self.cache_hits as f64 / lookups as f64 };
+
+        // Writing to a String can't fail
+        let _ = writeln!(text, "# HELP simple_grep_searches_total Searches answered with results.");
+        let _ = writeln!(text, "# TYPE simple_grep_searches_total counter");
+        let _ = writeln!(text, "simple_grep_searches_total {}", self.searches);
+
+        let _ = writeln!(text, "# HELP simple_grep_search_duration_seconds How long searches took.");
+        let _ = writeln!(text, "# TYPE simple_grep_search_duration_seconds histogram");
+        let mut cumulative = 0;
+
+        for ((label, _), count) in LATENCY_BUCKETS.iter().zip(self.latency) {
+            cumulative += count;
+            let _ = writeln!(text, "simple_grep_search_duration_seconds_bucket{{le=\"{label}\"}} {cumulative}");
+        }
+
+        let _ = writeln!(text, "simple_grep_search_duration_seconds_bucket{{le=\"+Inf\"}} {}", self.searches);
+        let _ = writeln!(text, "simple_grep_search_duration_seconds_sum {}", self.latency_sum.as_secs_f64());
+        let _ = writeln!(text, "simple_grep_search_duration_seconds_count {}", self.searches);
+
+        let _ = writeln!(text, "# HELP simple_grep_scanned_bytes_total Bytes of files searched.");
+        let _ = writeln!(text, "# TYPE simple_grep_scanned_bytes_total counter");
+        let _ = writeln!(text, "simple_grep_scanned_bytes_total {}", self.bytes);
+
+        let _ = writeln!(text, "# HELP simple_grep_file_list_cache_hits_total Recursive searches that reused the files found by the last walk.");
+        let _ = writeln!(text, "# TYPE simple_grep_file_list_cache_hits_total counter");
+        let _ = writeln!(text, "simple_grep_file_list_cache_hits_total {}", self.cache_hits);
+        let _ = writeln!(text, "# HELP simple_grep_file_list_cache_lookups_total Recursive searches.");
+        let _ = writeln!(text, "# TYPE simple_grep_file_list_cache_lookups_total counter");
+        let _ = writeln!(text, "simple_grep_file_list_cache_lookups_total {lookups}");
+        let _ = writeln!(text, "# HELP simple_grep_file_list_cache_hit_ratio Share of recursive searches that reused the files found.");
+        let _ = writeln!(text, "# TYPE simple_grep_file_list_cache_hit_ratio gauge");
+        let _ = writeln!(text, "simple_grep_file_list_cache_hit_ratio {ratio}");
+
+        text
+    }
+}
+
+/// The paths and options searched, and what's kept between searches
+#[derive(Debug)]
+struct Server {
+    config: Argument,
+    /// The files found by the last walk of the paths, with `-r`
+    walk: Option<Walk>,
+    metrics: Metrics,
+}
+
+/// A response to send back
+#[derive(Debug, PartialEq)]
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn text(status: &'static str, message: &str) -> Self {
+        Response { status, content_type: "text/plain; charset=utf-8", body: format!("{message}\n").into_bytes() }
+    }
+}
+
+/// Answer searches over HTTP on `--listen` until killed
+///
+/// Positional arguments are all paths, since the pattern comes with each request.
+///
+/// # Errors
+///
+/// Will error if no path was given or the address can't be listened on
+pub fn serve(args: &ServeArgs) -> Result<(), Box<dyn Error>> {
+    let mut config = args.search.clone();
+    let catalog = config.lang().catalog();
+
+    if let Some(path) = config.pattern.take() {
+        config.files.insert(0, path);
+    }
+
+    if config.files.is_empty() {
+        return Err(catalog.no_path.into());
+    }
+
+    let listener = TcpListener::bind(&args.listen)?;
+    eprintln!("{}", fill(catalog.serving, &[("address", &listener.local_addr()?.to_string())]));
+
+    let mut server = Server::new(config);
+    let (sender, requests) = mpsc::channel();
+
+    // A client that went away (or took too long) doesn't stop the server
+    thread::spawn(move || {
+        for (stream, request) in requests {
+            let _ = server.answer(&stream, request);
+        }
+    });
+
+    for stream in listener.incoming().flatten() {
+        let sender = sender.clone();
+
+        thread::spawn(move || {
+            if let Ok(request) = read_request(&stream) {
+                let _ = sender.send((stream, request));
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Reads a stream until a deadline, so a client gets that long for all of its request instead
+/// of for each read
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        self.stream.set_read_timeout(Some(left))?;
+        (&mut &*self.stream).read(buf)
+    }
+}
+
+/// The request line of the request on `stream`, or the response for one that's too big
+///
+/// # Errors
+///
+/// Will error if the client took longer than [`TIMEOUT`] or the connection failed, which isn't
+/// worth answering
+fn read_request(stream: &TcpStream) -> io::Result<Result<String, Response>> {
+    let deadline = Deadline { stream, deadline: Instant::now() + TIMEOUT };
+
+    read_head(&mut BufReader::new(deadline.take(MAX_HEAD)))
+}
+
+/// The request line of what `reader` reads, after reading the headers, or the response for a
+/// request line (400) or headers (431) that don't fit in what's left of its limit
+fn read_head<R: Read>(reader: &mut BufReader<Take<R>>) -> io::Result<Result<String, Response>> {
+    // A line without its newline was cut off by the limit, unless the client stopped sending
+    let cut_off = |reader: &BufReader<Take<R>>, line: &str| !line.ends_with('\n') && reader.get_ref().limit() == 0;
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    if cut_off(reader, &request_line) {
+        return Ok(Err(Response::text("400 Bad Request", "request line too long")));
+    }
+
+    // The headers don't change anything, but are read so the client isn't cut off mid-request
+    let mut header = String::new();
+
+    while reader.read_line(&mut header)? > 0 {
+        if cut_off(reader, &header) {
+            return Ok(Err(Response::text("431 Request Header Fields Too Large", "headers too long")));
+        }
+
+        if header.trim_end().is_empty() {
+            break;
+        }
+
+        header.clear();
+    }
+
+    Ok(Ok(request_line))
+}
+
+impl Server {
+    fn new(config: Argument) -> Self {
+        Server { config, walk: None, metrics: Metrics::default() }
+    }
+
+    /// Write the response to `request` (read by [`read_request`]) to `stream`
+    fn answer(&mut self, stream: &TcpStream, request: Result<String, Response>) -> io::Result<()> {
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        let response = match request {
+            Ok(request_line) => self.respond(request_line.trim_end()),
+            Err(too_big) => too_big,
+        };
+        let mut writer = stream;
+
+        write!(
+            writer,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.status,
+            response.content_type,
+            response.body.len(),
+        )?;
+        writer.write_all(&response.body)?;
+        writer.flush()
+    }
+
+    /// The response to a request line like `GET /search?q=fox HTTP/1.1`
+    fn respond(&mut self, request_line: &str) -> Response {
+        let mut words = request_line.split(' ');
+        let (Some(method), Some(target)) = (words.next(), words.next()) else {
+            return Response::text("400 Bad Request", "not an HTTP request");
+        };
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+        if path != "/search" && path != "/metrics" {
+            return Response::text("404 Not Found", "not found (searches are at /search?q=PATTERN)");
+        }
+
+        if method != "GET" {
+            return Response::text("405 Method Not Allowed", "only GET requests are answered");
+        }
+
+        if path == "/metrics" {
+            return Response { status: "200 OK", content_type: "text/plain; version=0.0.4", body: self.metrics.text().into_bytes() };
+        }
+
+        let pattern = query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(name, _)| *name == "q")
+            .and_then(|(_, value)| decode(value));
+
+        match pattern {
+            Some(pattern) => self.search(pattern),
+            None => Response::text("400 Bad Request", "give the pattern as q, like /search?q=PATTERN"),
+        }
+    }
+
+    /// Search the served paths for `pattern`, as JSON Lines
+    fn search(&mut self, pattern: String) -> Response {
+        let start = Instant::now();
+        let mut config = self.config.clone();
+        config.regexp = vec![pattern];
+
+        if let Err(e) = config.normalize() {
+            return Response::text("400 Bad Request", &e.to_string());
+        }
+
+        if config.recursive {
+            let files = match &mut self.walk {
+                Some(walk) if !walk.is_stale() => {
+                    self.metrics.cache_hits += 1;
+                    walk.files.clone()
+                }
+                walk => {
+                    self.metrics.cache_misses += 1;
+                    walk.insert(Walk::new(&config)).files.clone()
+                }
+            };
+
+            config.replay_files = Some(sized_and_sorted(&config, files));
+        }
+
+        let mut body = Vec::new();
+        let mut stats = Stats::default();
+        let searched = Searcher::new(config.clone()).and_then(|searcher| {
+            searcher.run(&mut StatsPrinter::new(Box::new(JsonPrinter::new(&config, &mut body)), &mut stats))
+        });
+
+        match searched {
+            Ok(()) => {
+                self.metrics.searched(start.elapsed(), stats.bytes());
+                Response { status: "200 OK", content_type: "application/x-ndjson", body }
+            }
+            Err(e @ crate::error::Error::Pattern(_)) => Response::text("400 Bad Request", &e.to_string()),
+            Err(e) => Response::text("500 Internal Server Error", &e.to_string()),
+        }
+    }
+}
+
+/// A query string value with its `%XX` escapes and `+`s (spaces) decoded, if it's UTF-8
+fn decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+
+    while let Some((&byte, after)) = rest.split_first() {
+        match byte {
+            b'%' => {
+                let hex = std::str::from_utf8(after.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &after[2..];
+            }
+            b'+' => {
+                bytes.push(b' ');
+                rest = after;
+            }
+            byte => {
+                bytes.push(byte);
+                rest = after;
+            }
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("quick+fox"), Some(String::from("quick fox")));
+        assert_eq!(decode("a%2Bb%20%C3%A9"), Some(String::from("a+b é")));
+        assert_eq!(decode("%zz"), None);
+        assert_eq!(decode("%2"), None);
+        assert_eq!(decode("%ff"), None); // Not UTF-8
+    }
+
+    #[test]
+    fn test_respond() {
+        let dir = TempDir::new("serve");
+        dir.write("a.txt", "the quick fox\nthe lazy dog\n");
+        let mut server = Server::new(Argument::try_from_args(["-r", "-e", "unused", &dir.display()]).unwrap());
+
+        let found = server.respond("GET /search?q=quick+f.x HTTP/1.1");
+        assert_eq!((found.status, found.content_type), ("200 OK", "application/x-ndjson"));
+        let body = String::from_utf8(found.body).unwrap();
+        assert!(body.contains(r#""type":"match""#) && body.contains("the quick fox") && !body.contains("lazy"));
+
+        assert_eq!(server.respond("GET /search?q=%28 HTTP/1.1").status, "400 Bad Request"); // Invalid pattern
+        assert_eq!(server.respond("GET /search HTTP/1.1").status, "400 Bad Request");
+        assert_eq!(server.respond("POST /search?q=fox HTTP/1.1").status, "405 Method Not Allowed");
+        assert_eq!(server.respond("GET / HTTP/1.1").status, "404 Not Found");
+        assert_eq!(server.respond("").status, "400 Bad Request");
+    }
+
+    #[test]
+    fn test_metrics_text() {
+        let mut metrics = Metrics { cache_hits: 1, cache_misses: 3, ..Metrics::default() };
+        metrics.searched(Duration::from_millis(3), 100);
+        metrics.searched(Duration::from_millis(70), 20);
+        metrics.searched(Duration::from_secs(20), 0);
+
+        let text = metrics.text();
+
+        assert!(text.contains("\nsimple_grep_searches_total 3\n"));
+        assert!(text.contains("\nsimple_grep_search_duration_seconds_bucket{le=\"0.001\"} 0\n"));
+        assert!(text.contains("\nsimple_grep_search_duration_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(text.contains("\nsimple_grep_search_duration_seconds_bucket{le=\"0.1\"} 2\n"));
+        assert!(text.contains("\nsimple_grep_search_duration_seconds_bucket{le=\"10\"} 2\n"));
+        assert!(text.contains("\nsimple_grep_search_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("\nsimple_grep_search_duration_seconds_sum 20.073\n"));
+        assert!(text.contains("\nsimple_grep_scanned_bytes_total 120\n"));
+        assert!(text.contains("\nsimple_grep_file_list_cache_lookups_total 4\n"));
+        assert!(text.contains("\nsimple_grep_file_list_cache_hit_ratio 0.25\n"));
+        assert!(Metrics::default().text().contains("\nsimple_grep_file_list_cache_hit_ratio NaN\n"));
+    }
+
+    #[test]
+    fn test_metrics_count_searches() {
+        let dir = TempDir::new("serve-metrics");
+        dir.write("a.txt", "the quick fox\n");
+        let mut server = Server::new(Argument::try_from_args(["-r", "-e", "unused", &dir.display()]).unwrap());
+
+        server.respond("GET /search?q=fox HTTP/1.1");
+        server.respond("GET /search?q=dog HTTP/1.1");
+        server.respond("GET /search?q=%28 HTTP/1.1"); // Not answered with results
+
+        let metrics = server.respond("GET /metrics HTTP/1.1");
+        assert_eq!((metrics.status, metrics.content_type), ("200 OK", "text/plain; version=0.0.4"));
+        let text = String::from_utf8(metrics.body).unwrap();
+
+        assert!(text.contains("\nsimple_grep_searches_total 2\n"));
+        assert!(text.contains("\nsimple_grep_scanned_bytes_total 28\n"));
+        assert!(text.contains("\nsimple_grep_file_list_cache_lookups_total 3\n")); // The files are found before the pattern is compiled
+        assert!(text.contains("\nsimple_grep_file_list_cache_hits_total 0\n")); // Walked just now, so not trusted yet
+    }
+
+    #[test]
+    fn test_answer() {
+        let config = Argument::try_from_args(["-e", "unused", "./tests/test_poem.txt"]).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(b"GET /search?q=frog HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+            let mut response = String::new();
+            io::Read::read_to_string(&mut stream, &mut response).unwrap();
+            response
+        });
+
+        let stream = listener.accept().unwrap().0;
+        Server::new(config).answer(&stream, read_request(&stream).unwrap()).unwrap();
+        let response = client.join().unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Type: application/x-ndjson\r\n"));
+        assert!(response.ends_with('\n') && response.contains(r#""type":"match""#));
+    }
+
+    #[test]
+    fn test_read_head() {
+        let read = |request: &[u8], limit| read_head(&mut BufReader::new(request.take(limit))).unwrap();
+
+        let request = b"GET /search?q=fox HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert_eq!(read(request, MAX_HEAD), Ok(String::from("GET /search?q=fox HTTP/1.1\r\n")));
+        assert_eq!(read(b"GET / HTTP/1.1\r\n", MAX_HEAD), Ok(String::from("GET / HTTP/1.1\r\n"))); // Closed before the headers ended
+
+        assert_eq!(read(request, 10).unwrap_err().status, "400 Bad Request");
+        assert_eq!(read(request, 40).unwrap_err().status, "431 Request Header Fields Too Large");
+
+        let endless = io::repeat(b'a').take(MAX_HEAD * 2);
+        assert_eq!(read_head(&mut BufReader::new(endless.take(MAX_HEAD))).unwrap().unwrap_err().status, "400 Bad Request");
+    }
+}
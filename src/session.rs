@@ -0,0 +1,303 @@
+//! Paging through search results, for programs that show them a page at a time
+
+// Standard library
+use std::io; // For stopping a search nobody reads
+use std::sync::atomic::{AtomicBool, Ordering}; // For stopping a dropped session's search
+use std::sync::{mpsc, Arc}; // For handing results over from the search thread
+use std::thread; // For searching in the background
+use std::time::Duration; // For file timings
+use std::vec; // For cached results
+
+// My stuff
+use crate::error::Error;
+use crate::printer::Printer;
+use crate::search::{Match, Progress, Searcher};
+
+/// How many results a live search gets ahead of the pages asked for, at most
+const BUFFERED: usize = 1024;
+
+/// A matching line and the path of the file it's in
+pub type Hit = (String, Match);
+
+/// Where a session's results come from
+#[derive(Debug)]
+enum Source {
+    /// A search running on another thread, paused whenever it's [`BUFFERED`] results ahead
+    Live(mpsc::Receiver<Result<Hit, Error>>),
+    /// Results that were already there
+    Cached(vec::IntoIter<Hit>),
+}
+
+/// Search results handed out a page at a time, so a GUI can show the first ones without
+/// waiting for (or keeping) all of them
+///
+/// With [`SearchSession::start`], the search runs in the background and stops when the session
+/// is dropped. With [`SearchSession::from_results`], pages come from results searched earlier.
+/// Either way results come in the order [`Searcher::run`] gives them. Binary files are skipped,
+/// like [`Printer::binary_file`] does by default.
+///
+/// ```no_run
+/// use simple_grep::prelude::*;
+///
+/// let searcher = Searcher::new(SearchConfig::try_from_args(["-r", "fox", "src"])?)?;
+/// let mut session = SearchSession::start(searcher);
+///
+/// for (path, found) in session.next_page(50)? {
+///     println!("{path}:{}: {}", found.line_number(), found.line());
+/// }
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug)]
+pub struct SearchSession {
+    source: Source,
+    /// An error that came in after the results of the last page, for the next one
+    error: Option<Error>,
+    /// Set when the session is dropped, so a live search stops at the next file even if it
+    /// finds nothing more to send
+    cancelled: Arc<AtomicBool>,
+}
+
+impl SearchSession {
+    /// Start searching with `searcher` in the background
+    #[must_use]
+    pub fn start(searcher: Searcher) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(BUFFERED);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut printer = SessionPrinter { sender: sender.clone(), cancelled: Arc::clone(&cancelled) };
+
+        thread::spawn(move || {
+            // Failing to send means the session was dropped, so there's nobody to tell
+            if let Err(e) = searcher.run(&mut printer) {
+                let _ = sender.send(Err(e));
+            }
+        });
+
+        SearchSession { source: Source::Live(receiver), error: None, cancelled }
+    }
+
+    /// Session over results that were already searched (like ones kept from an earlier session)
+    #[must_use]
+    pub fn from_results(results: Vec<Hit>) -> Self {
+        SearchSession { source: Source::Cached(results.into_iter()), error: None, cancelled: Arc::default() }
+    }
+
+    /// The next (up to) `n` results, waiting for a live search to find them
+    ///
+    /// A page is only shorter than `n` at the end of the results (or before an error), and
+    /// empty once they've all been handed out.
+    ///
+    /// # Errors
+    ///
+    /// Will error if the search failed (like [`Searcher::run`]), after the pages of the results
+    /// found before that
+    pub fn next_page(&mut self, n: usize) -> Result<Vec<Hit>, Error> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+
+        let receiver = match &mut self.source {
+            Source::Live(receiver) => receiver,
+            Source::Cached(results) => return Ok(results.take(n).collect()),
+        };
+
+        let mut page = Vec::with_capacity(n.min(BUFFERED));
+
+        while page.len() < n {
+            match receiver.recv() {
+                Ok(Ok(hit)) => page.push(hit),
+                Ok(Err(e)) if page.is_empty() => return Err(e),
+                Ok(Err(e)) => {
+                    self.error = Some(e);
+                    break;
+                }
+                Err(mpsc::RecvError) => break, // The search is done
+            }
+        }
+
+        Ok(page)
+    }
+
+    /// Like [`SearchSession::next_page`], but only with the results found so far, without
+    /// waiting (for showing results as they come in, like `--tui` does)
+    ///
+    /// # Errors
+    ///
+    /// Like [`SearchSession::next_page`]
+    pub fn try_next_page(&mut self, n: usize) -> Result<Vec<Hit>, Error> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+
+        let receiver = match &mut self.source {
+            Source::Live(receiver) => receiver,
+            Source::Cached(results) => return Ok(results.take(n).collect()),
+        };
+
+        let mut page = Vec::new();
+
+        while page.len() < n {
+            match receiver.try_recv() {
+                Ok(Ok(hit)) => page.push(hit),
+                Ok(Err(e)) if page.is_empty() => return Err(e),
+                Ok(Err(e)) => {
+                    self.error = Some(e);
+                    break;
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.source = Source::Cached(Vec::new().into_iter()); // The search is done
+                    break;
+                }
+            }
+        }
+
+        Ok(page)
+    }
+
+    /// Whether every result has been handed out (a live search may still find more otherwise)
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.error.is_none() && matches!(&self.source, Source::Cached(results) if results.as_slice().is_empty())
+    }
+
+    /// Gone once the search thread has stopped (it holds the only other reference)
+    #[cfg(test)]
+    pub(crate) fn search_thread(&self) -> std::sync::Weak<AtomicBool> {
+        Arc::downgrade(&self.cancelled)
+    }
+}
+
+impl Drop for SearchSession {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Hands every matching line to a session
+struct SessionPrinter {
+    sender: mpsc::SyncSender<Result<Hit, Error>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl SessionPrinter {
+    /// Stops the search (by failing, like a closed pipe) once nobody is reading the results
+    fn check(&self) -> Result<(), Error> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(Error::Output(io::Error::new(io::ErrorKind::BrokenPipe, "search session dropped")));
+        }
+
+        Ok(())
+    }
+}
+
+impl Printer for SessionPrinter {
+    fn file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        for found in matches {
+            if self.sender.send(Ok((path.to_string(), found.clone()))).is_err() {
+                self.cancelled.store(true, Ordering::Relaxed); // The session is gone
+            }
+
+            self.check()?;
+        }
+
+        Ok(())
+    }
+
+    // Called for every file, matching or not
+    fn searched(&mut self, _path: &str, _bytes: u64, _took: Duration) -> Result<(), Error> {
+        self.check()
+    }
+
+    fn skipped(&mut self, _path: &str, _error: &Error) -> Result<(), Error> {
+        self.check()
+    }
+
+    fn progress(&mut self, _progress: &Progress) -> Result<(), Error> {
+        self.check()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+    use crate::Argument;
+
+    #[test]
+    fn test_live_pages() {
+        let temp = TempDir::new("session");
+        let path = temp.write("a.txt", "fox 1\nfox 2\ndog\nfox 3\nfox 4\nfox 5\n");
+
+        let config = Argument::try_from_args(["fox", &path]).unwrap();
+        let mut session = SearchSession::start(Searcher::new(config).unwrap());
+
+        let pages: Vec<Vec<String>> = (0..4)
+            .map(|_| session.next_page(2).unwrap().into_iter().map(|(_, found)| found.line().to_string()).collect())
+            .collect();
+
+        assert_eq!(pages, vec![vec!["fox 1", "fox 2"], vec!["fox 3", "fox 4"], vec!["fox 5"], vec![]]);
+    }
+
+    #[test]
+    fn test_try_next_page() {
+        let temp = TempDir::new("session");
+        let path = temp.write("a.txt", "fox 1\ndog\nfox 2\n");
+
+        let mut session = SearchSession::start(Searcher::new(Argument::try_from_args(["fox", &path]).unwrap()).unwrap());
+        let mut results = Vec::new();
+
+        while !session.is_done() {
+            results.extend(session.try_next_page(10).unwrap());
+            thread::yield_now();
+        }
+
+        assert_eq!(results.len(), 2);
+        assert!(session.try_next_page(10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cached_pages() {
+        let searcher = Searcher::new(Argument { pattern: Some(String::from("o")), ..Default::default() }).unwrap();
+        let results: Vec<Hit> = searcher.search_str("one\ntwo\nthree\nfour\n").into_iter().map(|found| (String::from("a.txt"), found)).collect();
+
+        let mut session = SearchSession::from_results(results);
+
+        assert_eq!(session.next_page(2).unwrap().len(), 2);
+        assert_eq!(session.next_page(2).unwrap().len(), 1);
+        assert!(session.next_page(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dropping_stops_the_search() {
+        let temp = TempDir::new("session");
+        let found = temp.write("a.txt", "fox\n");
+        let nothing = temp.write("b.txt", "dog\n".repeat(1 << 18)); // 1 MiB
+
+        // Searching them all would read 20 GiB, with nothing more to send after the first file
+        let mut args = vec![String::from("-j1"), String::from("fox"), found];
+        args.extend(std::iter::repeat_n(nothing, 20_000));
+
+        let mut session = SearchSession::start(Searcher::new(Argument::try_from_args(args).unwrap()).unwrap());
+        assert_eq!(session.next_page(1).unwrap().len(), 1);
+
+        let search_thread = session.search_thread();
+        drop(session);
+
+        let start = std::time::Instant::now();
+
+        while search_thread.upgrade().is_some() && start.elapsed() < Duration::from_secs(2) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(search_thread.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_search_errors() {
+        let config = Argument::try_from_args(["fox", "/no/such/file.txt"]).unwrap();
+        let mut session = SearchSession::start(Searcher::new(config).unwrap());
+
+        assert!(matches!(session.next_page(10), Err(Error::Io { .. })));
+        assert!(session.next_page(10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+//! Results quoted for the shell (`--output shell`), for scripts
+//!
+//! Each matching line is printed as its path and the line, each quoted so the shell reads it
+//! back exactly, separated by a tab: `eval "set -- $record"` in a `while read -r record` loop
+//! gives the path in `$1` and the line in `$2`, whatever they contain. Words with nothing special
+//! in them are left alone, others are put in single quotes, and ones with tabs, newlines or
+//! other control characters in `$'...'` with escapes (like bash's `printf %q`), so every result
+//! stays on a line of its own.
+
+// Standard library
+use std::fmt::Write as _; // For escapes
+use std::io::{self, Write}; // For writing results
+
+// My stuff
+use crate::error::Error;
+use crate::printer::Printer;
+use crate::search::Match;
+use crate::{Argument, PathDisplay};
+
+/// `word` quoted so a POSIX shell (or bash, zsh and ksh, for control characters) reads it back
+/// as one word, exactly
+pub(crate) fn quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+
+    if word.is_empty() {
+        return String::from("''");
+    }
+
+    if word.chars().all(plain) {
+        return word.to_string();
+    }
+
+    if !word.chars().any(char::is_control) {
+        return format!("'{}'", word.replace('\'', r"'\''")); // End the quotes for each quote
+    }
+
+    let mut quoted = String::from("$'");
+
+    for c in word.chars() {
+        match c {
+            '\\' => quoted.push_str(r"\\"),
+            '\'' => quoted.push_str(r"\'"),
+            '\t' => quoted.push_str(r"\t"),
+            '\n' => quoted.push_str(r"\n"),
+            '\r' => quoted.push_str(r"\r"),
+            c if c.is_control() => {
+                let mut bytes = [0; 4];
+
+                for byte in c.encode_utf8(&mut bytes).bytes() {
+                    let _ = write!(quoted, r"\x{byte:02x}"); // Writing to a String can't fail
+                }
+            }
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('\'');
+    quoted
+}
+
+/// Prints results as shell-quoted `path<TAB>line` records, one per line
+///
+/// With `-l` only the quoted paths are printed, and with `-c` or `--count-matches` the quoted
+/// path and count.
+#[derive(Debug)]
+pub(crate) struct ShellPrinter<W> {
+    writer: W,
+    paths: PathDisplay,
+    count: bool,
+    count_matches: bool,
+    files_with_matches: bool,
+}
+
+impl<W: Write> ShellPrinter<W> {
+    /// Printer for the output options in `config`, writing to `writer`
+    pub(crate) fn new(config: &Argument, writer: W) -> Self {
+        ShellPrinter {
+            writer,
+            paths: config.path_display(),
+            count: config.count,
+            count_matches: config.count_matches,
+            files_with_matches: config.files_with_matches,
+        }
+    }
+
+    fn write_file(&mut self, path: &str, matches: &[Match], binary: bool) -> io::Result<()> {
+        if matches.is_empty() {
+            return Ok(());
+        }
+
+        let path = quote(&self.paths.resolve(path));
+
+        if self.files_with_matches {
+            return writeln!(self.writer, "{path}");
+        }
+
+        if self.count || self.count_matches {
+            let count = if self.count { matches.len() } else { matches.iter().map(|found| found.spans().len()).sum() };
+            return writeln!(self.writer, "{path}\t{count}");
+        }
+
+        // Binary files' lines aren't worth printing, like in text output
+        if binary {
+            return Ok(());
+        }
+
+        for found in matches {
+            writeln!(self.writer, "{path}\t{}", quote(found.line()))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Printer for ShellPrinter<W> {
+    fn file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        self.write_file(path, matches, false).map_err(Error::Output)
+    }
+
+    fn binary_file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        self.write_file(path, matches, true).map_err(Error::Output)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.writer.flush().map_err(Error::Output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Words that need every kind of quoting
+    const AWKWARD: [&str; 9] = ["", "plain-file_1.txt", "with space", "it's", "tab\there", "new\nline", "back\\slash 'quotes' \"too\"", "\x1b[1mbold\x1b[0m", "naïve $HOME `date` *"];
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("src/main.rs"), "src/main.rs");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("it's here"), r"'it'\''s here'");
+        assert_eq!(quote("a\tb'c"), r"$'a\tb\'c'");
+        assert_eq!(quote("\u{7f}"), r"$'\x7f'");
+        assert!(AWKWARD.iter().all(|word| !quote(word).contains(['\t', '\n'])));
+    }
+
+    #[test]
+    fn test_shell_printer() {
+        let config = Argument::try_from_args(["fox", "my notes.txt"]).unwrap();
+        let mut output = Vec::new();
+        let mut printer = ShellPrinter::new(&config, &mut output);
+
+        printer.file("my notes.txt", &[Match::new(2, 10, String::from("the fox's\tfox den"), vec![4..7, 10..13])]).unwrap();
+        printer.binary_file("photo.jpg", &[Match::new(1, 0, String::from("fox fox"), vec![0..3, 4..7])]).unwrap();
+        printer.file("empty.txt", &[]).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "'my notes.txt'\t$'the fox\\'s\\tfox den'\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_quote_round_trips_through_bash() {
+        use std::process::{Command, Stdio};
+
+        let records: String = AWKWARD.iter().flat_map(|path| AWKWARD.iter().map(move |line| format!("{}\t{}\n", quote(path), quote(line)))).collect();
+        let Ok(mut bash) = Command::new("bash")
+            .arg("-c")
+            .arg(r#"while read -r record; do eval "set -- $record"; printf '%s\0' "$#" "$@"; done"#)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+        else {
+            return; // No bash to check with
+        };
+
+        bash.stdin.take().unwrap().write_all(records.as_bytes()).unwrap();
+        let output = bash.wait_with_output().unwrap();
+        let expected: String = AWKWARD.iter().flat_map(|path| AWKWARD.iter().map(move |line| format!("2\0{path}\0{line}\0"))).collect();
+
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    }
+}
@@ -0,0 +1,394 @@
+//! Writing results into a database with sqlite3 (`--output sqlite:PATH`, the `sqlite` feature)
+//!
+//! Results go to the `sqlite3` program as SQL, so it has to be installed. Every run adds to
+//! three tables, so runs can be compared over time with plain SQL:
+//!
+//! - `runs`: one row per search (`started` in seconds since the Unix epoch, `version`,
+//!   `patterns` and `paths` as JSON arrays, and the totals `files`, `files_with_matches`,
+//!   `matched_lines`, `matches`, `bytes` and `elapsed_ms`)
+//! - `files`: each file with matches (`run_id`, `path`, `binary`, `decoded` as a JSON array like
+//!   in `--json`, `matched_lines` and `matches`)
+//! - `matches`: each match (`file_id`, `line_number`, `line`, `span_start` and `span_end` in the
+//!   line, `text`). Binary files' lines are left out, like in text output, and with
+//!   `--only-binary` the spans are offsets in the file, without a line. With `--hash-lines`,
+//!   lines and match text are left out and `hash` is set instead.
+//!
+//! A run is one transaction, so a failed search leaves the database as it was.
+
+// Standard library
+use std::fmt::Write as _; // For writing hex into strings
+use std::io::{self, BufWriter, Write}; // For writing SQL
+use std::ops::Range; // For match spans
+use std::process::{ChildStdin, Command, Stdio}; // For running sqlite3
+use std::time::{SystemTime, UNIX_EPOCH}; // For when runs started
+
+// My stuff
+use crate::decompress::Process;
+use crate::error::Error;
+use crate::json;
+use crate::printer::{line_hash, Printer};
+use crate::search::{Match, Progress};
+use crate::{Argument, PathDisplay};
+
+/// Tables results are written into, created if the database doesn't have them yet
+const SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    started INTEGER NOT NULL,
+    version TEXT NOT NULL,
+    patterns TEXT NOT NULL,
+    paths TEXT NOT NULL,
+    files INTEGER,
+    files_with_matches INTEGER,
+    matched_lines INTEGER,
+    matches INTEGER,
+    bytes INTEGER,
+    elapsed_ms INTEGER
+);
+CREATE TABLE IF NOT EXISTS files (
+    id INTEGER PRIMARY KEY,
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    path TEXT NOT NULL,
+    binary INTEGER NOT NULL,
+    decoded TEXT,
+    matched_lines INTEGER NOT NULL,
+    matches INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS matches (
+    id INTEGER PRIMARY KEY,
+    file_id INTEGER NOT NULL REFERENCES files(id),
+    line_number INTEGER,
+    line TEXT,
+    span_start INTEGER,
+    span_end INTEGER,
+    text TEXT,
+    hash TEXT
+);
+CREATE INDEX IF NOT EXISTS files_by_run ON files(run_id);
+CREATE INDEX IF NOT EXISTS matches_by_file ON matches(file_id);
+";
+
+/// `sqlite3` running on a database, reading SQL from its stdin
+#[derive(Debug)]
+pub(crate) struct Database {
+    process: Process,
+    stdin: BufWriter<ChildStdin>,
+}
+
+impl Database {
+    /// Start `sqlite3` on the database at `path` (created if it doesn't exist), and start a run
+    /// of `config` in it
+    ///
+    /// # Errors
+    ///
+    /// Will error if sqlite3 can't be run (like when it isn't installed)
+    pub(crate) fn open(path: &str, config: &Argument) -> io::Result<Self> {
+        let mut child = Command::new("sqlite3")
+            .args(["-bail", "-batch", path])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("can't run sqlite3: {e}")))?;
+
+        let Some(stdin) = child.stdin.take() else {
+            return Err(io::Error::other("can't write to sqlite3"));
+        };
+
+        let mut database = Database { process: Process::new("sqlite3", child), stdin: BufWriter::new(stdin) };
+        database.stdin.write_all(start_run(config).as_bytes())?;
+
+        Ok(database)
+    }
+
+    /// Where to write the run's SQL
+    pub(crate) fn writer(&mut self) -> &mut BufWriter<ChildStdin> {
+        &mut self.stdin
+    }
+
+    /// Let sqlite3 finish and wait for it to exit
+    ///
+    /// # Errors
+    ///
+    /// Will error with what sqlite3 said if it failed (like when the database is locked)
+    pub(crate) fn close(self) -> io::Result<()> {
+        let Database { process, mut stdin } = self;
+        let flushed = stdin.flush();
+        drop(stdin); // So sqlite3 sees the end of the SQL
+
+        process.wait()?; // Its own error explains a failed write best
+
+        flushed
+    }
+}
+
+/// The SQL that creates the tables if needed, opens the run's transaction and adds its row
+fn start_run(config: &Argument) -> String {
+    let started = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let patterns: Vec<&str> = config.pattern.iter().chain(&config.regexp).map(String::as_str).collect();
+    let paths: Vec<&str> = config.files.iter().map(String::as_str).collect();
+
+    format!(
+        "PRAGMA busy_timeout = 5000;\n{SCHEMA}BEGIN IMMEDIATE;\nINSERT INTO runs (started, version, patterns, paths) VALUES ({started}, {}, {}, {});\n",
+        string(env!("CARGO_PKG_VERSION")),
+        string(&json::string_array(&patterns)),
+        string(&json::string_array(&paths)),
+    )
+}
+
+/// Writes results as SQL for `sqlite3`, into the run [`Database::open`] started
+///
+/// New rows refer to the latest run and file, which are this run's since the transaction holds
+/// the only write lock on the database.
+#[derive(Debug)]
+pub(crate) struct SqlitePrinter<W> {
+    writer: W,
+    paths: PathDisplay,
+    hash_salt: Option<String>,
+    files_with_matches: usize,
+    matched_lines: usize,
+    matches: usize,
+    progress: Option<Progress>,
+    /// How the file about to be written was decoded
+    decoded: Vec<String>,
+}
+
+impl<W: Write> SqlitePrinter<W> {
+    /// Printer for the output options in `config`, writing to `writer`
+    pub(crate) fn new(config: &Argument, writer: W) -> Self {
+        SqlitePrinter {
+            writer,
+            paths: config.path_display(),
+            hash_salt: config.hash_salt(),
+            files_with_matches: 0,
+            matched_lines: 0,
+            matches: 0,
+            progress: None,
+            decoded: Vec::new(),
+        }
+    }
+
+    /// Give back the writer
+    #[cfg(test)]
+    fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Add the file's row, with `matched_lines` and `matches`
+    fn write_file_row(&mut self, path: &str, binary: bool, matched_lines: usize, matches: usize) -> io::Result<()> {
+        let decoded = if self.decoded.is_empty() {
+            String::from("NULL")
+        } else {
+            let steps: Vec<&str> = self.decoded.iter().map(String::as_str).collect();
+            string(&json::string_array(&steps))
+        };
+
+        self.files_with_matches += 1;
+        self.matched_lines += matched_lines;
+        self.matches += matches;
+
+        writeln!(
+            self.writer,
+            "INSERT INTO files (run_id, path, binary, decoded, matched_lines, matches) VALUES ((SELECT max(id) FROM runs), {}, {}, {decoded}, {matched_lines}, {matches});",
+            string(&self.paths.resolve(path)),
+            u8::from(binary),
+        )
+    }
+
+    fn write_file(&mut self, path: &str, matches: &[Match], binary: bool) -> io::Result<()> {
+        if matches.is_empty() {
+            return Ok(());
+        }
+
+        let count = matches.iter().map(|found| found.spans().len()).sum();
+        self.write_file_row(path, binary, matches.len(), count)?;
+
+        // Binary files' lines aren't worth keeping, like in text output
+        if binary {
+            return Ok(());
+        }
+
+        for found in matches {
+            let (line, hash) = match &self.hash_salt {
+                Some(salt) => (String::from("NULL"), string(&format!("{:016x}", line_hash(found, salt)))),
+                None => (string(found.line()), String::from("NULL")),
+            };
+
+            let text = |span: &Range<usize>| match self.hash_salt {
+                Some(_) => String::from("NULL"),
+                None => string(&found.line()[span.clone()]),
+            };
+
+            let spans: Vec<String> = match found.spans() {
+                [] => vec![String::from("NULL, NULL, NULL")],
+                spans => spans.iter().map(|span| format!("{}, {}, {}", span.start, span.end, text(span))).collect(),
+            };
+
+            for span in spans {
+                writeln!(
+                    self.writer,
+                    "INSERT INTO matches (file_id, line_number, line, span_start, span_end, text, hash) VALUES ((SELECT max(id) FROM files), {}, {line}, {span}, {hash});",
+                    found.line_number(),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_binary_matches(&mut self, path: &str, bytes: &[u8], spans: &[Range<usize>]) -> io::Result<()> {
+        if spans.is_empty() {
+            return Ok(());
+        }
+
+        self.write_file_row(path, true, 0, spans.len())?;
+
+        for span in spans {
+            writeln!(
+                self.writer,
+                "INSERT INTO matches (file_id, span_start, span_end, text) VALUES ((SELECT max(id) FROM files), {}, {}, {});",
+                span.start,
+                span.end,
+                string(&String::from_utf8_lossy(&bytes[span.clone()])),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Fill in the run's totals and commit it
+    fn write_end(&mut self) -> io::Result<()> {
+        let (files, bytes, elapsed) = self
+            .progress
+            .map_or((0, 0, 0), |progress| (progress.files(), progress.bytes(), progress.elapsed().as_millis()));
+
+        writeln!(
+            self.writer,
+            "UPDATE runs SET files = {files}, files_with_matches = {}, matched_lines = {}, matches = {}, bytes = {bytes}, elapsed_ms = {elapsed} WHERE id = (SELECT max(id) FROM runs);\nCOMMIT;",
+            self.files_with_matches,
+            self.matched_lines,
+            self.matches,
+        )?;
+
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Printer for SqlitePrinter<W> {
+    fn file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        let written = self.write_file(path, matches, false);
+        self.decoded.clear();
+        written.map_err(Error::Output)
+    }
+
+    fn binary_file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        let written = self.write_file(path, matches, true);
+        self.decoded.clear();
+        written.map_err(Error::Output)
+    }
+
+    fn binary_matches(&mut self, path: &str, bytes: &[u8], spans: &[Range<usize>]) -> Result<(), Error> {
+        self.decoded.clear(); // Offsets are in the raw bytes
+        self.write_binary_matches(path, bytes, spans).map_err(Error::Output)
+    }
+
+    fn decoded(&mut self, _path: &str, steps: &[&str]) -> Result<(), Error> {
+        self.decoded = steps.iter().map(ToString::to_string).collect();
+        Ok(())
+    }
+
+    fn progress(&mut self, progress: &Progress) -> Result<(), Error> {
+        self.progress = Some(*progress);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.write_end().map_err(Error::Output)
+    }
+}
+
+/// `value` as a SQL string literal
+///
+/// Text with line breaks or NUL bytes (like `-z` records) is written as a blob cast to text
+/// instead, so sqlite3 reads it back exactly and never mistakes part of it for a dot-command.
+fn string(value: &str) -> String {
+    if value.contains(['\0', '\n', '\r']) {
+        let mut hex = String::with_capacity(value.len() * 2);
+        for byte in value.bytes() {
+            let _ = write!(hex, "{byte:02X}"); // Writing to a String can't fail
+        }
+
+        return format!("CAST(X'{hex}' AS TEXT)");
+    }
+
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Searcher;
+
+    /// The SQL `config` writes for each `(path, contents)`, up to the commit
+    fn write(config: Argument, files: &[(&str, &str)]) -> Vec<String> {
+        let mut printer = SqlitePrinter::new(&config, Vec::new());
+        let searcher = Searcher::new(config).unwrap();
+
+        for (path, contents) in files {
+            printer.file(path, &searcher.search_str(contents)).unwrap();
+        }
+
+        printer.finish().unwrap();
+
+        String::from_utf8(printer.into_inner()).unwrap().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_sql_string() {
+        assert_eq!(string("it's"), "'it''s'");
+        assert_eq!(string(""), "''");
+        assert_eq!(string("a\n.b"), "CAST(X'610A2E62' AS TEXT)");
+    }
+
+    #[test]
+    fn test_sqlite_rows() {
+        let config = Argument { pattern: Some(String::from("o")), ..Default::default() };
+
+        let result = write(config, &[("a.txt", "one 'two'\nsix\n"), ("b.txt", "six\n")]);
+
+        assert_eq!(result, vec![
+            "INSERT INTO files (run_id, path, binary, decoded, matched_lines, matches) VALUES ((SELECT max(id) FROM runs), 'a.txt', 0, NULL, 1, 2);",
+            "INSERT INTO matches (file_id, line_number, line, span_start, span_end, text, hash) VALUES ((SELECT max(id) FROM files), 1, 'one ''two''', 0, 1, 'o', NULL);",
+            "INSERT INTO matches (file_id, line_number, line, span_start, span_end, text, hash) VALUES ((SELECT max(id) FROM files), 1, 'one ''two''', 7, 8, 'o', NULL);",
+            "UPDATE runs SET files = 0, files_with_matches = 1, matched_lines = 1, matches = 2, bytes = 0, elapsed_ms = 0 WHERE id = (SELECT max(id) FROM runs);",
+            "COMMIT;",
+        ]);
+    }
+
+    #[test]
+    fn test_sqlite_decoded_and_hashed() {
+        let config = Argument { pattern: Some(String::from("i")), hash_lines: true, ..Default::default() };
+        let mut printer = SqlitePrinter::new(&config, Vec::new());
+        let matches = Searcher::new(config).unwrap().search_str("six\n");
+
+        printer.decoded("a.gz", &["gzip"]).unwrap();
+        printer.file("a.gz", &matches).unwrap();
+
+        let result = String::from_utf8(printer.into_inner()).unwrap();
+
+        assert!(result.contains("'a.gz', 0, '[\"gzip\"]', 1, 1);"));
+        assert!(result.contains("1, NULL, 1, 2, NULL, '")); // Only the hash
+        assert!(!result.contains("six"));
+    }
+
+    #[test]
+    fn test_start_run() {
+        let config = Argument::try_from_args(["-e", "TODO", "-e", "FIXME", "src"]).unwrap();
+
+        let sql = start_run(&config);
+
+        assert!(sql.contains("CREATE TABLE IF NOT EXISTS matches"));
+        assert!(sql.contains("BEGIN IMMEDIATE;\nINSERT INTO runs"));
+        assert!(sql.ends_with(", '[\"TODO\",\"FIXME\"]', '[\"src\"]');\n"));
+    }
+}
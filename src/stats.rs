@@ -0,0 +1,301 @@
+//! Statistics about a search, printed after it with `--stats` (and by file size with `--by-size`)
+
+// Standard library
+use std::io::{self, Write}; // For writing the trailer
+use std::ops::Range; // For match spans
+use std::time::Duration; // For elapsed time
+
+// My stuff
+use crate::error::Error;
+use crate::i18n::{fill, Catalog};
+use crate::printer::Printer;
+use crate::search::{Match, Progress};
+
+/// File size buckets for `--by-size`: their label, and the largest size in them
+const SIZE_BUCKETS: [(&str, u64); 4] = [("<=4K", 4 << 10), ("<=64K", 64 << 10), ("<=1M", 1 << 20), (">1M", u64::MAX)];
+
+/// Totals of the files in one size bucket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Bucket {
+    files: usize,
+    bytes: u64,
+    /// Time spent reading and searching them, added up over threads
+    took: Duration,
+    matches: usize,
+}
+
+/// Totals of a search
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Stats {
+    files: usize,
+    files_with_matches: usize,
+    matched_lines: usize,
+    matches: usize,
+    bytes: u64,
+    elapsed: Duration,
+    /// Files skipped because they couldn't be read
+    errors: usize,
+    buckets: [Bucket; SIZE_BUCKETS.len()],
+}
+
+impl Stats {
+    /// Bytes searched, once the search is done
+    pub(crate) fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Write the totals as a trailer, after a blank line, and a table of them by file size if `by_size`
+    pub(crate) fn write(&self, writer: &mut dyn Write, catalog: &Catalog, by_size: bool) -> io::Result<()> {
+        let seconds = format!("{:.3}", self.elapsed.as_secs_f64());
+
+        let trailer = fill(catalog.stats, &[
+            ("files", &self.files.to_string()),
+            ("files_with_matches", &self.files_with_matches.to_string()),
+            ("lines", &self.matched_lines.to_string()),
+            ("matches", &self.matches.to_string()),
+            ("bytes", &self.bytes.to_string()),
+            ("seconds", &seconds),
+        ]);
+
+        writeln!(writer)?;
+        writeln!(writer, "{trailer}")?;
+
+        if by_size {
+            self.write_by_size(writer, catalog)?;
+        }
+
+        Ok(())
+    }
+
+    /// The `--exit-summary` line, in a format that stays the same in every language and release
+    pub(crate) fn summary_line(&self) -> String {
+        format!(
+            "{}: matched={} files={} errors={} elapsed={:.3}s",
+            env!("CARGO_PKG_NAME"),
+            self.matched_lines,
+            self.files_with_matches,
+            self.errors,
+            self.elapsed.as_secs_f64()
+        )
+    }
+
+    /// Write the totals of each size bucket as a table, after a blank line
+    fn write_by_size(&self, writer: &mut dyn Write, catalog: &Catalog) -> io::Result<()> {
+        let mut rows = vec![catalog.stats_by_size.map(String::from)];
+
+        for ((label, _), bucket) in SIZE_BUCKETS.iter().zip(&self.buckets) {
+            rows.push([
+                (*label).to_string(),
+                bucket.files.to_string(),
+                bucket.bytes.to_string(),
+                format!("{:.3}", bucket.took.as_secs_f64()),
+                bucket.matches.to_string(),
+            ]);
+        }
+
+        let widths: Vec<usize> = (0..5).map(|column| rows.iter().map(|row| row[column].chars().count()).max().unwrap_or(0)).collect();
+
+        writeln!(writer)?;
+
+        // Sizes on the left, numbers lined up on the right
+        for row in rows {
+            write!(writer, "{:<width$}", row[0], width = widths[0])?;
+
+            for (cell, width) in row.iter().zip(&widths).skip(1) {
+                write!(writer, "  {cell:>width$}")?;
+            }
+
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts what goes through to the printer it wraps
+pub(crate) struct StatsPrinter<'a> {
+    inner: Box<dyn Printer + 'a>,
+    stats: &'a mut Stats,
+    /// Size bucket of the file being printed
+    bucket: usize,
+}
+
+impl<'a> StatsPrinter<'a> {
+    /// Count the results given to `inner` into `stats`
+    pub(crate) fn new(inner: Box<dyn Printer + 'a>, stats: &'a mut Stats) -> Self {
+        StatsPrinter { inner, stats, bucket: 0 }
+    }
+
+    fn count(&mut self, matches: &[Match]) {
+        let found = matches.iter().map(|found| found.spans().len()).sum::<usize>();
+
+        self.stats.files_with_matches += usize::from(!matches.is_empty());
+        self.stats.matched_lines += matches.len();
+        self.stats.matches += found;
+        self.stats.buckets[self.bucket].matches += found;
+    }
+}
+
+impl Printer for StatsPrinter<'_> {
+    fn file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        self.count(matches);
+        self.inner.file(path, matches)
+    }
+
+    fn binary_file(&mut self, path: &str, matches: &[Match]) -> Result<(), Error> {
+        self.count(matches);
+        self.inner.binary_file(path, matches)
+    }
+
+    fn binary_matches(&mut self, path: &str, bytes: &[u8], spans: &[Range<usize>]) -> Result<(), Error> {
+        self.stats.files_with_matches += usize::from(!spans.is_empty());
+        self.stats.matches += spans.len();
+        self.stats.buckets[self.bucket].matches += spans.len();
+        self.inner.binary_matches(path, bytes, spans)
+    }
+
+    fn searched(&mut self, path: &str, bytes: u64, took: Duration) -> Result<(), Error> {
+        self.bucket = SIZE_BUCKETS.iter().position(|(_, largest)| bytes <= *largest).unwrap_or_default();
+
+        let bucket = &mut self.stats.buckets[self.bucket];
+        bucket.files += 1;
+        bucket.bytes += bytes;
+        bucket.took += took;
+
+        self.inner.searched(path, bytes, took)
+    }
+
+    fn decoded(&mut self, path: &str, steps: &[&str]) -> Result<(), Error> {
+        self.inner.decoded(path, steps)
+    }
+
+    fn header(&mut self, path: &str, lines: &[Match]) -> Result<(), Error> {
+        self.inner.header(path, lines) // Not matches, so not counted
+    }
+
+    fn skipped(&mut self, path: &str, error: &Error) -> Result<(), Error> {
+        self.stats.errors += 1;
+        self.inner.skipped(path, error)
+    }
+
+    fn progress(&mut self, progress: &Progress) -> Result<(), Error> {
+        // The last one has the totals
+        self.stats.files = progress.files();
+        self.stats.bytes = progress.bytes();
+        self.stats.elapsed = progress.elapsed();
+        self.inner.progress(progress)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Argument, Lang, Searcher};
+
+    /// Prints nothing
+    struct Discard;
+
+    impl Printer for Discard {
+        fn file(&mut self, _path: &str, _matches: &[Match]) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stats_printer_counts() {
+        let config = Argument {
+            pattern: Some(String::from("o")),
+            files: vec![String::from("./tests/test_2.txt"), String::from("./tests/test_poem.txt")],
+            ..Default::default()
+        };
+        let searcher = Searcher::new(config).unwrap();
+        let mut stats = Stats::default();
+
+        searcher.run(&mut StatsPrinter::new(Box::new(Discard), &mut stats)).unwrap();
+
+        let expected: Vec<Match> = ["./tests/test_2.txt", "./tests/test_poem.txt"]
+            .iter()
+            .flat_map(|path| searcher.search_path(path).unwrap())
+            .collect();
+
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.matched_lines, expected.len());
+        assert_eq!(stats.matches, expected.iter().map(|found| found.spans().len()).sum::<usize>());
+        assert!(stats.bytes > 0);
+
+        // Both files are small
+        assert_eq!(stats.buckets[0].files, 2);
+        assert_eq!(stats.buckets[0].bytes, stats.bytes);
+        assert_eq!(stats.buckets[0].matches, stats.matches);
+    }
+
+    #[test]
+    fn test_stats_write() {
+        let stats = Stats {
+            files: 3,
+            files_with_matches: 2,
+            matched_lines: 5,
+            matches: 7,
+            bytes: 1024,
+            elapsed: Duration::from_millis(1500),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+
+        stats.write(&mut output, Lang::En.catalog(), false).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\n5 matched lines\n7 matches\n2 files with matches\n3 files searched\n1024 bytes searched\n1.500 seconds\n"
+        );
+    }
+
+    #[test]
+    fn test_summary_line() {
+        let stats = Stats {
+            files: 50,
+            files_with_matches: 45,
+            matched_lines: 123,
+            matches: 130,
+            errors: 2,
+            elapsed: Duration::from_millis(1300),
+            ..Default::default()
+        };
+
+        assert_eq!(stats.summary_line(), "simple-grep: matched=123 files=45 errors=2 elapsed=1.300s");
+    }
+
+    #[test]
+    fn test_stats_printer_counts_skipped_files() {
+        let mut stats = Stats::default();
+        let error = Error::Io { path: String::from("a.txt"), source: io::Error::from(io::ErrorKind::PermissionDenied) };
+
+        StatsPrinter::new(Box::new(Discard), &mut stats).skipped("a.txt", &error).unwrap();
+
+        assert_eq!(stats.errors, 1);
+    }
+
+    #[test]
+    fn test_stats_write_by_size() {
+        let mut stats = Stats::default();
+        stats.buckets[0] = Bucket { files: 12, bytes: 2048, took: Duration::from_millis(5), matches: 3 };
+        stats.buckets[3] = Bucket { files: 1, bytes: 5 << 20, took: Duration::from_millis(250), matches: 1200 };
+        let mut output = Vec::new();
+
+        stats.write_by_size(&mut output, Lang::En.catalog()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\n\
+             size   files    bytes  seconds  matches\n\
+             <=4K      12     2048    0.005        3\n\
+             <=64K      0        0    0.000        0\n\
+             <=1M       0        0    0.000        0\n\
+             >1M        1  5242880    0.250     1200\n"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! One-line status on stderr when the process gets SIGUSR1, without stopping the search
+//!
+//! The search keeps [`STATUS`] up to date once [`report_status_on_signal`] has turned it on, so
+//! library users that never do don't pay for it.
+
+// Standard library
+use std::sync::Mutex; // For the current file
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering}; // For counters shared with the search threads
+
+// My stuff
+use crate::i18n::{fill, Catalog, Lang};
+
+/// How far the running search has got
+pub(crate) struct Status {
+    enabled: AtomicBool,
+    files_done: AtomicUsize,
+    files_total: AtomicUsize,
+    matched_lines: AtomicUsize,
+    /// The file searching started on most recently
+    current: Mutex<String>,
+}
+
+/// Status of the search running in this process
+pub(crate) static STATUS: Status = Status::new();
+
+impl Status {
+    const fn new() -> Self {
+        Status {
+            enabled: AtomicBool::new(false),
+            files_done: AtomicUsize::new(0),
+            files_total: AtomicUsize::new(0),
+            matched_lines: AtomicUsize::new(0),
+            current: Mutex::new(String::new()),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// A search over `files` files starts
+    pub(crate) fn start(&self, files: usize) {
+        if self.enabled() {
+            self.files_total.store(files, Ordering::Relaxed);
+            self.files_done.store(0, Ordering::Relaxed);
+            self.matched_lines.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Searching the file at `path` starts
+    pub(crate) fn searching(&self, path: &str) {
+        if self.enabled()
+            && let Ok(mut current) = self.current.lock()
+        {
+            path.clone_into(&mut current);
+        }
+    }
+
+    /// A file was searched, with `matched_lines` matching lines
+    pub(crate) fn searched(&self, matched_lines: usize) {
+        if self.enabled() {
+            self.files_done.fetch_add(1, Ordering::Relaxed);
+            self.matched_lines.fetch_add(matched_lines, Ordering::Relaxed);
+        }
+    }
+
+    /// The status as one line
+    fn line(&self, catalog: &Catalog) -> String {
+        let current = self.current.lock().map(|current| current.clone()).unwrap_or_default();
+
+        fill(catalog.status, &[
+            ("done", &self.files_done.load(Ordering::Relaxed).to_string()),
+            ("total", &self.files_total.load(Ordering::Relaxed).to_string()),
+            ("lines", &self.matched_lines.load(Ordering::Relaxed).to_string()),
+            ("file", &current),
+        ])
+    }
+}
+
+/// Print the status to stderr, in `lang`, every time the process gets SIGUSR1
+///
+/// Has to be called before any other thread starts, since SIGUSR1 is blocked for every thread but
+/// the one waiting for it (otherwise it would stop the process). Does nothing outside Unix.
+pub fn report_status_on_signal(lang: Lang) {
+    #[cfg(unix)]
+    {
+        let Some(signals) = block_sigusr1() else {
+            return; // Keep the default, rather than promise a status that won't come
+        };
+
+        STATUS.enabled.store(true, Ordering::Relaxed);
+
+        std::thread::spawn(move || {
+            loop {
+                let mut signal = 0;
+
+                // SAFETY: `signals` is an initialized set, and `signal` a valid place for the result
+                if unsafe { libc::sigwait(&raw const signals, &raw mut signal) } != 0 {
+                    break;
+                }
+
+                eprintln!("{}: {}", env!("CARGO_PKG_NAME"), STATUS.line(lang.catalog()));
+            }
+        });
+    }
+
+    #[cfg(not(unix))]
+    let _ = lang;
+}
+
+/// Block SIGUSR1 in this thread (and the ones it starts), returning the set to wait for
+#[cfg(unix)]
+fn block_sigusr1() -> Option<libc::sigset_t> {
+    let mut signals = std::mem::MaybeUninit::<libc::sigset_t>::uninit();
+
+    // SAFETY: the set is initialized by sigemptyset before anything else reads it
+    unsafe {
+        libc::sigemptyset(signals.as_mut_ptr());
+        libc::sigaddset(signals.as_mut_ptr(), libc::SIGUSR1);
+
+        let signals = signals.assume_init();
+
+        (libc::pthread_sigmask(libc::SIG_BLOCK, &raw const signals, std::ptr::null_mut()) == 0).then_some(signals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_line() {
+        let status = Status::new();
+        status.enabled.store(true, Ordering::Relaxed);
+
+        status.start(10);
+        status.searching("a.txt");
+        status.searched(2);
+        status.searching("b.txt");
+        status.searched(3);
+
+        assert_eq!(status.line(Lang::En.catalog()), "2/10 files searched, 5 matching lines, now searching b.txt");
+    }
+
+    #[test]
+    fn test_status_off_by_default() {
+        let status = Status::new();
+
+        status.start(10);
+        status.searched(2);
+
+        assert_eq!(status.line(Lang::En.catalog()), "0/0 files searched, 0 matching lines, now searching ");
+    }
+}
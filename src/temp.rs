@@ -0,0 +1,61 @@
+//! Temporary directories for tests, removed when they're dropped (so also when a test fails)
+
+// Standard library
+use std::env; // For the temporary directory
+use std::fs; // For creating and removing files
+use std::path::{Path, PathBuf}; // For paths in the directory
+use std::process; // For process IDs
+use std::sync::atomic::{AtomicUsize, Ordering}; // For telling directories apart
+
+/// Directories made so far, so tests running at once each get their own
+static MADE: AtomicUsize = AtomicUsize::new(0);
+
+/// A directory of its own in the system's temporary directory
+#[derive(Debug)]
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// A new empty directory, with `name` in its name to tell what made it
+    ///
+    /// The path is canonical, since the temporary directory can be a link itself (like on macOS).
+    pub(crate) fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("simple-grep-{name}-{}-{}", process::id(), MADE.fetch_add(1, Ordering::Relaxed)));
+        fs::create_dir_all(&path).unwrap();
+
+        TempDir { path: fs::canonicalize(&path).unwrap_or(path) }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `path` in the directory
+    pub(crate) fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.path.join(path)
+    }
+
+    /// Write `contents` to `name` in the directory (making the directories it's in) and give its path
+    pub(crate) fn write(&self, name: &str, contents: impl AsRef<[u8]>) -> String {
+        let path = self.join(name);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// The directory's path as a string, for arguments
+    pub(crate) fn display(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path); // Nothing to do about it in a test
+    }
+}
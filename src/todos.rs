@@ -0,0 +1,197 @@
+//! Report of TODO/FIXME/HACK markers (`--todos`)
+
+// Standard library
+use std::collections::BTreeMap; // For sorted grouping
+use std::error::Error; // For error handling
+use std::fs; // For file stuff
+use std::io::{self, Write}; // For writing the report
+
+// External crates
+use regex::Regex; // For regular expressions
+
+// My stuff
+use crate::{files_to_search, warn_skipped, Argument};
+
+/// A TODO-style marker found in a file
+#[derive(Debug, PartialEq, Eq)]
+struct Todo {
+    line_number: usize,
+    marker: String,
+    owner: Option<String>,
+    text: String,
+}
+
+/// Markers look like `TODO: text`, `FIXME(owner): text` or `HACK text`
+fn todo_regex() -> Regex {
+    Regex::new(r"\b(TODO|FIXME|HACK)\b(?:\(([^)]*)\))?:?(.*)").unwrap()
+}
+
+fn find_todos(regex: &Regex, contents: &str) -> Vec<Todo> {
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let captures = regex.captures(line)?;
+
+            let owner = captures
+                .get(2)
+                .map(|owner| owner.as_str().trim().to_string())
+                .filter(|owner| !owner.is_empty()); // `TODO()` has no owner
+
+            // Drop block comment closers so the text reads cleanly
+            let text = captures[3].trim();
+            let text = text
+                .strip_suffix("*/")
+                .or_else(|| text.strip_suffix("-->"))
+                .unwrap_or(text)
+                .trim();
+
+            Some(Todo {
+                line_number: index + 1,
+                marker: captures[1].to_string(),
+                owner,
+                text: text.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Print every marker under its owner, then under its file
+///
+/// Markers without an owner are listed last as unassigned
+///
+/// # Errors
+///
+/// Will error if a file is not readable or cannot be found (recursive mode skips them instead)
+pub fn print_todo_report(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    let regex = todo_regex();
+
+    // Owner -> file -> markers
+    let mut report: BTreeMap<Option<String>, BTreeMap<String, Vec<Todo>>> = BTreeMap::new();
+
+    for file in files_to_search(arg) {
+        let contents = match fs::read(&file) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(), // Searched like search_path does
+            Err(e) if !arg.recursive => return Err(e.into()),
+            Err(e) => {
+                warn_skipped(arg, &format!("{file}: {e}")); // Keep going, like recursive search does
+                continue;
+            }
+        };
+
+        for todo in find_todos(&regex, &contents) {
+            report
+                .entry(todo.owner.clone())
+                .or_default()
+                .entry(arg.display_path(&file))
+                .or_default()
+                .push(todo);
+        }
+    }
+
+    let unassigned = report.remove(&None);
+    let mut out = io::stdout().lock();
+
+    for (owner, files) in report {
+        print_owner(&mut out, owner.as_deref().unwrap_or_default(), &files)?;
+    }
+
+    if let Some(files) = unassigned {
+        print_owner(&mut out, arg.lang().catalog().unassigned, &files)?;
+    }
+
+    Ok(())
+}
+
+fn print_owner(out: &mut impl Write, owner: &str, files: &BTreeMap<String, Vec<Todo>>) -> io::Result<()> {
+    let total: usize = files.values().map(Vec::len).sum();
+
+    writeln!(out, "{owner} ({total})")?;
+
+    for (file, todos) in files {
+        writeln!(out, "  {file}")?;
+
+        for todo in todos {
+            writeln!(out, "    {}: {} {}", todo.line_number, todo.marker, todo.text)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_todos_extracts_owner_and_text() {
+        let contents = "fn main() {}\n// TODO(alice): handle errors\n";
+
+        let result = find_todos(&todo_regex(), contents);
+
+        assert_eq!(result, vec![Todo {
+            line_number: 2,
+            marker: String::from("TODO"),
+            owner: Some(String::from("alice")),
+            text: String::from("handle errors"),
+        }]);
+    }
+
+    #[test]
+    fn test_find_todos_without_owner() {
+        let contents = "# FIXME this is slow\n/* HACK() work around bug */";
+
+        let result = find_todos(&todo_regex(), contents);
+
+        assert_eq!(result, vec![
+            Todo {
+                line_number: 1,
+                marker: String::from("FIXME"),
+                owner: None,
+                text: String::from("this is slow"),
+            },
+            Todo {
+                line_number: 2,
+                marker: String::from("HACK"),
+                owner: None,
+                text: String::from("work around bug"),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_find_todos_ignores_partial_words() {
+        let contents = "TODOS are not markers\nnor is HACKER";
+
+        let result = find_todos(&todo_regex(), contents);
+
+        assert_eq!(result, Vec::<Todo>::new());
+    }
+
+    #[test]
+    fn test_print_todo_report_missing_file() {
+        let arg = Argument {
+            files: vec![String::from("nonexistent_file.nonsense")],
+            todos: true,
+            ..Default::default()
+        };
+
+        let result = print_todo_report(&arg);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_print_todo_report_dir() {
+        let arg = Argument {
+            files: vec![String::from("./tests")],
+            todos: true,
+            recursive: true,
+            ..Default::default()
+        };
+
+        let result = print_todo_report(&arg);
+
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,587 @@
+//! Interactive search in the terminal (`--tui`)
+//!
+//! The pattern is typed into a box at the top, and every change starts a new [`SearchSession`]
+//! with it (dropping the old one stops its search), whose results come into the list as they're
+//! found. Below the list, a preview shows the lines around the selected result. The terminal is
+//! drawn with plain ANSI escapes on `/dev/tty`, so stdout only gets the result picked with Enter.
+
+// Standard library
+use std::error::Error; // For error handling
+use std::fs; // For previews
+use std::ops::Range; // For match spans
+
+// My stuff
+use crate::i18n::{fill, Catalog};
+use crate::printer::Printer;
+use crate::session::{Hit, SearchSession};
+use crate::{Argument, Searcher, StandardPrinter};
+
+/// Most results taken from the search each time the screen is drawn, so typing stays responsive
+const RESULTS_PER_FRAME: usize = 2000;
+
+/// A key press
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    Backspace,
+    Enter,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    /// Ctrl-E
+    Edit,
+    /// Ctrl-U
+    ClearQuery,
+    /// Esc or Ctrl-C
+    Quit,
+}
+
+/// What a key press leads to
+#[derive(Debug, PartialEq)]
+enum Action {
+    Continue,
+    Quit,
+    Print(Hit),
+    Edit(Hit),
+}
+
+/// Keys in bytes read from the terminal (raw mode, so one read can hold several)
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut rest = bytes;
+
+    while let Some(&first) = rest.first() {
+        let (key, len) = match first {
+            0x1b => match rest.get(1..3) {
+                Some(b"[A" | b"OA") => (Some(Key::Up), 3),
+                Some(b"[B" | b"OB") => (Some(Key::Down), 3),
+                Some(b"[5") if rest.get(3) == Some(&b'~') => (Some(Key::PageUp), 4),
+                Some(b"[6") if rest.get(3) == Some(&b'~') => (Some(Key::PageDown), 4),
+                Some([b'[', ..]) => (None, escape_len(rest)), // Other keys, ignored
+                _ => (Some(Key::Quit), 1),
+            },
+            b'\r' | b'\n' => (Some(Key::Enter), 1),
+            0x7f | 0x08 => (Some(Key::Backspace), 1),
+            0x03 => (Some(Key::Quit), 1),
+            0x05 => (Some(Key::Edit), 1),
+            0x0e => (Some(Key::Down), 1), // Ctrl-N
+            0x10 => (Some(Key::Up), 1), // Ctrl-P
+            0x15 => (Some(Key::ClearQuery), 1),
+            0x00..=0x1f => (None, 1),
+            _ => {
+                let len = utf8_len(first).min(rest.len());
+                let key = std::str::from_utf8(&rest[..len]).ok().and_then(|c| c.chars().next()).map(Key::Char);
+                (key, len)
+            }
+        };
+
+        keys.extend(key);
+        rest = &rest[len..];
+    }
+
+    keys
+}
+
+/// Length of the escape sequence at the start of `bytes`, up to its final byte
+fn escape_len(bytes: &[u8]) -> usize {
+    bytes.iter().skip(2).position(|byte| (0x40..=0x7e).contains(byte)).map_or(bytes.len(), |end| end + 3)
+}
+
+/// Length of the UTF-8 character starting with `first`
+fn utf8_len(first: u8) -> usize {
+    match first {
+        0xf0..=0xff => 4,
+        0xe0..=0xef => 3,
+        0xc0..=0xdf => 2,
+        _ => 1,
+    }
+}
+
+/// `line` cut to `width` characters, with `spans` highlighted and `style` (an SGR code, like
+/// `7` for reverse video) on the rest
+fn styled(line: &str, spans: &[Range<usize>], width: usize, style: &str) -> String {
+    let end = line.char_indices().nth(width).map_or(line.len(), |(at, _)| at);
+    let line = &line[..end];
+    let normal = format!("\x1b[0;{style}m");
+
+    let mut styled = normal.clone();
+    let mut last = 0;
+
+    for span in spans.iter().filter(|span| span.start < end) {
+        let span_end = span.end.min(end);
+
+        styled.push_str(&line[last..span.start]);
+        styled.push_str("\x1b[1;31m");
+        styled.push_str(&line[span.start..span_end]);
+        styled.push_str(&normal);
+        last = span_end;
+    }
+
+    styled.push_str(&line[last..]);
+    styled.push_str("\x1b[0m");
+
+    styled
+}
+
+/// The state of the search screen
+struct App {
+    base: Argument,
+    query: String,
+    session: Option<SearchSession>,
+    /// Whether the query changed since the search was started, so keys that come in together
+    /// (like when pasting) start one search rather than one each
+    stale: bool,
+    results: Vec<Hit>,
+    /// Why the query can't be searched (like an unclosed group), or why the search failed
+    error: Option<String>,
+    selected: usize,
+    /// The first result in the list
+    scroll: usize,
+    /// The lines of the file last previewed, or why it can't be
+    preview: Option<(String, Result<Vec<String>, String>)>,
+}
+
+impl App {
+    fn new(base: &Argument) -> Self {
+        let query = base.pattern.clone().or_else(|| base.regexp.first().cloned()).unwrap_or_default();
+
+        let mut app = App {
+            base: base.clone(),
+            query,
+            session: None,
+            stale: false,
+            results: Vec::new(),
+            error: None,
+            selected: 0,
+            scroll: 0,
+            preview: None,
+        };
+
+        app.search();
+        app
+    }
+
+    /// Start searching for the query, dropping the results of the last one
+    fn search(&mut self) {
+        self.stale = false;
+        self.session = None; // Stops the old search at its next file
+        self.results.clear();
+        (self.selected, self.scroll, self.error) = (0, 0, None);
+
+        if self.query.is_empty() {
+            return;
+        }
+
+        let config = Argument { pattern: Some(self.query.clone()), regexp: Vec::new(), ..self.base.clone() };
+
+        match Searcher::new(config) {
+            Ok(searcher) => self.session = Some(SearchSession::start(searcher)),
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    /// Take in the results found since last time, telling whether there were any (or the search
+    /// just finished), after starting a new search if the query changed
+    fn poll(&mut self) -> bool {
+        if self.stale {
+            self.search();
+        }
+
+        let Some(session) = &mut self.session else {
+            return false;
+        };
+
+        let searching = !session.is_done();
+
+        match session.try_next_page(RESULTS_PER_FRAME) {
+            Ok(page) if page.is_empty() => searching && session.is_done(),
+            Ok(page) => {
+                self.results.extend(page);
+                true
+            }
+            Err(e) => {
+                self.error = Some(e.to_string());
+                self.session = None;
+                true
+            }
+        }
+    }
+
+    /// Whether results may still come in
+    fn searching(&self) -> bool {
+        self.stale || self.session.as_ref().is_some_and(|session| !session.is_done())
+    }
+
+    /// Handle `key`, with `page` results shown at once
+    fn handle(&mut self, key: Key, page: usize) -> Action {
+        let last = self.results.len().saturating_sub(1);
+
+        match key {
+            Key::Char(c) => {
+                self.query.push(c);
+                self.stale = true;
+            }
+            Key::Backspace => {
+                if self.query.pop().is_some() {
+                    self.stale = true;
+                }
+            }
+            Key::ClearQuery => {
+                self.query.clear();
+                self.stale = true;
+            }
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(last),
+            Key::PageUp => self.selected = self.selected.saturating_sub(page),
+            Key::PageDown => self.selected = (self.selected + page).min(last),
+            Key::Enter | Key::Edit => {
+                let Some(hit) = self.results.get(self.selected).cloned() else {
+                    return Action::Continue;
+                };
+
+                return if key == Key::Enter { Action::Print(hit) } else { Action::Edit(hit) };
+            }
+            Key::Quit => return Action::Quit,
+        }
+
+        Action::Continue
+    }
+
+    /// The lines of the file at `path`, read once for as long as it's the one previewed
+    fn preview_lines(&mut self, path: &str) -> &Result<Vec<String>, String> {
+        if self.preview.as_ref().is_none_or(|(previewed, _)| previewed != path) {
+            let lines = match fs::read(path) {
+                Ok(bytes) if bytes.contains(&0) => Err(String::from("binary file")),
+                Ok(bytes) => Ok(String::from_utf8_lossy(&bytes).lines().map(String::from).collect()),
+                Err(e) => Err(e.to_string()),
+            };
+
+            self.preview = Some((path.to_string(), lines));
+        }
+
+        &self.preview.as_ref().expect("set above").1
+    }
+
+    /// The whole screen, `width` by `height`, as escapes to draw it with
+    fn render(&mut self, width: usize, height: usize, catalog: &Catalog) -> String {
+        let height = height.max(4);
+        let list_height = (height - 2) / 2;
+        let preview_height = height - 2 - list_height;
+
+        // Keep the selection on screen
+        self.selected = self.selected.min(self.results.len().saturating_sub(1));
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_height {
+            self.scroll = self.selected + 1 - list_height;
+        }
+
+        let mut rows = Vec::with_capacity(height);
+
+        let status = match &self.error {
+            Some(error) => error.lines().last().unwrap_or_default().to_string(),
+            None if self.searching() => catalog.tui_searching.to_string(),
+            None => fill(catalog.tui_results, &[("lines", &self.results.len().to_string())]),
+        };
+        rows.push(format!("> {}\x1b[0;2m  {status}\x1b[0m", self.query));
+
+        for (index, (path, found)) in self.results.iter().enumerate().skip(self.scroll).take(list_height) {
+            let prefix = format!("{path}:{}: ", found.line_number());
+            let style = if index == self.selected { "7" } else { "" };
+            let spans: Vec<Range<usize>> = found.spans().iter().map(|span| span.start + prefix.len()..span.end + prefix.len()).collect();
+
+            rows.push(styled(&format!("{prefix}{}", found.line()), &spans, width, style));
+        }
+        rows.resize(1 + list_height, String::new());
+
+        let selected = self.results.get(self.selected).cloned();
+        let heading = selected.as_ref().map_or_else(|| catalog.tui_keys.to_string(), |(path, _)| path.clone());
+        rows.push(styled(&format!("── {heading} ──"), &[], width, "2"));
+
+        if let Some((path, found)) = selected {
+            let gutter = 8;
+
+            match self.preview_lines(&path) {
+                Ok(lines) => {
+                    // The matching line in the middle, where there's room
+                    let first = found.line_number().saturating_sub(1 + preview_height / 2);
+
+                    for (index, line) in lines.iter().enumerate().skip(first).take(preview_height) {
+                        let number = index + 1;
+
+                        rows.push(if number == found.line_number() {
+                            format!("\x1b[1m{number:>6}\x1b[0m  {}", styled(found.line(), found.spans(), width.saturating_sub(gutter), ""))
+                        } else {
+                            format!("\x1b[2m{number:>6}\x1b[0m  {}", styled(line, &[], width.saturating_sub(gutter), ""))
+                        });
+                    }
+                }
+                Err(error) => rows.push(fill(catalog.tui_no_preview, &[("error", error)])),
+            }
+        }
+        rows.resize(height, String::new());
+
+        // Each row clears what was there, then the cursor goes back to the end of the query
+        let mut screen = String::from("\x1b[H");
+        for row in &rows {
+            screen.push_str(row);
+            screen.push_str("\x1b[K\r\n");
+        }
+        screen.truncate(screen.len() - 2);
+
+        format!("{screen}\x1b[1;{}H", 3 + self.query.chars().count())
+    }
+}
+
+/// Search interactively: type a pattern and pick a result, which is printed (Enter) or opened
+/// in `$VISUAL` or `$EDITOR` at its line (Ctrl-E)
+///
+/// # Errors
+///
+/// Will error if there's no terminal (`/dev/tty`) to draw on, or printing the result fails
+pub fn run_tui(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    let catalog = arg.lang().catalog();
+    let mut app = App::new(arg);
+
+    let action = terminal::run(&mut app, catalog).map_err(|e| format!("{}: {e}", catalog.tui_terminal))?;
+
+    match action {
+        Action::Print((path, found)) => {
+            let mut printer = StandardPrinter::new(arg, std::io::stdout());
+            Ok(printer.file(&path, &[found])?)
+        }
+        Action::Edit((path, found)) => {
+            let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or_else(|_| String::from("vi"));
+
+            // `+LINE` is understood by vi, nano, emacs and most others
+            std::process::Command::new(editor).arg(format!("+{}", found.line_number())).arg(path).status()?;
+            Ok(())
+        }
+        Action::Continue | Action::Quit => Ok(()),
+    }
+}
+
+/// Drawing on the terminal and reading keys from it
+#[cfg(unix)]
+mod terminal {
+    // Standard library
+    use std::fs::{File, OpenOptions}; // For /dev/tty
+    use std::io::{self, Read, Write}; // For drawing and reading keys
+    use std::os::fd::AsRawFd; // For terminal settings
+
+    // My stuff
+    use super::{parse_keys, Action, App, Key};
+    use crate::i18n::Catalog;
+
+    /// How long to wait for a key before taking in new results, in milliseconds
+    const POLL_MS: i32 = 50;
+
+    /// The terminal in raw mode on the alternate screen, put back the way it was when dropped
+    struct Terminal {
+        tty: File,
+        original: libc::termios,
+    }
+
+    impl Terminal {
+        fn open() -> io::Result<Self> {
+            let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+
+            let mut original = std::mem::MaybeUninit::<libc::termios>::uninit();
+
+            // SAFETY: tcgetattr fills in `original` when it succeeds, which is checked before reading it
+            let original = unsafe {
+                if libc::tcgetattr(tty.as_raw_fd(), original.as_mut_ptr()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                original.assume_init()
+            };
+
+            let mut raw = original;
+            // SAFETY: `raw` is a valid termios
+            unsafe { libc::cfmakeraw(&raw mut raw) };
+
+            // SAFETY: `raw` is a valid termios for this terminal
+            if unsafe { libc::tcsetattr(tty.as_raw_fd(), libc::TCSANOW, &raw const raw) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            write!(tty, "\x1b[?1049h\x1b[H\x1b[2J")?; // Alternate screen, cleared
+            Ok(Terminal { tty, original })
+        }
+
+        /// Columns and rows, or 80 by 24 if the terminal doesn't say
+        fn size(&self) -> (usize, usize) {
+            let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+
+            // SAFETY: TIOCGWINSZ writes a winsize, which `size` is
+            let ok = unsafe { libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, &raw mut size) } == 0;
+
+            if ok && size.ws_col > 0 && size.ws_row > 0 { (size.ws_col.into(), size.ws_row.into()) } else { (80, 24) }
+        }
+
+        /// Keys pressed within [`POLL_MS`], if any
+        fn keys(&mut self) -> io::Result<Vec<Key>> {
+            let mut poll = libc::pollfd { fd: self.tty.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+
+            // SAFETY: `poll` is one valid pollfd
+            if unsafe { libc::poll(&raw mut poll, 1, POLL_MS) } <= 0 {
+                return Ok(Vec::new()); // Timed out (or interrupted, like by a resize)
+            }
+
+            let mut buffer = [0; 64];
+            let len = self.tty.read(&mut buffer)?;
+
+            Ok(parse_keys(&buffer[..len]))
+        }
+    }
+
+    impl Drop for Terminal {
+        fn drop(&mut self) {
+            let _ = write!(self.tty, "\x1b[?1049l");
+
+            // SAFETY: `original` is the termios read from this terminal
+            unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSANOW, &raw const self.original) };
+        }
+    }
+
+    /// Show `app` until a key leads somewhere, drawing it again whenever a key was pressed, new
+    /// results came in or the terminal was resized
+    pub(super) fn run(app: &mut App, catalog: &Catalog) -> io::Result<Action> {
+        let mut terminal = Terminal::open()?;
+        let mut redraw = true;
+        let mut last_size = (0, 0);
+
+        loop {
+            let (width, height) = terminal.size();
+            redraw |= (width, height) != last_size;
+            last_size = (width, height);
+
+            if app.poll() || redraw {
+                terminal.tty.write_all(app.render(width, height, catalog).as_bytes())?;
+                terminal.tty.flush()?;
+            }
+
+            redraw = false;
+
+            for key in terminal.keys()? {
+                match app.handle(key, height.saturating_sub(2) / 2) {
+                    Action::Continue => redraw = true,
+                    action => return Ok(action),
+                }
+            }
+        }
+    }
+}
+
+/// Stands in for the terminal where there's no termios
+#[cfg(not(unix))]
+mod terminal {
+    use std::io;
+
+    use super::{Action, App};
+    use crate::i18n::Catalog;
+
+    pub(super) fn run(_app: &mut App, _catalog: &Catalog) -> io::Result<Action> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "only Unix terminals are supported"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Lang;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(parse_keys(b"ab\x7f"), vec![Key::Char('a'), Key::Char('b'), Key::Backspace]);
+        assert_eq!(parse_keys(b"\x1b[A\x1b[B\x1b[5~\x1b[6~"), vec![Key::Up, Key::Down, Key::PageUp, Key::PageDown]);
+        assert_eq!(parse_keys("é\r".as_bytes()), vec![Key::Char('é'), Key::Enter]);
+        assert_eq!(parse_keys(b"\x1b"), vec![Key::Quit]);
+        assert_eq!(parse_keys(b"\x1b[1;5Cx\x05"), vec![Key::Char('x'), Key::Edit]); // Ctrl-Right is ignored
+    }
+
+    #[test]
+    fn test_styled() {
+        let spans = [0..1, 4..7];
+
+        assert_eq!(styled("one fox", &spans, 80, ""), "\x1b[0;m\x1b[1;31mo\x1b[0;mne \x1b[1;31mfox\x1b[0;m\x1b[0m");
+        assert_eq!(styled("one fox", &spans, 5, "7"), "\x1b[0;7m\x1b[1;31mo\x1b[0;7mne \x1b[1;31mf\x1b[0;7m\x1b[0m"); // Cut short
+    }
+
+    #[test]
+    fn test_app_searches_as_the_query_changes() {
+        let temp = TempDir::new("tui");
+        let path = temp.write("a.txt", "fox\nfoxes\nfo(\ndog\n");
+
+        let mut app = App::new(&Argument::try_from_args(["fo", &path]).unwrap());
+        let results = |app: &mut App| {
+            while app.searching() {
+                app.poll();
+                std::thread::yield_now();
+            }
+
+            app.results.iter().map(|(_, found)| found.line().to_string()).collect::<Vec<String>>()
+        };
+
+        assert_eq!(results(&mut app), vec!["fox", "foxes", "fo("]);
+
+        app.handle(Key::Char('x'), 10);
+        app.handle(Key::Char('e'), 10);
+        assert_eq!(results(&mut app), vec!["foxes"]);
+
+        app.handle(Key::Backspace, 10);
+        app.handle(Key::Backspace, 10);
+        app.handle(Key::Char('('), 10);
+        app.poll();
+        assert!(app.error.is_some()); // Unclosed group
+
+        app.handle(Key::ClearQuery, 10);
+        app.handle(Key::Char('o'), 10);
+        assert_eq!(results(&mut app).len(), 4);
+
+        app.handle(Key::Down, 10);
+        let screen = app.render(40, 10, Lang::En.catalog());
+
+        assert!(screen.contains("> o\x1b[0;2m  4 matching lines"));
+        assert!(screen.contains("simple-grep-tui-"));
+        assert!(matches!(app.handle(Key::Enter, 10), Action::Print((_, found)) if found.line() == "foxes"));
+    }
+
+    #[test]
+    fn test_typing_stops_the_old_search() {
+        let temp = TempDir::new("tui");
+        let found = temp.write("a.txt", "fox\n");
+        let nothing = temp.write("b.txt", "dog\n".repeat(1 << 18)); // 1 MiB
+
+        let mut args = vec![String::from("-j1"), String::from("fox"), found];
+        args.extend(std::iter::repeat_n(nothing, 20_000));
+
+        let mut app = App::new(&Argument::try_from_args(args).unwrap());
+        let first = app.session.as_ref().unwrap().search_thread();
+
+        // Keys read together start one search, and the one before it stops
+        app.handle(Key::Char('e'), 10);
+        app.handle(Key::Char('s'), 10);
+        app.poll();
+
+        let second = app.session.as_ref().unwrap().search_thread();
+        let start = std::time::Instant::now();
+
+        while first.upgrade().is_some() && start.elapsed() < std::time::Duration::from_secs(2) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        assert!(first.upgrade().is_none());
+        assert_eq!(app.query, "foxes");
+
+        drop(app);
+        while second.upgrade().is_some() && start.elapsed() < std::time::Duration::from_secs(4) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        assert!(second.upgrade().is_none());
+    }
+}
@@ -0,0 +1,118 @@
+//! File types for scoping searches by language (`--type`, `--type-not`)
+
+// Standard library
+use std::fmt::Write as _; // For listing the types
+use std::io::{self, Write}; // For printing the types
+use std::sync::LazyLock; // For compiling the globs once
+
+// My stuff
+use crate::glob::Glob;
+
+/// Built-in type names and the globs of their file names, sorted by name
+const TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.h"]),
+    ("csharp", &["*.cs"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json"]),
+    ("kotlin", &["*.kt", "*.kts"]),
+    ("lua", &["*.lua"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("php", &["*.php"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("ruby", &["*.rb", "Gemfile", "Rakefile"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("sql", &["*.sql"]),
+    ("swift", &["*.swift"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.tsx", "*.mts", "*.cts"]),
+    ("txt", &["*.txt"]),
+    ("xml", &["*.xml"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// Check that `name` is a known type
+///
+/// # Errors
+///
+/// Will error (listing the known types) if it isn't
+pub fn parse_file_type(name: &str) -> Result<String, String> {
+    if TYPES.iter().any(|(known, _)| *known == name) {
+        return Ok(name.to_string());
+    }
+
+    let known: Vec<&str> = TYPES.iter().map(|(known, _)| *known).collect();
+
+    Err(format!("unknown type `{name}` (known types: {})", known.join(", ")))
+}
+
+/// Whether a file name is of type `name`
+pub(crate) fn is_type(name: &str, file_name: &str) -> bool {
+    static COMPILED: LazyLock<Vec<(&str, Vec<Glob>)>> =
+        LazyLock::new(|| TYPES.iter().map(|(name, globs)| (*name, globs.iter().map(|glob| Glob::new(glob)).collect())).collect());
+
+    COMPILED
+        .iter()
+        .filter(|(known, _)| *known == name)
+        .flat_map(|(_, globs)| globs.iter())
+        .any(|glob| glob.is_match(file_name))
+}
+
+/// Every type with its globs, one per line
+fn types_text() -> String {
+    let mut text = String::new();
+
+    for (name, globs) in TYPES {
+        let _ = writeln!(text, "{name}: {}", globs.join(", ")); // Writing to a String can't fail
+    }
+
+    text
+}
+
+/// Print the built-in types and the globs of their file names (`simple-grep types`)
+///
+/// # Errors
+///
+/// Will error if stdout can't be written to
+pub fn print_types() -> io::Result<()> {
+    write!(io::stdout().lock(), "{}", types_text())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_types_are_sorted() {
+        assert!(TYPES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_parse_file_type() {
+        assert_eq!(parse_file_type("rust"), Ok(String::from("rust")));
+        assert!(parse_file_type("cobol").unwrap_err().contains("known types: c, cpp"));
+    }
+
+    #[test]
+    fn test_is_type() {
+        assert!(is_type("rust", "lib.rs"));
+        assert!(is_type("make", "Makefile"));
+        assert!(!is_type("rust", "lib.rs.bak"));
+        assert!(!is_type("py", "lib.rs"));
+    }
+
+    #[test]
+    fn test_types_text() {
+        let text = types_text();
+
+        assert_eq!(text.lines().count(), TYPES.len());
+        assert!(text.starts_with("c: *.c, *.h\n"));
+        assert!(text.contains("\nrust: *.rs\n"));
+    }
+}
@@ -0,0 +1,119 @@
+//! Sizes, durations and percentages with human-friendly suffixes (`10M`, `2GiB`, `500ms`, `50%`), for option values
+
+// Standard library
+use std::time::Duration; // For durations
+
+/// Size suffixes and how many bytes they stand for, compared case-insensitively
+///
+/// Like grep and ripgrep, `K`, `M`... are powers of 1024. `KB`, `MB`... are powers of 1000.
+const SIZE_SUFFIXES: &[(&str, u64)] = &[
+    ("", 1),
+    ("b", 1),
+    ("k", 1 << 10),
+    ("kib", 1 << 10),
+    ("kb", 1000),
+    ("m", 1 << 20),
+    ("mib", 1 << 20),
+    ("mb", 1000 * 1000),
+    ("g", 1 << 30),
+    ("gib", 1 << 30),
+    ("gb", 1000 * 1000 * 1000),
+    ("t", 1 << 40),
+    ("tib", 1 << 40),
+    ("tb", 1000 * 1000 * 1000 * 1000),
+];
+
+/// Duration suffixes and how many milliseconds they stand for (no suffix is seconds)
+const DURATION_SUFFIXES: &[(&str, u64)] = &[("ms", 1), ("", 1000), ("s", 1000), ("m", 60 * 1000), ("h", 60 * 60 * 1000)];
+
+/// Split `value` into its number and (lowercase) suffix
+fn split_number(value: &str) -> Option<(u64, String)> {
+    let value = value.trim();
+    let digits = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+
+    let number = value[..digits].parse().ok()?;
+
+    Some((number, value[digits..].trim_start().to_ascii_lowercase()))
+}
+
+/// Number of bytes in a size like `4096`, `10M` or `2GiB`
+///
+/// # Errors
+///
+/// Errors with a message for clap if `value` isn't a whole number with a known suffix, or is too large
+pub(crate) fn parse_size(value: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid size `{value}` (expected a whole number of bytes with an optional K, M, G or T suffix, like 10M or 2GiB)");
+
+    let (number, suffix) = split_number(value).ok_or_else(invalid)?;
+    let (_, multiplier) = SIZE_SUFFIXES.iter().find(|(name, _)| *name == suffix).ok_or_else(invalid)?;
+
+    number.checked_mul(*multiplier).ok_or_else(|| format!("size `{value}` is too large"))
+}
+
+/// Duration like `30` (seconds), `500ms`, `10s`, `2m` or `1h`
+///
+/// # Errors
+///
+/// Errors with a message for clap if `value` isn't a whole number with a known suffix, or is too large
+pub(crate) fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration `{value}` (expected a whole number with an optional ms, s, m or h suffix, like 500ms or 2m)");
+
+    let (number, suffix) = split_number(value).ok_or_else(invalid)?;
+    let (_, millis) = DURATION_SUFFIXES.iter().find(|(name, _)| *name == suffix).ok_or_else(invalid)?;
+
+    number.checked_mul(*millis).map(Duration::from_millis).ok_or_else(|| format!("duration `{value}` is too long"))
+}
+
+/// Percentage like `50%` or `12.5` (the `%` is optional), from 0 to 100
+///
+/// # Errors
+///
+/// Errors with a message for clap if `value` isn't a number from 0 to 100
+pub(crate) fn parse_percent(value: &str) -> Result<f64, String> {
+    let number = value.trim().strip_suffix('%').unwrap_or(value.trim());
+
+    number
+        .parse()
+        .ok()
+        .filter(|percent| (0.0..=100.0).contains(percent))
+        .ok_or_else(|| format!("invalid percentage `{value}` (expected a number from 0 to 100, like 50%)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("10M"), Ok(10 << 20));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert_eq!(parse_size("1kb"), Ok(1000));
+        assert_eq!(parse_size("512 B"), Ok(512));
+    }
+
+    #[test]
+    fn test_parse_size_errors() {
+        assert!(parse_size("10X").unwrap_err().starts_with("invalid size `10X`"));
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("1.5G").is_err());
+        assert_eq!(parse_size("99999999999T"), Err(String::from("size `99999999999T` is too large")));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_mins(2)));
+        assert_eq!(parse_duration("1H"), Ok(Duration::from_hours(1)));
+        assert!(parse_duration("2d").unwrap_err().starts_with("invalid duration `2d`"));
+    }
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("50%"), Ok(50.0));
+        assert_eq!(parse_percent("12.5"), Ok(12.5));
+        assert!(parse_percent("101%").unwrap_err().starts_with("invalid percentage `101%`"));
+        assert!(parse_percent("half").is_err());
+    }
+}
@@ -0,0 +1,74 @@
+//! Version information (`--version`, `--version --json`)
+
+// Standard library
+use std::io::{self, Write}; // For printing
+
+// My stuff
+use crate::{json, Argument};
+
+/// Cargo features compiled into this build
+fn enabled_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+
+    if cfg!(feature = "guess-encoding") {
+        features.push("guess-encoding");
+    }
+
+    if cfg!(feature = "sqlite") {
+        features.push("sqlite");
+    }
+
+    if cfg!(all(feature = "pcre2", unix)) {
+        features.push("pcre2");
+    }
+
+    features
+}
+
+/// Formats search results can be printed in
+const OUTPUT_FORMATS: &[&str] = &["text", "json"];
+
+fn version_text() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+/// Version, enabled features and output formats, so wrapper tools can check capabilities
+fn version_json() -> String {
+    format!(
+        "{{\"name\":{},\"version\":{},\"features\":{},\"output_formats\":{}}}",
+        json::string(env!("CARGO_PKG_NAME")),
+        json::string(env!("CARGO_PKG_VERSION")),
+        json::string_array(&enabled_features()),
+        json::string_array(OUTPUT_FORMATS),
+    )
+}
+
+/// Print the version, as JSON with `--json`
+///
+/// # Errors
+///
+/// Will error if it can't be written (like when stdout is closed)
+pub fn print_version(arg: &Argument) -> io::Result<()> {
+    let version = if arg.json { version_json() } else { version_text() };
+
+    writeln!(io::stdout().lock(), "{version}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_text() {
+        assert_eq!(version_text(), format!("simple-grep {}", env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn test_version_json() {
+        let result = version_json();
+
+        assert!(result.starts_with("{\"name\":\"simple-grep\",\"version\":"));
+        assert!(result.contains("\"features\":["));
+        assert!(result.contains("\"output_formats\":[\"text\",\"json\"]"));
+    }
+}
@@ -0,0 +1,107 @@
+//! Searching again whenever the searched files change (`--watch`)
+//!
+//! Files are polled rather than watched through the operating system, which works the same
+//! everywhere (network drives included) without a dependency. Each poll checks the times of the
+//! files searched, and of the directories they were found in (like `--cache-file-list`), so
+//! directories are only walked again when one of them changes, and new and deleted files count
+//! as changes too.
+
+// Standard library
+use std::error::Error; // For reporting errors
+use std::fs; // For file sizes and modification times
+use std::io::{self, IsTerminal, Write}; // For clearing the screen
+use std::thread; // For waiting between polls
+use std::time::SystemTime; // For modification times
+
+// My stuff
+use crate::file_list::Walk;
+use crate::{files_to_search, is_broken_pipe, read_file_and_print_matches, Argument};
+
+/// Every file to search, with its size and modification time (if it could be read)
+type Snapshot = Vec<(String, Option<(u64, SystemTime)>)>;
+
+fn snapshot(files: &[String]) -> Snapshot {
+    files
+        .iter()
+        .map(|file| {
+            let stamp = fs::metadata(file).ok().and_then(|metadata| Some((metadata.len(), metadata.modified().ok()?)));
+            (file.clone(), stamp)
+        })
+        .collect()
+}
+
+/// The snapshot of the files to search, walking the directories again (into `walk`) only if
+/// one changed since the last walk
+fn poll(arg: &Argument, walk: &mut Option<Walk>) -> Snapshot {
+    if !arg.recursive {
+        return snapshot(&files_to_search(arg)); // Just the files given
+    }
+
+    match walk {
+        Some(walk) if !walk.is_stale() => snapshot(&walk.files),
+        _ => snapshot(&walk.insert(Walk::new(arg)).files),
+    }
+}
+
+/// Search and print the results, then again every time a file to search changes, until killed
+///
+/// On a terminal the screen is cleared before each search, so only the latest results show.
+/// Errors (like a file that doesn't exist yet) are reported without stopping.
+///
+/// # Errors
+///
+/// Will error if the results can't be written (like when stdout is closed)
+pub fn watch(arg: &Argument) -> Result<(), Box<dyn Error>> {
+    let terminal = io::stdout().is_terminal();
+    let mut last = None;
+    let mut walk = None;
+
+    loop {
+        let current = poll(arg, &mut walk);
+
+        if last.as_ref() != Some(&current) {
+            if terminal {
+                let mut stdout = io::stdout().lock();
+                write!(stdout, "\x1b[2J\x1b[H")?; // Clear the screen and go to the top
+                stdout.flush()?;
+            }
+
+            if let Err(e) = read_file_and_print_matches(arg) {
+                if is_broken_pipe(&*e) {
+                    return Err(e);
+                }
+
+                eprintln!("{}: {e}", arg.lang().application_error());
+            }
+
+            last = Some(current);
+        }
+
+        thread::sleep(arg.watch_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    #[test]
+    fn test_snapshot_changes() {
+        let dir = TempDir::new("watch");
+        let path = dir.write("a.txt", "one\n");
+
+        let config = Argument::try_from_args(["-r", "fox", &dir.display()]).unwrap();
+        let mut walk = None;
+        let before = poll(&config, &mut walk);
+        assert_eq!(before.len(), 1);
+        assert_eq!(poll(&config, &mut walk), before);
+
+        fs::write(&path, "one two\n").unwrap(); // Bigger, whatever the clock's resolution
+        let edited = poll(&config, &mut walk);
+        assert_ne!(edited, before);
+
+        fs::write(dir.join("b.txt"), "new\n").unwrap();
+        assert_eq!(poll(&config, &mut walk).len(), 2);
+    }
+}